@@ -0,0 +1,72 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use muxide::bench::{clip_row, Layout, Screen};
+
+const ROWS: u16 = 50;
+const COLS: u16 = 200;
+
+/// A screen's worth of coloured output, like the output of a build or `ls --color`.
+fn output() -> Vec<u8> {
+    let mut output = Vec::new();
+
+    for line in 0..ROWS as usize {
+        output.extend_from_slice(
+            format!(
+                "\x1b[3{}m{:04}\x1b[0m {} 日本語\r\n",
+                line % 8,
+                line,
+                "output ".repeat(25)
+            )
+            .as_bytes(),
+        );
+    }
+
+    output
+}
+
+fn process_output(c: &mut Criterion) {
+    let output = output();
+    let mut group = c.benchmark_group("process_output");
+    group.throughput(Throughput::Bytes(output.len() as u64));
+
+    group.bench_function("screen", |b| {
+        let mut screen = Screen::new(ROWS, COLS, 1000);
+
+        b.iter(|| screen.process(black_box(&output)));
+    });
+
+    group.finish();
+}
+
+fn clip_rows(c: &mut Criterion) {
+    let rows = Screen::new(ROWS, COLS, 0).process(&output());
+
+    c.bench_function("clip_rows", |b| {
+        b.iter(|| {
+            for row in &rows {
+                black_box(clip_row(black_box(row), COLS / 3));
+            }
+        })
+    });
+}
+
+fn render_subdivisions(c: &mut Criterion) {
+    let rows = Screen::new(ROWS, COLS, 0).process(&output());
+    let mut group = c.benchmark_group("render_subdivisions");
+
+    for panels in [1, 4, 8].iter() {
+        let layout = Layout::new(ROWS, COLS, *panels, &rows).unwrap();
+        let mut rendered = Vec::new();
+
+        group.bench_function(format!("{} panels", panels), |b| {
+            b.iter(|| {
+                rendered.clear();
+                layout.render(&mut rendered).unwrap();
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, process_output, clip_rows, render_subdivisions);
+criterion_main!(benches);
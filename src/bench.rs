@@ -0,0 +1,88 @@
+use crate::display::{PanelPtr, SubDivision, SubDivisionSplit};
+use crate::geometry::{Point, Size};
+use crate::panel_screen::{PanelRow, PanelScreen};
+use crate::{Config, MuxideError};
+use std::io::Write;
+use std::sync::Arc;
+
+/// A panel's screen, which processes the output of the panel's program into the frames the
+/// display receives, as its pty task does. Used by the benchmarks in benches/pipeline.rs.
+pub struct Screen {
+    screen: PanelScreen,
+}
+
+/// A workspace split into panels that all display the same rows, which can be rendered without a
+/// terminal. Used by the benchmarks in benches/pipeline.rs.
+pub struct Layout {
+    root: SubDivision,
+    config: Config,
+}
+
+impl Screen {
+    pub fn new(rows: u16, cols: u16, scrollback_len: usize) -> Self {
+        return Self {
+            screen: PanelScreen::new(Size::new(rows, cols), scrollback_len),
+        };
+    }
+
+    /// Process the output and build a frame from the screen, returns the rows of the frame.
+    pub fn process(&mut self, output: &[u8]) -> Vec<Vec<u8>> {
+        self.screen.process(output);
+
+        return self
+            .screen
+            .frame()
+            .rows
+            .iter()
+            .map(|row| row.to_vec())
+            .collect();
+    }
+}
+
+impl Layout {
+    /// Split a terminal of the specified size into the number of panels, each new panel splits
+    /// the last one alternately side by side and one above the other.
+    pub fn new(
+        rows: u16,
+        cols: u16,
+        panels: usize,
+        content: &[Vec<u8>],
+    ) -> Result<Self, MuxideError> {
+        let content: Arc<[PanelRow]> = content.iter().map(|row| Arc::from(&row[..])).collect();
+        let mut root = SubDivision::new(Point::new(0, 0), Size::new(rows, cols));
+
+        for id in 0..panels {
+            if id > 0 {
+                let split = if id % 2 == 1 {
+                    SubDivisionSplit::Vertical
+                } else {
+                    SubDivisionSplit::Horizontal
+                };
+
+                root.split_panel(Some(id - 1), split, SubDivision::DEFAULT_RATIO)?;
+            }
+
+            if let Some((path, _, origin)) = root.next_panel_details() {
+                let mut panel = PanelPtr::new(id, (origin.column(), origin.row()));
+                panel.set_content(content.clone());
+                root.open_panel_at_path(panel, path)?;
+            }
+        }
+
+        return Ok(Self {
+            root,
+            config: Config::default(),
+        });
+    }
+
+    /// Render the panels and the lines between them, clipping each row to its panel.
+    pub fn render<W: Write>(&self, output: &mut W) -> Result<(), MuxideError> {
+        return self.root.render(output, &self.config, None);
+    }
+}
+
+/// Remove the characters of a formatted row that would be drawn past the specified number of
+/// columns, as the display does when rendering a panel.
+pub fn clip_row(row: &[u8], width: u16) -> Vec<u8> {
+    return SubDivision::clip_row(row, width);
+}
@@ -0,0 +1,141 @@
+use crate::logging::{self, LogFilter};
+use crate::session::FALLBACK_SESSION_NAME;
+use crate::terminal_guard::TerminalGuard;
+use crate::{Config, LogicManager};
+use std::time::Duration;
+
+/// An instance of the multiplexer that can be embedded in other binaries. It takes control of the
+/// terminal until every panel is closed or the quit command is used.
+///
+/// ```no_run
+/// use muxide::{Config, Muxide};
+///
+/// if let Err(e) = Muxide::builder().config(Config::default()).run() {
+///     eprintln!("Terminating with error: {}", e);
+/// }
+/// ```
+pub struct Muxide {
+    config: Config,
+    password: Option<String>,
+    session_name: String,
+}
+
+/// Configures and creates an instance of [`Muxide`].
+pub struct MuxideBuilder {
+    config: Config,
+    password: Option<String>,
+    session_name: String,
+}
+
+impl Muxide {
+    /// The amount of time given to write the queued log messages when the multiplexer exits.
+    const LOG_FLUSH_TIMEOUT: Duration = Duration::from_millis(200);
+    /// The environment variable that sets the log filter, it takes precedence over the config.
+    const LOG_FILTER_VARIABLE: &'static str = "MUXIDE_LOG";
+
+    /// Returns a builder using the default config and no lockscreen password.
+    pub fn builder() -> MuxideBuilder {
+        return MuxideBuilder::new();
+    }
+
+    /// Set the log filter and the number of log messages kept in memory from the config, the
+    /// MUXIDE_LOG environment variable takes precedence over its log_filter. This is done when the
+    /// multiplexer runs, calling it earlier filters the messages logged before then as well.
+    pub fn configure_logging(config: &Config) -> Result<(), String> {
+        let filter = std::env::var(Self::LOG_FILTER_VARIABLE)
+            .ok()
+            .or(config.get_environment_ref().log_filter().clone());
+
+        if let Some(filter) = filter {
+            logging::set_filter(
+                LogFilter::parse(&filter).map_err(|e| format!("Invalid log filter. {}", e))?,
+            );
+        }
+
+        logging::set_history_size(config.get_environment_ref().log_history_size());
+
+        return Ok(());
+    }
+
+    /// Run the multiplexer on a new tokio runtime, blocking until it exits.
+    pub fn run(self) -> Result<(), String> {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_io()
+            .enable_time()
+            .build()
+            .map_err(|e| format!("Failed to start the tokio runtime. Error: {}", e))?;
+
+        return rt.block_on(self.run_async());
+    }
+
+    /// Run the multiplexer on the current tokio runtime. The terminal is switched to the alternate
+    /// screen before starting and restored once the multiplexer exits, panics or is terminated by
+    /// a signal.
+    pub async fn run_async(self) -> Result<(), String> {
+        Self::configure_logging(&self.config)?;
+
+        let _guard = TerminalGuard::enter(self.config.get_environment_ref().keyboard_protocol());
+
+        let result = match LogicManager::new(self.config, self.password, self.session_name) {
+            Ok(logic_manager) => logic_manager.start_event_loop().await,
+            Err(e) => Err(e.description()),
+        };
+
+        logging::flush(Self::LOG_FLUSH_TIMEOUT);
+
+        return result;
+    }
+}
+
+impl MuxideBuilder {
+    pub fn new() -> Self {
+        return Self {
+            config: Config::default(),
+            password: None,
+            session_name: FALLBACK_SESSION_NAME.to_string(),
+        };
+    }
+
+    /// Set the config used by the multiplexer.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+
+        return self;
+    }
+
+    /// Set the lockscreen password, this should be a hash produced by
+    /// [`hash_password`](crate::hasher::hash_password). Without a password, unlocking doesn't
+    /// require one.
+    pub fn password(mut self, hashed_password: String) -> Self {
+        self.password = Some(hashed_password);
+
+        return self;
+    }
+
+    /// Set the name of the session, it is displayed in the title of the terminal's window.
+    pub fn session_name(mut self, name: String) -> Self {
+        self.session_name = name;
+
+        return self;
+    }
+
+    /// Create the configured instance.
+    pub fn build(self) -> Muxide {
+        return Muxide {
+            config: self.config,
+            password: self.password,
+            session_name: self.session_name,
+        };
+    }
+
+    /// Create the configured instance and run it, blocking until it exits.
+    pub fn run(self) -> Result<(), String> {
+        return self.build().run();
+    }
+}
+
+impl Default for MuxideBuilder {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
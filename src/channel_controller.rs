@@ -1,20 +1,38 @@
 use crate::error::{ErrorType, MuxideError};
 use crate::geometry::Size;
-use futures::FutureExt;
+use crate::panel_screen::PanelFrame;
+use futures::stream::{BoxStream, SelectAll};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use tokio::select;
-use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::sync::mpsc::{
+    self, error::SendError, Receiver, Sender, UnboundedReceiver, UnboundedSender,
+};
 use tokio::time::{self, Duration};
 
 #[derive(Clone, Debug, Hash)]
 pub enum ServerMessage {
     Bytes(Vec<u8>),
     Resize(Size),
+    /// Append the panel's output to a file, optionally prefixing each line with a timestamp.
+    StartLogging {
+        path: String,
+        timestamps: bool,
+    },
+    StopLogging,
     Shutdown,
 }
 
 #[derive(Clone, Debug, Hash)]
 pub enum PtyMessage {
-    Bytes(Vec<u8>),
+    /// The panel's contents changed, the pty task has already processed the program's output.
+    Frame(PanelFrame),
+    /// The program hasn't read its input for a while, the number of bytes waiting to be written
+    /// is reported. The input is kept until it is read.
+    InputBlocked(usize),
     Error(MuxideError),
 }
 
@@ -22,11 +40,20 @@ pub enum PtyMessage {
 pub enum ChannelID {
     Pty(usize),
     Stdin,
+    /// A report from a process running in the background, the bytes are a UTF-8 message.
+    Background,
+    /// The terminal was resized, there are no bytes.
+    TerminalResize,
 }
 
 #[derive(Clone, Debug)]
 pub struct ControllerResponse {
     pub bytes: Vec<u8>,
+    /// The contents of a panel, only set for messages from a pty.
+    pub frame: Option<PanelFrame>,
+    /// The number of bytes of input waiting to be written, set when a panel stops accepting
+    /// input.
+    pub blocked_input: Option<usize>,
     pub id: ChannelID,
 }
 
@@ -36,17 +63,130 @@ pub struct ChannelWaitFail {
     pub error: Option<MuxideError>,
 }
 
-/// Represents a pty, storing the id of the channels and two for communication with the channel and
-/// 1 to signal a shutdown.
+/// The number of messages waiting in each direction of a pty's channels.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// The input sent to the pty task that it hasn't received yet.
+    pub queued_input: usize,
+    /// The messages sent by the pty task that the event loop hasn't received yet.
+    pub queued_output: usize,
+}
+
+/// The sending half of a channel that counts the messages waiting to be received.
+pub struct CountedSender<T> {
+    tx: Sender<T>,
+    queued: Arc<AtomicUsize>,
+}
+
+/// The receiving half of a channel that counts the messages waiting to be received.
+pub struct CountedReceiver<T> {
+    rx: Receiver<T>,
+    queued: Arc<AtomicUsize>,
+}
+
+/// Creates a channel that counts its queued messages, the count is also returned.
+fn counted_channel<T>(buffer: usize) -> (CountedSender<T>, CountedReceiver<T>, Arc<AtomicUsize>) {
+    let (tx, rx) = mpsc::channel(buffer);
+    let queued = Arc::new(AtomicUsize::new(0));
+
+    return (
+        CountedSender {
+            tx,
+            queued: queued.clone(),
+        },
+        CountedReceiver {
+            rx,
+            queued: queued.clone(),
+        },
+        queued,
+    );
+}
+
+/// Removes a message from the count when dropped, unless the message was sent. A send can fail or
+/// be cancelled whilst waiting for space in the channel.
+struct Unsent<'a>(Option<&'a AtomicUsize>);
+
+impl Drop for Unsent<'_> {
+    fn drop(&mut self) {
+        if let Some(queued) = self.0 {
+            queued.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<T> CountedSender<T> {
+    pub async fn send(&self, message: T) -> Result<(), SendError<T>> {
+        // The message is counted before it is sent, so it is never received before it is counted.
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        let mut unsent = Unsent(Some(&self.queued));
+        let result = self.tx.send(message).await;
+
+        if result.is_ok() {
+            unsent.0 = None;
+        }
+
+        return result;
+    }
+}
+
+impl<T> CountedReceiver<T> {
+    pub async fn recv(&mut self) -> Option<T> {
+        let message = self.rx.recv().await;
+
+        if message.is_some() {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        return message;
+    }
+}
+
+/// The receiving half of a pty task's channels. Control messages, i.e. resizes and shutdowns, have
+/// their own channel so they are never stuck behind input when the pty is busy.
+pub struct ServerReceiver {
+    control: UnboundedReceiver<ServerMessage>,
+    data: CountedReceiver<ServerMessage>,
+}
+
+impl ServerReceiver {
+    /// Receive the next message, control messages are received before any queued input.
+    pub async fn recv(&mut self) -> Option<ServerMessage> {
+        return select! {
+            biased;
+
+            Some(message) = self.control.recv() => Some(message),
+            message = self.data.recv() => message,
+        };
+    }
+}
+
+/// The controller's side of a pty's channels.
 struct Channel {
-    id: usize,
-    rx: Receiver<PtyMessage>,
-    tx: Sender<ServerMessage>,
+    control: UnboundedSender<ServerMessage>,
+    data: CountedSender<ServerMessage>,
+    queued_output: Arc<AtomicUsize>,
+    /// Distinguishes the channel from earlier channels with the same id, as the ids of closed
+    /// panels are reused.
+    generation: usize,
 }
 
+/// The messages from a pty, with the pty's id and the generation of its channel. The last message
+/// is None, once the pty task has closed its channel.
+type PtyMessages = BoxStream<'static, (usize, usize, Option<PtyMessage>)>;
+
 pub struct ChannelController {
     stdin_rx: Receiver<Vec<u8>>,
-    ptys: Vec<Channel>,
+    background_rx: Receiver<Vec<u8>>,
+    background_tx: Sender<Vec<u8>>,
+    /// Receives SIGWINCH, None if the handler couldn't be installed.
+    terminal_resize: Option<Signal>,
+    ptys: HashMap<usize, Channel>,
+    /// The messages from every pty, the messages of ptys that have been removed are ignored.
+    pty_messages: SelectAll<PtyMessages>,
+    /// The generation given to the next channel that is opened.
+    next_generation: usize,
+    /// The amount of time allowed for sending a message to a pty.
+    send_timeout: Duration,
 }
 
 impl ChannelController {
@@ -54,18 +194,23 @@ impl ChannelController {
     const BUFFER_SIZE: usize = 100;
     /// The amount of time allowed for each pty to shutdown
     const SHUTDOWN_TIMEOUT_MS: u64 = 200;
-    /// The amount of time to delay when writing
-    const SEND_TIMEOUT_MS: u64 = 200;
 
     /// Creates a new instance of the channel controller, it returns an instance and the stdin
     /// sender that should send any stdin input..
-    pub fn new() -> (Self, Sender<Vec<u8>>) {
+    pub fn new(send_timeout: Duration) -> (Self, Sender<Vec<u8>>) {
         let (tx, rx) = mpsc::channel(Self::BUFFER_SIZE);
+        let (background_tx, background_rx) = mpsc::channel(Self::BUFFER_SIZE);
 
         return (
             Self {
                 stdin_rx: rx,
-                ptys: Vec::new(),
+                background_rx,
+                background_tx,
+                terminal_resize: signal(SignalKind::window_change()).ok(),
+                ptys: HashMap::new(),
+                pty_messages: SelectAll::new(),
+                next_generation: 0,
+                send_timeout,
             },
             tx,
         );
@@ -73,59 +218,120 @@ impl ChannelController {
 
     /// Open a new channel the necessary components are kept and tracked in the controller whilst,
     /// the send stdout sender, input receiver and shutdown receiver are returned.
-    pub fn new_channel(&mut self, id: usize) -> (Sender<PtyMessage>, Receiver<ServerMessage>) {
-        let (stdout_tx, stdout_rx) = mpsc::channel(Self::BUFFER_SIZE);
-        let (stdin_tx, stdin_rx) = mpsc::channel(Self::BUFFER_SIZE);
-
-        self.ptys.push(Channel {
+    pub fn new_channel(&mut self, id: usize) -> (CountedSender<PtyMessage>, ServerReceiver) {
+        let (stdout_tx, stdout_rx, queued_output) = counted_channel(Self::BUFFER_SIZE);
+        let (stdin_tx, stdin_rx, _) = counted_channel(Self::BUFFER_SIZE);
+        let (control_tx, control_rx) = mpsc::unbounded_channel();
+        let generation = self.next_generation;
+        self.next_generation += 1;
+
+        self.ptys.insert(
             id,
-            rx: stdout_rx,
-            tx: stdin_tx,
-        });
+            Channel {
+                control: control_tx,
+                data: stdin_tx,
+                queued_output,
+                generation,
+            },
+        );
+        self.pty_messages
+            .push(Self::pty_message_stream(id, generation, stdout_rx));
 
-        return (stdout_tx, stdin_rx);
+        return (
+            stdout_tx,
+            ServerReceiver {
+                control: control_rx,
+                data: stdin_rx,
+            },
+        );
+    }
+
+    fn pty_message_stream(
+        id: usize,
+        generation: usize,
+        rx: CountedReceiver<PtyMessage>,
+    ) -> PtyMessages {
+        return futures::stream::unfold(Some(rx), move |rx| async move {
+            let mut rx = rx?;
+            let message = rx.recv().await;
+            // The stream ends after reporting that the channel closed.
+            let rx = message.as_ref().map(|_| rx);
+
+            return Some(((id, generation, message), rx));
+        })
+        .boxed();
+    }
+
+    /// Returns the number of messages queued in the channels of each pty.
+    pub fn channel_stats(&self) -> HashMap<usize, ChannelStats> {
+        return self
+            .ptys
+            .iter()
+            .map(|(id, channel)| {
+                (
+                    *id,
+                    ChannelStats {
+                        queued_input: channel.data.queued.load(Ordering::Relaxed),
+                        queued_output: channel.queued_output.load(Ordering::Relaxed),
+                    },
+                )
+            })
+            .collect();
+    }
+
+    /// Returns a sender that background tasks can use to report a message to the event loop.
+    pub fn background_sender(&self) -> Sender<Vec<u8>> {
+        return self.background_tx.clone();
+    }
+
+    /// Wait until the terminal is resized, this never completes if SIGWINCH isn't handled.
+    async fn wait_for_terminal_resize(terminal_resize: &mut Option<Signal>) -> Option<()> {
+        return match terminal_resize {
+            Some(signal) => signal.recv().await,
+            None => futures::future::pending().await,
+        };
     }
 
     /// Shutdown a pty thread and remove it from the channel controller.
     pub async fn send_shutdown(&mut self, id: usize) {
-        for i in 0..self.ptys.len() {
-            if self.ptys[i].id == id {
-                let timer = tokio::time::sleep(Duration::from_millis(Self::SHUTDOWN_TIMEOUT_MS));
-
-                select! {
-                    result = self.ptys[i].tx.send(ServerMessage::Shutdown) => {
-                         // Try to shutdown, if this fails then we just exit.
-                        if result.is_ok() {
-                            // Give the thread a chance to shutdown.
-                            std::thread::sleep(Duration::from_millis(Self::SHUTDOWN_TIMEOUT_MS));
-                        }
-                    }
-                    _ = timer => {}
-                }
-
-                self.ptys.remove(i);
-                return;
-            }
+        if let Some(channel) = self.ptys.remove(&id) {
+            Self::shutdown_channel(channel).await;
         }
     }
 
     /// Shutdown all open pty's.
     pub async fn shutdown_all(mut self) {
-        while self.ptys.len() > 0 {
-            let timer = tokio::time::sleep(Duration::from_millis(Self::SHUTDOWN_TIMEOUT_MS));
-
-            select! {
-                result = self.ptys[0].tx.send(ServerMessage::Shutdown) => {
-                     // Try to shutdown, if this fails then we just exit.
-                    if result.is_ok() {
-                        // Give the thread a chance to shutdown.
-                        std::thread::sleep(Duration::from_millis(Self::SHUTDOWN_TIMEOUT_MS));
-                    }
+        for (_, channel) in self.ptys.drain() {
+            Self::shutdown_channel(channel).await;
+        }
+    }
+
+    async fn shutdown_channel(channel: Channel) {
+        // Try to shutdown, if this fails then we just exit.
+        if channel.control.send(ServerMessage::Shutdown).is_ok() {
+            // Give the thread a chance to shutdown.
+            std::thread::sleep(Duration::from_millis(Self::SHUTDOWN_TIMEOUT_MS));
+        }
+    }
+
+    /// Wait for the next message from a pty that hasn't been removed, this never completes if
+    /// there are no ptys. The messages of a removed pty are ignored even if a new pty has its id.
+    async fn next_pty_message(
+        pty_messages: &mut SelectAll<PtyMessages>,
+        ptys: &HashMap<usize, Channel>,
+    ) -> (usize, Option<PtyMessage>) {
+        loop {
+            match pty_messages.next().await {
+                Some((id, generation, message))
+                    if ptys
+                        .get(&id)
+                        .is_some_and(|channel| channel.generation == generation) =>
+                {
+                    return (id, message)
                 }
-                _ = timer => {}
+                Some(_) => continue,
+                None => return futures::future::pending().await,
             }
-
-            self.ptys.remove(0);
         }
     }
 
@@ -134,56 +340,63 @@ impl ChannelController {
     /// that has shutdown.
     pub async fn wait_for_message(&mut self) -> Result<ControllerResponse, ChannelWaitFail> {
         let bytes;
+        let mut frame = None;
+        let mut blocked_input = None;
         let channel_id: ChannelID;
         let mut error = None;
-        let mut index = None;
 
-        if self.ptys.is_empty() {
-            bytes = self.stdin_rx.recv().await;
-            channel_id = ChannelID::Stdin;
-        } else {
-            tokio::select! {
-                b = self.stdin_rx.recv() => {
-                    bytes = b;
-                }
+        // Input is checked first, so it is handled before any queued panel output.
+        tokio::select! {
+            biased;
 
-                (message, i, _) = futures::future::select_all(
-                self.ptys
-                    .iter_mut()
-                    .map(|pair| pair.rx.recv().boxed())) => {
-                        match message {
-                            Some(PtyMessage::Bytes(b)) => {
-                                bytes = Some(b);
-                                error = None;
-                            },
-                            Some(PtyMessage::Error(e)) => {
-                                bytes = None;
-                                error = Some(e);
-                            },
-                            None => {
-                                bytes = None;
-                            }
-                        }
-
-                        index = Some(i);
-                   }
+            b = self.stdin_rx.recv() => {
+                bytes = b;
+                channel_id = ChannelID::Stdin;
             }
 
-            if let Some(i) = index {
-                channel_id = ChannelID::Pty(self.ptys[i].id);
-            } else {
-                channel_id = ChannelID::Stdin;
+            b = self.background_rx.recv() => {
+                bytes = b;
+                channel_id = ChannelID::Background;
+            }
+
+            Some(_) = Self::wait_for_terminal_resize(&mut self.terminal_resize) => {
+                bytes = Some(Vec::new());
+                channel_id = ChannelID::TerminalResize;
+            }
+
+            (id, message) = Self::next_pty_message(&mut self.pty_messages, &self.ptys) => {
+                match message {
+                    Some(PtyMessage::Frame(f)) => {
+                        bytes = Some(Vec::new());
+                        frame = Some(f);
+                    }
+                    Some(PtyMessage::InputBlocked(waiting)) => {
+                        bytes = Some(Vec::new());
+                        blocked_input = Some(waiting);
+                    }
+                    Some(PtyMessage::Error(e)) => {
+                        bytes = None;
+                        error = Some(e.with_panel_id(id));
+                    },
+                    None => {
+                        bytes = None;
+                    }
+                }
+
+                channel_id = ChannelID::Pty(id);
             }
         }
 
         if let Some(bytes) = bytes {
             return Ok(ControllerResponse {
                 bytes,
+                frame,
+                blocked_input,
                 id: channel_id,
             });
         } else {
-            if channel_id != ChannelID::Stdin {
-                self.ptys.remove(index.unwrap());
+            if let ChannelID::Pty(id) = channel_id {
+                self.ptys.remove(&id);
             }
 
             return Err(ChannelWaitFail {
@@ -206,34 +419,109 @@ impl ChannelController {
     }
 
     /// Send a message to a channel with the specified id. Returns an error if something
-    /// failed when sending the data or if no panel exists with the specified id.
+    /// failed when sending the data or if no panel exists with the specified id. Input can time
+    /// out if the pty is busy, control messages are queued separately and are always sent.
     pub async fn write_message(
         &mut self,
         id: usize,
         message: ServerMessage,
     ) -> Result<(), MuxideError> {
-        for channel in &mut self.ptys {
-            if channel.id == id {
-                let slp = time::sleep(Duration::from_millis(Self::SEND_TIMEOUT_MS));
-
-                select! {
-                    res = channel.tx.send(message) => {
-                        if let Err(e) = res {
-                            return Err(ErrorType::PTYWriteError { description: format!("Error while sending message. Error: {}", e)}.into_error());
-                        }
-                    }
-                    _ = slp => {
-                        return Err(ErrorType::PTYWriteError { description: String::from("Timeout while sending message.")}.into_error());
-                    }
+        let channel = match self.ptys.get(&id) {
+            Some(channel) => channel,
+            None => {
+                return Err(ErrorType::PTYWriteError {
+                    description: format!("No panel with the id: {}", id),
                 }
-
-                return Ok(());
+                .into_error()
+                .with_panel_id(id))
             }
+        };
+
+        if !matches!(message, ServerMessage::Bytes(_)) {
+            return channel.control.send(message).map_err(|e| {
+                ErrorType::PTYWriteError {
+                    description: format!("Error while sending message. Error: {}", e),
+                }
+                .into_error()
+                .with_panel_id(id)
+            });
         }
 
-        return Err(ErrorType::PTYWriteError {
-            description: format!("No panel with the id: {}", id),
+        let slp = time::sleep(self.send_timeout);
+
+        select! {
+            res = channel.data.send(message) => {
+                if let Err(e) = res {
+                    return Err(ErrorType::PTYWriteError { description: format!("Error while sending message. Error: {}", e)}.into_error().with_panel_id(id));
+                }
+            }
+            _ = slp => {
+                return Err(ErrorType::PTYWriteError { description: String::from("Timeout while sending message.")}.into_error().with_panel_id(id));
+            }
         }
-        .into_error());
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChannelController, ChannelID, ChannelStats, PtyMessage, ServerMessage};
+    use crate::error::ErrorType;
+    use crate::geometry::Size;
+    use crate::panel_screen::PanelScreen;
+    use tokio::time::Duration;
+
+    #[tokio::test]
+    async fn test_channels() {
+        let (mut controller, _stdin_tx) = ChannelController::new(Duration::from_millis(50));
+        let (tx, mut rx) = controller.new_channel(1);
+        let (removed_tx, _removed_rx) = controller.new_channel(2);
+
+        controller
+            .write_message(1, ServerMessage::Bytes(b"ls".to_vec()))
+            .await
+            .unwrap();
+        controller
+            .write_message(1, ServerMessage::Resize(Size::new(5, 5)))
+            .await
+            .unwrap();
+        let frame = PanelScreen::new(Size::new(1, 1), 0).frame();
+        tx.send(PtyMessage::Frame(frame)).await.unwrap();
+
+        assert_eq!(
+            controller.channel_stats()[&1],
+            ChannelStats {
+                queued_input: 1,
+                queued_output: 1,
+            }
+        );
+        // The resize is received before the input that was queued first.
+        assert!(matches!(rx.recv().await, Some(ServerMessage::Resize(_))));
+        assert!(matches!(rx.recv().await, Some(ServerMessage::Bytes(_))));
+        assert!(controller
+            .write_message(3, ServerMessage::StopLogging)
+            .await
+            .is_err());
+
+        // Messages from a removed channel are ignored, even once its id is reused.
+        controller.send_shutdown(2).await;
+        let (_reused_tx, _reused_rx) = controller.new_channel(2);
+        removed_tx
+            .send(PtyMessage::Error(ErrorType::PTYStoppedRunning.into_error()))
+            .await
+            .unwrap();
+        drop(removed_tx);
+
+        let response = controller.wait_for_message().await.unwrap();
+        assert_eq!(response.id, ChannelID::Pty(1));
+        assert_eq!(controller.channel_stats()[&1], ChannelStats::default());
+
+        drop(tx);
+        assert_eq!(
+            controller.wait_for_message().await.unwrap_err().id,
+            ChannelID::Pty(1)
+        );
+        assert_eq!(controller.channel_stats().keys().collect::<Vec<_>>(), [&2]);
     }
 }
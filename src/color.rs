@@ -16,6 +16,21 @@ macro_rules! define_new_color {
     };
 }
 
+/// Returns true if the terminal muxide is running in supports 24 bit colors, according to
+/// COLORTERM or the terminal's terminfo entry.
+pub fn truecolor_supported() -> bool {
+    if let Ok(colorterm) = std::env::var("COLORTERM") {
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return true;
+        }
+    }
+
+    return TERMINFO_DATABASE
+        .as_ref()
+        .and_then(|database| database.get::<terminfo::capability::TrueColor>())
+        .is_some_and(|b| b.0);
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Color {
     r: u8,
@@ -1,50 +1,181 @@
-#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+use crate::config::KeyTable;
+use std::collections::HashMap;
+
+/// Maps the lowercase name of a user defined alias to the commands it runs.
+pub type CommandAliases = HashMap<String, Vec<Command>>;
+
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
 pub enum Command {
     EnterSingleCharacterCommand,
+    SendPrefixCommand,
     CloseSelectedPanelCommand,
     OpenPanelCommand,
     FocusWorkspaceCommand(usize),
+    /// Focuses the next workspace in the order they are displayed, 1 to 9 then 0.
+    NextWorkspaceCommand,
+    PreviousWorkspaceCommand,
+    /// Focuses the workspace that was focused before the current workspace.
+    FocusLastWorkspaceCommand,
+    /// Moves the selected panel to the first empty workspace.
+    BreakPanelCommand,
+    /// Moves the selected panel of a workspace into the current workspace.
+    JoinPanelCommand(usize),
     SubdivideSelectedVerticalCommand,
     SubdivideSelectedHorizontalCommand,
     FocusPanelLeftCommand,
     FocusPanelRightCommand,
     FocusPanelUpCommand,
     FocusPanelDownCommand,
+    /// Focuses the next panel in the workspace, from the top left to the bottom right.
+    NextPanelCommand,
+    PreviousPanelCommand,
     MergePanelCommand,
+    /// Moves the split line next to the selected panel to make the panel larger.
+    GrowPanelCommand,
+    /// Moves the split line next to the selected panel to make the panel smaller.
+    ShrinkPanelCommand,
+    /// Restores the layout of the workspace from before its last change.
+    UndoLayoutCommand,
     ScrollUpCommand,
     ScrollDownCommand,
+    /// Stops, or resumes, displaying the selected panel's output. The output is buffered whilst
+    /// paused.
+    PauseOutputCommand,
+    /// Stops, or resumes, sending input to the selected panel. Its output is still displayed.
+    ToggleReadOnlyCommand,
+    /// Labels the URLs and paths in the selected panel, the selected one is copied or opened.
+    HintsCommand,
+    /// Starts, or stops, appending the selected panel's output to a file.
+    ToggleLoggingCommand(String),
+    /// Opens a panel following the file the selected panel is logged to, it keeps its place
+    /// whilst scrolled back and follows the file again once scrolled to the bottom.
+    FollowLogCommand,
+    /// Runs the last ssh command of the selected panel again, once its connection has closed.
+    ReconnectPanelCommand,
     HelpMessageCommand,
+    IdentifyPanelsCommand,
+    ShowMessageHistoryCommand,
+    ShowStatsCommand,
+    /// Displays the most recent messages written to muxide's log.
+    ShowLogCommand,
+    EnterKeyTableCommand(KeyTable),
     LockCommand,
     QuitCommand,
+    /// Runs a shell command in the background, reporting its exit status once it finishes.
+    RunShellCommand(String),
+    /// Runs a command in a popup above the current layout, the popup closes when it exits.
+    PopupCommand(String),
+    /// Shows, or hides, a shell in a popup that keeps running whilst it is hidden.
+    ToggleScratchCommand,
+    /// Saves the layout of the selected workspace with the specified name.
+    SaveLayoutCommand(String),
+    /// A user defined alias, with its name and the commands it runs in order.
+    AliasCommand(String, Vec<Command>),
 }
 
 impl Command {
+    /// The categories commands are grouped by in the help message, in the order they are shown.
+    pub const CATEGORIES: [&'static str; 4] = ["General", "Panels", "Workspaces", "Custom"];
+
+    /// Returns the category this command is listed under in the help message.
+    pub fn category(&self) -> &'static str {
+        return match self {
+            Self::OpenPanelCommand
+            | Self::CloseSelectedPanelCommand
+            | Self::SubdivideSelectedVerticalCommand
+            | Self::SubdivideSelectedHorizontalCommand
+            | Self::FocusPanelLeftCommand
+            | Self::FocusPanelRightCommand
+            | Self::FocusPanelUpCommand
+            | Self::FocusPanelDownCommand
+            | Self::NextPanelCommand
+            | Self::PreviousPanelCommand
+            | Self::MergePanelCommand
+            | Self::GrowPanelCommand
+            | Self::ShrinkPanelCommand
+            | Self::UndoLayoutCommand
+            | Self::ScrollUpCommand
+            | Self::ScrollDownCommand
+            | Self::PauseOutputCommand
+            | Self::ToggleReadOnlyCommand
+            | Self::HintsCommand
+            | Self::ToggleLoggingCommand(_)
+            | Self::FollowLogCommand
+            | Self::ReconnectPanelCommand
+            | Self::IdentifyPanelsCommand
+            | Self::PopupCommand(_)
+            | Self::ToggleScratchCommand => Self::CATEGORIES[1],
+            Self::FocusWorkspaceCommand(_)
+            | Self::NextWorkspaceCommand
+            | Self::PreviousWorkspaceCommand
+            | Self::FocusLastWorkspaceCommand
+            | Self::BreakPanelCommand
+            | Self::JoinPanelCommand(_)
+            | Self::SaveLayoutCommand(_) => Self::CATEGORIES[2],
+            Self::RunShellCommand(_) | Self::AliasCommand(_, _) => Self::CATEGORIES[3],
+            _ => Self::CATEGORIES[0],
+        };
+    }
+
     pub fn get_name(&self) -> &str {
         return match self {
             Self::EnterSingleCharacterCommand => "EnterSingleCharacter",
+            Self::SendPrefixCommand => "SendPrefix",
             Self::CloseSelectedPanelCommand => "CloseSelectedPanel",
             Self::OpenPanelCommand => "OpenPanel",
             Self::FocusWorkspaceCommand(_) => "FocusWorkspace",
+            Self::NextWorkspaceCommand => "NextWorkspace",
+            Self::PreviousWorkspaceCommand => "PreviousWorkspace",
+            Self::FocusLastWorkspaceCommand => "FocusLastWorkspace",
+            Self::BreakPanelCommand => "BreakPanel",
+            Self::JoinPanelCommand(_) => "JoinPanel",
             Self::SubdivideSelectedVerticalCommand => "SubdivideSelectedVertical",
             Self::SubdivideSelectedHorizontalCommand => "SubdivideSelectedHorizontal",
             Self::FocusPanelLeftCommand => "FocusPanelLeft",
             Self::FocusPanelRightCommand => "FocusPanelRight",
             Self::FocusPanelUpCommand => "FocusPanelUp",
             Self::FocusPanelDownCommand => "FocusPanelDown",
+            Self::NextPanelCommand => "NextPanel",
+            Self::PreviousPanelCommand => "PreviousPanel",
             Self::MergePanelCommand => "MergePanel",
+            Self::GrowPanelCommand => "GrowPanel",
+            Self::ShrinkPanelCommand => "ShrinkPanel",
+            Self::UndoLayoutCommand => "UndoLayout",
             Self::ScrollUpCommand => "ScrollUp",
             Self::ScrollDownCommand => "ScrollDown",
+            Self::PauseOutputCommand => "PauseOutput",
+            Self::ToggleReadOnlyCommand => "ToggleReadOnly",
+            Self::HintsCommand => "Hints",
+            Self::ToggleLoggingCommand(_) => "ToggleLogging",
+            Self::FollowLogCommand => "FollowLog",
+            Self::ReconnectPanelCommand => "ReconnectPanel",
             Self::HelpMessageCommand => "Help",
+            Self::IdentifyPanelsCommand => "IdentifyPanels",
+            Self::ShowMessageHistoryCommand => "ShowMessageHistory",
+            Self::ShowStatsCommand => "ShowStats",
+            Self::ShowLogCommand => "ShowLog",
+            Self::EnterKeyTableCommand(_) => "EnterKeyTable",
             Self::LockCommand => "Lock",
             Self::QuitCommand => "Quit",
+            Self::RunShellCommand(_) => "RunShell",
+            Self::PopupCommand(_) => "Popup",
+            Self::ToggleScratchCommand => "ToggleScratch",
+            Self::SaveLayoutCommand(_) => "SaveLayout",
+            Self::AliasCommand(name, _) => name.as_str(),
         };
     }
 
     pub fn help_text(&self) -> Option<String> {
         return Some(match self {
+            Self::SendPrefixCommand => "Send the prefix to the panel".to_string(),
             Self::CloseSelectedPanelCommand => "Close selected panel".to_string(),
             Self::OpenPanelCommand => "Open new panel".to_string(),
             Self::FocusWorkspaceCommand(n) => format!("Focus workspace {}", n),
+            Self::NextWorkspaceCommand => "Focus next workspace".to_string(),
+            Self::PreviousWorkspaceCommand => "Focus previous workspace".to_string(),
+            Self::FocusLastWorkspaceCommand => "Focus last used workspace".to_string(),
+            Self::BreakPanelCommand => "Move panel to an empty workspace".to_string(),
+            Self::JoinPanelCommand(n) => format!("Move panel from workspace {} here", n),
             Self::SubdivideSelectedVerticalCommand => {
                 "Split panel with a vertical line".to_string()
             }
@@ -55,12 +186,45 @@ impl Command {
             Self::FocusPanelRightCommand => "Focus panel to the right".to_string(),
             Self::FocusPanelUpCommand => "Focus panel upwards".to_string(),
             Self::FocusPanelDownCommand => "Focus panel downwards".to_string(),
-            Self::MergePanelCommand => "Merge empty split".to_string(),
+            Self::NextPanelCommand => "Focus next panel".to_string(),
+            Self::PreviousPanelCommand => "Focus previous panel".to_string(),
+            Self::MergePanelCommand => "Merge split, closing the other panels".to_string(),
+            Self::GrowPanelCommand => "Grow selected panel".to_string(),
+            Self::ShrinkPanelCommand => "Shrink selected panel".to_string(),
+            Self::UndoLayoutCommand => "Undo the last layout change".to_string(),
             Self::ScrollUpCommand => "Scroll panel up".to_string(),
             Self::ScrollDownCommand => "Scroll panel down".to_string(),
+            Self::PauseOutputCommand => "Pause or resume panel output".to_string(),
+            Self::ToggleReadOnlyCommand => "Block or allow input to the panel".to_string(),
+            Self::HintsCommand => "Copy or open a URL or path".to_string(),
+            Self::ToggleLoggingCommand(path) => format!("Toggle logging panel output to {}", path),
+            Self::FollowLogCommand => "Follow the panel's log file in a new panel".to_string(),
+            Self::ReconnectPanelCommand => "Run the panel's ssh command again".to_string(),
             Self::HelpMessageCommand => "Display help".to_string(),
+            Self::IdentifyPanelsCommand => "Display panel ids".to_string(),
+            Self::ShowMessageHistoryCommand => "Display previous messages".to_string(),
+            Self::ShowStatsCommand => {
+                "Display throughput statistics and the activity of each panel".to_string()
+            }
+            Self::ShowLogCommand => "Display recent log messages".to_string(),
+            Self::EnterKeyTableCommand(KeyTable::Root) => {
+                "Return to the root key table".to_string()
+            }
+            Self::EnterKeyTableCommand(t) => format!("Enter {} mode", t),
             Self::LockCommand => "Lock the display".to_string(),
             Self::QuitCommand => "Quit".to_string(),
+            Self::RunShellCommand(command) => format!("Run `{}`", command),
+            Self::PopupCommand(command) => format!("Open `{}` in a popup", command),
+            Self::ToggleScratchCommand => "Show or hide the scratch terminal".to_string(),
+            Self::SaveLayoutCommand(name) => format!("Save the layout as {}", name),
+            Self::AliasCommand(_, commands) => format!(
+                "Run {}",
+                commands
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
             _ => return None,
         });
     }
@@ -68,10 +232,55 @@ impl Command {
     pub fn args(&self) -> Vec<String> {
         return match self {
             Command::FocusWorkspaceCommand(a) => vec![format!("{}", a)],
+            Command::JoinPanelCommand(a) => vec![format!("{}", a)],
+            Command::EnterKeyTableCommand(t) => vec![t.to_string()],
+            Command::RunShellCommand(command) => vec![command.clone()],
+            Command::PopupCommand(command) => vec![command.clone()],
+            Command::SaveLayoutCommand(name) => vec![name.clone()],
+            Command::ToggleLoggingCommand(path) => vec![path.clone()],
             _ => Vec::new(),
         };
     }
 
+    /// Parses a command, checking the user defined aliases before the built in commands.
+    pub fn try_from_string_with_aliases(
+        name: String,
+        args: Vec<String>,
+        aliases: &CommandAliases,
+    ) -> Result<Self, String> {
+        if let Some(commands) = aliases.get(&name.to_lowercase()) {
+            if !args.is_empty() {
+                return Err(format!(
+                    "The {} alias expects 0 arguments but {} were provided",
+                    name,
+                    args.len()
+                ));
+            }
+
+            return Ok(Self::AliasCommand(name, commands.clone()));
+        }
+
+        return Self::try_from_string(name, args);
+    }
+
+    /// Parses an alias definition, i.e. "FocusWorkspace 2", into a built in command.
+    pub fn try_from_definition(definition: &str) -> Result<Self, String> {
+        let mut parts = definition.split_whitespace().map(|s| s.to_string());
+        let name = parts
+            .next()
+            .ok_or("Expected a command name in the alias definition.".to_string())?;
+
+        return Self::try_from_string(name, parts.collect());
+    }
+
+    /// Returns true if a name refers to a built in command.
+    pub fn is_builtin_name(name: &str) -> bool {
+        return match Self::try_from_string(name.to_string(), Vec::new()) {
+            Ok(_) => true,
+            Err(e) => !e.starts_with("Unknown command"),
+        };
+    }
+
     pub fn try_from_string(name: String, mut args: Vec<String>) -> Result<Self, String> {
         let lowered_name = name.to_lowercase();
 
@@ -79,20 +288,40 @@ impl Command {
 
         let cmd = match lowered_name.as_str() {
             "entersinglecharacter" => Self::EnterSingleCharacterCommand,
+            "sendprefix" => Self::SendPrefixCommand,
             "openpanel" => Self::OpenPanelCommand,
             "quit" => Self::QuitCommand,
             "subdivideselectedhorizontal" => Self::SubdivideSelectedHorizontalCommand,
             "subdivideselectedvertical" => Self::SubdivideSelectedVerticalCommand,
+            "nextworkspace" => Self::NextWorkspaceCommand,
+            "previousworkspace" => Self::PreviousWorkspaceCommand,
+            "focuslastworkspace" => Self::FocusLastWorkspaceCommand,
+            "breakpanel" => Self::BreakPanelCommand,
             "focuspanelleft" => Self::FocusPanelLeftCommand,
             "focuspanelright" => Self::FocusPanelRightCommand,
             "focuspanelup" => Self::FocusPanelUpCommand,
             "focuspaneldown" => Self::FocusPanelDownCommand,
+            "nextpanel" => Self::NextPanelCommand,
+            "previouspanel" => Self::PreviousPanelCommand,
             "mergepanel" => Self::MergePanelCommand,
+            "growpanel" => Self::GrowPanelCommand,
+            "shrinkpanel" => Self::ShrinkPanelCommand,
+            "undolayout" => Self::UndoLayoutCommand,
             "closeselectedpanel" => Self::CloseSelectedPanelCommand,
             "lock" => Self::LockCommand,
             "scrollup" => Self::ScrollUpCommand,
             "scrolldown" => Self::ScrollDownCommand,
+            "pauseoutput" => Self::PauseOutputCommand,
+            "togglereadonly" => Self::ToggleReadOnlyCommand,
+            "hints" => Self::HintsCommand,
+            "followlog" => Self::FollowLogCommand,
+            "reconnectpanel" => Self::ReconnectPanelCommand,
             "help" => Self::HelpMessageCommand,
+            "identifypanels" => Self::IdentifyPanelsCommand,
+            "showmessagehistory" => Self::ShowMessageHistoryCommand,
+            "showstats" => Self::ShowStatsCommand,
+            "showlog" => Self::ShowLogCommand,
+            "togglescratch" => Self::ToggleScratchCommand,
             "focusworkspace" => {
                 if args.len() != 1 {
                     return Err(
@@ -108,10 +337,77 @@ impl Command {
                 required_1_arg = false;
                 Self::FocusWorkspaceCommand(arg)
             }
+            "joinpanel" => {
+                if args.len() != 1 {
+                    return Err(
+                        "The join panel command must be supplied an integer argument.".to_string(),
+                    );
+                }
+
+                let arg = args.pop().unwrap().parse::<usize>().map_err(|_| {
+                    "The join panel command must be supplied an integer argument.".to_string()
+                })?;
+
+                required_1_arg = false;
+                Self::JoinPanelCommand(arg)
+            }
+            "enterkeytable" => {
+                if args.len() != 1 {
+                    return Err(
+                        "The enter key table command must be supplied the name of a key table."
+                            .to_string(),
+                    );
+                }
+
+                required_1_arg = false;
+                Self::EnterKeyTableCommand(KeyTable::try_from_string(&args.pop().unwrap())?)
+            }
+            "runshell" => {
+                if args.is_empty() {
+                    return Err(
+                        "The run shell command must be supplied the command to run.".to_string()
+                    );
+                }
+
+                required_1_arg = false;
+                Self::RunShellCommand(args.join(" "))
+            }
+            "popup" => {
+                if args.is_empty() {
+                    return Err(
+                        "The popup command must be supplied the command to run.".to_string()
+                    );
+                }
+
+                required_1_arg = false;
+                Self::PopupCommand(args.join(" "))
+            }
+            "togglelogging" => {
+                if args.is_empty() {
+                    return Err(
+                        "The toggle logging command must be supplied the path of the log file."
+                            .to_string(),
+                    );
+                }
+
+                required_1_arg = false;
+                Self::ToggleLoggingCommand(args.join(" "))
+            }
+            "savelayout" => {
+                if args.len() != 1 {
+                    return Err(
+                        "The save layout command must be supplied the name of the layout."
+                            .to_string(),
+                    );
+                }
+
+                required_1_arg = false;
+                Self::SaveLayoutCommand(args.pop().unwrap())
+            }
             _ => return Err(format!("Unknown command: {}", name)),
         };
 
-        if required_1_arg && args.len() != 0 {
+        if required_1_arg && !args.is_empty() {
             return Err(format!(
                 "The {} command expects 0 arguments but {} were provided",
                 cmd.get_name(),
@@ -128,3 +424,57 @@ impl std::fmt::Display for Command {
         return write!(f, "{}", self.get_name());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Command;
+    use proptest::prelude::*;
+
+    const WORDS: &[&str] = &[
+        "FocusWorkspace",
+        "joinpanel",
+        "EnterKeyTable",
+        "RunShell",
+        "popup",
+        "SaveLayout",
+        "ToggleLogging",
+        "OpenPanel",
+        "help",
+        "resize",
+        "copy",
+        "2",
+        "-1",
+        "18446744073709551616",
+        "",
+        "é",
+        "İ",
+    ];
+
+    fn words() -> impl Strategy<Value = Vec<String>> {
+        return prop::collection::vec(0..WORDS.len(), 0..6)
+            .prop_map(|words| words.iter().map(|i| WORDS[*i].to_string()).collect());
+    }
+
+    proptest! {
+        #[test]
+        fn test_commands_never_panic(
+            words in words(),
+            bytes in prop::collection::vec(any::<u8>(), 0..40)
+        ) {
+            if let Some((name, args)) = words.split_first() {
+                // A command that parses is parsed the same from its name and arguments.
+                if let Ok(cmd) = Command::try_from_string(name.clone(), args.to_vec()) {
+                    prop_assert_eq!(
+                        Command::try_from_string(cmd.get_name().to_string(), cmd.args()),
+                        Ok(cmd)
+                    );
+                }
+            }
+
+            let text = String::from_utf8_lossy(&bytes).into_owned();
+            let _ = Command::try_from_definition(&words.join(" "));
+            let _ = Command::try_from_definition(&text);
+            let _ = Command::is_builtin_name(&text);
+        }
+    }
+}
@@ -1,6 +1,14 @@
-use super::{Keys, PasswordSettings};
+use super::{
+    Highlight, HighlightRule, KeyboardProtocol, Keys, Layout, ModifiedKey, PasswordSettings,
+    StatusBar, Theme, Tuning, WorkspaceSetup,
+};
+use crate::command::{Command, CommandAliases};
+use crate::logging;
 use crate::Color;
+use muxide_logging::format::FormatItem;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::time::Duration;
 
 #[inline]
@@ -41,8 +49,74 @@ const fn serde_default_5() -> usize {
     return 5;
 }
 
+#[inline]
+const fn default_chord_timeout_ms() -> u64 {
+    return 1000;
+}
+
+#[inline]
+const fn default_message_duration_ms() -> u64 {
+    return 5000;
+}
+
+#[inline]
+const fn default_split_ratio() -> u16 {
+    return 50;
+}
+
+fn default_window_title() -> String {
+    return String::from("muxide: [{session}] {title}");
+}
+
+fn default_panel_term() -> String {
+    return String::from("screen-256color");
+}
+
+fn default_opener() -> String {
+    if cfg!(target_os = "macos") {
+        return String::from("open");
+    } else {
+        return String::from("xdg-open");
+    }
+}
+
+#[inline]
+fn default_focus_passthrough_processes() -> Vec<String> {
+    return vec!["vim".to_string(), "nvim".to_string()];
+}
+
+#[inline]
+const fn default_log_max_size_kb() -> u64 {
+    return 5120;
+}
+
+#[inline]
+const fn default_log_max_files() -> usize {
+    return 3;
+}
+
+#[inline]
+const fn default_log_history_size() -> usize {
+    return 500;
+}
+
+fn default_log_format() -> String {
+    return String::from("{time} [{level}] {message}");
+}
+
+fn default_log_time_format() -> String {
+    return String::from("%Y-%m-%d %H:%M:%S");
+}
+
+const fn default_scrollback_max_size_kb() -> usize {
+    return 1024;
+}
+
 #[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
 pub struct Config {
+    /// The name of the theme that is used, a theme defined in the config or a built in theme.
+    /// TOML requires values to come before tables, so this is the first field.
+    theme: Option<String>,
     #[serde(default)]
     environment: Environment,
     #[serde(default)]
@@ -51,6 +125,30 @@ pub struct Config {
     keys: Keys,
     #[serde(default)]
     password: PasswordSettings,
+    /// User defined commands, each name maps to a list of commands that are run in order.
+    #[serde(default)]
+    aliases: HashMap<String, Vec<String>>,
+    /// Named arrangements of panels.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    layouts: Vec<Layout>,
+    /// The panels workspaces start with.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    workspaces: Vec<WorkspaceSetup>,
+    /// Rules styling the text in panels that matches their patterns.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    highlights: Vec<Highlight>,
+    /// User defined themes, keyed by their names.
+    #[serde(default)]
+    themes: HashMap<String, Theme>,
+    /// The theme selected by the config, with the environment's colors if no theme is selected.
+    #[serde(skip)]
+    active_theme: Theme,
+    /// The timings of the tasks running the panels.
+    #[serde(default)]
+    tuning: Tuning,
+    /// The position and contents of the status bar.
+    #[serde(default)]
+    status_bar: StatusBar,
 
     /// Potentially can be removed
     thread_delay_period: Option<Duration>,
@@ -62,17 +160,105 @@ pub struct Environment {
     panel_init_command: String,
     #[serde(default = "default_prompt_text")]
     prompt_text: String,
+    /// Replaced by the theme's selected_border color if a theme is selected.
     #[serde(default)]
     selected_panel_color: Color,
+    /// Replaced by the theme's selected_workspace color if a theme is selected.
     #[serde(default)]
     selected_workspace_color: Color,
     #[serde(default = "serde_default_as_true")]
     show_workspaces: bool,
+    /// Draws a label on the top border of each panel with its number in the workspace, the
+    /// program running in it and whether it is read only, paused or logged.
+    #[serde(default)]
+    show_panel_labels: bool,
     #[serde(default = "serde_default_1")]
     log_level: usize,
     log_file: Option<String>,
+    /// The size the log file can grow to before it is rotated, 0 disables rotation. It is checked
+    /// when muxide starts and before each message is written.
+    #[serde(default = "default_log_max_size_kb")]
+    log_max_size_kb: u64,
+    /// The number of rotated log files that are kept, i.e. muxide.log.1 to muxide.log.3.
+    #[serde(default = "default_log_max_files")]
+    log_max_files: usize,
+    /// Compresses the rotated log files with gzip, i.e. muxide.log.1.gz.
+    #[serde(default)]
+    log_compress: bool,
+    /// The levels logged for each module, i.e. "warn,muxide::display=info". The MUXIDE_LOG
+    /// environment variable takes precedence.
+    log_filter: Option<String>,
+    /// The format of each line of the log, "{time}", "{level}", "{file}", "{line}", "{thread}"
+    /// and "{message}" are replaced with the details of the message.
+    #[serde(default = "default_log_format")]
+    log_format: String,
+    /// The strftime format of the time in the log, i.e. "%Y-%m-%d %H:%M:%S".
+    #[serde(default = "default_log_time_format")]
+    log_time_format: String,
+    /// The number of recent log messages kept in memory for the ShowLog command, 0 keeps none.
+    #[serde(default = "default_log_history_size")]
+    log_history_size: usize,
     #[serde(default = "serde_default_5")]
     scroll_lines: usize,
+    #[serde(default = "default_chord_timeout_ms")]
+    chord_timeout_ms: u64,
+    /// The amount of time a message is displayed for, 0 displays messages until they are replaced.
+    #[serde(default = "default_message_duration_ms")]
+    message_duration_ms: u64,
+    /// Disables all transient visual effects, i.e. highlighting layout changes.
+    #[serde(default)]
+    reduced_motion: bool,
+    /// Draws without colors, selections are shown in reverse video. Setting the NO_COLOR
+    /// environment variable has the same effect.
+    #[serde(default)]
+    monochrome: bool,
+    /// The title of the terminal's window, "{session}", "{title}" and "{workspace}" are replaced
+    /// with the session's name, the selected panel's title and the selected workspace. An empty
+    /// format leaves the title unchanged.
+    #[serde(default = "default_window_title")]
+    window_title: String,
+    /// The keyboard protocol requested from the terminal, an extended protocol is required for
+    /// shortcuts like "ctrl+shift+a" or "ctrl+enter".
+    #[serde(default)]
+    keyboard_protocol: KeyboardProtocol,
+    /// Skips workspaces without panels when cycling through the workspaces.
+    #[serde(default)]
+    skip_empty_workspaces: bool,
+    /// The percentage of a split panel's space given to the left or top panel.
+    #[serde(default = "default_split_ratio")]
+    split_ratio: u16,
+    /// The foreground processes that receive the shortcuts mapped to the focus panel commands,
+    /// so they can move between their own windows, i.e. with vim-tmux-navigator.
+    #[serde(default = "default_focus_passthrough_processes")]
+    focus_passthrough_processes: Vec<String>,
+    /// The command URLs and paths selected from a panel are opened with, they are passed as its
+    /// argument.
+    #[serde(default = "default_opener")]
+    opener: String,
+    /// Passes images drawn in the selected panel with sixel, the kitty graphics protocol or iTerm2
+    /// inline images through to the terminal. Images are placed in the panel but can extend past
+    /// its edges, as they aren't clipped.
+    #[serde(default)]
+    graphics_passthrough: bool,
+    /// The TERM set in panels, programs in panels use its terminfo entry rather than the outer
+    /// terminal's. An empty value leaves TERM unchanged.
+    #[serde(default = "default_panel_term")]
+    panel_term: String,
+    /// Prefixes each line of a panel's output logged with the ToggleLogging command with the
+    /// time it was output.
+    #[serde(default)]
+    panel_log_timestamps: bool,
+    /// The file each panel's recent output is saved to as text when the panel closes or muxide
+    /// quits, "{session}" and "{panel}" are replaced with the session's name and the panel's id.
+    /// A relative path is in the state directory, an empty path doesn't save the output.
+    #[serde(default)]
+    scrollback_file: String,
+    /// The amount of each panel's most recent output that is saved.
+    #[serde(default = "default_scrollback_max_size_kb")]
+    scrollback_max_size_kb: usize,
+    /// The shortcut that precedes single character commands, replaces any shortcut mapped to
+    /// the EnterSingleCharacter command.
+    prefix: Option<String>,
 }
 
 #[derive(Copy, Clone, PartialEq, Debug, Deserialize, Serialize)]
@@ -108,6 +294,14 @@ impl Config {
         return &mut self.keys;
     }
 
+    pub fn tuning(&self) -> Tuning {
+        return self.tuning;
+    }
+
+    pub fn status_bar(&self) -> &StatusBar {
+        return &self.status_bar;
+    }
+
     pub fn get_borders_ref(&self) -> &Borders {
         return &self.borders;
     }
@@ -124,31 +318,214 @@ impl Config {
         return &self.password;
     }
 
+    pub fn workspace_setups(&self) -> &Vec<WorkspaceSetup> {
+        return &self.workspaces;
+    }
+
+    /// Returns the layout with the specified name, if one is defined.
+    pub fn layout(&self, name: &str) -> Option<&Layout> {
+        return self.layouts.iter().find(|l| l.name == name);
+    }
+
     pub fn get_panel_init_command(&self) -> &String {
         return &self.environment.panel_init_command;
     }
 
     pub fn from_toml_string(toml: &str) -> Result<Self, String> {
-        return toml::from_str(toml).map_err(|e| e.to_string());
+        let mut config: Self = toml::from_str(toml).map_err(|e| e.to_string())?;
+        config.apply_prefix()?;
+        config.apply_aliases()?;
+        config.highlight_rules()?;
+        config.apply_theme()?;
+        config.check_workspace_setups()?;
+        config.tuning.validate()?;
+        config.status_bar.validate()?;
+        config.environment.log_format()?;
+
+        return Ok(config);
     }
 
     pub fn from_json_string(json: &str) -> Result<Self, String> {
-        return serde_json::from_str(json).map_err(|e| e.to_string());
+        let mut config: Self = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        config.apply_prefix()?;
+        config.apply_aliases()?;
+        config.highlight_rules()?;
+        config.apply_theme()?;
+        config.check_workspace_setups()?;
+        config.tuning.validate()?;
+        config.status_bar.validate()?;
+        config.environment.log_format()?;
+
+        return Ok(config);
+    }
+
+    /// Maps the prefix from the environment settings, if one was specified.
+    fn apply_prefix(&mut self) -> Result<(), String> {
+        if let Some(prefix) = self.environment.prefix.as_ref() {
+            match super::keys::key_from_string(prefix.clone()) {
+                Ok(key) => self.keys.set_prefix(key),
+                Err(e) => {
+                    // Prefixes the legacy encoding can't express, i.e. "ctrl+shift+p", are
+                    // received from terminals using an extended keyboard protocol.
+                    let key = ModifiedKey::from_string(prefix)
+                        .map_err(|_| format!("Invalid prefix: {}", e))?;
+
+                    self.keys.set_modified_prefix(key);
+                }
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Parses the alias definitions and substitutes them into the key mappings.
+    fn apply_aliases(&mut self) -> Result<(), String> {
+        let aliases = self.command_aliases()?;
+
+        return self.keys.resolve_aliases(&aliases);
+    }
+
+    /// Resolves the selected theme, themes defined in the config take precedence over the built
+    /// in themes with the same name.
+    fn apply_theme(&mut self) -> Result<(), String> {
+        self.active_theme = match self.theme.as_ref() {
+            Some(name) => self
+                .themes
+                .get(name)
+                .copied()
+                .or_else(|| Theme::builtin(name))
+                .ok_or(format!(
+                    "Unknown theme {}, the built in themes are: {}.",
+                    name,
+                    Theme::BUILTIN_NAMES.join(", ")
+                ))?,
+            None => Theme {
+                selected_border: self.environment.selected_panel_color,
+                selected_workspace: self.environment.selected_workspace_color,
+                ..Theme::default()
+            },
+        };
+
+        return Ok(());
+    }
+
+    /// Checks each workspace is set up once, with a layout that is defined. A workspace without a
+    /// layout has a single panel, so it can run at most one command.
+    fn check_workspace_setups(&self) -> Result<(), String> {
+        for (i, setup) in self.workspaces.iter().enumerate() {
+            if setup.workspace >= 10 {
+                return Err(format!("There is no workspace {}.", setup.workspace));
+            }
+
+            if self.workspaces[..i]
+                .iter()
+                .any(|s| s.workspace == setup.workspace)
+            {
+                return Err(format!(
+                    "Workspace {} is set up more than once.",
+                    setup.workspace
+                ));
+            }
+
+            match setup.layout.as_ref() {
+                Some(name) if self.layout(name).is_none() => {
+                    return Err(format!(
+                        "Workspace {} uses the layout {}, which isn't defined.",
+                        setup.workspace, name
+                    ));
+                }
+                None if setup.commands.len() > 1 => {
+                    return Err(format!(
+                        "Workspace {} runs more than one command without a layout.",
+                        setup.workspace
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the colors to draw with.
+    pub fn theme(&self) -> &Theme {
+        return &self.active_theme;
+    }
+
+    /// Returns the highlight rules with their patterns compiled, in the order they are defined.
+    pub fn highlight_rules(&self) -> Result<Vec<HighlightRule>, String> {
+        return self.highlights.iter().map(HighlightRule::new).collect();
+    }
+
+    /// Returns the parsed alias definitions, keyed by their lowercase names.
+    pub fn command_aliases(&self) -> Result<CommandAliases, String> {
+        let mut aliases = CommandAliases::new();
+
+        for (name, definition) in &self.aliases {
+            if Command::is_builtin_name(name) {
+                return Err(format!(
+                    "The alias {} has the same name as a built in command.",
+                    name
+                ));
+            }
+
+            if definition.is_empty() {
+                return Err(format!("The alias {} does not run any commands.", name));
+            }
+
+            let commands = definition
+                .iter()
+                .map(|d| Command::try_from_definition(d))
+                .collect::<Result<Vec<Command>, String>>()
+                .map_err(|e| format!("Invalid alias {}: {}", name, e))?;
+
+            aliases.insert(name.to_lowercase(), commands);
+        }
+
+        return Ok(aliases);
     }
 
     pub fn default_path(format: &str) -> Option<String> {
-        let mut path = dirs::home_dir()?;
+        let mut path = super::config_directory()?;
 
         if format.to_lowercase() == "toml" {
-            path.push(".config/muxide/config.toml");
+            path.push("config.toml");
         } else if format.to_lowercase() == "json" {
-            path.push(".config/muxide/config.json");
+            path.push("config.json");
         } else {
             return None;
         }
 
         return path.to_str().map(|s| s.to_string());
     }
+
+    /// Searches the config directory for an existing config file, returning the path and format
+    /// of the first one found. TOML is preferred over JSON.
+    pub fn discover_path() -> Option<(String, &'static str)> {
+        for format in &["TOML", "JSON"] {
+            let path = Self::default_path(format)?;
+
+            if std::path::Path::new(&path).exists() {
+                return Some((path, format));
+            }
+        }
+
+        return None;
+    }
+
+    /// Determines the format of a config file from its extension.
+    pub fn format_for_path(path: &str) -> Option<&'static str> {
+        let extension = std::path::Path::new(path)
+            .extension()?
+            .to_str()?
+            .to_lowercase();
+
+        return match extension.as_str() {
+            "toml" => Some("TOML"),
+            "json" => Some("JSON"),
+            _ => None,
+        };
+    }
 }
 
 impl Borders {
@@ -173,8 +550,101 @@ impl Environment {
         return self.show_workspaces;
     }
 
-    pub fn selected_workspace_color(&self) -> Color {
-        return self.selected_workspace_color;
+    pub fn show_panel_labels(&self) -> bool {
+        return self.show_panel_labels;
+    }
+
+    pub fn reduced_motion(&self) -> bool {
+        return self.reduced_motion;
+    }
+
+    /// Returns true if colors are disabled by the config or the NO_COLOR environment variable.
+    pub fn monochrome(&self) -> bool {
+        return self.monochrome
+            || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    }
+
+    /// Returns the title of the terminal's window using the configured format, None if the
+    /// title isn't set.
+    pub fn window_title(&self, session: &str, title: &str, workspace: u8) -> Option<String> {
+        if self.window_title.is_empty() {
+            return None;
+        }
+
+        let result = expand_placeholders(
+            &self.window_title,
+            &[
+                ("session", session.to_string()),
+                ("title", title.to_string()),
+                ("workspace", workspace.to_string()),
+            ],
+        );
+
+        return Some(result.trim_end().to_string());
+    }
+
+    pub fn keyboard_protocol(&self) -> KeyboardProtocol {
+        return self.keyboard_protocol;
+    }
+
+    pub fn skip_empty_workspaces(&self) -> bool {
+        return self.skip_empty_workspaces;
+    }
+
+    /// The percentage of a split panel's space given to the left or top panel, between 1 and 99.
+    pub fn split_ratio(&self) -> u16 {
+        return self.split_ratio.clamp(1, 99);
+    }
+
+    pub fn focus_passthrough_processes(&self) -> &Vec<String> {
+        return &self.focus_passthrough_processes;
+    }
+
+    pub fn opener(&self) -> &String {
+        return &self.opener;
+    }
+
+    pub fn graphics_passthrough(&self) -> bool {
+        return self.graphics_passthrough;
+    }
+
+    /// The TERM set in panels, None if panels inherit muxide's TERM.
+    pub fn panel_term(&self) -> Option<&str> {
+        if self.panel_term.is_empty() {
+            return None;
+        }
+
+        return Some(&self.panel_term);
+    }
+
+    pub fn panel_log_timestamps(&self) -> bool {
+        return self.panel_log_timestamps;
+    }
+
+    /// Returns the file a panel's output is saved to, None if output isn't saved.
+    pub fn scrollback_file(&self, session: &str, panel: usize) -> Option<PathBuf> {
+        if self.scrollback_file.is_empty() {
+            return None;
+        }
+
+        let path = PathBuf::from(expand_placeholders(
+            &self.scrollback_file,
+            &[
+                ("session", session.to_string()),
+                ("panel", panel.to_string()),
+            ],
+        ));
+
+        if path.is_relative() {
+            return Some(super::state_directory()?.join(path));
+        }
+
+        return Some(path);
+    }
+
+    /// The amount of each panel's output that is saved, in bytes.
+    pub fn scrollback_max_size(&self) -> usize {
+        return self.scrollback_max_size_kb.saturating_mul(1024);
     }
 
     pub fn set_log_file(&mut self, file: String) {
@@ -185,6 +655,19 @@ impl Environment {
         return &self.log_file;
     }
 
+    /// Resolves a relative log file path against the state directory, so a config can specify
+    /// just a file name.
+    pub fn resolve_log_file(&mut self) {
+        if let Some(file) = self.log_file.as_ref() {
+            if std::path::Path::new(file).is_relative() {
+                if let Some(mut path) = super::state_directory() {
+                    path.push(file);
+                    self.log_file = Some(path.to_string_lossy().to_string());
+                }
+            }
+        }
+    }
+
     pub fn set_log_level(&mut self, level: usize) {
         self.log_level = level;
     }
@@ -193,9 +676,87 @@ impl Environment {
         return self.log_level;
     }
 
+    /// The size in bytes the log file can grow to before it is rotated, None if it isn't rotated.
+    pub fn log_max_size(&self) -> Option<u64> {
+        if self.log_max_size_kb == 0 {
+            return None;
+        }
+
+        return Some(self.log_max_size_kb * 1024);
+    }
+
+    pub fn log_max_files(&self) -> usize {
+        return self.log_max_files;
+    }
+
+    pub fn log_compress(&self) -> bool {
+        return self.log_compress;
+    }
+
+    pub fn log_filter(&self) -> &Option<String> {
+        return &self.log_filter;
+    }
+
+    /// Returns the format of the log's lines, or an error if the format is invalid.
+    pub fn log_format(&self) -> Result<Vec<FormatItem>, String> {
+        return logging::parse_format(&self.log_format, &self.log_time_format)
+            .map_err(|e| format!("Invalid log_format. {}", e));
+    }
+
+    pub fn log_history_size(&self) -> usize {
+        return self.log_history_size;
+    }
+
     pub fn scroll_lines(&self) -> usize {
         return self.scroll_lines;
     }
+
+    /// The amount of time to wait for the next key of a multiple key command sequence.
+    pub fn chord_timeout(&self) -> Duration {
+        return Duration::from_millis(self.chord_timeout_ms);
+    }
+
+    /// The amount of time a message is displayed for, None if messages don't expire.
+    pub fn message_duration(&self) -> Option<Duration> {
+        if self.message_duration_ms == 0 {
+            return None;
+        }
+
+        return Some(Duration::from_millis(self.message_duration_ms));
+    }
+}
+
+/// Returns the format with each "{name}" replaced by its value, unknown placeholders are left as
+/// they are. The placeholders are replaced in a single pass, so a value containing a placeholder
+/// isn't expanded.
+fn expand_placeholders(format: &str, values: &[(&str, String)]) -> String {
+    let mut result = String::new();
+    let mut rest = format;
+
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let name = rest.find('}').map(|end| &rest[1..end]);
+        let value = values
+            .iter()
+            .find(|(placeholder, _)| Some(*placeholder) == name);
+
+        match value {
+            Some((_, value)) => {
+                result.push_str(value);
+                rest = &rest[rest.find('}').unwrap() + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+
+    return result;
 }
 
 impl Default for Config {
@@ -205,9 +766,18 @@ impl Default for Config {
             keys: Keys::default(),
             borders: Borders::default(),
 
-            /// Potentially can be removed
+            // Potentially can be removed
             thread_delay_period: None,
             password: PasswordSettings::default(),
+            aliases: HashMap::new(),
+            layouts: Vec::new(),
+            workspaces: Vec::new(),
+            highlights: Vec::new(),
+            theme: None,
+            themes: HashMap::new(),
+            active_theme: Theme::default(),
+            tuning: Tuning::default(),
+            status_bar: StatusBar::default(),
         };
     }
 }
@@ -220,9 +790,33 @@ impl Default for Environment {
             selected_panel_color: Color::default(),
             selected_workspace_color: Color::default(),
             show_workspaces: true,
+            show_panel_labels: false,
             log_level: 1,
             log_file: None,
+            log_max_size_kb: default_log_max_size_kb(),
+            log_max_files: default_log_max_files(),
+            log_compress: false,
+            log_filter: None,
+            log_format: default_log_format(),
+            log_time_format: default_log_time_format(),
+            log_history_size: default_log_history_size(),
             scroll_lines: 5,
+            chord_timeout_ms: default_chord_timeout_ms(),
+            message_duration_ms: default_message_duration_ms(),
+            reduced_motion: false,
+            monochrome: false,
+            window_title: default_window_title(),
+            keyboard_protocol: KeyboardProtocol::default(),
+            skip_empty_workspaces: false,
+            split_ratio: default_split_ratio(),
+            focus_passthrough_processes: default_focus_passthrough_processes(),
+            opener: default_opener(),
+            graphics_passthrough: false,
+            panel_term: default_panel_term(),
+            panel_log_timestamps: false,
+            scrollback_file: String::new(),
+            scrollback_max_size_kb: default_scrollback_max_size_kb(),
+            prefix: None,
         };
     }
 }
@@ -240,10 +834,146 @@ impl Default for Borders {
 
 #[cfg(test)]
 mod tests {
-    use super::{Color, Config};
+    use super::{Color, Config, KeyboardProtocol, Theme};
     use crate::command::Command;
+    use crate::config::{ModifiedKey, StatusBarElement, StatusBarPosition};
+    use muxide_logging::format::FormatItem;
+    use proptest::prelude::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
     use termion::event::Key;
 
+    /// The keys and section headers of the generated configs, a header is written on its own.
+    const TOML_KEYS: &[&str] = &[
+        "[environment]",
+        "[borders]",
+        "[password]",
+        "[tuning]",
+        "[status_bar]",
+        "[aliases]",
+        "[themes.mine]",
+        "[[keys]]",
+        "[[layouts]]",
+        "[layouts.root]",
+        "[layouts.root.a]",
+        "[[workspaces]]",
+        "[[highlights]]",
+        "theme",
+        "prefix",
+        "key",
+        "shortcut",
+        "command",
+        "args",
+        "table",
+        "description",
+        "mine",
+        "name",
+        "split",
+        "ratio",
+        "workspace",
+        "layout",
+        "commands",
+        "pattern",
+        "color",
+        "border",
+        "window_title",
+        "show_panel_labels",
+        "log_format",
+        "log_time_format",
+        "log_history_size",
+        "log_compress",
+        "position",
+        "elements",
+        "clock_format",
+        "update_interval_ms",
+        "split_ratio",
+        "scroll_lines",
+        "chord_timeout_ms",
+        "frame_delay_ms",
+        "vertical_character",
+        "hash_algorithm",
+    ];
+
+    const TOML_VALUES: &[&str] = &[
+        "\"ctrl+a\"",
+        "\"+\"",
+        "\"alt+shift+\"",
+        "\"ctrl+shift+up\"",
+        "\"F13\"",
+        "\"é\"",
+        "\"\"",
+        "\"FocusWorkspace\"",
+        "\"FocusWorkspace 12\"",
+        "\"EnterKeyTable\"",
+        "\"mine\"",
+        "\"resize\"",
+        "\"Horizontal\"",
+        "\"Bottom\"",
+        "[\"Clock\", \"Session\"]",
+        "[\"Battery\", { Text = \"x\" }]",
+        "\"{title} {\"",
+        "\"(\"",
+        "\"red\"",
+        "\"300, 0, 0\"",
+        "\"#fff\"",
+        "0",
+        "9",
+        "70000",
+        "-1",
+        "true",
+        "[]",
+        "[\"5\", \"resize\"]",
+        "{ a = 1 }",
+    ];
+
+    /// Generates configs from the keys and values that are checked after a config is parsed, so
+    /// the checks are reached more often than they would be by arbitrary text.
+    fn toml_config() -> impl Strategy<Value = String> {
+        return prop::collection::vec((0..TOML_KEYS.len(), 0..TOML_VALUES.len()), 0..30).prop_map(
+            |lines| {
+                lines
+                    .iter()
+                    .map(|(key, value)| {
+                        if TOML_KEYS[*key].starts_with('[') {
+                            format!("{}\n", TOML_KEYS[*key])
+                        } else {
+                            format!("{} = {}\n", TOML_KEYS[*key], TOML_VALUES[*value])
+                        }
+                    })
+                    .collect()
+            },
+        );
+    }
+
+    fn arbitrary_text() -> impl Strategy<Value = String> {
+        return prop::collection::vec(any::<u8>(), 0..200)
+            .prop_map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+    }
+
+    proptest! {
+        #[test]
+        fn test_generated_configs_never_panic(input in toml_config()) {
+            if let Ok(conf) = Config::from_toml_string(&input) {
+                conf.get_environment_ref().window_title("work", "{title}", 1);
+
+                // A config that loads can be written out and loaded again.
+                let output = toml::to_string(&conf).unwrap();
+                prop_assert_eq!(Config::from_toml_string(&output).unwrap(), conf);
+            }
+
+            // The same config as JSON exercises the JSON parser with the same values.
+            if let Ok(value) = toml::from_str::<toml::Value>(&input) {
+                let _ = Config::from_json_string(&serde_json::to_string(&value).unwrap());
+            }
+        }
+
+        #[test]
+        fn test_arbitrary_configs_never_panic(input in arbitrary_text()) {
+            let _ = Config::from_toml_string(&input);
+            let _ = Config::from_json_string(&input);
+        }
+    }
+
     #[test]
     fn basic_toml_test() {
         let input = "
@@ -286,4 +1016,373 @@ mod tests {
 
         assert_eq!(conf, comp);
     }
+
+    #[test]
+    fn key_description_toml_test() {
+        let input = "
+        [[keys]]\n\
+        shortcut = \"ctrl+a\"\n\
+        command = \"OpenPanel\"\n\
+        description = \"Open a scratch shell\"\n\
+        ";
+
+        let conf: Config = toml::from_str(input).unwrap();
+
+        let mut comp = Config::default();
+        comp.keys
+            .map_shortcut(Key::Ctrl('a'), Command::OpenPanelCommand);
+        comp.keys
+            .describe_shortcut(Key::Ctrl('a'), "Open a scratch shell".to_string());
+
+        assert_eq!(conf, comp);
+        assert!(conf
+            .key_map()
+            .help_sections()
+            .into_iter()
+            .any(|(_, lines)| lines.contains(&"ctrl+a - Open a scratch shell".to_string())));
+    }
+
+    #[test]
+    fn prefix_toml_test() {
+        let input = "
+        [environment]\n\
+        prefix = \"ctrl+b\"\n\
+        ";
+
+        let conf = Config::from_toml_string(input).unwrap();
+
+        assert_eq!(conf.key_map().prefix(), Some(Key::Ctrl('b')));
+        assert!(conf
+            .key_map()
+            .command_for_shortcut(&Key::Ctrl('a'))
+            .is_none());
+
+        let input = "
+        [environment]\n\
+        prefix = \"ctrl+shift+p\"\n\
+        ";
+
+        let conf = Config::from_toml_string(input).unwrap();
+
+        assert_eq!(conf.key_map().prefix(), None);
+        assert_eq!(
+            conf.key_map().modified_prefix(),
+            Some(ModifiedKey::from_string("ctrl+shift+p").unwrap())
+        );
+    }
+
+    #[test]
+    fn keyboard_protocol_toml_test() {
+        let input = "
+        [environment]\n\
+        keyboard_protocol = \"Auto\"\n\
+        [[keys]]\n\
+        shortcut = \"ctrl+shift+t\"\n\
+        command = \"OpenPanel\"\n\
+        ";
+
+        let conf = Config::from_toml_string(input).unwrap();
+
+        assert_eq!(
+            conf.get_environment_ref().keyboard_protocol(),
+            KeyboardProtocol::Auto
+        );
+        assert_eq!(
+            conf.key_map()
+                .command_for_modified_shortcut(&ModifiedKey::from_string("ctrl+T").unwrap()),
+            Some(&Command::OpenPanelCommand)
+        );
+    }
+
+    #[test]
+    fn default_toml_round_trip_test() {
+        let conf = Config::default();
+        let output = toml::to_string(&conf).unwrap();
+
+        assert_eq!(Config::from_toml_string(&output).unwrap(), conf);
+    }
+
+    #[test]
+    fn alias_toml_test() {
+        let input = "
+        [aliases]\n\
+        splitright = [\"SubdivideSelectedVertical\", \"OpenPanel\", \"FocusPanelRight\"]\n\
+        [[keys]]\n\
+        key = \"s\"\n\
+        command = \"SplitRight\"\n\
+        ";
+
+        let conf = Config::from_toml_string(input).unwrap();
+
+        assert_eq!(
+            conf.key_map().command_for_character(&'s'),
+            Some(&Command::AliasCommand(
+                "SplitRight".to_string(),
+                vec![
+                    Command::SubdivideSelectedVerticalCommand,
+                    Command::OpenPanelCommand,
+                    Command::FocusPanelRightCommand
+                ]
+            ))
+        );
+
+        let input = "
+        [[keys]]\n\
+        key = \"s\"\n\
+        command = \"SplitRight\"\n\
+        ";
+
+        assert!(Config::from_toml_string(input).is_err());
+    }
+
+    #[test]
+    fn highlight_toml_test() {
+        let input = "
+        [[highlights]]\n\
+        pattern = \".*ERROR.*\"\n\
+        color = \"red\"\n\
+        ";
+
+        let conf = Config::from_toml_string(input).unwrap();
+        assert_eq!(conf.highlight_rules().unwrap().len(), 1);
+
+        let input = "
+        [[highlights]]\n\
+        pattern = \"(ERROR\"\n\
+        ";
+
+        assert!(Config::from_toml_string(input).is_err());
+    }
+
+    #[test]
+    fn theme_toml_test() {
+        let input = "
+        theme = \"mine\"\n\
+        [themes.mine]\n\
+        border = \"blue\"\n\
+        error = \"red\"\n\
+        ";
+
+        let conf = Config::from_toml_string(input).unwrap();
+        assert_eq!(conf.theme().border, Some(Color::BLUE));
+        assert_eq!(conf.theme().error, Color::RED);
+        assert_eq!(conf.theme().info, Theme::default().info);
+
+        let conf = Config::from_toml_string("theme = \"gruvbox\"").unwrap();
+        assert_eq!(Some(*conf.theme()), Theme::builtin("gruvbox"));
+
+        let output = toml::to_string(&conf).unwrap();
+        assert_eq!(Config::from_toml_string(&output).unwrap(), conf);
+
+        assert!(Config::from_toml_string("theme = \"missing\"").is_err());
+    }
+
+    #[test]
+    fn workspace_setup_toml_test() {
+        let input = "
+        [[layouts]]\n\
+        name = \"dev\"\n\
+        [layouts.root]\n\
+        split = \"Vertical\"\n\
+        a = {}\n\
+        b = {}\n\
+        [[workspaces]]\n\
+        workspace = 1\n\
+        layout = \"dev\"\n\
+        commands = [\"nvim\", \"npm run dev\"]\n\
+        ";
+
+        let conf = Config::from_toml_string(input).unwrap();
+        assert_eq!(conf.workspace_setups()[0].commands.len(), 2);
+
+        let input = "
+        [[workspaces]]\n\
+        workspace = 2\n\
+        layout = \"missing\"\n\
+        ";
+        assert!(Config::from_toml_string(input).is_err());
+
+        let input = "
+        [[workspaces]]\n\
+        workspace = 2\n\
+        commands = [\"nvim\", \"htop\"]\n\
+        ";
+        assert!(Config::from_toml_string(input).is_err());
+    }
+
+    #[test]
+    fn tuning_toml_test() {
+        let input = "
+        [tuning]\n\
+        error_timeout_ms = 20\n\
+        frame_delay_ms = 0\n\
+        ";
+        let conf = Config::from_toml_string(input).unwrap();
+        assert_eq!(conf.tuning().error_timeout(), Duration::from_millis(20));
+        assert_eq!(conf.tuning().frame_delay(), Duration::from_millis(0));
+        assert_eq!(conf.tuning().send_timeout(), Duration::from_millis(200));
+
+        let input = "
+        [tuning]\n\
+        send_timeout_ms = 0\n\
+        ";
+        assert!(Config::from_toml_string(input).is_err());
+
+        let input = "
+        [tuning]\n\
+        frame_delay_ms = 5000\n\
+        ";
+        assert!(Config::from_toml_string(input).is_err());
+    }
+
+    #[test]
+    fn status_bar_toml_test() {
+        let conf = Config::default();
+        assert_eq!(conf.status_bar().position(), StatusBarPosition::Top);
+        assert!(conf.status_bar().shows(StatusBarElement::Workspaces));
+        assert!(!conf.status_bar().shows(StatusBarElement::Clock));
+
+        let input = "
+        [status_bar]\n\
+        position = \"Bottom\"\n\
+        elements = [\"Session\", \"Clock\"]\n\
+        ";
+        let conf = Config::from_toml_string(input).unwrap();
+        assert_eq!(conf.status_bar().position(), StatusBarPosition::Bottom);
+        assert_eq!(
+            conf.status_bar().elements(),
+            [StatusBarElement::Session, StatusBarElement::Clock]
+        );
+
+        let input = "
+        [status_bar]\n\
+        position = \"Left\"\n\
+        ";
+        assert!(Config::from_toml_string(input).is_err());
+
+        let input = "
+        [status_bar]\n\
+        elements = [\"Clock\", { Text = \"hello\" }]\n\
+        clock_format = \"%H:%M:%S\"\n\
+        ";
+        let conf = Config::from_toml_string(input).unwrap();
+        assert_eq!(
+            conf.status_bar().elements(),
+            [
+                StatusBarElement::Clock,
+                StatusBarElement::Text(String::from("hello"))
+            ]
+        );
+        assert_eq!(conf.status_bar().clock_format(), "%H:%M:%S");
+        assert!(conf.status_bar().has_widgets());
+
+        let input = "
+        [status_bar]\n\
+        update_interval_ms = 0\n\
+        ";
+        assert!(Config::from_toml_string(input).is_err());
+    }
+
+    #[test]
+    fn log_format_toml_test() {
+        let conf = Config::default();
+        assert!(conf.get_environment_ref().log_format().is_ok());
+
+        let input = "
+        [environment]\n\
+        log_format = \"{time} {level} {file}:{line} {message}\"\n\
+        log_time_format = \"%d/%m/%Y\"\n\
+        ";
+        let conf = Config::from_toml_string(input).unwrap();
+        let format = conf.get_environment_ref().log_format().unwrap();
+        assert_eq!(format.len(), 9);
+        assert!(matches!(&format[0], FormatItem::Time(time) if time == "%d/%m/%Y"));
+
+        let input = "
+        [environment]\n\
+        log_format = \"{time} {task} {message}\"\n\
+        ";
+        assert!(Config::from_toml_string(input).is_err());
+    }
+
+    #[test]
+    fn panel_term_test() {
+        let conf = Config::default();
+        assert_eq!(
+            conf.get_environment_ref().panel_term(),
+            Some("screen-256color")
+        );
+
+        let input = "
+        [environment]\n\
+        panel_term = \"\"\n\
+        ";
+        let conf = Config::from_toml_string(input).unwrap();
+        assert_eq!(conf.get_environment_ref().panel_term(), None);
+    }
+
+    #[test]
+    fn window_title_test() {
+        let conf = Config::default();
+        let env = conf.get_environment_ref();
+        assert_eq!(
+            env.window_title("work", "vim", 1),
+            Some("muxide: [work] vim".to_string())
+        );
+        assert_eq!(
+            env.window_title("work", "", 1),
+            Some("muxide: [work]".to_string())
+        );
+
+        let input = "
+        [environment]\n\
+        window_title = \"{workspace} {title} {other}\"\n\
+        ";
+        let conf = Config::from_toml_string(input).unwrap();
+        assert_eq!(
+            conf.get_environment_ref()
+                .window_title("work", "{session}", 3),
+            Some("3 {session} {other}".to_string())
+        );
+
+        let input = "
+        [environment]\n\
+        window_title = \"\"\n\
+        ";
+        let conf = Config::from_toml_string(input).unwrap();
+        assert_eq!(
+            conf.get_environment_ref().window_title("work", "vim", 1),
+            None
+        );
+    }
+
+    #[test]
+    fn scrollback_file_test() {
+        let conf = Config::default();
+        assert_eq!(conf.get_environment_ref().scrollback_file("work", 1), None);
+
+        let input = "
+        [environment]\n\
+        scrollback_file = \"/tmp/{session}/{panel}-{other}.txt\"\n\
+        scrollback_max_size_kb = 2\n\
+        ";
+        let conf = Config::from_toml_string(input).unwrap();
+        let env = conf.get_environment_ref();
+        assert_eq!(
+            env.scrollback_file("work", 3),
+            Some(PathBuf::from("/tmp/work/3-{other}.txt"))
+        );
+        assert_eq!(env.scrollback_max_size(), 2048);
+
+        let input = "
+        [environment]\n\
+        scrollback_file = \"scrollback/{panel}.txt\"\n\
+        ";
+        let conf = Config::from_toml_string(input).unwrap();
+
+        if let Some(path) = conf.get_environment_ref().scrollback_file("work", 3) {
+            assert!(path.is_absolute());
+            assert!(path.ends_with("muxide/scrollback/3.txt"));
+        }
+    }
 }
@@ -0,0 +1,149 @@
+use crate::Color;
+use crossterm::queue;
+use crossterm::style::{Attribute, Color as CrosstermColor, SetAttribute};
+use crossterm::style::{SetBackgroundColor, SetForegroundColor};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// A rule in the `[[highlights]]` section of a config, the text in panels matching its pattern is
+/// displayed in its style, i.e. `pattern = ".*ERROR.*"` and `color = "red"` colors error lines.
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize)]
+pub struct Highlight {
+    pub pattern: String,
+    #[serde(default)]
+    pub color: Option<Color>,
+    #[serde(default)]
+    pub background: Option<Color>,
+    #[serde(default)]
+    pub bold: bool,
+}
+
+/// A highlight with its pattern compiled and its style converted to escape sequences.
+#[derive(Clone, Debug)]
+pub struct HighlightRule {
+    regex: Regex,
+    style: Vec<u8>,
+}
+
+impl HighlightRule {
+    const RESET: &'static [u8] = b"\x1b[0m";
+
+    pub fn new(highlight: &Highlight) -> Result<Self, String> {
+        let regex = Regex::new(&highlight.pattern)
+            .map_err(|e| format!("Invalid highlight pattern {}: {}", highlight.pattern, e))?;
+        let mut style = Vec::new();
+
+        if let Some(color) = highlight.color {
+            queue!(
+                style,
+                SetForegroundColor(color.crossterm_color(CrosstermColor::Reset))
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        if let Some(color) = highlight.background {
+            queue!(
+                style,
+                SetBackgroundColor(color.crossterm_color(CrosstermColor::Reset))
+            )
+            .map_err(|e| e.to_string())?;
+        }
+
+        if highlight.bold {
+            queue!(style, SetAttribute(Attribute::Bold)).map_err(|e| e.to_string())?;
+        }
+
+        return Ok(Self { regex, style });
+    }
+}
+
+/// Returns a row's text with the parts matching the rules styled, the first rule to match a
+/// character decides its style. The row's own formatting is replaced. Returns None if no rule
+/// matches.
+pub fn highlight_row(text: &str, rules: &[HighlightRule]) -> Option<Vec<u8>> {
+    // The index of the rule styling each byte of the text.
+    let mut styles: Vec<Option<usize>> = vec![None; text.len()];
+
+    for (i, rule) in rules.iter().enumerate() {
+        for m in rule.regex.find_iter(text) {
+            for style in &mut styles[m.start()..m.end()] {
+                style.get_or_insert(i);
+            }
+        }
+    }
+
+    if styles.iter().all(|s| s.is_none()) {
+        return None;
+    }
+
+    // The row starts without any of the previous row's style.
+    let mut row = HighlightRule::RESET.to_vec();
+    let mut current = None;
+
+    for (i, ch) in text.char_indices() {
+        if styles[i] != current {
+            row.extend_from_slice(HighlightRule::RESET);
+
+            if let Some(rule) = styles[i] {
+                row.extend_from_slice(&rules[rule].style);
+            }
+
+            current = styles[i];
+        }
+
+        row.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+    }
+
+    if current.is_some() {
+        row.extend_from_slice(HighlightRule::RESET);
+    }
+
+    return Some(row);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{highlight_row, Highlight, HighlightRule};
+    use crate::Color;
+
+    fn rule(pattern: &str, bold: bool) -> HighlightRule {
+        return HighlightRule::new(&Highlight {
+            pattern: pattern.to_string(),
+            color: Some(Color::RED),
+            background: None,
+            bold,
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_highlight_row() {
+        let rules = vec![rule("ERROR", false), rule("ERR|warn", true)];
+
+        assert_eq!(highlight_row("all fine", &rules), None);
+
+        let row = highlight_row("an ERROR, a warning", &rules).unwrap();
+        let text = String::from_utf8(row).unwrap();
+        let error_style = String::from_utf8(rules[0].style.clone()).unwrap();
+        let warn_style = String::from_utf8(rules[1].style.clone()).unwrap();
+
+        assert_eq!(
+            text,
+            format!(
+                "\x1b[0man \x1b[0m{}ERROR\x1b[0m, a \x1b[0m{}warn\x1b[0ming",
+                error_style, warn_style
+            )
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(HighlightRule::new(&Highlight {
+            pattern: "(".to_string(),
+            color: None,
+            background: None,
+            bold: false,
+        })
+        .is_err());
+    }
+}
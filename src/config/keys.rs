@@ -1,4 +1,4 @@
-use crate::command::Command;
+use crate::command::{Command, CommandAliases};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use termion::event::Key;
@@ -7,60 +7,348 @@ use termion::event::Key;
 pub struct Keys {
     single_key_map: HashMap<char, Command>,
     shortcut_map: HashMap<Key, Command>,
+    single_key_descriptions: HashMap<char, String>,
+    shortcut_descriptions: HashMap<Key, String>,
+    sequence_tree: SequenceNode,
+    /// The key maps for each key table other than the root table.
+    table_maps: HashMap<KeyTable, HashMap<Key, Command>>,
+    /// Shortcuts using modifiers the legacy encoding can't express, they are only received when
+    /// the terminal uses an extended keyboard protocol.
+    modified_shortcut_map: HashMap<ModifiedKey, Command>,
+    modified_shortcut_descriptions: HashMap<ModifiedKey, String>,
 }
 
-fn key_to_string(key: Key) -> Result<String, &'static str> {
+/// A key pressed with modifiers the legacy encoding can't express, i.e. "ctrl+shift+a",
+/// "ctrl+enter" or "ctrl+up". Terminals report them with the kitty keyboard protocol or
+/// modifyOtherKeys, named keys are also reported with modifiers by most terminals.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub struct ModifiedKey {
+    key: Key,
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+/// The keyboard protocol requested from the terminal, an extended protocol reports keys with
+/// modifiers the legacy encoding can't express. Terminals that don't support the requested
+/// protocol keep using the legacy encoding.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum KeyboardProtocol {
+    Legacy,
+    /// The kitty keyboard protocol, with the disambiguate escape codes enhancement.
+    Kitty,
+    /// xterm's modifyOtherKeys mode 2.
+    ModifyOtherKeys,
+    /// The kitty keyboard protocol, falling back to modifyOtherKeys if the terminal doesn't
+    /// report supporting it.
+    Auto,
+}
+
+/// A key table is a set of key bindings that are active whilst muxide is in a mode, i.e. the copy
+/// mode. The root table contains the regular shortcuts.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum KeyTable {
+    Root,
+    Resize,
+    Copy,
+}
+
+/// A node in the tree of multiple character command sequences, each edge is a character in the
+/// sequence.
+#[derive(Clone, PartialEq, Debug, Default)]
+struct SequenceNode {
+    command: Option<Command>,
+    description: Option<String>,
+    children: HashMap<char, SequenceNode>,
+}
+
+pub fn key_to_string(key: Key) -> Result<String, &'static str> {
     return Ok(match key {
+        Key::Char(' ') => "space".to_string(),
+        Key::Char('\t') => "tab".to_string(),
+        Key::Char('\n') => "enter".to_string(),
         Key::Char(ch) => format!("{}", ch),
         Key::Alt(ch) => format!("alt+{}", ch),
         Key::Ctrl(ch) => format!("ctrl+{}", ch),
+        Key::F(n) => format!("f{}", n),
+        Key::Up => "up".to_string(),
+        Key::Down => "down".to_string(),
+        Key::Left => "left".to_string(),
+        Key::Right => "right".to_string(),
+        Key::Home => "home".to_string(),
+        Key::End => "end".to_string(),
+        Key::PageUp => "pageup".to_string(),
+        Key::PageDown => "pagedown".to_string(),
+        Key::Insert => "insert".to_string(),
+        Key::Delete => "delete".to_string(),
+        Key::Backspace => "backspace".to_string(),
+        Key::BackTab => "shift+tab".to_string(),
+        Key::Esc => "esc".to_string(),
         _ => {
-            return Err("Only the \"Alt\" and \"Ctrl\" modifiers are supported.");
+            return Err("Unsupported key.");
+        }
+    });
+}
+
+/// Returns the bytes a terminal sends for a key.
+pub fn key_bytes(key: Key) -> Option<Vec<u8>> {
+    return Some(match key {
+        // The enter key sends a carriage return.
+        Key::Char('\n') => vec![b'\r'],
+        Key::Char(ch) => ch.to_string().into_bytes(),
+        Key::Alt(ch) => {
+            let mut bytes = vec![0x1b];
+            bytes.extend(ch.to_string().into_bytes());
+            bytes
+        }
+        Key::Ctrl(ch) => {
+            if !ch.is_ascii() {
+                return None;
+            }
+
+            vec![(ch.to_ascii_lowercase() as u8) & 0x1f]
+        }
+        Key::F(n) => match n {
+            1 => b"\x1bOP".to_vec(),
+            2 => b"\x1bOQ".to_vec(),
+            3 => b"\x1bOR".to_vec(),
+            4 => b"\x1bOS".to_vec(),
+            5 => b"\x1b[15~".to_vec(),
+            6 => b"\x1b[17~".to_vec(),
+            7 => b"\x1b[18~".to_vec(),
+            8 => b"\x1b[19~".to_vec(),
+            9 => b"\x1b[20~".to_vec(),
+            10 => b"\x1b[21~".to_vec(),
+            11 => b"\x1b[23~".to_vec(),
+            12 => b"\x1b[24~".to_vec(),
+            _ => return None,
+        },
+        Key::Up => b"\x1b[A".to_vec(),
+        Key::Down => b"\x1b[B".to_vec(),
+        Key::Right => b"\x1b[C".to_vec(),
+        Key::Left => b"\x1b[D".to_vec(),
+        Key::Home => b"\x1b[H".to_vec(),
+        Key::End => b"\x1b[F".to_vec(),
+        Key::PageUp => b"\x1b[5~".to_vec(),
+        Key::PageDown => b"\x1b[6~".to_vec(),
+        Key::Insert => b"\x1b[2~".to_vec(),
+        Key::Delete => b"\x1b[3~".to_vec(),
+        Key::Backspace => vec![0x7f],
+        Key::BackTab => b"\x1b[Z".to_vec(),
+        Key::Esc => vec![0x1b],
+        _ => return None,
+    });
+}
+
+/// Converts the name of a non character key to the key, i.e. "f1" or "pageup".
+fn named_key(name: &str) -> Option<Key> {
+    let lowered = name.to_lowercase();
+
+    if lowered.len() > 1 && lowered.starts_with('f') {
+        if let Ok(n) = lowered[1..].parse::<u8>() {
+            if (1..=12).contains(&n) {
+                return Some(Key::F(n));
+            }
         }
+    }
+
+    return Some(match lowered.as_str() {
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" | "pgup" => Key::PageUp,
+        "pagedown" | "pgdn" => Key::PageDown,
+        "insert" => Key::Insert,
+        "delete" | "del" => Key::Delete,
+        "backspace" => Key::Backspace,
+        "esc" | "escape" => Key::Esc,
+        "space" => Key::Char(' '),
+        "tab" => Key::Char('\t'),
+        "enter" => Key::Char('\n'),
+        _ => return None,
     });
 }
 
-fn key_from_string(string: String) -> Result<Key, &'static str> {
-    let mut first_half = String::new();
-    let mut string: Vec<char> = string.chars().collect();
+/// Splits the modifiers from the key in a shortcut, returning whether ctrl, alt and shift are
+/// used and the key.
+fn split_modifiers(string: &str) -> Result<((bool, bool, bool), &str), &'static str> {
+    // A trailing "++" means the key itself is a '+', as does a '+' on its own.
+    let (modifier_string, key_string) = if string == "+" {
+        ("", string)
+    } else if string.len() > 1 && string.ends_with("++") {
+        (&string[..string.len() - 2], "+")
+    } else if let Some(i) = string.rfind('+') {
+        if i == string.len() - 1 {
+            return Err("A key is required to follow a '+'.");
+        }
+
+        (&string[..i], &string[i + 1..])
+    } else {
+        ("", string)
+    };
+
+    let (mut ctrl, mut alt, mut shift) = (false, false, false);
 
-    while string.len() > 0 {
-        if string[0] == '+' {
-            if first_half.len() == 0 {
-                return Err("A single character is required to follow a '+'");
+    if !modifier_string.is_empty() {
+        for modifier in modifier_string.split('+') {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" => ctrl = true,
+                "alt" => alt = true,
+                "shift" => shift = true,
+                "" => return Err("A modifier is required before a '+'."),
+                _ => {
+                    return Err("Only the \"Alt\", \"Ctrl\" and \"Shift\" modifiers are supported.")
+                }
             }
+        }
+    }
+
+    return Ok(((ctrl, alt, shift), key_string));
+}
 
-            string.remove(0);
-            break;
+pub fn key_from_string(string: String) -> Result<Key, &'static str> {
+    let ((ctrl, alt, shift), key_string) = split_modifiers(&string)?;
+    let key_characters: Vec<char> = key_string.chars().collect();
+
+    if key_characters.len() != 1 {
+        let key = named_key(key_string).ok_or(
+            "A single character key, modifier '+' single character or a named key is expected.",
+        )?;
+
+        if !ctrl && !alt && !shift {
+            return Ok(key);
+        } else if shift && !ctrl && !alt && key == Key::Char('\t') {
+            return Ok(Key::BackTab);
         } else {
-            first_half.push(string.remove(0));
+            return Err("Modifiers are only supported with character keys and \"shift+tab\".");
         }
     }
 
-    if string.len() > 0 {
-        let lowered = first_half.to_lowercase();
+    let mut ch = key_characters[0];
 
-        if lowered == "ctrl" {
-            if string.len() != 1 {
-                return Err("Expected a single character to follow '+'.");
-            } else {
-                return Ok(Key::Ctrl(string[0]));
+    if shift {
+        if ctrl {
+            return Err("Only extended keyboard protocols report \"ctrl+shift\" shortcuts.");
+        }
+
+        ch = ch.to_ascii_uppercase();
+    }
+
+    return match (ctrl, alt) {
+        (true, true) => Err("The \"Alt\" and \"Ctrl\" modifiers cannot be combined."),
+        (true, false) => Ok(Key::Ctrl(ch)),
+        (false, true) => Ok(Key::Alt(ch)),
+        (false, false) => Ok(Key::Char(ch)),
+    };
+}
+
+impl ModifiedKey {
+    /// Shifted letters are stored in lowercase, with the shift modifier.
+    pub fn new(key: Key, ctrl: bool, alt: bool, shift: bool) -> Self {
+        let (key, shift) = match key {
+            Key::Char(ch) if ch.is_ascii_uppercase() => (Key::Char(ch.to_ascii_lowercase()), true),
+            key => (key, shift),
+        };
+
+        return Self {
+            key,
+            ctrl,
+            alt,
+            shift,
+        };
+    }
+
+    /// Parses a shortcut in the same format as the regular shortcuts, i.e. "ctrl+shift+a".
+    pub fn from_string(string: &str) -> Result<Self, &'static str> {
+        let ((ctrl, alt, shift), key_string) = split_modifiers(string)?;
+        let mut characters = key_string.chars();
+
+        let key = match (characters.next(), characters.next()) {
+            (Some(ch), None) => Key::Char(ch),
+            _ => named_key(key_string).ok_or(
+                "A single character key, modifier '+' single character or a named key is expected.",
+            )?,
+        };
+
+        return Ok(Self::new(key, ctrl, alt, shift));
+    }
+
+    /// Returns the key as the legacy encoding reports it, None if the encoding can't express its
+    /// modifiers.
+    pub fn legacy_key(&self) -> Option<Key> {
+        return match (self.key, self.ctrl, self.alt, self.shift) {
+            (key, false, false, false) => Some(key),
+            (Key::Char('\t'), false, false, true) => Some(Key::BackTab),
+            (Key::Char(ch), false, false, true) if ch.is_ascii_lowercase() => {
+                Some(Key::Char(ch.to_ascii_uppercase()))
             }
-        } else if lowered == "alt" {
-            if string.len() != 1 {
-                return Err("Expected a single character to follow '+'.");
-            } else {
-                return Ok(Key::Alt(string[0]));
+            (Key::Char(ch), false, true, true) if ch.is_ascii_lowercase() => {
+                Some(Key::Alt(ch.to_ascii_uppercase()))
             }
-        } else {
-            return Err("Only the \"Alt\" and \"Ctrl\" modifiers are supported.");
+            (Key::Char(ch), true, false, false) if ch.is_ascii_graphic() || ch == ' ' => {
+                Some(Key::Ctrl(ch))
+            }
+            (Key::Char(ch), false, true, false) if !ch.is_control() => Some(Key::Alt(ch)),
+            _ => None,
+        };
+    }
+
+    /// Returns the bytes sent to a panel for the key, the closest key the legacy encoding can
+    /// express, i.e. "ctrl+shift+a" is sent as "ctrl+a".
+    pub fn legacy_bytes(&self) -> Vec<u8> {
+        // Alt is sent as a preceding escape, so it can be combined with any key.
+        let mut bytes = if self.alt { vec![0x1b] } else { Vec::new() };
+        let without_alt = Self {
+            alt: false,
+            ..*self
+        };
+        let candidates = [
+            without_alt,
+            Self {
+                shift: false,
+                ..without_alt
+            },
+            Self::new(self.key, false, false, false),
+        ];
+
+        if let Some(key_bytes) = candidates
+            .iter()
+            .find_map(|key| key.legacy_key().and_then(key_bytes))
+        {
+            bytes.extend(key_bytes);
         }
-    } else {
-        if first_half.len() != 1 {
-            return Err("A single character key or modifier '+' single character is expected.");
-        } else {
-            return Ok(Key::Char(first_half.remove(0)));
+
+        return bytes;
+    }
+}
+
+impl std::fmt::Display for ModifiedKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (enabled, name) in [
+            (self.ctrl, "ctrl+"),
+            (self.alt, "alt+"),
+            (self.shift, "shift+"),
+        ]
+        .iter()
+        {
+            if *enabled {
+                write!(f, "{}", name)?;
+            }
         }
+
+        return match key_to_string(self.key) {
+            Ok(key) => write!(f, "{}", key),
+            Err(_) => write!(f, "{:?}", self.key),
+        };
+    }
+}
+
+impl Default for KeyboardProtocol {
+    fn default() -> Self {
+        return Self::Legacy;
     }
 }
 
@@ -77,8 +365,84 @@ impl Keys {
         self.shortcut_map.insert(key, cmd);
     }
 
+    pub fn command_for_modified_shortcut(&self, key: &ModifiedKey) -> Option<&Command> {
+        return self.modified_shortcut_map.get(key);
+    }
+
+    /// Map a shortcut that is only received from terminals using an extended keyboard protocol.
+    pub fn map_modified_shortcut(&mut self, key: ModifiedKey, cmd: Command) {
+        self.modified_shortcut_map.insert(key, cmd);
+    }
+
     pub fn unmap_shortcut(&mut self, key: &Key) {
         self.shortcut_map.remove(key);
+        self.shortcut_descriptions.remove(key);
+    }
+
+    /// Set the text displayed in the help message for a shortcut, overriding the command's
+    /// built-in help text.
+    pub fn describe_shortcut(&mut self, key: Key, description: String) {
+        self.shortcut_descriptions.insert(key, description);
+    }
+
+    /// Returns the shortcut mapped to the single character command, the prefix for all single
+    /// character and sequence commands.
+    pub fn prefix(&self) -> Option<Key> {
+        return self
+            .shortcut_map
+            .iter()
+            .find(|(_, cmd)| **cmd == Command::EnterSingleCharacterCommand)
+            .map(|(key, _)| *key);
+    }
+
+    /// Returns the prefix if it is a shortcut that is only received from terminals using an
+    /// extended keyboard protocol, i.e. "ctrl+shift+p".
+    pub fn modified_prefix(&self) -> Option<ModifiedKey> {
+        return self
+            .modified_shortcut_map
+            .iter()
+            .find(|(_, cmd)| **cmd == Command::EnterSingleCharacterCommand)
+            .map(|(key, _)| *key);
+    }
+
+    /// Replace the shortcut mapped to the single character command.
+    pub fn set_prefix(&mut self, key: Key) {
+        self.remove_prefix();
+        self.shortcut_map
+            .insert(key, Command::EnterSingleCharacterCommand);
+    }
+
+    /// Replace the shortcut mapped to the single character command with a shortcut that is only
+    /// received from terminals using an extended keyboard protocol.
+    pub fn set_modified_prefix(&mut self, key: ModifiedKey) {
+        self.remove_prefix();
+        self.modified_shortcut_map
+            .insert(key, Command::EnterSingleCharacterCommand);
+    }
+
+    fn remove_prefix(&mut self) {
+        self.shortcut_map
+            .retain(|_, cmd| *cmd != Command::EnterSingleCharacterCommand);
+        self.modified_shortcut_map
+            .retain(|_, cmd| *cmd != Command::EnterSingleCharacterCommand);
+    }
+
+    /// Returns the command mapped to a key in a key table.
+    pub fn command_for_table_key(&self, table: KeyTable, key: &Key) -> Option<&Command> {
+        if table == KeyTable::Root {
+            return self.command_for_shortcut(key);
+        }
+
+        return self.table_maps.get(&table)?.get(key);
+    }
+
+    /// Map a key in a key table to a command.
+    pub fn map_table_key(&mut self, table: KeyTable, key: Key, cmd: Command) {
+        if table == KeyTable::Root {
+            self.map_shortcut(key, cmd);
+        } else {
+            self.table_maps.entry(table).or_default().insert(key, cmd);
+        }
     }
 
     pub fn command_for_character(&self, ch: &char) -> Option<&Command> {
@@ -91,51 +455,222 @@ impl Keys {
 
     pub fn unmap_character(&mut self, key: &char) {
         self.single_key_map.remove(key);
+        self.single_key_descriptions.remove(key);
+    }
+
+    /// Replaces each alias in the key mappings with its definition, returning an error if a
+    /// mapping refers to an alias that is not defined.
+    pub fn resolve_aliases(&mut self, aliases: &CommandAliases) -> Result<(), String> {
+        let mut commands: Vec<&mut Command> = Vec::new();
+        commands.extend(self.single_key_map.values_mut());
+        commands.extend(self.shortcut_map.values_mut());
+        commands.extend(self.modified_shortcut_map.values_mut());
+        commands.extend(self.sequence_tree.commands_mut());
+
+        for map in self.table_maps.values_mut() {
+            commands.extend(map.values_mut());
+        }
+
+        for cmd in commands {
+            if let Command::AliasCommand(name, _) = cmd {
+                *cmd = aliases
+                    .get(&name.to_lowercase())
+                    .map(|definition| Command::AliasCommand(name.clone(), definition.clone()))
+                    .ok_or(format!("Unknown command: {}", name))?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Set the text displayed in the help message for a single character command, overriding
+    /// the command's built-in help text.
+    pub fn describe_character(&mut self, key: char, description: String) {
+        self.single_key_descriptions.insert(key, description);
     }
 
-    pub fn help_message_keymap(&self) -> (Vec<String>, usize) {
+    /// Returns the command mapped to a sequence of characters entered after the single
+    /// character command shortcut and whether any longer sequences start with the sequence.
+    pub fn command_for_sequence(&self, sequence: &[char]) -> (Option<&Command>, bool) {
+        if sequence.is_empty() {
+            return (None, true);
+        }
+
+        let mut node = &self.sequence_tree;
+
+        for ch in sequence {
+            match node.children.get(ch) {
+                Some(n) => node = n,
+                None => {
+                    if sequence.len() == 1 {
+                        return (self.single_key_map.get(&sequence[0]), false);
+                    } else {
+                        return (None, false);
+                    }
+                }
+            }
+        }
+
+        let command = if sequence.len() == 1 {
+            self.single_key_map.get(&sequence[0])
+        } else {
+            node.command.as_ref()
+        };
+
+        return (command, !node.children.is_empty());
+    }
+
+    /// Map a sequence of characters to a command, a sequence of a single character is
+    /// equivalent to mapping a character.
+    pub fn map_sequence(&mut self, sequence: &[char], cmd: Command) {
+        if sequence.len() == 1 {
+            self.map_character(sequence[0], cmd);
+        } else if !sequence.is_empty() {
+            self.sequence_node_mut(sequence).command = Some(cmd);
+        }
+    }
+
+    /// Set the text displayed in the help message for a sequence of characters.
+    pub fn describe_sequence(&mut self, sequence: &[char], description: String) {
+        if sequence.len() == 1 {
+            self.describe_character(sequence[0], description);
+        } else if !sequence.is_empty() {
+            self.sequence_node_mut(sequence).description = Some(description);
+        }
+    }
+
+    fn sequence_node_mut(&mut self, sequence: &[char]) -> &mut SequenceNode {
+        let mut node = &mut self.sequence_tree;
+
+        for ch in sequence {
+            node = node.children.entry(*ch).or_default();
+        }
+
+        return node;
+    }
+
+    /// Returns the help text for every mapped key, grouped by the category of the command in the
+    /// order the categories are displayed. Key tables are listed in their own category.
+    pub fn help_sections(&self) -> Vec<(String, Vec<String>)> {
+        let mut sections: Vec<(String, Vec<String>)> = Command::CATEGORIES
+            .iter()
+            .map(|c| (c.to_string(), Vec::new()))
+            .collect();
+
+        let mut add_line = |category: &str, line: String| match sections
+            .iter_mut()
+            .find(|(name, _)| name == category)
+        {
+            Some((_, lines)) => lines.push(line),
+            None => sections.push((category.to_string(), vec![line])),
+        };
+
         let mut single_character_shortcut = None;
-        let mut longest = 0;
-        let mut descriptions = Vec::new();
+        let mut shortcuts: Vec<(String, Option<&String>, &Command)> = self
+            .shortcut_map
+            .iter()
+            .filter_map(|(k, c)| {
+                key_to_string(*k)
+                    .ok()
+                    .map(|s| (s, self.shortcut_descriptions.get(k), c))
+            })
+            .collect();
+        shortcuts.extend(
+            self.modified_shortcut_map
+                .iter()
+                .map(|(k, c)| (k.to_string(), self.modified_shortcut_descriptions.get(k), c)),
+        );
+        shortcuts.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+
+        for (key_string, description, command) in shortcuts {
+            if command == &Command::EnterSingleCharacterCommand {
+                single_character_shortcut = Some(key_string.clone());
+            }
+
+            let help_text = description.cloned().or(command.help_text());
+
+            if let Some(help_text) = help_text {
+                add_line(
+                    command.category(),
+                    format!("{} - {}", key_string, help_text),
+                );
+            }
+        }
 
-        for (key, command) in self.shortcut_map.iter() {
-            if let Some(help_text) = command.help_text() {
-                let key_string = key_to_string(*key).unwrap();
-                let line = format!("{} - {}", key_string, help_text);
+        if let Some(key_string) = single_character_shortcut {
+            let mut characters = self
+                .single_key_map
+                .iter()
+                .collect::<Vec<(&char, &Command)>>();
+            characters.sort_by_key(|(a, _)| *a);
 
-                if line.len() > longest {
-                    longest = line.len();
+            for (key, command) in characters {
+                let help_text = self
+                    .single_key_descriptions
+                    .get(key)
+                    .cloned()
+                    .or(command.help_text());
+
+                if let Some(help_text) = help_text {
+                    add_line(
+                        command.category(),
+                        format!("{} {} - {}", key_string, key, help_text),
+                    );
                 }
+            }
 
-                descriptions.push(line);
-            } else {
-                if command == &Command::EnterSingleCharacterCommand {
-                    single_character_shortcut = Some(*key);
+            let mut sequences = self.sequence_tree.sequences();
+            sequences.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            for (sequence, node) in sequences {
+                let command = match node.command.as_ref() {
+                    Some(c) => c,
+                    None => continue,
+                };
+
+                let help_text = node.description.clone().or(command.help_text());
+
+                if let Some(help_text) = help_text {
+                    add_line(
+                        command.category(),
+                        format!(
+                            "{} {} - {}",
+                            key_string,
+                            sequence
+                                .iter()
+                                .map(|c| c.to_string())
+                                .collect::<Vec<String>>()
+                                .join(" "),
+                            help_text
+                        ),
+                    );
                 }
             }
         }
 
-        if let Some(character_shortcut) = single_character_shortcut {
-            let key_string = key_to_string(character_shortcut).unwrap();
-            let mut single_character_descriptions = self.single_key_map.iter().collect::<Vec<(&char, &Command)>>();
-            single_character_descriptions.sort_by(|(a_char, _), (b_char, _)| a_char.cmp(b_char));
+        let mut tables: Vec<(&KeyTable, &HashMap<Key, Command>)> = self.table_maps.iter().collect();
+        tables.sort_by(|(a, _), (b, _)| a.name().cmp(b.name()));
 
-            let mut iterator = single_character_descriptions.into_iter();
+        for (table, map) in tables {
+            let mut keys: Vec<(String, &Command)> = map
+                .iter()
+                .filter_map(|(k, c)| key_to_string(*k).ok().map(|k| (k, c)))
+                .collect();
+            keys.sort_by(|(a, _), (b, _)| a.cmp(b));
 
-            while let Some((key, command)) = iterator.next_back()  {
+            for (key, command) in keys {
                 if let Some(help_text) = command.help_text() {
-                    let line = format!("{} {} - {}", key_string, key, help_text);
-
-                    if line.len() > longest {
-                        longest = line.len();
-                    }
-
-                    descriptions.push(line);
+                    add_line(
+                        "Key tables",
+                        format!("[{}] {} - {}", table.name(), key, help_text),
+                    );
                 }
             }
         }
 
-        return (descriptions, longest);
+        sections.retain(|(_, lines)| !lines.is_empty());
+
+        return sections;
     }
 
     #[inline]
@@ -178,11 +713,77 @@ impl Keys {
     }
 }
 
+impl KeyTable {
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Self::Root => "root",
+            Self::Resize => "resize",
+            Self::Copy => "copy",
+        };
+    }
+
+    pub fn try_from_string(name: &str) -> Result<Self, String> {
+        return Ok(match name.to_lowercase().as_str() {
+            "root" => Self::Root,
+            "resize" => Self::Resize,
+            "copy" => Self::Copy,
+            _ => return Err(format!("Unknown key table: {}", name)),
+        });
+    }
+}
+
+impl std::fmt::Display for KeyTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}", self.name());
+    }
+}
+
+impl SequenceNode {
+    /// Returns every sequence in this tree that is mapped to a command.
+    fn sequences(&self) -> Vec<(Vec<char>, &SequenceNode)> {
+        let mut sequences = Vec::new();
+
+        for (ch, child) in &self.children {
+            if child.command.is_some() {
+                sequences.push((vec![*ch], child));
+            }
+
+            for (mut sequence, node) in child.sequences() {
+                sequence.insert(0, *ch);
+                sequences.push((sequence, node));
+            }
+        }
+
+        return sequences;
+    }
+
+    /// Returns every command mapped in this tree.
+    fn commands_mut(&mut self) -> Vec<&mut Command> {
+        let mut commands = Vec::new();
+
+        if let Some(cmd) = self.command.as_mut() {
+            commands.push(cmd);
+        }
+
+        for child in self.children.values_mut() {
+            commands.append(&mut child.commands_mut());
+        }
+
+        return commands;
+    }
+}
+
 impl Default for Keys {
     fn default() -> Self {
         let mut n = Self {
             single_key_map: HashMap::new(),
             shortcut_map: HashMap::new(),
+            single_key_descriptions: HashMap::new(),
+            shortcut_descriptions: HashMap::new(),
+            sequence_tree: SequenceNode::default(),
+            table_maps: HashMap::new(),
+            modified_shortcut_map: HashMap::new(),
+            modified_shortcut_descriptions: HashMap::new(),
         };
 
         n.shortcut_map
@@ -203,10 +804,58 @@ impl Default for Keys {
             .insert('r', Command::FocusPanelRightCommand);
         n.single_key_map.insert('u', Command::FocusPanelUpCommand);
         n.single_key_map.insert('d', Command::FocusPanelDownCommand);
+        n.single_key_map.insert('.', Command::NextPanelCommand);
+        n.single_key_map.insert(',', Command::PreviousPanelCommand);
         n.single_key_map.insert('m', Command::MergePanelCommand);
+        n.single_key_map.insert('+', Command::GrowPanelCommand);
+        n.single_key_map.insert('-', Command::ShrinkPanelCommand);
         n.single_key_map.insert('o', Command::ScrollUpCommand);
         n.single_key_map.insert('k', Command::ScrollDownCommand);
         n.single_key_map.insert('/', Command::HelpMessageCommand);
+        n.single_key_map.insert('i', Command::IdentifyPanelsCommand);
+        n.single_key_map
+            .insert('M', Command::ShowMessageHistoryCommand);
+        n.single_key_map.insert('S', Command::ShowStatsCommand);
+        n.single_key_map.insert('G', Command::ShowLogCommand);
+        n.single_key_map
+            .insert('c', Command::EnterKeyTableCommand(KeyTable::Copy));
+        n.single_key_map
+            .insert('R', Command::EnterKeyTableCommand(KeyTable::Resize));
+        n.single_key_map.insert(']', Command::NextWorkspaceCommand);
+        n.single_key_map
+            .insert('[', Command::PreviousWorkspaceCommand);
+        n.single_key_map
+            .insert('L', Command::FocusLastWorkspaceCommand);
+        n.single_key_map.insert('!', Command::BreakPanelCommand);
+        n.single_key_map.insert('z', Command::UndoLayoutCommand);
+        n.single_key_map.insert('p', Command::PauseOutputCommand);
+        n.single_key_map.insert('w', Command::ToggleReadOnlyCommand);
+        n.single_key_map.insert('f', Command::HintsCommand);
+        n.single_key_map.insert('t', Command::ToggleScratchCommand);
+        n.single_key_map.insert('C', Command::ReconnectPanelCommand);
+        n.single_key_map.insert('F', Command::FollowLogCommand);
+
+        n.map_table_key(KeyTable::Copy, Key::Char('k'), Command::ScrollUpCommand);
+        n.map_table_key(KeyTable::Copy, Key::Up, Command::ScrollUpCommand);
+        n.map_table_key(KeyTable::Copy, Key::Char('j'), Command::ScrollDownCommand);
+        n.map_table_key(KeyTable::Copy, Key::Down, Command::ScrollDownCommand);
+        n.map_table_key(
+            KeyTable::Copy,
+            Key::Char('q'),
+            Command::EnterKeyTableCommand(KeyTable::Root),
+        );
+
+        n.map_table_key(KeyTable::Resize, Key::Char('+'), Command::GrowPanelCommand);
+        n.map_table_key(
+            KeyTable::Resize,
+            Key::Char('-'),
+            Command::ShrinkPanelCommand,
+        );
+        n.map_table_key(
+            KeyTable::Resize,
+            Key::Char('q'),
+            Command::EnterKeyTableCommand(KeyTable::Root),
+        );
 
         for i in 0..10 {
             n.single_key_map.insert(
@@ -230,43 +879,102 @@ impl<'de> Deserialize<'de> for Keys {
             key: Option<String>,
             command: String,
             args: Option<Vec<String>>,
-        };
+            description: Option<String>,
+            table: Option<String>,
+        }
         let keys: Vec<KeyPair> = Deserialize::deserialize(deserializer)?;
         let mut res = Self::default();
 
         for key_pair in keys {
-            let (shortcut, key, command, args) = (
+            let (shortcut, key, command, args, description) = (
                 key_pair.shortcut,
                 key_pair.key,
                 key_pair.command,
                 key_pair.args.unwrap_or(Vec::new()),
+                key_pair.description,
             );
 
-            let cmd =
-                Command::try_from_string(command, args).map_err(|e| serde::de::Error::custom(e))?;
+            // Aliases are defined outside of the key mappings, so they are resolved once the
+            // whole config has been read.
+            let cmd = if args.is_empty() && !Command::is_builtin_name(&command) {
+                Command::AliasCommand(command, Vec::new())
+            } else {
+                Command::try_from_string(command, args).map_err(serde::de::Error::custom)?
+            };
+
+            let table = match key_pair.table {
+                Some(t) => KeyTable::try_from_string(&t).map_err(serde::de::Error::custom)?,
+                None => KeyTable::Root,
+            };
+
+            if table != KeyTable::Root {
+                if key.is_some() {
+                    return Err(serde::de::Error::custom(
+                        "Key table bindings must use 'shortcut' rather than 'key'.",
+                    ));
+                }
+
+                let shortcut = shortcut.ok_or(serde::de::Error::custom(
+                    "Key table bindings require a 'shortcut'.",
+                ))?;
+                let shortcut = key_from_string(shortcut).map_err(serde::de::Error::custom)?;
+
+                res.map_table_key(table, shortcut, cmd);
+
+                continue;
+            }
 
             if let Some(shortcut) = shortcut {
-                let shortcut =
-                    key_from_string(shortcut).map_err(|e| serde::de::Error::custom(e))?;
+                let shortcut = match key_from_string(shortcut.clone()) {
+                    Ok(key) => key,
+                    Err(e) => {
+                        // Shortcuts the legacy encoding can't express are received from
+                        // terminals using an extended keyboard protocol.
+                        let key = ModifiedKey::from_string(&shortcut)
+                            .map_err(|_| serde::de::Error::custom(e))?;
+
+                        res.modified_shortcut_map.insert(key, cmd.clone());
+
+                        if let Some(description) = description.as_ref() {
+                            res.modified_shortcut_descriptions
+                                .insert(key, description.clone());
+                        }
+
+                        continue;
+                    }
+                };
 
                 res.shortcut_map.insert(shortcut, cmd.clone());
+
+                if let Some(description) = description.as_ref() {
+                    res.shortcut_descriptions
+                        .insert(shortcut, description.clone());
+                }
             }
 
             if let Some(key) = key {
                 let key: Vec<char> = key.chars().collect();
 
-                if key.len() != 1 {
+                if key.is_empty() {
                     return Err(serde::de::Error::custom(
-                        "Expected a single character 'key'.",
+                        "Expected at least one character for 'key'.",
                     ));
-                } else if !Self::is_permitted_char(*key.first().unwrap()) {
-                    return Err(serde::de::Error::custom(format!(
-                        "Unsupported 'key': {}",
-                        key.first().unwrap()
-                    )));
                 }
 
-                res.single_key_map.insert(*key.first().unwrap(), cmd);
+                for ch in &key {
+                    if !Self::is_permitted_char(*ch) {
+                        return Err(serde::de::Error::custom(format!(
+                            "Unsupported 'key': {}",
+                            ch
+                        )));
+                    }
+                }
+
+                res.map_sequence(&key, cmd);
+
+                if let Some(description) = description {
+                    res.describe_sequence(&key, description);
+                }
             }
         }
 
@@ -282,24 +990,28 @@ impl Serialize for Keys {
         #[derive(Serialize)]
         struct KeyPair {
             shortcut: Option<String>,
-            key: Option<char>,
+            key: Option<String>,
             command: String,
             args: Option<Vec<String>>,
-        };
+            description: Option<String>,
+            table: Option<String>,
+        }
 
         let mut map_to_pair: HashMap<Command, KeyPair> = HashMap::new();
 
         for (character, cmd) in &self.single_key_map {
             let args = cmd.args();
-            let args = if args.len() == 0 { None } else { Some(args) };
+            let args = if args.is_empty() { None } else { Some(args) };
 
             map_to_pair.insert(
-                *cmd,
+                cmd.clone(),
                 KeyPair {
                     shortcut: None,
-                    key: Some(*character),
+                    key: Some(character.to_string()),
                     command: cmd.to_string(),
                     args,
+                    description: self.single_key_descriptions.get(character).cloned(),
+                    table: None,
                 },
             );
         }
@@ -308,41 +1020,223 @@ impl Serialize for Keys {
 
         for (key, cmd) in &self.shortcut_map {
             let args = cmd.args();
-            let args = if args.len() == 0 { None } else { Some(args) };
+            let args = if args.is_empty() { None } else { Some(args) };
+            let description = self.shortcut_descriptions.get(key).cloned();
 
             if map_to_pair.contains_key(cmd) {
-                if map_to_pair.get(cmd).unwrap().args == args {
+                let pair = map_to_pair.get(cmd).unwrap();
+
+                if pair.args == args && pair.description == description {
                     map_to_pair.get_mut(cmd).unwrap().shortcut =
-                        Some(key_to_string(*key).map_err(|e| serde::ser::Error::custom(e))?);
+                        Some(key_to_string(*key).map_err(serde::ser::Error::custom)?);
                 } else {
                     extras.push(KeyPair {
-                        shortcut: Some(
-                            key_to_string(*key).map_err(|e| serde::ser::Error::custom(e))?,
-                        ),
+                        shortcut: Some(key_to_string(*key).map_err(serde::ser::Error::custom)?),
                         key: None,
                         command: cmd.to_string(),
                         args,
+                        description,
+                        table: None,
                     });
                 }
             } else {
                 map_to_pair.insert(
-                    *cmd,
+                    cmd.clone(),
                     KeyPair {
-                        shortcut: Some(
-                            key_to_string(*key).map_err(|e| serde::ser::Error::custom(e))?,
-                        ),
+                        shortcut: Some(key_to_string(*key).map_err(serde::ser::Error::custom)?),
                         key: None,
                         command: cmd.to_string(),
                         args,
+                        description,
+                        table: None,
                     },
                 );
             }
         }
 
-        let mut key_pairs: Vec<KeyPair> = map_to_pair.into_iter().map(|(_, pair)| pair).collect();
+        for (key, cmd) in &self.modified_shortcut_map {
+            let args = cmd.args();
+
+            extras.push(KeyPair {
+                shortcut: Some(key.to_string()),
+                key: None,
+                command: cmd.to_string(),
+                args: if args.is_empty() { None } else { Some(args) },
+                description: self.modified_shortcut_descriptions.get(key).cloned(),
+                table: None,
+            });
+        }
+
+        for (sequence, node) in self.sequence_tree.sequences() {
+            let cmd = node.command.as_ref().unwrap();
+            let args = cmd.args();
+
+            extras.push(KeyPair {
+                shortcut: None,
+                key: Some(sequence.into_iter().collect()),
+                command: cmd.to_string(),
+                args: if args.is_empty() { None } else { Some(args) },
+                description: node.description.clone(),
+                table: None,
+            });
+        }
+
+        for (table, map) in &self.table_maps {
+            for (key, cmd) in map {
+                let args = cmd.args();
+
+                extras.push(KeyPair {
+                    shortcut: Some(key_to_string(*key).map_err(serde::ser::Error::custom)?),
+                    key: None,
+                    command: cmd.to_string(),
+                    args: if args.is_empty() { None } else { Some(args) },
+                    description: None,
+                    table: Some(table.name().to_string()),
+                });
+            }
+        }
+
+        let mut key_pairs: Vec<KeyPair> = map_to_pair.into_values().collect();
 
         key_pairs.append(&mut extras);
 
         return Serialize::serialize(&key_pairs, serializer);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{key_bytes, key_from_string, key_to_string, Keys, ModifiedKey};
+    use crate::command::Command;
+    use termion::event::Key;
+
+    #[test]
+    fn test_special_keys() {
+        assert_eq!(key_from_string("F5".to_string()), Ok(Key::F(5)));
+        assert_eq!(key_from_string("pageup".to_string()), Ok(Key::PageUp));
+        assert_eq!(key_from_string("shift+tab".to_string()), Ok(Key::BackTab));
+        assert_eq!(
+            key_from_string("alt+shift+p".to_string()),
+            Ok(Key::Alt('P'))
+        );
+        assert_eq!(key_from_string("ctrl++".to_string()), Ok(Key::Ctrl('+')));
+        assert_eq!(key_from_string("+".to_string()), Ok(Key::Char('+')));
+        assert!(key_from_string("ctrl+shift+p".to_string()).is_err());
+        assert!(key_from_string("ctrl+up".to_string()).is_err());
+        assert!(key_from_string("f13".to_string()).is_err());
+    }
+
+    #[test]
+    fn test_command_sequences() {
+        let mut keys = Keys::default();
+        keys.map_sequence(&['g', 'w'], Command::OpenPanelCommand);
+
+        assert_eq!(keys.command_for_sequence(&['g']), (None, true));
+        assert_eq!(
+            keys.command_for_sequence(&['g', 'w']),
+            (Some(&Command::OpenPanelCommand), false)
+        );
+        assert_eq!(keys.command_for_sequence(&['g', 'x']), (None, false));
+        assert_eq!(
+            keys.command_for_sequence(&['n']),
+            (Some(&Command::OpenPanelCommand), false)
+        );
+    }
+
+    #[test]
+    fn test_set_prefix() {
+        let mut keys = Keys::default();
+        keys.set_prefix(Key::Ctrl('b'));
+
+        assert_eq!(keys.prefix(), Some(Key::Ctrl('b')));
+        assert_eq!(keys.command_for_shortcut(&Key::Ctrl('a')), None);
+        assert_eq!(key_bytes(Key::Ctrl('b')), Some(vec![0x02]));
+
+        let key = ModifiedKey::from_string("ctrl+shift+p").unwrap();
+        keys.set_modified_prefix(key);
+
+        assert_eq!(keys.prefix(), None);
+        assert_eq!(keys.modified_prefix(), Some(key));
+        assert_eq!(keys.command_for_shortcut(&Key::Ctrl('b')), None);
+        assert_eq!(
+            keys.command_for_modified_shortcut(&key),
+            Some(&Command::EnterSingleCharacterCommand)
+        );
+    }
+
+    #[test]
+    fn test_key_round_trip() {
+        for key in &[
+            Key::F(12),
+            Key::Home,
+            Key::BackTab,
+            Key::Ctrl('a'),
+            Key::Char(' '),
+        ] {
+            assert_eq!(key_from_string(key_to_string(*key).unwrap()), Ok(*key));
+        }
+    }
+
+    #[test]
+    fn test_modified_keys() {
+        let key = ModifiedKey::from_string("ctrl+shift+a").unwrap();
+        assert_eq!(key, ModifiedKey::new(Key::Char('A'), true, false, false));
+        assert_eq!(key.to_string(), "ctrl+shift+a");
+        assert_eq!(key.legacy_key(), None);
+        assert_eq!(key.legacy_bytes(), vec![0x01]);
+
+        let key = ModifiedKey::from_string("ctrl+enter").unwrap();
+        assert_eq!(key.legacy_bytes(), b"\r".to_vec());
+        assert_eq!(
+            ModifiedKey::from_string("ctrl+alt+x")
+                .unwrap()
+                .legacy_bytes(),
+            vec![0x1b, 0x18]
+        );
+        assert_eq!(
+            ModifiedKey::new(Key::Char('a'), true, false, false).legacy_key(),
+            Some(Key::Ctrl('a'))
+        );
+        assert_eq!(
+            ModifiedKey::new(Key::Char('\t'), false, false, true).legacy_key(),
+            Some(Key::BackTab)
+        );
+    }
+
+    #[test]
+    fn test_serialize_descriptions() {
+        let mut keys = Keys::new();
+        keys.map_character('o', Command::SendPrefixCommand);
+        keys.describe_character('o', String::from("Send the prefix"));
+        keys.map_shortcut(Key::Ctrl('o'), Command::SendPrefixCommand);
+
+        // The shortcut isn't described, so it isn't paired with the described character.
+        let keys: Keys = serde_json::from_str(&serde_json::to_string(&keys).unwrap()).unwrap();
+        assert_eq!(
+            keys.single_key_descriptions.get(&'o'),
+            Some(&String::from("Send the prefix"))
+        );
+        assert_eq!(keys.shortcut_descriptions.get(&Key::Ctrl('o')), None);
+        assert_eq!(
+            keys.command_for_shortcut(&Key::Ctrl('o')),
+            Some(&Command::SendPrefixCommand)
+        );
+    }
+
+    #[test]
+    fn test_help_sections() {
+        let mut keys = Keys::default();
+        keys.map_sequence(&['g', 'w'], Command::FocusWorkspaceCommand(2));
+
+        let sections = keys.help_sections();
+        let names: Vec<&str> = sections.iter().map(|(name, _)| name.as_str()).collect();
+
+        assert_eq!(names, vec!["General", "Panels", "Workspaces", "Key tables"]);
+
+        let workspaces = &sections[2].1;
+        assert!(workspaces.contains(&"ctrl+a g w - Focus workspace 2".to_string()));
+        assert!(sections[3]
+            .1
+            .contains(&"[copy] k - Scroll panel up".to_string()));
+    }
+}
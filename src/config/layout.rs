@@ -0,0 +1,174 @@
+use crate::error::{ErrorType, MuxideError};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// A named arrangement of panels, stored in the `[[layouts]]` section of a config.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct Layout {
+    pub name: String,
+    pub root: LayoutNode,
+}
+
+/// A node in a layout, either a panel running a command or a split containing two nodes. An empty
+/// node is a space without a panel.
+#[derive(Clone, PartialEq, Eq, Debug, Default, Deserialize, Serialize)]
+pub struct LayoutNode {
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub split: Option<LayoutSplit>,
+    /// The percentage of a split's space given to the first node.
+    #[serde(default)]
+    pub ratio: Option<u16>,
+    #[serde(default)]
+    pub a: Option<Box<LayoutNode>>,
+    #[serde(default)]
+    pub b: Option<Box<LayoutNode>>,
+}
+
+/// The panels a workspace starts with, from the `[[workspaces]]` section of a config. They are
+/// opened the first time the workspace is displayed, if it doesn't have any panels.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct WorkspaceSetup {
+    pub workspace: u8,
+    /// The name of the layout the workspace is split into.
+    #[serde(default)]
+    pub layout: Option<String>,
+    /// The commands run in the layout's panels, from the first (top left) panel to the last. The
+    /// panels after the last command run the layout's commands.
+    #[serde(default)]
+    pub commands: Vec<String>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum LayoutSplit {
+    Horizontal,
+    Vertical,
+}
+
+impl LayoutNode {
+    /// Returns the split of this node and its two nodes, None if it is at the bottom of the
+    /// layout.
+    pub fn split_nodes(&self) -> Option<(LayoutSplit, &LayoutNode, &LayoutNode)> {
+        return match (self.split, self.a.as_ref(), self.b.as_ref()) {
+            (Some(split), Some(a), Some(b)) => Some((split, a, b)),
+            _ => None,
+        };
+    }
+
+    /// Returns the command of each node at the bottom of the layout, from the first (top left)
+    /// node to the last.
+    pub fn leaf_commands(&self) -> Vec<Option<String>> {
+        return match self.split_nodes() {
+            Some((_, a, b)) => {
+                let mut commands = a.leaf_commands();
+                commands.append(&mut b.leaf_commands());
+                commands
+            }
+            None => vec![self.command.clone()],
+        };
+    }
+}
+
+impl Layout {
+    /// Returns the path a layout with the specified name is saved to, in the layouts folder of
+    /// the config directory.
+    pub fn path_for_name(name: &str) -> Option<PathBuf> {
+        let mut path = super::config_directory()?;
+        path.push("layouts");
+        path.push(format!("{}.toml", name));
+
+        return Some(path);
+    }
+
+    /// Serializes the layout in the `[[layouts]]` format, so it can be copied into a config.
+    pub fn to_toml_string(&self) -> Result<String, String> {
+        #[derive(Serialize)]
+        struct Layouts<'a> {
+            layouts: Vec<&'a Layout>,
+        }
+
+        return toml::to_string(&Layouts {
+            layouts: vec![self],
+        })
+        .map_err(|e| e.to_string());
+    }
+
+    /// Writes the layout to a file, creating the parent directory if necessary.
+    pub fn save(&self, path: &PathBuf) -> Result<(), MuxideError> {
+        let io_error = |reason: String| {
+            ErrorType::IOError {
+                read: false,
+                target: path.to_string_lossy().to_string(),
+                reason,
+            }
+            .into_error()
+        };
+
+        let content = self.to_toml_string().map_err(&io_error)?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| io_error(e.to_string()))?;
+        }
+
+        return std::fs::write(path, content).map_err(|e| io_error(e.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Layout, LayoutNode, LayoutSplit};
+
+    #[test]
+    fn test_leaf_commands() {
+        let leaf = |command: Option<&str>| {
+            Box::new(LayoutNode {
+                command: command.map(|c| c.to_string()),
+                ..LayoutNode::default()
+            })
+        };
+        let node = LayoutNode {
+            split: Some(LayoutSplit::Vertical),
+            a: Some(leaf(Some("nvim"))),
+            b: Some(Box::new(LayoutNode {
+                split: Some(LayoutSplit::Horizontal),
+                a: Some(leaf(None)),
+                b: Some(leaf(Some("htop"))),
+                ..LayoutNode::default()
+            })),
+            ..LayoutNode::default()
+        };
+
+        assert_eq!(
+            node.leaf_commands(),
+            vec![Some("nvim".to_string()), None, Some("htop".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_layout_round_trip() {
+        let layout = Layout {
+            name: "dev".to_string(),
+            root: LayoutNode {
+                split: Some(LayoutSplit::Vertical),
+                ratio: Some(30),
+                a: Some(Box::new(LayoutNode {
+                    command: Some("/bin/sh".to_string()),
+                    ..LayoutNode::default()
+                })),
+                b: Some(Box::new(LayoutNode::default())),
+                ..LayoutNode::default()
+            },
+        };
+
+        let toml = layout.to_toml_string().unwrap();
+
+        #[derive(serde::Deserialize)]
+        struct Layouts {
+            layouts: Vec<Layout>,
+        }
+
+        let parsed: Layouts = toml::from_str(&toml).unwrap();
+        assert_eq!(parsed.layouts, vec![layout]);
+    }
+}
@@ -1,7 +1,20 @@
 mod config;
+mod highlight;
 mod keys;
+mod layout;
 mod password_settings;
+mod paths;
+mod status_bar;
+mod theme;
+mod tuning;
 
 pub use config::Config;
+pub use highlight::{highlight_row, Highlight, HighlightRule};
 use keys::Keys;
+pub use keys::{key_bytes, key_to_string, KeyTable, KeyboardProtocol, ModifiedKey};
+pub use layout::{Layout, LayoutNode, LayoutSplit, WorkspaceSetup};
 pub use password_settings::{HashAlgorithm, PasswordSettings};
+pub use paths::{config_directory, state_directory};
+pub use status_bar::{StatusBar, StatusBarElement, StatusBarPosition};
+pub use theme::Theme;
+pub use tuning::Tuning;
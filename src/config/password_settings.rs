@@ -6,12 +6,22 @@ const fn serde_default_as_false() -> bool {
 }
 
 fn default_password_file_location() -> String {
-    if let Some(mut path) = dirs::home_dir() {
-        path.push(".config/muxide/password");
+    // Password files created before the state directory was used are stored alongside the
+    // config, keep using them if they exist.
+    if let Some(mut path) = super::config_directory() {
+        path.push("password");
 
-        return path.to_str().map(|s| s.to_string()).unwrap();
+        if path.exists() {
+            return path.to_string_lossy().to_string();
+        }
+    }
+
+    if let Some(mut path) = super::state_directory() {
+        path.push("password");
+
+        return path.to_string_lossy().to_string();
     } else {
-        return String::from("~/.config/muxide/password");
+        return String::from("~/.local/state/muxide/password");
     }
 }
 
@@ -103,7 +113,7 @@ impl HashAlgorithm {
         #[cfg(feature = "scrypt")]
         algorithms.push_str("Scrypt, ");
 
-        if algorithms.chars().last() == Some(',') {
+        if algorithms.ends_with(',') {
             algorithms.pop();
         }
 
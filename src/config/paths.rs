@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+/// Returns the value of an XDG base directory variable, the XDG specification requires these to
+/// be absolute paths so any relative paths are ignored.
+fn xdg_directory(variable: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(std::env::var_os(variable)?);
+
+    if path.is_absolute() {
+        return Some(path);
+    } else {
+        return None;
+    }
+}
+
+/// Returns the directory muxide reads its config from. This is `$XDG_CONFIG_HOME/muxide` if set,
+/// otherwise `~/.config/muxide`.
+pub fn config_directory() -> Option<PathBuf> {
+    let mut path = match xdg_directory("XDG_CONFIG_HOME") {
+        Some(p) => p,
+        None => {
+            let mut p = dirs::home_dir()?;
+            p.push(".config");
+            p
+        }
+    };
+
+    path.push("muxide");
+
+    return Some(path);
+}
+
+/// Returns the directory muxide stores persistent state in, i.e. the password file and logs.
+/// This is `$XDG_STATE_HOME/muxide` if set, otherwise `~/.local/state/muxide`.
+pub fn state_directory() -> Option<PathBuf> {
+    let mut path = match xdg_directory("XDG_STATE_HOME") {
+        Some(p) => p,
+        None => {
+            let mut p = dirs::home_dir()?;
+            p.push(".local/state");
+            p
+        }
+    };
+
+    path.push("muxide");
+
+    return Some(path);
+}
@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The row of the terminal the status bar is displayed on.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum StatusBarPosition {
+    Top,
+    Bottom,
+}
+
+/// An element displayed in the status bar. The workspaces are displayed at the start of the bar,
+/// the other elements at its end in the order they are listed.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub enum StatusBarElement {
+    Workspaces,
+    /// The name of the session.
+    Session,
+    /// The local time, formatted with the status bar's clock_format.
+    Clock,
+    /// "CMD" whilst a command is entered after the prefix, or the name of the active key table.
+    Mode,
+    /// The name of the host, up to the first dot.
+    Hostname,
+    /// The charge of the first battery and whether it is charging, read from /sys.
+    Battery,
+    /// The load averages over the last 1, 5 and 15 minutes.
+    LoadAverage,
+    /// Text that is always displayed.
+    Text(String),
+}
+
+impl StatusBarElement {
+    /// Returns true if the element is a widget, its text is read when the widgets are updated
+    /// rather than each time the status bar is displayed.
+    pub fn is_widget(&self) -> bool {
+        return matches!(
+            self,
+            Self::Clock | Self::Hostname | Self::Battery | Self::LoadAverage
+        );
+    }
+}
+
+fn default_position() -> StatusBarPosition {
+    return StatusBarPosition::Top;
+}
+
+fn default_elements() -> Vec<StatusBarElement> {
+    return vec![StatusBarElement::Workspaces, StatusBarElement::Mode];
+}
+
+fn default_clock_format() -> String {
+    return String::from("%H:%M");
+}
+
+fn default_update_interval_ms() -> u64 {
+    return 1000;
+}
+
+/// The position and contents of the status bar, from the `[status_bar]` section of a config. The
+/// status bar is only displayed if the environment's show_workspaces is set.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct StatusBar {
+    #[serde(default = "default_position")]
+    position: StatusBarPosition,
+    #[serde(default = "default_elements")]
+    elements: Vec<StatusBarElement>,
+    /// The strftime format of the clock.
+    #[serde(default = "default_clock_format")]
+    clock_format: String,
+    /// The time between updates of the widgets.
+    #[serde(default = "default_update_interval_ms")]
+    update_interval_ms: u64,
+}
+
+impl StatusBar {
+    /// The shortest time between updates of the widgets that can be configured.
+    const MIN_UPDATE_INTERVAL_MS: u64 = 100;
+    /// The longest time between updates of the widgets that can be configured.
+    const MAX_UPDATE_INTERVAL_MS: u64 = 3_600_000;
+
+    /// Returns an error describing the first setting that is invalid.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.clock_format.contains('\0') {
+            return Err(String::from(
+                "Invalid status bar: clock_format can't contain a null character",
+            ));
+        }
+
+        if self.update_interval_ms < Self::MIN_UPDATE_INTERVAL_MS
+            || self.update_interval_ms > Self::MAX_UPDATE_INTERVAL_MS
+        {
+            return Err(format!(
+                "Invalid status bar: update_interval_ms must be between {} and {}, not {}",
+                Self::MIN_UPDATE_INTERVAL_MS,
+                Self::MAX_UPDATE_INTERVAL_MS,
+                self.update_interval_ms
+            ));
+        }
+
+        return Ok(());
+    }
+
+    pub fn position(&self) -> StatusBarPosition {
+        return self.position;
+    }
+
+    pub fn elements(&self) -> &[StatusBarElement] {
+        return &self.elements;
+    }
+
+    pub fn shows(&self, element: StatusBarElement) -> bool {
+        return self.elements.contains(&element);
+    }
+
+    /// Returns true if any of the elements is a widget that has to be updated.
+    pub fn has_widgets(&self) -> bool {
+        return self.elements.iter().any(StatusBarElement::is_widget);
+    }
+
+    pub fn clock_format(&self) -> &String {
+        return &self.clock_format;
+    }
+
+    pub fn update_interval(&self) -> Duration {
+        return Duration::from_millis(self.update_interval_ms);
+    }
+}
+
+impl Default for StatusBar {
+    fn default() -> Self {
+        return Self {
+            position: default_position(),
+            elements: default_elements(),
+            clock_format: default_clock_format(),
+            update_interval_ms: default_update_interval_ms(),
+        };
+    }
+}
@@ -0,0 +1,113 @@
+use crate::Color;
+use serde::{Deserialize, Serialize};
+
+/// A named palette of the colors muxide draws with, defined in the `[themes.<name>]` section of a
+/// config and selected with `theme = "<name>"`. Colors a theme leaves out are taken from the
+/// default theme.
+#[derive(Copy, Clone, PartialEq, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Theme {
+    /// The color of the borders and split lines, the terminal's default color if it isn't set.
+    pub border: Option<Color>,
+    /// The color of a split line highlighted after the layout changes.
+    pub selected_border: Color,
+    /// The color of the text in the workspace bar, the terminal's default color if it isn't set.
+    pub status_bar: Option<Color>,
+    /// The background of the selected workspace in the workspace bar.
+    pub selected_workspace: Color,
+    /// The color of the text in the message bar.
+    pub message_text: Color,
+    /// The background of the message bar for each level of message.
+    pub info: Color,
+    pub warning: Color,
+    pub error: Color,
+    /// The color of the lock screen, the terminal's default color if it isn't set.
+    pub lock_accent: Option<Color>,
+    pub hint_text: Color,
+    pub hint_background: Color,
+}
+
+impl Theme {
+    /// The names of the themes that are available without being defined in a config.
+    pub const BUILTIN_NAMES: [&'static str; 4] = ["default", "gruvbox", "nord", "dracula"];
+
+    /// Returns the built in theme with the specified name, if there is one.
+    pub fn builtin(name: &str) -> Option<Self> {
+        return match name.to_lowercase().as_str() {
+            "default" => Some(Self::default()),
+            "gruvbox" => Some(Self {
+                border: Some(Color::new(146, 131, 116)),
+                selected_border: Color::new(250, 189, 47),
+                status_bar: Some(Color::new(235, 219, 178)),
+                selected_workspace: Color::new(69, 133, 136),
+                message_text: Color::new(40, 40, 40),
+                info: Color::new(131, 165, 152),
+                warning: Color::new(250, 189, 47),
+                error: Color::new(251, 73, 52),
+                lock_accent: Some(Color::new(254, 128, 25)),
+                hint_text: Color::new(40, 40, 40),
+                hint_background: Color::new(250, 189, 47),
+            }),
+            "nord" => Some(Self {
+                border: Some(Color::new(76, 86, 106)),
+                selected_border: Color::new(136, 192, 208),
+                status_bar: Some(Color::new(216, 222, 233)),
+                selected_workspace: Color::new(94, 129, 172),
+                message_text: Color::new(46, 52, 64),
+                info: Color::new(136, 192, 208),
+                warning: Color::new(235, 203, 139),
+                error: Color::new(191, 97, 106),
+                lock_accent: Some(Color::new(180, 142, 173)),
+                hint_text: Color::new(46, 52, 64),
+                hint_background: Color::new(235, 203, 139),
+            }),
+            "dracula" => Some(Self {
+                border: Some(Color::new(98, 114, 164)),
+                selected_border: Color::new(189, 147, 249),
+                status_bar: Some(Color::new(248, 248, 242)),
+                selected_workspace: Color::new(68, 71, 90),
+                message_text: Color::new(40, 42, 54),
+                info: Color::new(139, 233, 253),
+                warning: Color::new(255, 184, 108),
+                error: Color::new(255, 85, 85),
+                lock_accent: Some(Color::new(255, 121, 198)),
+                hint_text: Color::new(40, 42, 54),
+                hint_background: Color::new(241, 250, 140),
+            }),
+            _ => None,
+        };
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        return Self {
+            border: None,
+            selected_border: Color::WHITE,
+            status_bar: None,
+            selected_workspace: Color::WHITE,
+            message_text: Color::WHITE,
+            info: Color::new(97, 175, 239),
+            warning: Color::new(229, 192, 123),
+            error: Color::new(255, 105, 97),
+            lock_accent: None,
+            hint_text: Color::BLACK,
+            hint_background: Color::YELLOW,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Theme;
+
+    #[test]
+    fn test_builtin_themes() {
+        for name in Theme::BUILTIN_NAMES.iter() {
+            assert!(Theme::builtin(name).is_some());
+        }
+
+        assert_eq!(Theme::builtin("Default"), Some(Theme::default()));
+        assert_eq!(Theme::builtin("missing"), None);
+    }
+}
@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+fn default_error_timeout_ms() -> u64 {
+    return 100;
+}
+
+fn default_file_timeout_ms() -> u64 {
+    return 750;
+}
+
+fn default_send_timeout_ms() -> u64 {
+    return 200;
+}
+
+fn default_frame_delay_ms() -> u64 {
+    return 5;
+}
+
+/// The timings of the tasks running the panels, from the `[tuning]` section of a config. Shorter
+/// timings reduce latency at the cost of CPU usage, longer timings help on slow connections.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Deserialize, Serialize)]
+pub struct Tuning {
+    /// The time allowed for a panel to report an error to the event loop.
+    #[serde(default = "default_error_timeout_ms")]
+    error_timeout_ms: u64,
+    /// The time a panel's program can leave its input unread before the panel is reported as not
+    /// accepting input. The input is kept until it is read.
+    #[serde(default = "default_file_timeout_ms")]
+    file_timeout_ms: u64,
+    /// The time allowed for sending a message to a panel's task.
+    #[serde(default = "default_send_timeout_ms")]
+    send_timeout_ms: u64,
+    /// The time a panel waits after sending a frame before reading more output.
+    #[serde(default = "default_frame_delay_ms")]
+    frame_delay_ms: u64,
+}
+
+impl Tuning {
+    /// The longest timeout that can be configured.
+    const MAX_TIMEOUT_MS: u64 = 10_000;
+    /// The longest frame delay that can be configured.
+    const MAX_FRAME_DELAY_MS: u64 = 1000;
+
+    /// Returns an error describing the first timing that is out of range.
+    pub fn validate(&self) -> Result<(), String> {
+        let timeouts = [
+            ("error_timeout_ms", self.error_timeout_ms),
+            ("file_timeout_ms", self.file_timeout_ms),
+            ("send_timeout_ms", self.send_timeout_ms),
+        ];
+
+        for (name, value) in timeouts.iter() {
+            if *value == 0 || *value > Self::MAX_TIMEOUT_MS {
+                return Err(format!(
+                    "Invalid tuning: {} must be between 1 and {}, not {}",
+                    name,
+                    Self::MAX_TIMEOUT_MS,
+                    value
+                ));
+            }
+        }
+
+        if self.frame_delay_ms > Self::MAX_FRAME_DELAY_MS {
+            return Err(format!(
+                "Invalid tuning: frame_delay_ms must be at most {}, not {}",
+                Self::MAX_FRAME_DELAY_MS,
+                self.frame_delay_ms
+            ));
+        }
+
+        return Ok(());
+    }
+
+    pub fn error_timeout(&self) -> Duration {
+        return Duration::from_millis(self.error_timeout_ms);
+    }
+
+    pub fn file_timeout(&self) -> Duration {
+        return Duration::from_millis(self.file_timeout_ms);
+    }
+
+    pub fn send_timeout(&self) -> Duration {
+        return Duration::from_millis(self.send_timeout_ms);
+    }
+
+    pub fn frame_delay(&self) -> Duration {
+        return Duration::from_millis(self.frame_delay_ms);
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        return Self {
+            error_timeout_ms: default_error_timeout_ms(),
+            file_timeout_ms: default_file_timeout_ms(),
+            send_timeout_ms: default_send_timeout_ms(),
+            frame_delay_ms: default_frame_delay_ms(),
+        };
+    }
+}
@@ -0,0 +1,108 @@
+/// The number of rows each banner glyph occupies.
+pub const BANNER_HEIGHT: usize = 5;
+/// The character used to draw the filled cells of a glyph.
+const FILL_CHARACTER: char = '█';
+
+/// Returns the pattern for a character, '#' represents a filled cell.
+fn glyph(ch: char) -> [&'static str; BANNER_HEIGHT] {
+    return match ch.to_ascii_uppercase() {
+        '0' => ["###", "#.#", "#.#", "#.#", "###"],
+        '1' => [".#.", "##.", ".#.", ".#.", "###"],
+        '2' => ["###", "..#", "###", "#..", "###"],
+        '3' => ["###", "..#", "###", "..#", "###"],
+        '4' => ["#.#", "#.#", "###", "..#", "..#"],
+        '5' => ["###", "#..", "###", "..#", "###"],
+        '6' => ["###", "#..", "###", "#.#", "###"],
+        '7' => ["###", "..#", "..#", "..#", "..#"],
+        '8' => ["###", "#.#", "###", "#.#", "###"],
+        '9' => ["###", "#.#", "###", "..#", "###"],
+        'A' => [".#.", "#.#", "###", "#.#", "#.#"],
+        'B' => ["##.", "#.#", "##.", "#.#", "##."],
+        'C' => ["###", "#..", "#..", "#..", "###"],
+        'D' => ["##.", "#.#", "#.#", "#.#", "##."],
+        'E' => ["###", "#..", "##.", "#..", "###"],
+        'F' => ["###", "#..", "##.", "#..", "#.."],
+        'G' => ["###", "#..", "#.#", "#.#", "###"],
+        'H' => ["#.#", "#.#", "###", "#.#", "#.#"],
+        'I' => ["###", ".#.", ".#.", ".#.", "###"],
+        'J' => ["..#", "..#", "..#", "#.#", "###"],
+        'K' => ["#.#", "#.#", "##.", "#.#", "#.#"],
+        'L' => ["#..", "#..", "#..", "#..", "###"],
+        'M' => ["#.#", "###", "###", "#.#", "#.#"],
+        'N' => ["##.", "#.#", "#.#", "#.#", "#.#"],
+        'O' => ["###", "#.#", "#.#", "#.#", "###"],
+        'P' => ["###", "#.#", "###", "#..", "#.."],
+        'Q' => ["###", "#.#", "#.#", "###", "..#"],
+        'R' => ["###", "#.#", "##.", "#.#", "#.#"],
+        'S' => ["###", "#..", "###", "..#", "###"],
+        'T' => ["###", ".#.", ".#.", ".#.", ".#."],
+        'U' => ["#.#", "#.#", "#.#", "#.#", "###"],
+        'V' => ["#.#", "#.#", "#.#", "#.#", ".#."],
+        'W' => ["#.#", "#.#", "###", "###", "#.#"],
+        'X' => ["#.#", "#.#", ".#.", "#.#", "#.#"],
+        'Y' => ["#.#", "#.#", ".#.", ".#.", ".#."],
+        'Z' => ["###", "..#", ".#.", "#..", "###"],
+        ' ' => ["...", "...", "...", "...", "..."],
+        '-' => ["...", "...", "###", "...", "..."],
+        ':' => [".", "#", ".", "#", "."],
+        '.' => [".", ".", ".", ".", "#"],
+        _ => ["###", "..#", ".#.", "...", ".#."],
+    };
+}
+
+/// Renders text as large block characters, returning one string per row. Each glyph is separated
+/// by a single column of spacing and unsupported characters are drawn as a '?'.
+pub fn render_banner(text: &str) -> Vec<String> {
+    let mut rows = vec![String::new(); BANNER_HEIGHT];
+
+    for (i, ch) in text.chars().enumerate() {
+        let pattern = glyph(ch);
+
+        for (row, line) in rows.iter_mut().enumerate() {
+            if i != 0 {
+                line.push(' ');
+            }
+
+            line.extend(
+                pattern[row]
+                    .chars()
+                    .map(|c| if c == '#' { FILL_CHARACTER } else { ' ' }),
+            );
+        }
+    }
+
+    return rows;
+}
+
+/// Returns the number of columns the rendered banner for some text occupies.
+pub fn banner_width(text: &str) -> usize {
+    let count = text.chars().count();
+
+    if count == 0 {
+        return 0;
+    }
+
+    return text.chars().map(|ch| glyph(ch)[0].len()).sum::<usize>() + count - 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{banner_width, render_banner, BANNER_HEIGHT};
+
+    #[test]
+    fn test_render_single_digit() {
+        let rows = render_banner("1");
+
+        assert_eq!(rows.len(), BANNER_HEIGHT);
+        assert_eq!(rows[0], " █ ");
+        assert_eq!(rows[4], "███");
+    }
+
+    #[test]
+    fn test_banner_width() {
+        assert_eq!(banner_width(""), 0);
+        assert_eq!(banner_width("12"), 7);
+        assert_eq!(banner_width("1:2"), 9);
+        assert_eq!(render_banner("1:2")[0].chars().count(), banner_width("1:2"));
+    }
+}
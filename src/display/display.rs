@@ -1,19 +1,29 @@
+use super::banner::{self, BANNER_HEIGHT};
+use super::message::{MessageLevel, Messages};
 use super::subdivision::{SubDivision, SubDivisionSplit};
+use super::widgets::Widgets;
 use super::workspace::Workspace;
 use super::{panel::PanelPtr, subdivision::SubdivisionPath};
+use crate::config::{Layout, LayoutNode, StatusBarElement, StatusBarPosition};
 use crate::geometry::{Point, Size};
+use crate::graphics::PanelGraphic;
+use crate::panel_screen::PanelRow;
+use crate::Config;
 use crate::{
     error::{ErrorType, MuxideError},
     geometry::Direction,
+    Color,
 };
-use crate::{Color, Config};
 use crossterm::style::Color as CrosstermColor;
 use crossterm::terminal::ClearType;
 use crossterm::{cursor, execute, queue, style, terminal};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{
     collections::HashMap,
     io::{stdout, Stdout, Write},
 };
+use unicode_width::UnicodeWidthStr;
 
 const LOCK_SYMBOL: [&'static str; 13] = [
     "     .--------.",
@@ -31,6 +41,9 @@ const LOCK_SYMBOL: [&'static str; 13] = [
     "'.________________.'",
 ];
 
+/// The position of a hint in its panel and its label.
+type Hint = (Point<u16>, String);
+
 macro_rules! queue_map_err {
     ($($v:expr),*) => {
         queue!($($v),*).map_err(|e| {
@@ -38,7 +51,8 @@ macro_rules! queue_map_err {
                 reason: e.to_string(),
             }
             .into_error()
-        });
+            .with_source(e)
+        })
     };
 }
 
@@ -48,71 +62,258 @@ pub struct Display {
     panel_map: HashMap<usize, PanelPtr>, // id, panel
     workspaces: Vec<Workspace>,
     selected_workspace: u8,
+    /// The workspace that was selected before the selected workspace.
+    last_workspace: Option<u8>,
     completed_initialization: bool,
-    error_message: Option<String>,
+    messages: Messages,
     is_locked: bool,
     display_help_message: bool,
+    /// The number of lines the help message has been scrolled down by.
+    help_scroll: usize,
+    display_panel_identifiers: bool,
+    display_message_history: bool,
+    /// The lines of the statistics overlay, if it is displayed.
+    stats: Option<Vec<String>>,
+    /// The recent log messages, if they are displayed.
+    log: Option<Vec<String>>,
+    /// The workspace and path of a split line that is highlighted until the deadline.
+    highlighted_split: Option<(u8, SubdivisionPath, Instant)>,
+    /// A panel displayed in a floating box above the layout of the selected workspace.
+    popup: Option<PanelPtr>,
+    /// The layouts of the workspaces before they were changed, the most recent is last.
+    layout_history: Vec<(u8, SubDivision)>,
+    /// The id of the panel hints are displayed over, with their labels and positions in the panel.
+    hints: Option<(usize, Vec<Hint>)>,
+    /// The title last written to the terminal's window.
+    window_title: Option<String>,
+    /// Displayed at the end of the workspace bar whilst a command is being entered or a key table
+    /// is active.
+    mode_indicator: Option<String>,
+    /// The name of the session, displayed in the status bar if it is configured to.
+    session_name: String,
+    /// The text of the widgets displayed in the status bar.
+    widgets: Widgets,
 }
 
 impl Display {
-    const ERROR_COLOR: Color = Color::new(255, 105, 97);
     const HELP_TITLE: &'static str = "HELP";
+    const MESSAGE_HISTORY_TITLE: &'static str = "MESSAGES";
+    const STATS_TITLE: &'static str = "STATISTICS";
+    const LOG_TITLE: &'static str = "LOG";
+    const PAUSED_TEXT: &'static str = " PAUSED ";
+    const READ_ONLY_TEXT: &'static str = " \u{1f512} ";
+    /// The amount of time a split line is highlighted after the layout changes.
+    const HIGHLIGHT_DURATION: Duration = Duration::from_millis(300);
+    /// The percentage of the terminal's width and height a popup occupies, including its border.
+    const POPUP_PERCENTAGE: u16 = 80;
+    /// The smallest terminal the display is rendered in, a smaller terminal only displays a
+    /// message asking for a larger one. The lock symbol is the largest fixed size element.
+    const MINIMUM_SIZE: Size = Size::new(15, 40);
+    /// The number of times a frame is rendered before an interrupted write is reported.
+    const RENDER_ATTEMPTS: usize = 3;
+    /// The time waited before rendering a frame again, giving a busy terminal time to catch up.
+    const RENDER_RETRY_DELAY: Duration = Duration::from_millis(5);
+    /// The order the workspaces are displayed and cycled through in, the same order as the number
+    /// keys.
+    const WORKSPACE_ORDER: [u8; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 0];
+    /// The number of layout changes that can be undone.
+    const LAYOUT_HISTORY_LEN: usize = 20;
 
     /// Create a new "display" instance.
     pub fn new(config: Config) -> Self {
+        let mut widgets = Widgets::new();
+        widgets.update(config.status_bar());
+
         return Self {
             config,
             panel_map: HashMap::new(),
             workspaces: vec![Workspace::new(); 10],
             completed_initialization: false,
             selected_workspace: 0,
-            error_message: None,
+            last_workspace: None,
+            messages: Messages::new(),
             is_locked: false,
             display_help_message: false,
+            help_scroll: 0,
+            display_panel_identifiers: false,
+            display_message_history: false,
+            stats: None,
+            log: None,
+            highlighted_split: None,
+            popup: None,
+            layout_history: Vec::new(),
+            hints: None,
+            window_title: None,
+            mode_indicator: None,
+            session_name: String::new(),
+            widgets,
         };
     }
 
     /// Initializes the terminal for output by taking control of the stdout and clearing the
     /// terminal. This must be run before any other methods are.
-    pub fn init(mut self) -> Option<Self> {
-        let origin = if self.config.get_environment_ref().show_workspaces() {
-            Point::new(0, 2)
-        } else {
-            Point::new(0, 0)
-        };
-
-        let dimensions = if self.config.get_environment_ref().show_workspaces() {
-            Self::get_terminal_size().ok()? - Size::new(2, 0)
-        } else {
-            Self::get_terminal_size().ok()?
-        };
+    pub fn init(mut self) -> Result<Self, MuxideError> {
+        let (origin, dimensions) = self.layout_bounds()?;
 
         for workspace in &mut self.workspaces {
             workspace.root_subdivision = SubDivision::new(origin, dimensions);
         }
 
         let mut stdout = stdout();
-        queue!(
+        queue_map_err!(
             stdout,
             terminal::Clear(ClearType::All),
             cursor::MoveTo(0, 0)
-        )
-        .ok()?;
+        )?;
 
-        stdout.flush().ok()?;
+        stdout.flush().map_err(|e| {
+            ErrorType::QueueExecuteError {
+                reason: e.to_string(),
+            }
+            .into_error()
+            .with_source(e)
+        })?;
 
         self.completed_initialization = true;
-        return Some(self);
+        return Ok(self);
     }
 
     pub fn show_help(&mut self) {
         self.display_help_message = true;
+        self.help_scroll = 0;
     }
 
     pub fn hide_help(&mut self) {
         self.display_help_message = false;
     }
 
+    /// Scroll the help message up or down by a number of lines, without scrolling past the end.
+    pub fn scroll_help(&mut self, up: bool, lines: usize) {
+        if up {
+            self.help_scroll = self.help_scroll.saturating_sub(lines);
+        } else {
+            let visible = Self::get_terminal_size()
+                .map(|size| Self::help_visible_rows(&size))
+                .unwrap_or(0);
+            let max_scroll = self.help_lines().len().saturating_sub(visible);
+
+            self.help_scroll = (self.help_scroll + lines).min(max_scroll);
+        }
+    }
+
+    pub fn show_panel_identifiers(&mut self) {
+        self.display_panel_identifiers = true;
+    }
+
+    pub fn hide_panel_identifiers(&mut self) {
+        self.display_panel_identifiers = false;
+    }
+
+    pub fn show_message_history(&mut self) {
+        self.display_message_history = true;
+    }
+
+    pub fn hide_message_history(&mut self) {
+        self.display_message_history = false;
+    }
+
+    /// Display the statistics overlay, or update the lines it displays.
+    pub fn show_stats(&mut self, lines: Vec<String>) {
+        self.stats = Some(lines);
+    }
+
+    pub fn hide_stats(&mut self) {
+        self.stats = None;
+    }
+
+    /// Display the recent log messages, the newest that fit are displayed.
+    pub fn show_log(&mut self, lines: Vec<String>) {
+        self.log = Some(lines);
+    }
+
+    pub fn hide_log(&mut self) {
+        self.log = None;
+    }
+
+    /// Display labels over a panel, at positions relative to the panel's top left cell.
+    pub fn show_hints(&mut self, id: usize, hints: Vec<Hint>) {
+        self.hints = Some((id, hints));
+    }
+
+    pub fn hide_hints(&mut self) {
+        self.hints = None;
+    }
+
+    /// Copy text to the system clipboard through the terminal, using OSC 52. Terminals that
+    /// don't support it ignore the request.
+    pub fn copy_to_clipboard(&self, text: &str) -> Result<(), MuxideError> {
+        let mut stdout = stdout();
+
+        queue_map_err!(
+            stdout,
+            style::Print(format!(
+                "\x1b]52;c;{}\x07",
+                Self::base64_encode(text.as_bytes())
+            ))
+        )?;
+
+        return stdout.flush().map_err(|e| {
+            ErrorType::StdoutFlushError {
+                reason: format!("{}", e),
+            }
+            .into_error()
+            .with_source(e)
+        });
+    }
+
+    /// Returns the time at which the next transient effect, or message, expires and the display
+    /// should be rendered again.
+    pub fn next_effect_deadline(&self) -> Option<Instant> {
+        let highlight = self
+            .highlighted_split
+            .as_ref()
+            .map(|(_, _, deadline)| *deadline);
+
+        return match (highlight, self.messages.deadline()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+
+    /// Returns the time the status bar's widgets should next be updated, None if none are
+    /// displayed.
+    pub fn next_widget_update(&self) -> Option<Instant> {
+        if !self.config.get_environment_ref().show_workspaces() {
+            return None;
+        }
+
+        return self.widgets.next_update(self.config.status_bar());
+    }
+
+    /// Reads the values displayed by the status bar's widgets again.
+    pub fn update_widgets(&mut self) {
+        self.widgets.update(self.config.status_bar());
+    }
+
+    /// Briefly highlight the split line that borders the panel with the specified id, unless
+    /// reduced motion is enabled.
+    fn highlight_split_for_panel(&mut self, id: usize) {
+        if self.config.get_environment_ref().reduced_motion() {
+            return;
+        }
+
+        self.highlighted_split = self
+            .root_subdivision()
+            .parent_path_for_panel_id(id)
+            .map(|path| {
+                (
+                    self.selected_workspace,
+                    path,
+                    Instant::now() + Self::HIGHLIGHT_DURATION,
+                )
+            });
+    }
+
     pub fn lock(&mut self) {
         self.is_locked = true;
     }
@@ -126,7 +327,7 @@ impl Display {
     pub fn update_panel_content(
         &mut self,
         id: usize,
-        content: Vec<Vec<u8>>,
+        content: Arc<[PanelRow]>,
     ) -> Result<(), MuxideError> {
         if !self.completed_initialization {
             return Err(ErrorType::DisplayNotRunningError.into_error());
@@ -140,6 +341,93 @@ impl Display {
         }
     }
 
+    /// Returns the size and origin of the contents of a popup, centered in the terminal.
+    pub fn popup_details(&self) -> Result<(Size, Point<u16>), MuxideError> {
+        let terminal_size = Self::get_terminal_size()?;
+        let rows = terminal_size.get_rows() * Self::POPUP_PERCENTAGE / 100;
+        let cols = terminal_size.get_cols() * Self::POPUP_PERCENTAGE / 100;
+
+        // Leave room for the border and at least one cell of content.
+        if rows < 3 || cols < 3 {
+            return Err(ErrorType::TerminalTooSmallForPopup.into_error());
+        }
+
+        let origin = Point::new(
+            (terminal_size.get_cols() - cols) / 2 + 1,
+            (terminal_size.get_rows() - rows) / 2 + 1,
+        );
+
+        return Ok((Size::new(rows - 2, cols - 2), origin));
+    }
+
+    /// Opens a popup panel with the specified id at the origin from popup_details. Only one popup
+    /// can be open at a time.
+    pub fn open_popup(&mut self, id: usize, origin: Point<u16>) -> Result<(), MuxideError> {
+        if !self.completed_initialization {
+            return Err(ErrorType::DisplayNotRunningError.into_error());
+        }
+
+        if self.popup.is_some() {
+            return Err(ErrorType::PopupAlreadyOpen.into_error());
+        }
+
+        self.popup = Some(self.init_panel(id, (origin.column(), origin.row())));
+
+        return Ok(());
+    }
+
+    /// Closes the popup, if it has the specified id. Returns true if the popup was closed.
+    pub fn close_popup(&mut self, id: usize) -> bool {
+        if self.popup_id() != Some(id) {
+            return false;
+        }
+
+        self.popup = None;
+        self.panel_map.remove(&id);
+
+        return true;
+    }
+
+    /// Returns the layout of the selected workspace with the specified name. Panels run the
+    /// panel init command, as the commands running in each panel aren't tracked.
+    pub fn current_layout(&self, name: &str) -> Layout {
+        return Layout {
+            name: name.to_string(),
+            root: self
+                .root_subdivision()
+                .to_layout_node(self.config.get_panel_init_command()),
+        };
+    }
+
+    /// Split the selected workspace following a layout, it must not have any panels. Returns the
+    /// path, size and origin of the space for each panel of the layout, in order. The workspace
+    /// is left unchanged if it is too small for the layout.
+    pub fn apply_layout(
+        &mut self,
+        layout: &LayoutNode,
+    ) -> Result<Vec<(SubdivisionPath, Size, Point<u16>)>, MuxideError> {
+        if !self.completed_initialization {
+            return Err(ErrorType::DisplayNotRunningError.into_error());
+        }
+
+        let root = self.root_subdivision_mut();
+        let previous = root.clone();
+
+        return root.apply_layout(layout).inspect_err(|_e| {
+            *root = previous;
+        });
+    }
+
+    /// Returns the number of the selected workspace.
+    pub fn selected_workspace_number(&self) -> u8 {
+        return self.selected_workspace;
+    }
+
+    /// Returns the id of the open popup, if there is one.
+    pub fn popup_id(&self) -> Option<usize> {
+        return self.popup.as_ref().map(|p| p.get_id());
+    }
+
     pub fn next_panel_details(&self) -> Result<(SubdivisionPath, Size, Point<u16>), MuxideError> {
         return self
             .root_subdivision()
@@ -165,43 +453,150 @@ impl Display {
 
         self.root_subdivision_mut()
             .open_panel_at_path(panel, panel_path)?;
+        self.highlight_split_for_panel(id);
 
         return Ok(vec![(id, size)]);
     }
 
-    pub fn close_panel(&mut self, id: usize) -> Result<(), MuxideError> {
+    /// Close the panel with the specified id, the other panels in its workspace reclaim its space.
+    /// Returns the new sizes of the panels in the workspace.
+    pub fn close_panel(&mut self, id: usize) -> Result<Vec<(usize, Size)>, MuxideError> {
         if !self.completed_initialization {
             return Err(ErrorType::DisplayNotRunningError.into_error());
         }
 
-        if !self.root_subdivision_mut().close_panel_with_id(id) {
-            panic!("No panel with an id: {}", id);
-        } else {
-            if let Some(panel) = self.selected_panel() {
-                if panel.get_id() == id {
-                    self.selected_workspace_mut().selected_panel =
-                        self.selected_workspace().panels.first().map(|p| p.clone());
-                }
-            }
-
-            self.panel_map.remove(&id);
+        self.highlight_split_for_panel(id);
 
-            return Ok(());
+        // The panel may belong to a workspace that isn't displayed, e.g. if its program exited.
+        let index = self
+            .workspaces
+            .iter()
+            .position(|w| w.root_subdivision.panel_regions().iter().any(|r| r.0 == id))
+            .ok_or_else(|| ErrorType::NoPanelWithIDError { id }.into_error())?;
+        self.record_layout(index as u8);
+
+        let panel_map = &mut self.panel_map;
+        let workspace = &mut self.workspaces[index];
+        workspace.root_subdivision.close_panel_with_id(id);
+        let new_sizes = workspace
+            .root_subdivision
+            .panel_regions()
+            .into_iter()
+            .map(|(id, _, size)| (id, size))
+            .collect();
+
+        if workspace
+            .selected_panel
+            .as_ref()
+            .is_some_and(|p| p.get_id() == id)
+        {
+            workspace.selected_panel = workspace
+                .root_subdivision
+                .panel_regions()
+                .first()
+                .and_then(|(first, _, _)| panel_map.get(first))
+                .cloned();
         }
+
+        panel_map.remove(&id);
+
+        return Ok(new_sizes);
+    }
+
+    /// Returns the ids of the panels in the current workspace, ordered from the first (top left)
+    /// panel to the last.
+    pub fn workspace_panel_ids(&self) -> Vec<usize> {
+        return self
+            .root_subdivision()
+            .panel_regions()
+            .into_iter()
+            .map(|(id, _, _)| id)
+            .collect();
     }
 
-    /// Subdivide the currently selected panel into two panels split with a vertical line down the middle
+    /// Subdivide the currently selected panel into two panels split with a vertical line, at the
+    /// configured split ratio
     pub fn subdivide_selected_panel_vertical(&mut self) -> Result<Vec<(usize, Size)>, MuxideError> {
         return self.subdivide_selected_panel(SubDivisionSplit::Vertical);
     }
 
-    /// Subdivide the currently selected panel into two panels split with a horizontal line down the middle
+    /// Subdivide the currently selected panel into two panels split with a horizontal line, at the
+    /// configured split ratio
     pub fn subdivide_selected_panel_horizontal(
         &mut self,
     ) -> Result<Vec<(usize, Size)>, MuxideError> {
         return self.subdivide_selected_panel(SubDivisionSplit::Horizontal);
     }
 
+    /// Grow or shrink the selected panel by moving the split line next to it. Returns the new
+    /// sizes of the panels in the selected workspace.
+    pub fn resize_selected_panel(&mut self, grow: bool) -> Result<Vec<(usize, Size)>, MuxideError> {
+        let id = match self.selected_panel() {
+            Some(panel) => panel.get_id(),
+            None => return Err(ErrorType::PanelCannotBeResized.into_error()),
+        };
+
+        let previous = self.root_subdivision().clone();
+        if !self.root_subdivision_mut().resize_panel(id, grow)? {
+            return Err(ErrorType::PanelCannotBeResized.into_error());
+        }
+
+        self.push_layout_history(self.selected_workspace, previous);
+
+        self.highlight_split_for_panel(id);
+
+        return Ok(self
+            .root_subdivision()
+            .panel_regions()
+            .into_iter()
+            .map(|(id, _, size)| (id, size))
+            .collect());
+    }
+
+    /// Fit the layout of every workspace and the popup to the current size of the terminal, each
+    /// split keeps its ratio. Returns the new sizes of the panels.
+    pub fn resize_to_terminal(&mut self) -> Result<Vec<(usize, Size)>, MuxideError> {
+        let (origin, dimensions) = self.layout_bounds()?;
+        let mut sizes = Vec::new();
+
+        for workspace in &mut self.workspaces {
+            workspace.root_subdivision.set_geometry(origin, dimensions);
+            sizes.extend(
+                workspace
+                    .root_subdivision
+                    .panel_regions()
+                    .into_iter()
+                    .map(|(id, _, size)| (id, size)),
+            );
+        }
+
+        // The popup is left as it is if it no longer fits, it is resized once the terminal grows.
+        if let (Ok((size, origin)), Some(popup)) = (self.popup_details(), self.popup.as_mut()) {
+            popup.set_location((origin.column(), origin.row()));
+            sizes.push((popup.get_id(), size));
+        }
+
+        return Ok(sizes);
+    }
+
+    /// Returns the panel after, or before, the selected panel in the selected workspace, in the
+    /// order of the subdivision tree and wrapping around. The first panel is returned if no
+    /// panel is selected.
+    pub fn adjacent_panel(&self, forwards: bool) -> Option<usize> {
+        let ids = self.workspace_panel_ids();
+        let selected = self.selected_panel().map(|p| p.get_id());
+        let position = match ids.iter().position(|id| Some(*id) == selected) {
+            Some(position) => position,
+            None => return ids.first().copied(),
+        };
+
+        if forwards {
+            return Some(ids[(position + 1) % ids.len()]);
+        } else {
+            return Some(ids[(position + ids.len() - 1) % ids.len()]);
+        }
+    }
+
     pub fn focus_direction(&mut self, direction: Direction) -> Option<usize> {
         let id = self.selected_panel().map(|p| p.get_id())?;
         return self.root_subdivision_mut().focus_next_id(id, direction);
@@ -213,26 +608,245 @@ impl Display {
             return Err(ErrorType::NoWorkspaceWithID(workspace as usize).into_error());
         }
 
+        if workspace != self.selected_workspace {
+            self.last_workspace = Some(self.selected_workspace);
+        }
+
         self.selected_workspace = workspace;
         return Ok(self.selected_panel().map(|p| p.get_id()));
     }
 
-    /// Subdivide the currently selected panel into two panels split with the specified line down the middle
+    /// Returns the workspace after, or before, the selected workspace in the order they are
+    /// displayed, wrapping around. Workspaces without panels are skipped if the config enables
+    /// it, the selected workspace is returned if every other workspace is skipped.
+    pub fn adjacent_workspace(&self, forwards: bool) -> u8 {
+        let skip_empty = self.config.get_environment_ref().skip_empty_workspaces();
+        let position = Self::WORKSPACE_ORDER
+            .iter()
+            .position(|w| *w == self.selected_workspace)
+            .unwrap_or(0);
+        let len = Self::WORKSPACE_ORDER.len();
+
+        for offset in 1..len {
+            let index = if forwards {
+                (position + offset) % len
+            } else {
+                (position + len - offset) % len
+            };
+            let workspace = Self::WORKSPACE_ORDER[index];
+
+            if !skip_empty
+                || !self.workspaces[workspace as usize]
+                    .root_subdivision
+                    .panel_regions()
+                    .is_empty()
+            {
+                return workspace;
+            }
+        }
+
+        return self.selected_workspace;
+    }
+
+    /// Returns the workspace that was selected before the selected workspace.
+    pub fn last_workspace(&self) -> Option<u8> {
+        return self.last_workspace;
+    }
+
+    /// Restore the layout of the selected workspace from before its last change. Panels that
+    /// have closed since are left out and panels opened since are placed in the free
+    /// subdivisions of the restored layout. Returns the new sizes of the panels in the workspace.
+    pub fn undo_layout(&mut self) -> Result<Vec<(usize, Size)>, MuxideError> {
+        let position = self
+            .layout_history
+            .iter()
+            .rposition(|(w, _)| *w == self.selected_workspace)
+            .ok_or(ErrorType::NoLayoutToUndo.into_error())?;
+        let mut layout = self.layout_history[position].1.clone();
+        let current = self.workspace_panel_ids();
+
+        for (id, _, _) in layout.panel_regions() {
+            if !current.contains(&id) {
+                layout.close_panel_with_id(id);
+            }
+        }
+
+        let restored: Vec<usize> = layout.panel_regions().iter().map(|r| r.0).collect();
+        for id in current.into_iter().filter(|id| !restored.contains(id)) {
+            let (path, _, _) = layout
+                .next_panel_details()
+                .ok_or(ErrorType::LayoutCannotBeRestored.into_error())?;
+            let panel = self
+                .panel_map
+                .get(&id)
+                .cloned()
+                .ok_or(ErrorType::NoPanelWithIDError { id }.into_error())?;
+
+            layout.open_panel_at_path(panel, path)?;
+        }
+
+        // The terminal may have been resized since the layout was recorded.
+        let (origin, dimensions) = self.layout_bounds()?;
+        layout.set_geometry(origin, dimensions);
+
+        self.layout_history.remove(position);
+        self.highlighted_split = None;
+        self.selected_workspace_mut().root_subdivision = layout;
+
+        return Ok(self
+            .root_subdivision()
+            .panel_regions()
+            .into_iter()
+            .map(|(id, _, size)| (id, size))
+            .collect());
+    }
+
+    /// Record the current layout of a workspace so the change about to be made can be undone.
+    fn record_layout(&mut self, workspace: u8) {
+        let layout = self.workspaces[workspace as usize].root_subdivision.clone();
+        self.push_layout_history(workspace, layout);
+    }
+
+    fn push_layout_history(&mut self, workspace: u8, layout: SubDivision) {
+        if self.layout_history.len() == Self::LAYOUT_HISTORY_LEN {
+            self.layout_history.remove(0);
+        }
+
+        self.layout_history.push((workspace, layout));
+    }
+
+    /// Move the selected panel to the first empty workspace and focus it there. Returns the id of
+    /// the moved panel and the new sizes of the panels in both workspaces.
+    pub fn break_selected_panel(&mut self) -> Result<(usize, Vec<(usize, Size)>), MuxideError> {
+        let id = self
+            .selected_panel()
+            .map(|p| p.get_id())
+            .ok_or(ErrorType::NoAvailableSubdivision.into_error())?;
+        let target = *Self::WORKSPACE_ORDER
+            .iter()
+            .find(|w| {
+                self.workspaces[**w as usize]
+                    .root_subdivision
+                    .panel_regions()
+                    .is_empty()
+            })
+            .ok_or(ErrorType::NoEmptyWorkspace.into_error())?;
+
+        let sizes = self.move_panel(id, self.selected_workspace, target)?;
+        self.switch_to_workspace(target)?;
+
+        return Ok((id, sizes));
+    }
+
+    /// Move the selected panel of another workspace into the next free subdivision of the
+    /// selected workspace. Returns the id of the moved panel and the new sizes of the panels in
+    /// both workspaces.
+    pub fn join_panel(
+        &mut self,
+        workspace: u8,
+    ) -> Result<(usize, Vec<(usize, Size)>), MuxideError> {
+        if workspace >= 10 || workspace == self.selected_workspace {
+            return Err(ErrorType::NoWorkspaceWithID(workspace as usize).into_error());
+        }
+
+        let source = &self.workspaces[workspace as usize];
+        let id = source
+            .selected_panel
+            .as_ref()
+            .map(|p| p.get_id())
+            .or_else(|| source.root_subdivision.panel_regions().first().map(|r| r.0))
+            .ok_or(ErrorType::NoPanelInWorkspace(workspace as usize).into_error())?;
+
+        let sizes = self.move_panel(id, workspace, self.selected_workspace)?;
+
+        return Ok((id, sizes));
+    }
+
+    /// Move a panel between workspaces, placing it in the next free subdivision of the target
+    /// workspace where it becomes the selected panel. The panels left in the source workspace
+    /// reclaim its space.
+    fn move_panel(
+        &mut self,
+        id: usize,
+        source: u8,
+        target: u8,
+    ) -> Result<Vec<(usize, Size)>, MuxideError> {
+        let (path, size, origin) = self.workspaces[target as usize]
+            .root_subdivision
+            .next_panel_details()
+            .ok_or(ErrorType::NoAvailableSubdivision.into_error())?;
+        let mut panel = self
+            .panel_map
+            .get(&id)
+            .cloned()
+            .ok_or(ErrorType::NoPanelWithIDError { id }.into_error())?;
+
+        self.record_layout(source);
+        self.record_layout(target);
+
+        let panel_map = &self.panel_map;
+        let source_workspace = &mut self.workspaces[source as usize];
+        if !source_workspace.root_subdivision.close_panel_with_id(id) {
+            return Err(ErrorType::NoPanelWithIDError { id }.into_error());
+        }
+
+        if source_workspace
+            .selected_panel
+            .as_ref()
+            .is_some_and(|p| p.get_id() == id)
+        {
+            source_workspace.selected_panel = source_workspace
+                .root_subdivision
+                .panel_regions()
+                .first()
+                .and_then(|(first, _, _)| panel_map.get(first))
+                .cloned();
+        }
+
+        panel.set_location((origin.column(), origin.row()));
+        let target_workspace = &mut self.workspaces[target as usize];
+        target_workspace
+            .root_subdivision
+            .open_panel_at_path(panel.clone(), path)?;
+        target_workspace.selected_panel = Some(panel);
+
+        let mut sizes: Vec<(usize, Size)> = self.workspaces[source as usize]
+            .root_subdivision
+            .panel_regions()
+            .into_iter()
+            .map(|(id, _, size)| (id, size))
+            .collect();
+        sizes.push((id, size));
+
+        return Ok(sizes);
+    }
+
+    /// Subdivide the currently selected panel into two panels split with the specified line, at the
+    /// configured split ratio
     fn subdivide_selected_panel(
         &mut self,
         direction: SubDivisionSplit,
     ) -> Result<Vec<(usize, Size)>, MuxideError> {
         let id = self.selected_panel().map(|p| p.get_id());
-        let (sz, success) = self.root_subdivision_mut().split_panel(id, direction);
+        let ratio = self.config.get_environment_ref().split_ratio();
+        let previous = self.root_subdivision().clone();
+        let (sz, success) = self
+            .root_subdivision_mut()
+            .split_panel(id, direction, ratio)?;
 
         if !success {
             return Err(ErrorType::FailedSubdivision.into_error());
         }
 
-        return Ok(if let Some(sz) = sz {
-            vec![(self.selected_panel().unwrap().get_id(), sz)]
-        } else {
-            Vec::new()
+        self.push_layout_history(self.selected_workspace, previous);
+
+        if let Some(id) = id {
+            self.highlight_split_for_panel(id);
+        }
+
+        return Ok(match (id, sz) {
+            (Some(id), Some(sz)) => vec![(id, sz)],
+            _ => Vec::new(),
         });
     }
 
@@ -245,8 +859,24 @@ impl Display {
         return panel;
     }
 
-    /// Render the contents of the display to stdout.
+    /// Render the contents of the display to stdout. A frame that fails to be written because
+    /// writing was interrupted or would block is rendered again, since each frame starts by
+    /// clearing the terminal.
     pub fn render(&mut self) -> Result<(), MuxideError> {
+        let mut attempts = 1;
+
+        loop {
+            match self.render_frame() {
+                Err(e) if e.is_transient() && attempts < Self::RENDER_ATTEMPTS => {
+                    attempts += 1;
+                    std::thread::sleep(Self::RENDER_RETRY_DELAY);
+                }
+                res => return res,
+            }
+        }
+    }
+
+    fn render_frame(&mut self) -> Result<(), MuxideError> {
         if !self.completed_initialization {
             return Ok(());
         }
@@ -260,24 +890,84 @@ impl Display {
                 reason: e.to_string(),
             }
             .into_error()
+            .with_source(e)
         })?;
 
+        if size.get_rows() < Self::MINIMUM_SIZE.get_rows()
+            || size.get_cols() < Self::MINIMUM_SIZE.get_cols()
+        {
+            Self::queue_too_small_message(&mut stdout, &size)?;
+            Self::reset_stdout_style(&mut stdout)?;
+
+            return stdout.flush().map_err(|e| {
+                ErrorType::StdoutFlushError {
+                    reason: format!("{}", e),
+                }
+                .into_error()
+                .with_source(e)
+            });
+        }
+
+        self.messages.expire(Instant::now());
+
         if self.is_locked {
-            Self::queue_locked_message(&mut stdout, &size)?;
+            Self::queue_locked_message(
+                &mut stdout,
+                &size,
+                self.theme_color(self.config.theme().lock_accent),
+            )?;
         } else if self.display_help_message {
             self.queue_help_message(&mut stdout, &size)?;
+        } else if self.display_message_history {
+            self.queue_message_history(&mut stdout, &size)?;
+        } else if let Some(lines) = self.stats.as_ref() {
+            Self::queue_overlay(&mut stdout, &size, Self::STATS_TITLE, lines)?;
+        } else if let Some(lines) = self.log.as_ref() {
+            let visible = Self::help_visible_rows(&size);
+
+            Self::queue_overlay(
+                &mut stdout,
+                &size,
+                Self::LOG_TITLE,
+                &lines[lines.len().saturating_sub(visible)..],
+            )?;
         } else {
             self.queue_main_borders(&mut stdout, &size)?;
 
-            self.root_subdivision().render(&mut stdout, &self.config)?;
+            if let Some((_, _, deadline)) = self.highlighted_split.as_ref() {
+                if *deadline <= Instant::now() {
+                    self.highlighted_split = None;
+                }
+            }
+
+            let highlight = match self.highlighted_split.as_ref() {
+                Some((workspace, path, _)) if *workspace == self.selected_workspace => {
+                    Some(path.clone())
+                }
+                _ => None,
+            };
+
+            self.root_subdivision()
+                .render(&mut stdout, &self.config, highlight)?;
+            self.queue_graphics(&mut stdout)?;
+            self.queue_panel_titles(&mut stdout)?;
+            self.queue_panel_indicators(&mut stdout)?;
+            self.queue_hints(&mut stdout)?;
+
+            if self.display_panel_identifiers {
+                self.queue_panel_identifiers(&mut stdout)?;
+            }
+
+            self.queue_popup(&mut stdout)?;
         }
 
-        if self.error_message.is_some() {
-            self.queue_error_message(&mut stdout, &size).map_err(|e| {
+        if self.messages.current().is_some() {
+            self.queue_message(&mut stdout, &size).map_err(|e| {
                 ErrorType::QueueExecuteError {
                     reason: e.to_string(),
                 }
                 .into_error()
+                .with_source(e)
             })?;
         }
 
@@ -286,107 +976,628 @@ impl Display {
                 reason: e.to_string(),
             }
             .into_error()
+            .with_source(e)
         })?;
 
         Self::reset_stdout_style(&mut stdout)?;
 
-        return Ok(stdout.flush().map_err(|e| {
+        return stdout.flush().map_err(|e| {
             ErrorType::StdoutFlushError {
                 reason: format!("{}", e),
             }
-            .into_error()
-        })?);
+            .into_error()
+            .with_source(e)
+        });
+    }
+
+    /// Displays a message asking for a larger terminal, truncated to fit the terminal.
+    fn queue_too_small_message(stdout: &mut Stdout, size: &Size) -> Result<(), MuxideError> {
+        let message = format!(
+            "Terminal too small (need {}x{})",
+            Self::MINIMUM_SIZE.get_cols(),
+            Self::MINIMUM_SIZE.get_rows()
+        );
+        let message: String = message.chars().take(size.get_cols() as usize).collect();
+        let starting_col = size.get_cols().saturating_sub(message.len() as u16) / 2;
+
+        queue_map_err!(
+            stdout,
+            style::ResetColor,
+            cursor::MoveTo(starting_col, size.get_rows() / 2),
+            style::Print(message),
+            cursor::Hide
+        )?;
+
+        return Ok(());
+    }
+
+    fn queue_locked_message(
+        stdout: &mut Stdout,
+        size: &Size,
+        accent: Option<Color>,
+    ) -> Result<(), MuxideError> {
+        let starting_row = (size.get_rows() - LOCK_SYMBOL.len() as u16) / 2;
+        let starting_col = (size.get_cols() - LOCK_SYMBOL[LOCK_SYMBOL.len() - 1].len() as u16) / 2;
+
+        queue_map_err!(stdout, style::ResetColor)?;
+        Self::queue_foreground(stdout, accent)?;
+
+        for i in 0..LOCK_SYMBOL.len() as u16 {
+            queue_map_err!(
+                stdout,
+                cursor::MoveTo(starting_col, starting_row + i),
+                style::Print(LOCK_SYMBOL[i as usize])
+            )?;
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the lines of the help message, each category has a heading followed by its
+    /// bindings.
+    fn help_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for (i, (category, bindings)) in self
+            .config
+            .key_map()
+            .help_sections()
+            .into_iter()
+            .enumerate()
+        {
+            if i != 0 {
+                lines.push(String::new());
+            }
+
+            lines.push(format!("{}:", category));
+            lines.extend(bindings.into_iter().map(|b| format!("  {}", b)));
+        }
+
+        return lines;
+    }
+
+    /// The number of rows available for the help message, the title and the scroll indicator
+    /// take up the rest.
+    fn help_visible_rows(size: &Size) -> usize {
+        return (size.get_rows() as usize).saturating_sub(3);
+    }
+
+    fn queue_help_message(&self, stdout: &mut Stdout, size: &Size) -> Result<(), MuxideError> {
+        queue_map_err!(stdout, style::ResetColor)?;
+
+        let help_lines = self.help_lines();
+        let longest_line = help_lines
+            .iter()
+            .map(|l| l.chars().count())
+            .max()
+            .unwrap_or(0);
+        let visible = Self::help_visible_rows(size);
+        let scroll = self
+            .help_scroll
+            .min(help_lines.len().saturating_sub(visible));
+
+        let starting_col = if (size.get_cols() as usize) < longest_line {
+            0
+        } else {
+            (size.get_cols() - longest_line as u16) / 2
+        };
+
+        let starting_row = if help_lines.len() >= visible {
+            2
+        } else {
+            2 + (visible - help_lines.len()) as u16 / 2
+        };
+
+        for (i, line) in help_lines.iter().skip(scroll).take(visible).enumerate() {
+            let line: String = line.chars().take(size.get_cols() as usize).collect();
+
+            queue_map_err!(
+                stdout,
+                cursor::MoveTo(starting_col, starting_row + i as u16),
+                style::Print(line)
+            )?;
+        }
+
+        // Indicate which lines are being displayed when they don't all fit.
+        if help_lines.len() > visible {
+            let indicator = format!(
+                "-- {}-{} of {}, scroll for more --",
+                scroll + 1,
+                scroll + visible,
+                help_lines.len()
+            );
+            let indicator: String = indicator.chars().take(size.get_cols() as usize).collect();
+
+            queue_map_err!(
+                stdout,
+                cursor::MoveTo(
+                    (size.get_cols() - indicator.chars().count() as u16) / 2,
+                    size.get_rows() - 1
+                ),
+                style::Print(indicator)
+            )?;
+        }
+
+        queue_map_err!(
+            stdout,
+            cursor::MoveTo((size.get_cols() - Self::HELP_TITLE.len() as u16) / 2, 0),
+            style::Print(Self::HELP_TITLE)
+        )?;
+
+        return Ok(());
+    }
+
+    /// Draws the most recent messages that fit in the terminal, newest at the bottom.
+    fn queue_message_history(&self, stdout: &mut Stdout, size: &Size) -> Result<(), MuxideError> {
+        queue_map_err!(stdout, style::ResetColor)?;
+
+        let lines: Vec<String> = self
+            .messages
+            .history()
+            .map(|m| format!("[{}] {}", m.level.label(), m.text))
+            .collect();
+        let visible = Self::help_visible_rows(size);
+
+        if lines.is_empty() {
+            let text = "No messages";
+
+            queue_map_err!(
+                stdout,
+                cursor::MoveTo(
+                    (size.get_cols().saturating_sub(text.len() as u16)) / 2,
+                    size.get_rows() / 2
+                ),
+                style::Print(text)
+            )?;
+        }
+
+        for (i, line) in lines
+            .iter()
+            .skip(lines.len().saturating_sub(visible))
+            .enumerate()
+        {
+            let line: String = line.chars().take(size.get_cols() as usize).collect();
+
+            queue_map_err!(stdout, cursor::MoveTo(0, 2 + i as u16), style::Print(line))?;
+        }
+
+        queue_map_err!(
+            stdout,
+            cursor::MoveTo(
+                (size.get_cols() - Self::MESSAGE_HISTORY_TITLE.len() as u16) / 2,
+                0
+            ),
+            style::Print(Self::MESSAGE_HISTORY_TITLE)
+        )?;
+
+        return Ok(());
+    }
+
+    /// Draws an overlay with a title, such as the statistics, one line per row.
+    fn queue_overlay(
+        stdout: &mut Stdout,
+        size: &Size,
+        title: &str,
+        lines: &[String],
+    ) -> Result<(), MuxideError> {
+        queue_map_err!(stdout, style::ResetColor)?;
+
+        for (i, line) in lines.iter().take(Self::help_visible_rows(size)).enumerate() {
+            let line: String = line.chars().take(size.get_cols() as usize).collect();
+
+            queue_map_err!(stdout, cursor::MoveTo(0, 2 + i as u16), style::Print(line))?;
+        }
+
+        queue_map_err!(
+            stdout,
+            cursor::MoveTo(size.get_cols().saturating_sub(title.len() as u16) / 2, 0),
+            style::Print(title)
+        )?;
+
+        return Ok(());
+    }
+
+    /// Draws the id of each panel in large text over the center of the panel.
+    fn queue_panel_identifiers(&self, stdout: &mut Stdout) -> Result<(), MuxideError> {
+        queue_map_err!(stdout, style::ResetColor)?;
+
+        for (id, origin, size) in self.root_subdivision().panel_regions() {
+            let text = format!("{}", id);
+            let width = banner::banner_width(&text) as u16;
+
+            if width > size.get_cols() || BANNER_HEIGHT as u16 > size.get_rows() {
+                continue;
+            }
+
+            let starting_col = origin.column() + (size.get_cols() - width) / 2;
+            let starting_row = origin.row() + (size.get_rows() - BANNER_HEIGHT as u16) / 2;
+
+            for (r, line) in banner::render_banner(&text).into_iter().enumerate() {
+                queue_map_err!(
+                    stdout,
+                    cursor::MoveTo(starting_col, starting_row + r as u16),
+                    style::Print(line)
+                )?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Draws the title of each panel that has one on its top border, titles are cut short to fit
+    /// beside the panel's indicators. The panels' labels are drawn instead if they are shown.
+    fn queue_panel_titles(&self, stdout: &mut Stdout) -> Result<(), MuxideError> {
+        let show_labels = self.config.get_environment_ref().show_panel_labels();
+
+        for (index, (id, origin, size)) in self
+            .root_subdivision()
+            .panel_regions()
+            .into_iter()
+            .enumerate()
+        {
+            let title = match self.panel_map.get(&id) {
+                Some(panel) if show_labels => Self::panel_label(index + 1, panel),
+                Some(panel) => match panel.get_title() {
+                    Some(title) => title,
+                    None => continue,
+                },
+                None => continue,
+            };
+
+            let mut width = size.get_cols().saturating_sub(2) as usize;
+            let indicators = self.panel_indicators(id);
+            if !indicators.is_empty() {
+                width = width.saturating_sub(indicators.width() + 1);
+            }
+
+            let text: String = format!(" {} ", title).chars().take(width).collect();
+            if text.is_empty() {
+                continue;
+            }
+
+            queue_map_err!(
+                stdout,
+                cursor::MoveTo(origin.column() + 1, origin.row().saturating_sub(1)),
+                style::Print(text)
+            )?;
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the label of a panel, its number in the workspace followed by its state, the program
+    /// running in it and its title, e.g. "2 [RO LOG] vim".
+    fn panel_label(number: usize, panel: &PanelPtr) -> String {
+        let mut parts = vec![number.to_string()];
+        let mut flags = Vec::new();
+
+        if panel.is_read_only() {
+            flags.push("RO");
+        }
+
+        if panel.is_paused() {
+            flags.push("PAUSED");
+        }
+
+        if panel.is_logging() {
+            flags.push("LOG");
+        }
+
+        if !flags.is_empty() {
+            parts.push(format!("[{}]", flags.join(" ")));
+        }
+
+        parts.extend(panel.get_command());
+        parts.extend(panel.get_title());
+
+        let label = parts.join(" ");
+        return label.chars().filter(|c| !c.is_control()).collect();
+    }
+
+    /// Returns the indicators of a panel's state that are displayed on its top border, the
+    /// read only indicator followed by the paused indicator. They are part of the panels' labels
+    /// if they are shown instead.
+    fn panel_indicators(&self, id: usize) -> String {
+        let mut indicators = String::new();
+
+        if self.config.get_environment_ref().show_panel_labels() {
+            return indicators;
+        }
+
+        if let Some(panel) = self.panel_map.get(&id) {
+            if panel.is_read_only() {
+                indicators.push_str(Self::READ_ONLY_TEXT);
+            }
+
+            if panel.is_paused() {
+                indicators.push_str(Self::PAUSED_TEXT);
+            }
+        }
+
+        return indicators;
     }
 
-    fn queue_locked_message(stdout: &mut Stdout, size: &Size) -> Result<(), MuxideError> {
-        let starting_row = (size.get_rows() - LOCK_SYMBOL.len() as u16) / 2;
-        let starting_col = (size.get_cols() - LOCK_SYMBOL[LOCK_SYMBOL.len() - 1].len() as u16) / 2;
+    /// Marks the panels that are read only or whose output is paused on the border above them,
+    /// or on their first row if they are at the top of the terminal.
+    fn queue_panel_indicators(&self, stdout: &mut Stdout) -> Result<(), MuxideError> {
+        for (id, origin, size) in self.root_subdivision().panel_regions() {
+            let indicators = self.panel_indicators(id);
+            if indicators.is_empty() {
+                continue;
+            }
 
-        queue_map_err!(stdout, style::ResetColor)?;
+            let width = indicators.width() as u16;
+            if width + 1 > size.get_cols() {
+                continue;
+            }
 
-        for i in 0..LOCK_SYMBOL.len() as u16 {
             queue_map_err!(
                 stdout,
-                cursor::MoveTo(starting_col, starting_row + i),
-                style::Print(LOCK_SYMBOL[i as usize])
+                cursor::MoveTo(
+                    origin.column() + size.get_cols() - width - 1,
+                    origin.row().saturating_sub(1)
+                ),
+                style::SetAttribute(style::Attribute::Reverse),
+                style::Print(indicators),
+                style::SetAttribute(style::Attribute::Reset)
             )?;
         }
 
         return Ok(());
     }
 
-    fn queue_help_message(&self, stdout: &mut Stdout, size: &Size) -> Result<(), MuxideError> {
-        queue_map_err!(stdout, style::ResetColor)?;
+    /// Passes the images drawn in the selected panel through to the terminal, images drawn
+    /// outside the panel's region are left out. The screen is cleared before each render, so they
+    /// are drawn every time. Nothing is drawn whilst a popup is shown.
+    fn queue_graphics(&self, stdout: &mut Stdout) -> Result<(), MuxideError> {
+        if !self.config.get_environment_ref().graphics_passthrough() || self.popup.is_some() {
+            return Ok(());
+        }
 
-        let (mut help_lines, longest_line) = self.config.key_map().help_message_keymap();
+        let panel = match self.selected_panel() {
+            Some(panel) => panel,
+            None => return Ok(()),
+        };
+        let (origin, size) = match self
+            .root_subdivision()
+            .panel_regions()
+            .into_iter()
+            .find(|region| region.0 == panel.get_id())
+        {
+            Some((_, origin, size)) => (origin, size),
+            None => return Ok(()),
+        };
 
-        let starting_cols: [u16; 2];
-        let starting_row;
+        for graphic in panel.get_graphics().iter() {
+            if graphic.col >= size.get_cols() || graphic.row >= size.get_rows() {
+                continue;
+            }
 
-        if help_lines.len() + 2 > (size.get_rows() as usize) {
-            starting_row = 2;
-        } else {
-            starting_row = 2 + (size.get_rows() - 2 - help_lines.len() as u16 ) / 2;
+            queue_map_err!(
+                stdout,
+                cursor::MoveTo(origin.column() + graphic.col, origin.row() + graphic.row)
+            )?;
+            stdout
+                .write_all(&graphic.sequence)
+                .map_err(ErrorType::new_display_qe_error)?;
         }
 
-        let multiple_per_line = ((size.get_cols() as usize) - longest_line - 4) > 0;
-
-        if (size.get_cols() as usize) < longest_line {
-            starting_cols = [0, 0];
-        } else {
-            if multiple_per_line {
-                let spacing = (size.get_cols() - longest_line as u16 * 2 ) / 3;
+        return Ok(());
+    }
 
-                starting_cols = [spacing, spacing + longest_line as u16 + spacing];
-            } else {
-                starting_cols = [(size.get_cols() - longest_line as u16) / 2, 0];
+    /// Draws the hint labels over the panel they were found in, labels that don't fit in the
+    /// panel are left out.
+    fn queue_hints(&self, stdout: &mut Stdout) -> Result<(), MuxideError> {
+        let (id, hints) = match self.hints.as_ref() {
+            Some(hints) => hints,
+            None => return Ok(()),
+        };
+        let (origin, size) = match self
+            .root_subdivision()
+            .panel_regions()
+            .into_iter()
+            .find(|region| region.0 == *id)
+        {
+            Some((_, origin, size)) => (origin, size),
+            None => return Ok(()),
+        };
+        let theme = self.config.theme();
+        let (attribute, text_color, background_color) = self.highlight(
+            theme.hint_text.crossterm_color(CrosstermColor::Black),
+            theme
+                .hint_background
+                .crossterm_color(CrosstermColor::Yellow),
+        );
+
+        for (position, label) in hints {
+            if position.row() >= size.get_rows()
+                || position.column() + label.len() as u16 > size.get_cols()
+            {
+                continue;
             }
+
+            queue_map_err!(
+                stdout,
+                cursor::MoveTo(
+                    origin.column() + position.column(),
+                    origin.row() + position.row()
+                ),
+                style::SetAttribute(attribute),
+                style::SetForegroundColor(text_color),
+                style::SetBackgroundColor(background_color),
+                style::Print(label),
+                style::SetAttribute(style::Attribute::NoReverse),
+                style::ResetColor
+            )?;
         }
 
-        let mut r = 0;
+        return Ok(());
+    }
+
+    fn base64_encode(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut encoded = String::new();
 
-        while help_lines.len() > 0 {
-            if starting_row + r > size.get_rows() {
-                break;
+        for chunk in bytes.chunks(3) {
+            let n = (chunk[0] as u32) << 16
+                | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+                | *chunk.get(2).unwrap_or(&0) as u32;
+
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
             }
+        }
 
-            queue_map_err!(stdout, cursor::MoveTo(starting_cols[0], starting_row + r), style::Print(help_lines.pop().unwrap()))?;
+        return encoded;
+    }
 
-            if multiple_per_line && help_lines.len() > 0 {
-                queue_map_err!(stdout, cursor::MoveTo(starting_cols[1], starting_row + r), style::Print("    "), style::Print(help_lines.pop().unwrap()))?;
-            }
+    /// Draws the popup, if there is one, with a border around its contents.
+    fn queue_popup(&self, stdout: &mut Stdout) -> Result<(), MuxideError> {
+        let popup = match self.popup.as_ref() {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let (size, origin) = self.popup_details()?;
+        let borders = self.config.get_borders_ref();
+        let horizontal_line = borders
+            .get_horizontal_char()
+            .to_string()
+            .repeat(size.get_cols() as usize);
+
+        Self::reset_stdout_style(stdout)?;
+        Self::queue_foreground(stdout, self.theme_color(self.config.theme().border))?;
 
-            r += 1;
+        for row in [origin.row() - 1, origin.row() + size.get_rows()].iter() {
+            queue_map_err!(
+                stdout,
+                cursor::MoveTo(origin.column() - 1, *row),
+                style::Print(borders.get_intersection_char()),
+                style::Print(&horizontal_line),
+                style::Print(borders.get_intersection_char())
+            )?;
         }
 
-        for (i, line) in help_lines.into_iter().enumerate() {
-            if starting_row + (i as u16) > size.get_rows() {
-                break;
-            }
+        let blank = " ".repeat(size.get_cols() as usize);
 
-            queue_map_err!(stdout, cursor::MoveTo(starting_cols[0], starting_row + i as u16), style::Print(line))?;
+        for r in 0..size.get_rows() {
+            queue_map_err!(
+                stdout,
+                cursor::MoveTo(origin.column() - 1, origin.row() + r),
+                style::Print(borders.get_vertical_char()),
+                style::Print(&blank),
+                style::Print(borders.get_vertical_char())
+            )?;
         }
 
-        queue_map_err!(stdout, cursor::MoveTo((size.get_cols() - Self::HELP_TITLE.len() as u16) / 2, 0), style::Print(Self::HELP_TITLE))?;
+        for (row_number, row) in popup.get_content().iter().enumerate() {
+            queue_map_err!(
+                stdout,
+                cursor::MoveTo(origin.column(), origin.row() + row_number as u16),
+                style::ResetColor
+            )?;
+
+            stdout.write(row).map_err(ErrorType::new_display_qe_error)?;
+        }
 
         return Ok(());
     }
 
+    /// Returns the origin and dimensions of the area the panels are laid out in, above or below
+    /// the status bar if it is shown. The top row is left for the labels of the panels at the top
+    /// if they are shown without the status bar above them.
+    fn layout_bounds(&self) -> Result<(Point<u16>, Size), MuxideError> {
+        let environment = self.config.get_environment_ref();
+        // The number of rows above and below the panels.
+        let (mut top, mut bottom) = (0, 0);
+
+        if environment.show_workspaces() {
+            match self.config.status_bar().position() {
+                StatusBarPosition::Top => top = 2,
+                StatusBarPosition::Bottom => bottom = 2,
+            }
+        }
+
+        if top == 0 && environment.show_panel_labels() {
+            top = 1;
+        }
+
+        return Ok((
+            Point::new(0, top),
+            Self::get_terminal_size()? - Size::new(top + bottom, 0),
+        ));
+    }
+
+    /// Determines the size of the terminal. If stdout is not a terminal, i.e. it is piped to
+    /// another process, the size of the controlling terminal is used instead.
     fn get_terminal_size() -> Result<Size, MuxideError> {
-        let (cols, rows) = match terminal::size() {
-            Ok(t) => t,
-            Err(e) => {
-                return Err(ErrorType::DetermineTerminalSizeError {
+        let (cols, rows) = if unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1 {
+            terminal::size().map_err(|e| {
+                ErrorType::DetermineTerminalSizeError {
                     reason: e.to_string(),
                 }
-                .into_error());
-            }
+                .into_error()
+            })?
+        } else {
+            Self::controlling_terminal_size()?
         };
 
+        if cols == 0 || rows == 0 {
+            return Err(ErrorType::DetermineTerminalSizeError {
+                reason: format!("The terminal has a size of {}x{}.", cols, rows),
+            }
+            .into_error());
+        }
+
         return Ok(Size::new(rows, cols));
     }
 
+    /// Queries the size of the controlling terminal through /dev/tty, returning the columns and
+    /// rows.
+    fn controlling_terminal_size() -> Result<(u16, u16), MuxideError> {
+        use std::os::unix::io::AsRawFd;
+
+        let tty = std::fs::File::open("/dev/tty").map_err(|e| {
+            ErrorType::DetermineTerminalSizeError {
+                reason: format!(
+                    "stdout is not a terminal and the controlling terminal could not be opened. {}",
+                    e
+                ),
+            }
+            .into_error()
+        })?;
+
+        let mut size = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+
+        let res = unsafe { libc::ioctl(tty.as_raw_fd(), libc::TIOCGWINSZ, &mut size) };
+
+        if res != 0 {
+            return Err(ErrorType::IOCTLError {
+                code: res,
+                outcome: "Failed to determine the size of the controlling terminal.".to_string(),
+            }
+            .into_error());
+        }
+
+        return Ok((size.ws_col, size.ws_row));
+    }
+
     /// Moves the cursor to the correct position and changes it to hidden or visible appropriately
+    /// Returns the DECSCUSR sequence that sets the cursor style.
+    fn cursor_style_sequence(style: u8) -> String {
+        return format!("\x1b[{} q", style);
+    }
+
     fn reset_cursor(&self, stdout: &mut Stdout, _terminal_size: &Size) -> Result<(), MuxideError> {
         if self.is_locked || self.display_help_message {
             execute!(stdout, cursor::Hide, cursor::MoveTo(0, 0)).map_err(|e| {
@@ -394,18 +1605,20 @@ impl Display {
                     reason: e.to_string(),
                 }
                 .into_error()
+                .with_source(e)
             })?;
 
             return Ok(());
         }
 
-        match self.selected_panel() {
+        match self.popup.as_ref().or(self.selected_panel()) {
             Some(panel) => {
                 let loc = panel.get_cursor_position();
 
                 queue_map_err!(
                     stdout,
-                    cursor::MoveTo(loc.column(), loc.row()) // Column, row
+                    cursor::MoveTo(loc.column(), loc.row()), // Column, row
+                    style::Print(Self::cursor_style_sequence(panel.get_cursor_style()))
                 )?;
 
                 if panel.get_hide_cursor() {
@@ -414,6 +1627,7 @@ impl Display {
                             reason: e.to_string(),
                         }
                         .into_error()
+                        .with_source(e)
                     })?;
                 } else {
                     execute!(stdout, cursor::Show).map_err(|e| {
@@ -421,6 +1635,7 @@ impl Display {
                             reason: e.to_string(),
                         }
                         .into_error()
+                        .with_source(e)
                     })?;
                 }
             }
@@ -430,6 +1645,7 @@ impl Display {
                         reason: e.to_string(),
                     }
                     .into_error()
+                    .with_source(e)
                 })?;
             }
         }
@@ -449,11 +1665,21 @@ impl Display {
 
         Self::reset_stdout_style(stdout)?;
 
-        if self.config.get_environment_ref().show_workspaces() {
+        let show_workspaces = self.config.get_environment_ref().show_workspaces();
+        let (bar_row, border_row) = match self.config.status_bar().position() {
+            StatusBarPosition::Top => (0, 1),
+            StatusBarPosition::Bottom => {
+                (terminal_size.get_rows() - 1, terminal_size.get_rows() - 2)
+            }
+        };
+
+        if show_workspaces {
+            Self::queue_foreground(stdout, self.theme_color(self.config.theme().status_bar))?;
+
             // Print the workspaces
             self.queue_workspaces_line(
                 stdout,
-                (0, 0),
+                (0, bar_row),
                 self.selected_workspace as u16,
                 terminal_size.get_cols(),
                 vertical_character,
@@ -463,13 +1689,16 @@ impl Display {
                     reason: e.to_string(),
                 }
                 .into_error()
+                .with_source(e)
             })?;
 
-            // Print the bottom row
+            // Print the border between the status bar and the panels
+            Self::reset_stdout_style(stdout)?;
+            Self::queue_foreground(stdout, self.theme_color(self.config.theme().border))?;
 
             queue_map_err!(
                 stdout,
-                cursor::MoveTo(0, 1),
+                cursor::MoveTo(0, border_row),
                 style::Print(intersection_character),
                 style::Print(
                     horizontal_character
@@ -480,11 +1709,31 @@ impl Display {
             )?;
         }
 
+        let bar_at_top =
+            show_workspaces && self.config.status_bar().position() == StatusBarPosition::Top;
+
+        if self.config.get_environment_ref().show_panel_labels() && !bar_at_top {
+            Self::reset_stdout_style(stdout)?;
+            Self::queue_foreground(stdout, self.theme_color(self.config.theme().border))?;
+
+            queue_map_err!(
+                stdout,
+                cursor::MoveTo(0, 0),
+                style::Print(
+                    horizontal_character
+                        .to_string()
+                        .repeat(terminal_size.get_cols() as usize)
+                )
+            )?;
+        }
+
         Self::reset_stdout_style(stdout)?;
 
         return Ok(());
     }
 
+    /// Draws the status bar, the workspaces followed by the other elements that fit. Only the
+    /// selected workspace is displayed if there isn't space for all of them.
     fn queue_workspaces_line(
         &self,
         stdout: &mut Stdout,
@@ -500,16 +1749,20 @@ impl Display {
         // or
         // | [1] [2] [3] [4] ... [10] |
         queue!(stdout, cursor::MoveTo(location.0, location.1))?;
-        let selected_color = self
-            .config
-            .get_environment_ref()
-            .selected_workspace_color()
-            .crossterm_color(crossterm::style::Color::White);
+        let (attribute, _, selected_color) = self.highlight(
+            CrosstermColor::Reset,
+            self.config
+                .theme()
+                .selected_workspace
+                .crossterm_color(CrosstermColor::White),
+        );
 
         if width == 0 {
             queue!(stdout, style::Print(""))?;
+            return Ok(());
         } else if width == 1 {
             queue!(stdout, style::Print(" "))?;
+            return Ok(());
         } else if width < 7 {
             queue!(stdout, style::Print(vertical_character))?;
             queue!(
@@ -517,90 +1770,152 @@ impl Display {
                 style::Print((0..width - 2).map(|_| ' ').collect::<String>())
             )?;
             queue!(stdout, style::Print(vertical_character))?;
-        } else if width < 43 {
-            queue!(stdout, style::Print(vertical_character))?;
-            queue!(
-                stdout,
-                style::Print(vertical_character),
-                style::Print(' '),
-                style::SetBackgroundColor(selected_color),
-                style::Print(format!("[{}]", selected_workspace)),
-                style::ResetColor
-            )?;
+            return Ok(());
+        }
 
-            if width > 7 {
-                queue!(
-                    stdout,
-                    style::Print((0..(width as usize - 7)).map(|_| ' ').collect::<String>())
-                )?;
-            }
+        queue!(stdout, style::Print(vertical_character))?;
 
-            queue!(stdout, style::Print(' '))?;
-            queue!(stdout, style::Print(vertical_character))?;
-        } else {
-            queue!(stdout, style::Print(vertical_character))?;
+        // The space between the borders, less the space before the last border.
+        let inner_width = width as usize - 3;
+        let mut used = 0;
+
+        if self.config.status_bar().shows(StatusBarElement::Workspaces) {
+            let workspaces: Vec<u16> = if inner_width >= 40 {
+                Self::WORKSPACE_ORDER.iter().map(|w| *w as u16).collect()
+            } else {
+                vec![selected_workspace]
+            };
 
-            for i in 0..10 {
+            for i in workspaces {
                 if i == selected_workspace {
                     queue!(
                         stdout,
                         style::Print(' '),
+                        style::SetAttribute(attribute),
                         style::SetBackgroundColor(selected_color),
                         style::Print(format!("[{}]", selected_workspace)),
-                        style::ResetColor
+                        style::SetAttribute(style::Attribute::NoReverse),
+                        style::SetBackgroundColor(CrosstermColor::Reset)
                     )?;
                 } else {
                     queue!(stdout, style::Print(format!(" [{}]", i)))?;
                 }
+
+                used += 4;
+            }
+        }
+
+        // The other elements are only displayed if there is space for them after the workspaces,
+        // leaving a space between them.
+        let mut badges = Vec::new();
+
+        for (badge, highlighted) in self.status_bar_badges() {
+            if used + badge.width() >= inner_width {
+                break;
             }
 
-            if width > 43 {
+            used += badge.width();
+            badges.push((badge, highlighted));
+        }
+
+        let padding = inner_width - used;
+
+        queue!(
+            stdout,
+            style::Print((0..padding).map(|_| ' ').collect::<String>())
+        )?;
+
+        for (badge, highlighted) in badges {
+            if highlighted {
                 queue!(
                     stdout,
-                    style::Print((0..(width as usize - 43)).map(|_| ' ').collect::<String>())
+                    style::SetAttribute(attribute),
+                    style::SetBackgroundColor(selected_color),
+                    style::Print(badge),
+                    style::SetAttribute(style::Attribute::NoReverse),
+                    style::SetBackgroundColor(CrosstermColor::Reset)
                 )?;
+            } else {
+                queue!(stdout, style::Print(badge))?;
             }
-
-            queue!(stdout, style::Print(' '))?;
-            queue!(stdout, style::Print(vertical_character))?;
         }
 
+        queue!(stdout, style::Print(' '))?;
+        queue!(stdout, style::Print(vertical_character))?;
+
         return Ok(());
     }
 
-    fn queue_error_message(
+    /// Returns the elements displayed at the end of the status bar in the order they are
+    /// configured, padded with spaces, and whether each is highlighted.
+    fn status_bar_badges(&self) -> Vec<(String, bool)> {
+        let mut badges = Vec::new();
+
+        for (i, element) in self.config.status_bar().elements().iter().enumerate() {
+            let badge = match element {
+                StatusBarElement::Workspaces => None,
+                StatusBarElement::Session => {
+                    Some(self.session_name.clone()).filter(|name| !name.is_empty())
+                }
+                StatusBarElement::Mode => self.mode_indicator.clone(),
+                StatusBarElement::Text(text) => Some(text.clone()),
+                _ => self.widgets.text(i).cloned(),
+            };
+
+            if let Some(text) = badge {
+                let text: String = text.chars().filter(|c| !c.is_control()).collect();
+                badges.push((format!(" {} ", text), *element == StatusBarElement::Mode));
+            }
+        }
+
+        return badges;
+    }
+
+    fn queue_message(
         &self,
         stdout: &mut Stdout,
         terminal_size: &Size,
     ) -> Result<(), crossterm::ErrorKind> {
-        if let Some(text) = self.error_message.as_ref() {
-            let error_text;
-
-            if text.len() > terminal_size.get_cols() as usize {
-                error_text = format!(
+        if let Some(message) = self.messages.current() {
+            let text = &message.text;
+            let error_text = if text.len() > terminal_size.get_cols() as usize {
+                format!(
                     "{}...",
                     text.chars().collect::<Vec<char>>()[..terminal_size.get_cols() as usize - 3]
                         .iter()
                         .collect::<String>()
-                );
+                )
             } else {
                 let lhs = (terminal_size.get_cols() as usize - text.len()) / 2;
-                error_text = format!(
+                format!(
                     "{}{}{}",
                     (0..lhs).map(|_| ' ').collect::<String>(),
                     text,
                     (0..terminal_size.get_cols() as usize - text.len() - lhs)
                         .map(|_| ' ')
                         .collect::<String>(),
-                );
-            }
+                )
+            };
+
+            let fallback = match message.level {
+                MessageLevel::Info => CrosstermColor::Blue,
+                MessageLevel::Warning => CrosstermColor::Yellow,
+                MessageLevel::Error => CrosstermColor::Red,
+            };
+            let theme = self.config.theme();
+            let (attribute, text_color, background_color) = self.highlight(
+                theme.message_text.crossterm_color(CrosstermColor::White),
+                message.level.color(theme).crossterm_color(fallback),
+            );
 
             queue!(
                 stdout,
                 cursor::MoveTo(0, terminal_size.get_rows()),
-                style::SetBackgroundColor(Self::ERROR_COLOR.crossterm_color(CrosstermColor::Red)),
-                style::SetForegroundColor(CrosstermColor::White),
+                style::SetAttribute(attribute),
+                style::SetBackgroundColor(background_color),
+                style::SetForegroundColor(text_color),
                 style::Print(error_text),
+                style::SetAttribute(style::Attribute::NoReverse),
             )?;
         }
 
@@ -613,6 +1928,45 @@ impl Display {
         return Ok(());
     }
 
+    /// Returns a color from the theme, or None if colors are disabled.
+    fn theme_color(&self, color: Option<Color>) -> Option<Color> {
+        if self.config.get_environment_ref().monochrome() {
+            return None;
+        }
+
+        return color;
+    }
+
+    /// Returns the attribute, foreground and background highlighted text is drawn with. Highlights
+    /// are drawn in reverse video instead of colors if colors are disabled.
+    fn highlight(
+        &self,
+        foreground: CrosstermColor,
+        background: CrosstermColor,
+    ) -> (style::Attribute, CrosstermColor, CrosstermColor) {
+        if self.config.get_environment_ref().monochrome() {
+            return (
+                style::Attribute::Reverse,
+                CrosstermColor::Reset,
+                CrosstermColor::Reset,
+            );
+        }
+
+        return (style::Attribute::NoReverse, foreground, background);
+    }
+
+    /// Set the color of the text that follows, if the theme sets one.
+    fn queue_foreground(stdout: &mut Stdout, color: Option<Color>) -> Result<(), MuxideError> {
+        if let Some(color) = color {
+            queue_map_err!(
+                stdout,
+                style::SetForegroundColor(color.crossterm_color(CrosstermColor::Reset))
+            )?;
+        }
+
+        return Ok(());
+    }
+
     fn selected_workspace(&self) -> &Workspace {
         return self
             .workspaces
@@ -639,12 +1993,15 @@ impl Display {
         return &mut self.selected_workspace_mut().root_subdivision;
     }
 
-    pub fn set_error_message(&mut self, message: String) {
-        self.error_message = Some(message);
+    /// Display a message in the message bar, it is removed after the configured message duration.
+    pub fn set_message(&mut self, level: MessageLevel, message: String) {
+        let duration = self.config.get_environment_ref().message_duration();
+
+        self.messages.push(level, message, duration);
     }
 
-    pub fn clear_error_message(&mut self) {
-        self.error_message = None;
+    pub fn set_error_message(&mut self, message: String) {
+        self.set_message(MessageLevel::Error, message);
     }
 
     pub fn set_selected_panel(&mut self, id: Option<usize>) {
@@ -655,7 +2012,7 @@ impl Display {
 
         let id = id.unwrap();
 
-        self.selected_workspace_mut().selected_panel = self.panel_map.get(&id).map(|p| p.clone());
+        self.selected_workspace_mut().selected_panel = self.panel_map.get(&id).cloned();
     }
 
     pub fn update_panel_cursor(&mut self, id: usize, col: u16, row: u16, hide: bool) -> bool {
@@ -668,11 +2025,212 @@ impl Display {
         }
     }
 
+    /// Mark a panel's output as paused, an indicator is displayed on its border whilst paused.
+    pub fn set_panel_paused(&mut self, id: usize, paused: bool) -> bool {
+        if let Some(panel) = self.panel_map.get_mut(&id) {
+            panel.set_paused(paused);
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    /// Returns the title displayed on a panel's top border.
+    pub fn panel_title(&self, id: usize) -> Option<String> {
+        return self.panel_map.get(&id).and_then(|panel| panel.get_title());
+    }
+
+    /// Mark a panel as read only, an indicator is displayed on its border whilst input isn't sent
+    /// to it.
+    pub fn set_panel_read_only(&mut self, id: usize, read_only: bool) -> bool {
+        if let Some(panel) = self.panel_map.get_mut(&id) {
+            panel.set_read_only(read_only);
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    /// Set the text displayed at the end of the workspace bar, or remove it.
+    pub fn set_mode_indicator(&mut self, indicator: Option<String>) {
+        self.mode_indicator = indicator;
+    }
+
+    /// Set the name of the session, it is displayed in the status bar if it is configured to.
+    pub fn set_session_name(&mut self, name: String) {
+        self.session_name = name;
+    }
+
+    /// Set the title of the terminal's window (OSC 2), it is only written if it changed. Control
+    /// characters are removed, so a panel's title can't end the sequence early.
+    pub fn set_window_title(&mut self, title: &str) -> Result<(), MuxideError> {
+        let title: String = title.chars().filter(|c| !c.is_control()).collect();
+
+        if self.window_title.as_ref() == Some(&title) {
+            return Ok(());
+        }
+
+        let mut stdout = stdout();
+        queue_map_err!(stdout, style::Print(format!("\x1b]2;{}\x07", title)))?;
+
+        stdout.flush().map_err(|e| {
+            ErrorType::QueueExecuteError {
+                reason: e.to_string(),
+            }
+            .into_error()
+            .with_source(e)
+        })?;
+        self.window_title = Some(title);
+
+        return Ok(());
+    }
+
+    /// Set the title displayed on a panel's top border, or remove it.
+    pub fn set_panel_title(&mut self, id: usize, title: Option<String>) -> bool {
+        if let Some(panel) = self.panel_map.get_mut(&id) {
+            panel.set_title(title);
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    /// Set the name of the program running in a panel, it is displayed in the panel's label.
+    pub fn set_panel_command(&mut self, id: usize, command: Option<String>) -> bool {
+        if let Some(panel) = self.panel_map.get_mut(&id) {
+            panel.set_command(command);
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    /// Mark a panel as logged, it is displayed in the panel's label.
+    pub fn set_panel_logging(&mut self, id: usize, logging: bool) -> bool {
+        if let Some(panel) = self.panel_map.get_mut(&id) {
+            panel.set_logging(logging);
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    /// Set the cursor style (DECSCUSR) requested by a panel's program, it is used whilst the panel
+    /// is selected.
+    pub fn update_panel_cursor_style(&mut self, id: usize, style: u8) -> bool {
+        if let Some(panel) = self.panel_map.get_mut(&id) {
+            panel.set_cursor_style(style);
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    /// Set the images drawn in a panel, they are passed through to the terminal whilst the panel
+    /// is selected.
+    pub fn update_panel_graphics(&mut self, id: usize, graphics: Arc<[PanelGraphic]>) -> bool {
+        if let Some(panel) = self.panel_map.get_mut(&id) {
+            panel.set_graphics(graphics);
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    /// Returns the ids of the panels that are closed when the selected panel is merged.
+    pub fn merge_sibling_ids(&self) -> Vec<usize> {
+        return self
+            .selected_panel()
+            .and_then(|p| self.root_subdivision().sibling_panel_ids(p.get_id()))
+            .unwrap_or_default();
+    }
+
     pub fn merge_selected_panel(&mut self) -> Result<Option<(usize, Size)>, MuxideError> {
         let id = self.selected_panel().map(|p| p.get_id());
+        self.record_layout(self.selected_workspace);
+
         return self
             .root_subdivision_mut()
             .merge_selected_panel(id)
-            .map(|opt| opt.map(|sz| (id.unwrap(), sz)));
+            .map(|opt| opt.and_then(|sz| id.map(|id| (id, sz))));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Display;
+    use crate::display::PanelPtr;
+    use crate::Config;
+
+    #[test]
+    fn test_panel_label() {
+        let mut panel = PanelPtr::new(0, (0, 0));
+        panel.set_command(Some(String::from("cat")));
+        assert_eq!(Display::panel_label(1, &panel), "1 cat");
+
+        panel.set_read_only(true);
+        assert_eq!(Display::panel_label(1, &panel), "1 [RO] cat");
+
+        panel.set_paused(true);
+        panel.set_logging(true);
+        panel.set_title(Some(String::from("ti\x07tle")));
+        assert_eq!(
+            Display::panel_label(2, &panel),
+            "2 [RO PAUSED LOG] cat title"
+        );
+    }
+
+    #[test]
+    fn test_panel_indicators() {
+        let mut display = Display::new(Config::default());
+        display.panel_map.insert(0, PanelPtr::new(0, (0, 0)));
+        assert_eq!(display.panel_indicators(0), "");
+
+        assert!(display.set_panel_read_only(0, true));
+        assert!(display.set_panel_paused(0, true));
+        assert_eq!(display.panel_indicators(0), " \u{1f512}  PAUSED ");
+
+        // The labels include the panel's state instead.
+        let config = Config::from_toml_string(
+            "
+            [environment]\n\
+            show_panel_labels = true\n\
+            ",
+        )
+        .unwrap();
+        let mut display = Display::new(config);
+        display.panel_map.insert(0, PanelPtr::new(0, (0, 0)));
+        assert!(display.set_panel_read_only(0, true));
+        assert_eq!(display.panel_indicators(0), "");
+    }
+
+    #[test]
+    fn test_status_bar_badges() {
+        let config = Config::from_toml_string(
+            "
+            [status_bar]\n\
+            elements = [\"Workspaces\", \"Mode\", \"Session\", { Text = \"hello\" }, \"Clock\"]\n\
+            clock_format = \"year %Y\"\n\
+            ",
+        )
+        .unwrap();
+        let mut display = Display::new(config);
+        display.set_session_name(String::from("work"));
+
+        let badges = display.status_bar_badges();
+        assert_eq!(badges.len(), 3);
+        assert_eq!(badges[0], (String::from(" work "), false));
+        assert_eq!(badges[1], (String::from(" hello "), false));
+        assert!(badges[2].0.starts_with(" year 2"), "{:?}", badges[2]);
+
+        display.set_mode_indicator(Some(String::from("CMD")));
+        assert_eq!(
+            display.status_bar_badges()[0],
+            (String::from(" CMD "), true)
+        );
+
+        display.set_mode_indicator(None);
+        display.set_session_name(String::new());
+        assert_eq!(display.status_bar_badges().len(), 2);
     }
 }
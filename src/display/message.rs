@@ -0,0 +1,144 @@
+use crate::config::Theme;
+use crate::Color;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// The severity of a message, determines the color it is displayed with.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum MessageLevel {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A message displayed in the message bar at the bottom of the terminal.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Message {
+    pub level: MessageLevel,
+    pub text: String,
+    /// The time at which the message is removed from the message bar, None if it never expires.
+    pub expires: Option<Instant>,
+}
+
+/// Tracks the message currently displayed and a limited history of previous messages.
+#[derive(Clone, Debug)]
+pub struct Messages {
+    current: Option<Message>,
+    history: VecDeque<Message>,
+}
+
+impl MessageLevel {
+    /// Returns the background color of the message bar for messages of this level.
+    pub fn color(&self, theme: &Theme) -> Color {
+        return match self {
+            MessageLevel::Info => theme.info,
+            MessageLevel::Warning => theme.warning,
+            MessageLevel::Error => theme.error,
+        };
+    }
+
+    pub fn label(&self) -> &'static str {
+        return match self {
+            MessageLevel::Info => "info",
+            MessageLevel::Warning => "warning",
+            MessageLevel::Error => "error",
+        };
+    }
+}
+
+impl Messages {
+    /// The maximum number of messages kept in the history.
+    const HISTORY_LENGTH: usize = 100;
+
+    pub fn new() -> Self {
+        return Self {
+            current: None,
+            history: VecDeque::new(),
+        };
+    }
+
+    /// Display a new message, replacing the current one, and record it in the history.
+    pub fn push(&mut self, level: MessageLevel, text: String, duration: Option<Duration>) {
+        let message = Message {
+            level,
+            text,
+            expires: duration.map(|d| Instant::now() + d),
+        };
+
+        if self.history.len() == Self::HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(message.clone());
+        self.current = Some(message);
+    }
+
+    /// Returns the message currently displayed in the message bar.
+    pub fn current(&self) -> Option<&Message> {
+        return self.current.as_ref();
+    }
+
+    /// Removes the current message if it has expired by the specified time.
+    pub fn expire(&mut self, now: Instant) {
+        if let Some(expires) = self.deadline() {
+            if expires <= now {
+                self.current = None;
+            }
+        }
+    }
+
+    /// The time at which the current message expires.
+    pub fn deadline(&self) -> Option<Instant> {
+        return self.current.as_ref().and_then(|m| m.expires);
+    }
+
+    /// The past messages, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &Message> {
+        return self.history.iter();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MessageLevel, Messages};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_message_expiry() {
+        let mut messages = Messages::new();
+        messages.push(
+            MessageLevel::Info,
+            String::from("hello"),
+            Some(Duration::from_millis(100)),
+        );
+
+        messages.expire(Instant::now());
+        assert!(messages.current().is_some());
+
+        messages.expire(Instant::now() + Duration::from_millis(200));
+        assert!(messages.current().is_none());
+        assert_eq!(messages.history().count(), 1);
+    }
+
+    #[test]
+    fn test_message_without_expiry() {
+        let mut messages = Messages::new();
+        messages.push(MessageLevel::Error, String::from("failed"), None);
+
+        assert!(messages.deadline().is_none());
+        messages.expire(Instant::now() + Duration::from_secs(3600));
+        assert_eq!(messages.current().unwrap().text, "failed");
+    }
+
+    #[test]
+    fn test_history_length() {
+        let mut messages = Messages::new();
+
+        for i in 0..(Messages::HISTORY_LENGTH + 10) {
+            messages.push(MessageLevel::Warning, format!("{}", i), None);
+        }
+
+        assert_eq!(messages.history().count(), Messages::HISTORY_LENGTH);
+        assert_eq!(messages.history().next().unwrap().text, "10");
+    }
+}
@@ -1,6 +1,12 @@
+mod banner;
 mod display;
+mod message;
 mod panel;
 mod subdivision;
+mod widgets;
 mod workspace;
 
 pub use display::Display;
+pub use message::MessageLevel;
+pub use panel::PanelPtr;
+pub use subdivision::{SubDivision, SubDivisionSplit, SubdivisionPath};
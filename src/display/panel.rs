@@ -1,6 +1,9 @@
 use crate::geometry::Point;
+use crate::graphics::PanelGraphic;
+use crate::panel_screen::PanelRow;
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::sync::Arc;
 
 /// Defines a method that calls a method with the same name and args defined in panel from PanelPtr
 macro_rules! wrap_panel_method {
@@ -39,11 +42,25 @@ pub struct PanelPtr(Rc<RefCell<Panel>>);
 /// A panel is all the information required for a process.
 struct Panel {
     id: usize,
-    content: Vec<Vec<u8>>,
+    content: Arc<[PanelRow]>,
     hide_cursor: bool,
+    /// The DECSCUSR style of the cursor, 0 is the terminal's default.
+    cursor_style: u8,
     cursor_col: u16,
     cursor_row: u16,
     location: (u16, u16), // (col, row). The location in the global space of the top left (the first) cell
+    /// Set whilst the panel's output is paused.
+    paused: bool,
+    /// Set whilst input isn't sent to the panel.
+    read_only: bool,
+    /// Set whilst the panel's output is appended to a log file.
+    logging: bool,
+    /// The text displayed on the panel's top border.
+    title: Option<String>,
+    /// The name of the panel's foreground process, displayed in its label.
+    command: Option<String>,
+    /// The images drawn in the panel, relative to its top left cell.
+    graphics: Arc<[PanelGraphic]>,
 }
 
 impl PanelPtr {
@@ -55,22 +72,43 @@ impl PanelPtr {
     wrap_panel_method!(set_location, pub mut, location: (u16, u16));
     wrap_panel_method!(get_cursor_position, pub, => Point<u16>);
     wrap_panel_method!(set_cursor_position, pub mut, col: u16, row: u16);
-    wrap_panel_method!(set_content, pub mut, content: Vec<Vec<u8>>);
-    wrap_panel_method!(get_content, pub, => Vec<Vec<u8>>);
+    wrap_panel_method!(set_content, pub mut, content: Arc<[PanelRow]>);
+    wrap_panel_method!(get_content, pub, => Arc<[PanelRow]>);
     wrap_panel_method!(get_id, pub, => usize);
     wrap_panel_method!(get_hide_cursor, pub, => bool);
     wrap_panel_method!(set_hide_cursor, pub mut, hide: bool);
+    wrap_panel_method!(get_cursor_style, pub, => u8);
+    wrap_panel_method!(set_cursor_style, pub mut, style: u8);
+    wrap_panel_method!(is_paused, pub, => bool);
+    wrap_panel_method!(set_paused, pub mut, paused: bool);
+    wrap_panel_method!(is_read_only, pub, => bool);
+    wrap_panel_method!(set_read_only, pub mut, read_only: bool);
+    wrap_panel_method!(is_logging, pub, => bool);
+    wrap_panel_method!(set_logging, pub mut, logging: bool);
+    wrap_panel_method!(get_title, pub, => Option<String>);
+    wrap_panel_method!(set_title, pub mut, title: Option<String>);
+    wrap_panel_method!(get_command, pub, => Option<String>);
+    wrap_panel_method!(set_command, pub mut, command: Option<String>);
+    wrap_panel_method!(get_graphics, pub, => Arc<[PanelGraphic]>);
+    wrap_panel_method!(set_graphics, pub mut, graphics: Arc<[PanelGraphic]>);
 }
 
 impl Panel {
     pub fn new(id: usize, location: (u16, u16)) -> Self {
         return Self {
-            content: Vec::new(),
+            content: Arc::new([]),
             id,
             location,
             hide_cursor: false,
+            cursor_style: 0,
             cursor_col: 0,
             cursor_row: 0,
+            paused: false,
+            read_only: false,
+            logging: false,
+            title: None,
+            command: None,
+            graphics: Arc::new([]),
         };
     }
 
@@ -90,12 +128,12 @@ impl Panel {
     }
 
     /// Set the content of this panel
-    pub fn set_content(&mut self, content: Vec<Vec<u8>>) {
+    pub fn set_content(&mut self, content: Arc<[PanelRow]>) {
         self.content = content;
     }
 
-    /// Returns an immutable reference to the content of this panel
-    pub fn get_content(&self) -> Vec<Vec<u8>> {
+    /// Returns the content of this panel, the rows are shared rather than copied.
+    pub fn get_content(&self) -> Arc<[PanelRow]> {
         return self.content.clone();
     }
 
@@ -110,4 +148,60 @@ impl Panel {
     pub fn set_hide_cursor(&mut self, hide: bool) {
         self.hide_cursor = hide;
     }
+
+    pub fn get_cursor_style(&self) -> u8 {
+        return self.cursor_style;
+    }
+
+    pub fn set_cursor_style(&mut self, style: u8) {
+        self.cursor_style = style;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        return self.paused;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        return self.read_only;
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn is_logging(&self) -> bool {
+        return self.logging;
+    }
+
+    pub fn set_logging(&mut self, logging: bool) {
+        self.logging = logging;
+    }
+
+    pub fn get_title(&self) -> Option<String> {
+        return self.title.clone();
+    }
+
+    pub fn set_title(&mut self, title: Option<String>) {
+        self.title = title;
+    }
+
+    pub fn get_command(&self) -> Option<String> {
+        return self.command.clone();
+    }
+
+    pub fn set_command(&mut self, command: Option<String>) {
+        self.command = command;
+    }
+
+    pub fn get_graphics(&self) -> Arc<[PanelGraphic]> {
+        return self.graphics.clone();
+    }
+
+    pub fn set_graphics(&mut self, graphics: Arc<[PanelGraphic]>) {
+        self.graphics = graphics;
+    }
 }
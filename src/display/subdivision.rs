@@ -1,10 +1,12 @@
 use super::panel::PanelPtr;
+use crate::config::{LayoutNode, LayoutSplit};
 use crate::{
     geometry::{Direction, Point, Size},
     Config, ErrorType, MuxideError,
 };
 use crossterm::{cursor, queue, style};
-use std::io::{Stdout, Write};
+use std::io::Write;
+use unicode_width::UnicodeWidthChar;
 
 /// The text that is displayed when there are no open panels.
 const EMPTY_TEXT: &'static str = "No Panels Open";
@@ -16,7 +18,8 @@ macro_rules! queue_map_err {
                 reason: e.to_string(),
             }
             .into_error()
-        });
+            .with_source(e)
+        })
     };
 }
 
@@ -45,17 +48,27 @@ pub struct SubDivision {
     subdiv_b: Option<Box<SubDivision>>,
     /// Whether or not this subdivision is split vertically, horizontally or not at all.
     split: Option<SubDivisionSplit>,
+    /// The percentage of the space, excluding the split line, given to the first subdivision.
+    ratio: u16,
     origin: Point<u16>,
     dimensions: Size,
 }
 
 impl SubDivision {
+    /// The smallest panel a split can produce, smaller panels can't display anything useful.
+    pub const MINIMUM_PANEL_SIZE: Size = Size::new(2, 8);
+    /// The percentage of the space given to the first part of a split by default.
+    pub const DEFAULT_RATIO: u16 = 50;
+    /// The percentage a split line is moved by when a panel is grown or shrunk.
+    const RATIO_STEP: u16 = 5;
+
     pub const fn new(origin: Point<u16>, dimensions: Size) -> Self {
         return Self {
             panel: None,
             subdiv_a: None,
             subdiv_b: None,
             split: None,
+            ratio: Self::DEFAULT_RATIO,
             origin,
             dimensions,
         };
@@ -69,18 +82,40 @@ impl SubDivision {
         }
     }
 
+    /// Close the panel at the path. The subdivision it leaves behind is collapsed into its parent,
+    /// so the other part of the split reclaims the space.
     fn close_panel_at_path(&mut self, mut path: SubdivisionPath) -> bool {
         match path.pop() {
             Some(SubdivisionPathElement::A) => {
                 if let Some(subdiv) = self.subdiv_a.as_mut() {
-                    return subdiv.close_panel_at_path(path);
+                    if !subdiv.close_panel_at_path(path) {
+                        return false;
+                    }
+
+                    if subdiv.is_vacant() {
+                        if let Some(subdiv_b) = self.subdiv_b.take() {
+                            self.replace_with(*subdiv_b);
+                        }
+                    }
+
+                    return true;
                 } else {
                     return false;
                 }
             }
             Some(SubdivisionPathElement::B) => {
                 if let Some(subdiv) = self.subdiv_b.as_mut() {
-                    return subdiv.close_panel_at_path(path);
+                    if !subdiv.close_panel_at_path(path) {
+                        return false;
+                    }
+
+                    if subdiv.is_vacant() {
+                        if let Some(subdiv_a) = self.subdiv_a.take() {
+                            self.replace_with(*subdiv_a);
+                        }
+                    }
+
+                    return true;
                 } else {
                     return false;
                 }
@@ -97,11 +132,11 @@ impl SubDivision {
     }
 
     pub fn next_panel_details(&self) -> Option<(SubdivisionPath, Size, Point<u16>)> {
-        if self.subdiv_a.is_some() && self.subdiv_b.is_some() {
-            if let Some(mut path) = self.subdiv_a.as_ref().unwrap().next_panel_details() {
+        if let (Some(subdiv_a), Some(subdiv_b)) = (self.subdiv_a.as_ref(), self.subdiv_b.as_ref()) {
+            if let Some(mut path) = subdiv_a.next_panel_details() {
                 path.0.push(SubdivisionPathElement::A);
                 return Some(path);
-            } else if let Some(mut path) = self.subdiv_b.as_ref().unwrap().next_panel_details() {
+            } else if let Some(mut path) = subdiv_b.next_panel_details() {
                 path.0.push(SubdivisionPathElement::B);
                 return Some(path);
             } else {
@@ -121,26 +156,18 @@ impl SubDivision {
     ) -> Result<(), MuxideError> {
         match path.pop() {
             Some(SubdivisionPathElement::A) => {
-                if self.subdiv_a.is_none() {
-                    panic!("Invalid path");
-                } else {
-                    return self
-                        .subdiv_a
-                        .as_mut()
-                        .unwrap()
-                        .open_panel_at_path(panel, path);
-                }
+                return self
+                    .subdiv_a
+                    .as_mut()
+                    .expect("Invalid path")
+                    .open_panel_at_path(panel, path);
             }
             Some(SubdivisionPathElement::B) => {
-                if self.subdiv_b.is_none() {
-                    panic!("Invalid path");
-                } else {
-                    return self
-                        .subdiv_b
-                        .as_mut()
-                        .unwrap()
-                        .open_panel_at_path(panel, path);
-                }
+                return self
+                    .subdiv_b
+                    .as_mut()
+                    .expect("Invalid path")
+                    .open_panel_at_path(panel, path);
             }
             None => {
                 if self.panel.is_some() {
@@ -160,6 +187,37 @@ impl SubDivision {
         return self.focus_next_id_internal(path, focus_direction);
     }
 
+    /// Returns the ids of the panels in the other part of the split next to the panel with the
+    /// specified id, these are closed when the panel is merged. Returns None if the panel isn't
+    /// in a split.
+    pub fn sibling_panel_ids(&self, id: usize) -> Option<Vec<usize>> {
+        let (subdiv_a, subdiv_b) = (self.subdiv_a.as_ref()?, self.subdiv_b.as_ref()?);
+        let contains_id = |subdiv: &SubDivision| {
+            subdiv
+                .panel
+                .as_ref()
+                .map(|p| p.get_id() == id)
+                .unwrap_or(false)
+        };
+        let ids = |subdiv: &SubDivision| {
+            subdiv
+                .panel_regions()
+                .into_iter()
+                .map(|(id, _, _)| id)
+                .collect()
+        };
+
+        if contains_id(subdiv_a) {
+            return Some(ids(subdiv_b));
+        } else if contains_id(subdiv_b) {
+            return Some(ids(subdiv_a));
+        }
+
+        return subdiv_a
+            .sibling_panel_ids(id)
+            .or_else(|| subdiv_b.sibling_panel_ids(id));
+    }
+
     pub fn merge_selected_panel(
         &mut self,
         panel_id: Option<usize>,
@@ -181,14 +239,16 @@ impl SubDivision {
                 if let (Some(subdiv_a), Some(subdiv_b)) =
                     (self.subdiv_a.as_mut(), self.subdiv_b.as_mut())
                 {
-                    if subdiv_a.panel.is_some() && subdiv_b.panel.is_none() {
+                    if subdiv_a.panel.is_some() && subdiv_b.is_empty() {
                         panel = subdiv_a.panel.take().unwrap();
                         self.subdiv_a = None;
                         self.subdiv_b = None;
-                    } else if subdiv_a.panel.is_none() && subdiv_b.panel.is_some() {
+                        self.split = None;
+                    } else if subdiv_a.is_empty() && subdiv_b.panel.is_some() {
                         panel = subdiv_b.panel.take().unwrap();
                         self.subdiv_a = None;
                         self.subdiv_b = None;
+                        self.split = None;
                     } else {
                         return Ok(None);
                     }
@@ -204,16 +264,18 @@ impl SubDivision {
                 if let (Some(subdiv_a), Some(subdiv_b)) =
                     (parent.subdiv_a.as_mut(), parent.subdiv_b.as_mut())
                 {
-                    if subdiv_a.panel.is_some() && subdiv_b.panel.is_none() {
+                    if subdiv_a.panel.is_some() && subdiv_b.is_empty() {
                         panel = subdiv_a.panel.take().unwrap();
 
                         parent.subdiv_a = None;
                         parent.subdiv_b = None;
-                    } else if subdiv_a.panel.is_none() && subdiv_b.panel.is_some() {
+                        parent.split = None;
+                    } else if subdiv_a.is_empty() && subdiv_b.panel.is_some() {
                         panel = subdiv_b.panel.take().unwrap();
 
                         parent.subdiv_a = None;
                         parent.subdiv_b = None;
+                        parent.split = None;
                     } else {
                         return Ok(None);
                     }
@@ -268,6 +330,33 @@ impl SubDivision {
         }
     }
 
+    /// Returns true if this subdivision has neither a panel nor subdivisions.
+    fn is_vacant(&self) -> bool {
+        return self.panel.is_none() && self.subdiv_a.is_none() && self.subdiv_b.is_none();
+    }
+
+    /// Replace this subdivision with one of its subdivisions, which is resized to fill the space
+    /// this subdivision occupied.
+    fn replace_with(&mut self, subdivision: SubDivision) {
+        let (origin, dimensions) = (self.origin, self.dimensions);
+
+        *self = subdivision;
+        self.set_geometry(origin, dimensions);
+    }
+
+    /// Returns true if this subdivision contains no panels, including in any subdivisions.
+    fn is_empty(&self) -> bool {
+        if self.panel.is_some() {
+            return false;
+        } else if let (Some(subdiv_a), Some(subdiv_b)) =
+            (self.subdiv_a.as_ref(), self.subdiv_b.as_ref())
+        {
+            return subdiv_a.is_empty() && subdiv_b.is_empty();
+        } else {
+            return true;
+        }
+    }
+
     /// Returns true if this subdivision is empty.
     fn remove_unused_subdivisions(&mut self) -> bool {
         if self.panel.is_some() {
@@ -331,37 +420,29 @@ impl SubDivision {
     ) -> Option<usize> {
         match focus_direction {
             Direction::Up => {
-                if self.split == Some(SubDivisionSplit::Horizontal) {
-                    if path_element.is_b() {
-                        return self.subdiv_a.as_ref().unwrap().tail_b_for_id();
-                    }
+                if self.split == Some(SubDivisionSplit::Horizontal) && path_element.is_b() {
+                    return self.subdiv_a.as_ref().unwrap().tail_b_for_id();
                 }
 
                 return None;
             }
             Direction::Down => {
-                if self.split == Some(SubDivisionSplit::Horizontal) {
-                    if path_element.is_a() {
-                        return self.subdiv_b.as_ref().unwrap().tail_a_for_id();
-                    }
+                if self.split == Some(SubDivisionSplit::Horizontal) && path_element.is_a() {
+                    return self.subdiv_b.as_ref().unwrap().tail_a_for_id();
                 }
 
                 return None;
             }
             Direction::Left => {
-                if self.split == Some(SubDivisionSplit::Vertical) {
-                    if path_element.is_b() {
-                        return self.subdiv_a.as_ref().unwrap().tail_b_for_id();
-                    }
+                if self.split == Some(SubDivisionSplit::Vertical) && path_element.is_b() {
+                    return self.subdiv_a.as_ref().unwrap().tail_b_for_id();
                 }
 
                 return None;
             }
             Direction::Right => {
-                if self.split == Some(SubDivisionSplit::Vertical) {
-                    if path_element.is_a() {
-                        return self.subdiv_b.as_ref().unwrap().tail_a_for_id();
-                    }
+                if self.split == Some(SubDivisionSplit::Vertical) && path_element.is_a() {
+                    return self.subdiv_b.as_ref().unwrap().tail_a_for_id();
                 }
 
                 return None;
@@ -370,8 +451,8 @@ impl SubDivision {
     }
 
     fn tail_b_for_id(&self) -> Option<usize> {
-        if self.panel.is_some() {
-            return Some(self.panel.as_ref().unwrap().get_id());
+        if let Some(panel) = self.panel.as_ref() {
+            return Some(panel.get_id());
         } else if let (Some(subdiv_a), Some(subdiv_b)) =
             (self.subdiv_a.as_ref(), self.subdiv_b.as_ref())
         {
@@ -388,8 +469,8 @@ impl SubDivision {
     }
 
     fn tail_a_for_id(&self) -> Option<usize> {
-        if self.panel.is_some() {
-            return Some(self.panel.as_ref().unwrap().get_id());
+        if let Some(panel) = self.panel.as_ref() {
+            return Some(panel.get_id());
         } else if let (Some(subdiv_a), Some(subdiv_b)) =
             (self.subdiv_a.as_ref(), self.subdiv_b.as_ref())
         {
@@ -405,6 +486,23 @@ impl SubDivision {
         }
     }
 
+    /// Returns the id, origin and dimensions of every panel in this subdivision, ordered from the
+    /// first (top left) panel to the last.
+    pub fn panel_regions(&self) -> Vec<(usize, Point<u16>, Size)> {
+        if let Some(panel) = self.panel.as_ref() {
+            return vec![(panel.get_id(), self.origin, self.dimensions)];
+        } else if let (Some(subdiv_a), Some(subdiv_b)) =
+            (self.subdiv_a.as_ref(), self.subdiv_b.as_ref())
+        {
+            let mut regions = subdiv_a.panel_regions();
+            regions.append(&mut subdiv_b.panel_regions());
+
+            return regions;
+        } else {
+            return Vec::new();
+        }
+    }
+
     fn path_for_panel_id(&self, id: usize) -> Option<SubdivisionPath> {
         if let Some(panel) = self.panel.as_ref() {
             if panel.get_id() == id {
@@ -429,31 +527,30 @@ impl SubDivision {
         }
     }
 
+    /// Split the subdivision containing the panel with the specified id, or the first empty
+    /// subdivision if no id is specified, giving the first part the ratio, as a percentage, of
+    /// the space. Returns an error if the subdivision is too small to be
+    /// split into two panels of at least the minimum size.
     pub fn split_panel(
         &mut self,
         panel_id: Option<usize>,
         direction: SubDivisionSplit,
-    ) -> (Option<Size>, bool) {
+        ratio: u16,
+    ) -> Result<(Option<Size>, bool), MuxideError> {
         if panel_id.is_none() {
             if self.panel.is_none() && self.subdiv_a.is_none() && self.subdiv_b.is_none() {
-                match direction {
-                    SubDivisionSplit::Horizontal => self.subdivide_horizontal(),
-                    SubDivisionSplit::Vertical => self.subdivide_vertical(),
-                }
+                self.subdivide(direction, ratio)?;
 
-                return (None, true);
+                return Ok((None, true));
             } else {
-                return (None, false);
+                return Ok((None, false));
             }
         }
 
         let panel_id = panel_id.unwrap();
 
         if self.panel.is_some() && self.panel.as_ref().unwrap().get_id() == panel_id {
-            match direction {
-                SubDivisionSplit::Horizontal => self.subdivide_horizontal(),
-                SubDivisionSplit::Vertical => self.subdivide_vertical(),
-            }
+            self.subdivide(direction, ratio)?;
 
             let new_size = self
                 .subdiv_a
@@ -461,24 +558,24 @@ impl SubDivision {
                 .unwrap()
                 .set_panel(self.panel.take().unwrap());
 
-            return (Some(new_size), true);
+            return Ok((Some(new_size), true));
         } else if self.panel.is_none() && self.subdiv_a.is_some() && self.subdiv_b.is_some() {
-            let res_a = self
-                .subdiv_a
-                .as_mut()
-                .unwrap()
-                .split_panel(Some(panel_id), direction);
-            if res_a.1 {
-                return res_a;
-            } else {
-                return self
-                    .subdiv_b
+            let res_a =
+                self.subdiv_a
                     .as_mut()
                     .unwrap()
-                    .split_panel(Some(panel_id), direction);
+                    .split_panel(Some(panel_id), direction, ratio)?;
+            if res_a.1 {
+                return Ok(res_a);
+            } else {
+                return self.subdiv_b.as_mut().unwrap().split_panel(
+                    Some(panel_id),
+                    direction,
+                    ratio,
+                );
             }
         } else {
-            return (None, false);
+            return Ok((None, false));
         }
     }
 
@@ -489,54 +586,284 @@ impl SubDivision {
         return self.dimensions;
     }
 
-    fn subdivide_vertical(&mut self) {
-        let mut subdiv_a_dimensions = self.dimensions - Size::new(0, 1); // -1 for the center column
-        subdiv_a_dimensions.divide_width_by_const(2);
-
-        let subdiv_b_dimensinos =
-            self.dimensions - Size::new(0, 1) - Size::new(0, subdiv_a_dimensions.get_cols());
+    /// Split this subdivision in the specified direction, the first subdivision is the left or top
+    /// part and is given the ratio, as a percentage, of the space.
+    fn subdivide(&mut self, direction: SubDivisionSplit, ratio: u16) -> Result<(), MuxideError> {
+        let (subdiv_a_dimensions, subdiv_b_dimensions) =
+            self.split_dimensions(direction, ratio).ok_or_else(|| {
+                ErrorType::PanelTooSmallToSplit {
+                    minimum: Self::MINIMUM_PANEL_SIZE,
+                }
+                .into_error()
+            })?;
 
         self.subdiv_a = Some(Box::new(SubDivision::new(self.origin, subdiv_a_dimensions)));
-
         self.subdiv_b = Some(Box::new(SubDivision::new(
-            self.origin + Point::new(subdiv_a_dimensions.get_cols() + 1, 0),
-            subdiv_b_dimensinos,
+            Self::subdiv_b_origin(self.origin, direction, subdiv_a_dimensions),
+            subdiv_b_dimensions,
         )));
+        self.split = Some(direction);
+        self.ratio = ratio;
 
-        self.split = Some(SubDivisionSplit::Vertical); // The split line will be drawn vertically.
+        return Ok(());
     }
 
-    fn subdivide_horizontal(&mut self) {
-        let mut subdiv_a_dimensions = self.dimensions - Size::new(1, 0); // -1 for the center row
-        subdiv_a_dimensions.divide_height_by_const(2);
+    /// Returns the dimensions of the two parts this subdivision would be split into, leaving
+    /// room for the split line. Returns None if either part would be smaller than the minimum
+    /// panel size.
+    fn split_dimensions(&self, direction: SubDivisionSplit, ratio: u16) -> Option<(Size, Size)> {
+        if ratio == 0 || ratio >= 100 {
+            return None;
+        }
 
-        let subdiv_b_dimensinos =
-            self.dimensions - Size::new(1, 0) - Size::new(subdiv_a_dimensions.get_rows(), 0);
+        let (subdiv_a_dimensions, subdiv_b_dimensions) =
+            Self::child_dimensions(self.dimensions, direction, ratio);
 
-        self.subdiv_a = Some(Box::new(SubDivision::new(self.origin, subdiv_a_dimensions)));
+        if !subdiv_a_dimensions.contains(Self::MINIMUM_PANEL_SIZE)
+            || !subdiv_b_dimensions.contains(Self::MINIMUM_PANEL_SIZE)
+        {
+            return None;
+        }
 
-        //TODO: Test if this works
-        self.subdiv_b = Some(Box::new(SubDivision::new(
-            self.origin + Point::new(0, subdiv_a_dimensions.get_rows() + 1),
-            subdiv_b_dimensinos,
-        )));
+        return Some((subdiv_a_dimensions, subdiv_b_dimensions));
+    }
 
-        self.split = Some(SubDivisionSplit::Horizontal); // The split line will be drawn vertically.
+    /// Divides the dimensions, excluding the split line, between the two parts of a split. The
+    /// first part is given the ratio, as a percentage, of the space.
+    fn child_dimensions(dimensions: Size, direction: SubDivisionSplit, ratio: u16) -> (Size, Size) {
+        let share = |length: u16| (length as u32 * ratio as u32 / 100) as u16;
+
+        return match direction {
+            SubDivisionSplit::Horizontal => {
+                let available = dimensions - Size::new(1, 0);
+                let subdiv_a_dimensions =
+                    Size::new(share(available.get_rows()), available.get_cols());
+
+                (
+                    subdiv_a_dimensions,
+                    available - Size::new(subdiv_a_dimensions.get_rows(), 0),
+                )
+            }
+            SubDivisionSplit::Vertical => {
+                let available = dimensions - Size::new(0, 1);
+                let subdiv_a_dimensions =
+                    Size::new(available.get_rows(), share(available.get_cols()));
+
+                (
+                    subdiv_a_dimensions,
+                    available - Size::new(0, subdiv_a_dimensions.get_cols()),
+                )
+            }
+        };
     }
 
-    pub fn render(&self, stdout: &mut Stdout, config: &Config) -> Result<(), MuxideError> {
+    /// Returns the origin of the second part of a split, after the first part and the split line.
+    fn subdiv_b_origin(
+        origin: Point<u16>,
+        direction: SubDivisionSplit,
+        subdiv_a_dimensions: Size,
+    ) -> Point<u16> {
+        return match direction {
+            SubDivisionSplit::Horizontal => {
+                origin + Point::new(0, subdiv_a_dimensions.get_rows() + 1)
+            }
+            SubDivisionSplit::Vertical => {
+                origin + Point::new(subdiv_a_dimensions.get_cols() + 1, 0)
+            }
+        };
+    }
+
+    /// Returns true if every subdivision at the bottom of this subdivision would be at least the
+    /// minimum panel size, if this subdivision had the specified dimensions.
+    fn fits_minimum_size(&self, dimensions: Size) -> bool {
+        if let (Some(split), Some(subdiv_a), Some(subdiv_b)) =
+            (self.split, self.subdiv_a.as_ref(), self.subdiv_b.as_ref())
+        {
+            let (subdiv_a_dimensions, subdiv_b_dimensions) =
+                Self::child_dimensions(dimensions, split, self.ratio);
+
+            return subdiv_a.fits_minimum_size(subdiv_a_dimensions)
+                && subdiv_b.fits_minimum_size(subdiv_b_dimensions);
+        }
+
+        return dimensions.contains(Self::MINIMUM_PANEL_SIZE);
+    }
+
+    /// Move this subdivision and recompute the dimensions of its subdivisions from their ratios.
+    /// This is used when the terminal is resized, so the dimensions may be smaller than the
+    /// minimum size.
+    pub fn set_geometry(&mut self, origin: Point<u16>, dimensions: Size) {
+        self.origin = origin;
+        self.dimensions = dimensions;
+
+        if let Some(panel) = self.panel.as_mut() {
+            panel.set_location((origin.column(), origin.row()));
+        }
+
+        if let (Some(split), Some(subdiv_a), Some(subdiv_b)) =
+            (self.split, self.subdiv_a.as_mut(), self.subdiv_b.as_mut())
+        {
+            let (subdiv_a_dimensions, subdiv_b_dimensions) =
+                Self::child_dimensions(dimensions, split, self.ratio);
+
+            subdiv_a.set_geometry(origin, subdiv_a_dimensions);
+            subdiv_b.set_geometry(
+                Self::subdiv_b_origin(origin, split, subdiv_a_dimensions),
+                subdiv_b_dimensions,
+            );
+        }
+    }
+
+    /// Move the split line next to the panel with the specified id, growing or shrinking the
+    /// panel by the ratio step. Returns false if no split borders the panel.
+    pub fn resize_panel(&mut self, id: usize, grow: bool) -> Result<bool, MuxideError> {
+        let contains_id = |subdiv: &Option<Box<SubDivision>>| {
+            subdiv
+                .as_ref()
+                .and_then(|s| s.panel.as_ref())
+                .map(|p| p.get_id() == id)
+                .unwrap_or(false)
+        };
+        let (in_a, in_b) = (contains_id(&self.subdiv_a), contains_id(&self.subdiv_b));
+
+        if !in_a && !in_b {
+            for subdiv in [self.subdiv_a.as_mut(), self.subdiv_b.as_mut()]
+                .iter_mut()
+                .flatten()
+            {
+                if subdiv.resize_panel(id, grow)? {
+                    return Ok(true);
+                }
+            }
+
+            return Ok(false);
+        }
+
+        // Growing the first part or shrinking the second part moves the split line forwards.
+        let ratio = if in_a == grow {
+            self.ratio + Self::RATIO_STEP
+        } else {
+            self.ratio.saturating_sub(Self::RATIO_STEP)
+        };
+
+        if ratio == 0 || ratio >= 100 {
+            return Err(ErrorType::PanelCannotBeResized.into_error());
+        }
+
+        let split = self
+            .split
+            .ok_or(ErrorType::InvalidSubdivisionState.into_error())?;
+        let (subdiv_a_dimensions, subdiv_b_dimensions) =
+            Self::child_dimensions(self.dimensions, split, ratio);
+
+        let (subdiv_a, subdiv_b) = (
+            self.subdiv_a.as_ref().unwrap(),
+            self.subdiv_b.as_ref().unwrap(),
+        );
+
+        // The subdivisions keep their own ratios, so every panel in them is checked.
+        if !subdiv_a.fits_minimum_size(subdiv_a_dimensions)
+            || !subdiv_b.fits_minimum_size(subdiv_b_dimensions)
+        {
+            return Err(ErrorType::PanelCannotBeResized.into_error());
+        }
+
+        self.ratio = ratio;
+        self.set_geometry(self.origin, self.dimensions);
+
+        return Ok(true);
+    }
+
+    /// Split this empty subdivision following a layout. Returns the path, dimensions and origin of
+    /// the space for each node at the bottom of the layout, from the first (top left) to the last.
+    pub fn apply_layout(
+        &mut self,
+        node: &LayoutNode,
+    ) -> Result<Vec<(SubdivisionPath, Size, Point<u16>)>, MuxideError> {
+        let (split, node_a, node_b) = match node.split_nodes() {
+            Some(nodes) => nodes,
+            None => return Ok(vec![(SubdivisionPath::new(), self.dimensions, self.origin)]),
+        };
+        let direction = match split {
+            LayoutSplit::Horizontal => SubDivisionSplit::Horizontal,
+            LayoutSplit::Vertical => SubDivisionSplit::Vertical,
+        };
+
+        self.subdivide(direction, node.ratio.unwrap_or(Self::DEFAULT_RATIO))?;
+
+        let mut spaces = self.subdiv_a.as_mut().unwrap().apply_layout(node_a)?;
+        spaces
+            .iter_mut()
+            .for_each(|(path, _, _)| path.push(SubdivisionPathElement::A));
+
+        for (mut path, size, origin) in self.subdiv_b.as_mut().unwrap().apply_layout(node_b)? {
+            path.push(SubdivisionPathElement::B);
+            spaces.push((path, size, origin));
+        }
+
+        return Ok(spaces);
+    }
+
+    /// Converts this subdivision into a layout node, each panel runs the specified command.
+    pub fn to_layout_node(&self, command: &str) -> LayoutNode {
+        if self.panel.is_some() {
+            return LayoutNode {
+                command: Some(command.to_string()),
+                ..LayoutNode::default()
+            };
+        } else if let (Some(subdiv_a), Some(subdiv_b)) =
+            (self.subdiv_a.as_ref(), self.subdiv_b.as_ref())
+        {
+            return LayoutNode {
+                split: self.split.map(|split| match split {
+                    SubDivisionSplit::Horizontal => LayoutSplit::Horizontal,
+                    SubDivisionSplit::Vertical => LayoutSplit::Vertical,
+                }),
+                ratio: Some(self.ratio),
+                a: Some(Box::new(subdiv_a.to_layout_node(command))),
+                b: Some(Box::new(subdiv_b.to_layout_node(command))),
+                ..LayoutNode::default()
+            };
+        } else {
+            return LayoutNode::default();
+        }
+    }
+
+    /// Returns the path to the subdivision that contains the panel with the specified id as one
+    /// of its two halves, i.e. the subdivision whose split line borders the panel.
+    pub fn parent_path_for_panel_id(&self, id: usize) -> Option<SubdivisionPath> {
+        let mut path = self.path_for_panel_id(id)?;
+
+        if path.is_empty() {
+            return None;
+        }
+
+        // The elements are stored in reverse order so the first element is the deepest.
+        path.elements.remove(0);
+
+        return Some(path);
+    }
+
+    /// Render this subdivision, if a path is specified the split line of the subdivision at
+    /// that path is drawn with the highlight color.
+    pub fn render<W: Write>(
+        &self,
+        stdout: &mut W,
+        config: &Config,
+        highlight: Option<SubdivisionPath>,
+    ) -> Result<(), MuxideError> {
         if self.panel.is_none() && self.subdiv_a.is_none() && self.subdiv_b.is_none() {
             let (mut col, mut row) = (self.dimensions.get_cols(), self.dimensions.get_rows());
 
             // Determine the center
             col /= 2;
             // Align the empty text to the center
-            col -= EMPTY_TEXT.len() as u16 / 2;
+            col = col.saturating_sub(EMPTY_TEXT.len() as u16 / 2);
 
             // Determine the center
             row /= 2;
             // Subtract 1 for the height of the text
-            row -= 1;
+            row = row.saturating_sub(1);
 
             // Add 1 to offset by the left and top borders. Obviously it is useless having
             // the + and - operations that cancel each other but for clarity's sake they have
@@ -549,11 +876,49 @@ impl SubDivision {
 
             return Ok(());
         } else if self.panel.is_none() && self.subdiv_a.is_some() && self.subdiv_b.is_some() {
-            self.subdiv_a.as_ref().unwrap().render(stdout, config)?;
-            self.subdiv_b.as_ref().unwrap().render(stdout, config)?;
+            let mut highlight_a = None;
+            let mut highlight_b = None;
+            let mut highlight_self = false;
+
+            if let Some(mut path) = highlight {
+                match path.pop() {
+                    Some(SubdivisionPathElement::A) => highlight_a = Some(path),
+                    Some(SubdivisionPathElement::B) => highlight_b = Some(path),
+                    None => highlight_self = true,
+                }
+            }
+
+            self.subdiv_a
+                .as_ref()
+                .unwrap()
+                .render(stdout, config, highlight_a)?;
+            self.subdiv_b
+                .as_ref()
+                .unwrap()
+                .render(stdout, config, highlight_b)?;
 
             Self::reset_stdout_style(stdout)?;
 
+            let theme = config.theme();
+
+            if config.get_environment_ref().monochrome() {
+                if highlight_self {
+                    queue_map_err!(stdout, style::SetAttribute(style::Attribute::Reverse))?;
+                }
+            } else if highlight_self {
+                queue_map_err!(
+                    stdout,
+                    style::SetForegroundColor(
+                        theme.selected_border.crossterm_color(style::Color::White)
+                    )
+                )?;
+            } else if let Some(color) = theme.border {
+                queue_map_err!(
+                    stdout,
+                    style::SetForegroundColor(color.crossterm_color(style::Color::Reset))
+                )?;
+            }
+
             match &self.split {
                 Some(SubDivisionSplit::Vertical) => {
                     let center_col = self.dimensions.get_cols() / 2 + self.origin.column() - 1;
@@ -566,9 +931,11 @@ impl SubDivision {
                 None => panic!("Unexpected internal error."), // This shouldn't ever happen.
             }
 
+            Self::reset_stdout_style(stdout)?;
+
             return Ok(());
         } else if let Some(panel) = &self.panel {
-            for (row_number, row) in panel.get_content().into_iter().enumerate() {
+            for (row_number, row) in panel.get_content().iter().enumerate() {
                 queue_map_err!(
                     stdout,
                     cursor::MoveTo(self.origin.column(), self.origin.row() + row_number as u16),
@@ -576,8 +943,8 @@ impl SubDivision {
                 )?;
 
                 stdout
-                    .write(&row)
-                    .map_err(|e| ErrorType::new_display_qe_error(e))?;
+                    .write(&Self::clip_row(row, self.dimensions.get_cols()))
+                    .map_err(ErrorType::new_display_qe_error)?;
             }
 
             return Ok(());
@@ -586,9 +953,9 @@ impl SubDivision {
         }
     }
 
-    fn queue_vertical_line(
+    fn queue_vertical_line<W: Write>(
         &self,
-        stdout: &mut Stdout,
+        stdout: &mut W,
         config: &Config,
         col: u16,
     ) -> Result<(), MuxideError> {
@@ -605,9 +972,9 @@ impl SubDivision {
         return Ok(());
     }
 
-    fn queue_horizontal_line(
+    fn queue_horizontal_line<W: Write>(
         &self,
-        stdout: &mut Stdout,
+        stdout: &mut W,
         config: &Config,
         row: u16,
     ) -> Result<(), MuxideError> {
@@ -624,11 +991,82 @@ impl SubDivision {
         return Ok(());
     }
 
-    fn reset_stdout_style(stdout: &mut Stdout) -> Result<(), MuxideError> {
-        queue_map_err!(stdout, style::ResetColor)?;
+    fn reset_stdout_style<W: Write>(stdout: &mut W) -> Result<(), MuxideError> {
+        queue_map_err!(
+            stdout,
+            style::ResetColor,
+            style::SetAttribute(style::Attribute::NoReverse)
+        )?;
 
         return Ok(());
     }
+
+    /// Remove the characters of a formatted row that would be drawn past the specified number of
+    /// columns. Wide characters take up two columns and combining characters none, escape
+    /// sequences are kept so the style of the following rows is unchanged.
+    pub fn clip_row(row: &[u8], width: u16) -> Vec<u8> {
+        let width = width as usize;
+        let row = String::from_utf8_lossy(row);
+        let mut chars = row.chars();
+        let mut clipped = String::with_capacity(row.len());
+        let mut used = 0;
+
+        while let Some(ch) = chars.next() {
+            if ch == '\x1b' {
+                clipped.push(ch);
+
+                match chars.next() {
+                    Some('[') => {
+                        let mut sequence = String::new();
+
+                        for c in chars.by_ref() {
+                            sequence.push(c);
+
+                            if ('\x40'..='\x7e').contains(&c) {
+                                break;
+                            }
+                        }
+
+                        // Moving the cursor forward takes up columns in the same way printing
+                        // spaces does.
+                        if sequence.ends_with('C') {
+                            let count = sequence[..sequence.len() - 1]
+                                .parse::<usize>()
+                                .unwrap_or(1)
+                                .max(1);
+
+                            if used + count > width {
+                                clipped.pop();
+                                used = width;
+                                continue;
+                            }
+
+                            used += count;
+                        }
+
+                        clipped.push('[');
+                        clipped.push_str(&sequence);
+                    }
+                    Some(c) => clipped.push(c),
+                    None => (),
+                }
+            } else if ch.is_control() {
+                clipped.push(ch);
+            } else {
+                let ch_width = ch.width().unwrap_or(0);
+
+                if used + ch_width > width {
+                    used = width;
+                    continue;
+                }
+
+                used += ch_width;
+                clipped.push(ch);
+            }
+        }
+
+        return clipped.into_bytes();
+    }
 }
 
 impl Default for SubDivision {
@@ -670,3 +1108,276 @@ impl SubdivisionPathElement {
         return *self == SubdivisionPathElement::B;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{PanelPtr, SubDivision, SubDivisionSplit};
+    use crate::geometry::{Direction, Point, Size};
+    use proptest::prelude::*;
+
+    #[derive(Clone, Debug)]
+    enum Operation {
+        Open,
+        Split(usize, SubDivisionSplit, u16),
+        Resize(usize, bool),
+        Close(usize),
+        Merge(usize),
+        Focus(usize, Direction),
+    }
+
+    fn operation() -> impl Strategy<Value = Operation> {
+        let split = prop_oneof![
+            Just(SubDivisionSplit::Horizontal),
+            Just(SubDivisionSplit::Vertical)
+        ];
+        let direction = prop_oneof![
+            Just(Direction::Up),
+            Just(Direction::Down),
+            Just(Direction::Left),
+            Just(Direction::Right)
+        ];
+
+        return prop_oneof![
+            Just(Operation::Open),
+            (any::<usize>(), split, 1u16..100)
+                .prop_map(|(i, split, ratio)| Operation::Split(i, split, ratio)),
+            (any::<usize>(), any::<bool>()).prop_map(|(i, grow)| Operation::Resize(i, grow)),
+            any::<usize>().prop_map(Operation::Close),
+            any::<usize>().prop_map(Operation::Merge),
+            (any::<usize>(), direction).prop_map(|(i, direction)| Operation::Focus(i, direction)),
+        ];
+    }
+
+    /// Selects one of the open panels, like the selected panel in the display.
+    fn pick(ids: &[usize], index: usize) -> Option<usize> {
+        if ids.is_empty() {
+            return None;
+        }
+
+        return Some(ids[index % ids.len()]);
+    }
+
+    /// Returns true if the rectangles with the specified origins and sizes overlap.
+    fn overlaps(a: (Point<u16>, Size), b: (Point<u16>, Size)) -> bool {
+        return a.0.column() < b.0.column() + b.1.get_cols()
+            && b.0.column() < a.0.column() + a.1.get_cols()
+            && a.0.row() < b.0.row() + b.1.get_rows()
+            && b.0.row() < a.0.row() + a.1.get_rows();
+    }
+
+    /// A subdivision is split exactly when it has both of its parts, and only leaves hold panels.
+    fn check_splits(subdivision: &SubDivision) {
+        match (&subdivision.subdiv_a, &subdivision.subdiv_b) {
+            (Some(subdiv_a), Some(subdiv_b)) => {
+                assert!(subdivision.split.is_some());
+                assert!(subdivision.panel.is_none());
+                check_splits(subdiv_a);
+                check_splits(subdiv_b);
+            }
+            (None, None) => assert!(subdivision.split.is_none()),
+            _ => panic!("A subdivision has only one part"),
+        }
+    }
+
+    fn check_invariants(root: &SubDivision, size: Size, ids: &[usize]) {
+        let regions = root.panel_regions();
+        check_splits(root);
+
+        // Every open panel is in the tree exactly once and can be found by its path.
+        let mut region_ids: Vec<usize> = regions.iter().map(|(id, _, _)| *id).collect();
+        let mut expected_ids = ids.to_vec();
+        region_ids.sort();
+        expected_ids.sort();
+        assert_eq!(region_ids, expected_ids);
+
+        for id in ids {
+            assert!(root.path_for_panel_id(*id).is_some());
+        }
+
+        // The panels fit within the root and never overlap each other.
+        for (i, (_, origin, dimensions)) in regions.iter().enumerate() {
+            assert!(origin.column() + dimensions.get_cols() <= size.get_cols());
+            assert!(origin.row() + dimensions.get_rows() <= size.get_rows());
+            // Only the root can be smaller than the minimum panel size, splits never produce one.
+            assert!(dimensions.contains(SubDivision::MINIMUM_PANEL_SIZE) || *dimensions == size);
+
+            for (_, other_origin, other_dimensions) in &regions[i + 1..] {
+                assert!(!overlaps(
+                    (*origin, *dimensions),
+                    (*other_origin, *other_dimensions)
+                ));
+            }
+        }
+    }
+
+    fn check_focus(root: &SubDivision, ids: &[usize], id: usize, direction: Direction) {
+        let target = match root.focus_next_id(id, direction) {
+            Some(target) => target,
+            None => return,
+        };
+
+        assert!(ids.contains(&target));
+        assert_ne!(target, id);
+
+        let regions = root.panel_regions();
+        let (_, from, from_size) = regions.iter().find(|(i, _, _)| *i == id).unwrap();
+        let (_, to, to_size) = regions.iter().find(|(i, _, _)| *i == target).unwrap();
+
+        // The focused panel must be entirely on the side of the selected panel in the direction.
+        match direction {
+            Direction::Up => assert!(to.row() + to_size.get_rows() <= from.row()),
+            Direction::Down => assert!(to.row() >= from.row() + from_size.get_rows()),
+            Direction::Left => assert!(to.column() + to_size.get_cols() <= from.column()),
+            Direction::Right => assert!(to.column() >= from.column() + from_size.get_cols()),
+        }
+    }
+
+    #[test]
+    fn test_clip_row_wide_characters() {
+        // Each of these characters takes up two columns.
+        let row = "日本語".as_bytes();
+
+        assert_eq!(SubDivision::clip_row(row, 6), row.to_vec());
+        assert_eq!(SubDivision::clip_row(row, 5), "日本".as_bytes().to_vec());
+        assert_eq!(SubDivision::clip_row(row, 1), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_clip_row_combining_characters() {
+        let row = "e\u{301}e\u{301}e".as_bytes();
+
+        assert_eq!(
+            SubDivision::clip_row(row, 2),
+            "e\u{301}e\u{301}".as_bytes().to_vec()
+        );
+    }
+
+    #[test]
+    fn test_clip_row_escape_sequences() {
+        let row = "\x1b[31mab\x1b[3Ccd\x1b[0m".as_bytes();
+
+        assert_eq!(
+            SubDivision::clip_row(row, 4),
+            "\x1b[31mab\x1b[0m".as_bytes().to_vec()
+        );
+        assert_eq!(
+            SubDivision::clip_row(row, 6),
+            "\x1b[31mab\x1b[3Cc\x1b[0m".as_bytes().to_vec()
+        );
+    }
+
+    #[test]
+    fn test_split_ratio() {
+        let mut root = SubDivision::new(Point::new(0, 0), Size::new(20, 41));
+        let (path, _, origin) = root.next_panel_details().unwrap();
+        root.open_panel_at_path(PanelPtr::new(0, (origin.column(), origin.row())), path)
+            .unwrap();
+
+        root.split_panel(Some(0), SubDivisionSplit::Vertical, 25)
+            .unwrap();
+        assert_eq!(root.panel_regions()[0].2, Size::new(20, 10));
+
+        root.resize_panel(0, true).unwrap();
+        assert_eq!(root.panel_regions()[0].2, Size::new(20, 12));
+
+        // The ratio is kept when the terminal is resized.
+        root.set_geometry(Point::new(0, 2), Size::new(20, 81));
+        let (_, origin, size) = root.panel_regions()[0];
+        assert_eq!(size, Size::new(20, 24));
+        assert_eq!(origin, Point::new(0, 2));
+
+        // The panel can't be shrunk below the minimum panel size.
+        root.set_geometry(Point::new(0, 0), Size::new(20, 20));
+        assert!(root.resize_panel(0, false).is_err());
+    }
+
+    #[test]
+    fn test_merge_with_sibling_panels() {
+        let size = Size::new(30, 60);
+        let mut root = SubDivision::new(Point::new(0, 0), size);
+
+        for (id, split) in [
+            (0, Some(SubDivisionSplit::Vertical)),
+            (1, Some(SubDivisionSplit::Horizontal)),
+            (2, None),
+        ]
+        .iter()
+        {
+            let (path, _, origin) = root.next_panel_details().unwrap();
+            root.open_panel_at_path(PanelPtr::new(*id, (origin.column(), origin.row())), path)
+                .unwrap();
+
+            if let Some(split) = split {
+                root.split_panel(Some(*id), *split, SubDivision::DEFAULT_RATIO)
+                    .unwrap();
+            }
+        }
+
+        assert_eq!(root.sibling_panel_ids(0), Some(vec![1, 2]));
+        assert_eq!(root.sibling_panel_ids(2), Some(vec![1]));
+
+        // The panel is only merged once the panels next to it are closed.
+        assert_eq!(root.merge_selected_panel(Some(0)).unwrap(), None);
+
+        // Closing a panel gives its space to the other part of its split.
+        assert!(root.close_panel_with_id(2));
+        assert_eq!(root.sibling_panel_ids(0), Some(vec![1]));
+        assert_eq!(root.panel_regions()[1].2, Size::new(30, 30));
+
+        assert!(root.close_panel_with_id(1));
+        assert_eq!(root.panel_regions(), vec![(0, Point::new(0, 0), size)]);
+        assert_eq!(root.sibling_panel_ids(0), None);
+    }
+
+    proptest! {
+        #[test]
+        fn test_subdivision_operations(
+            rows in 1u16..60,
+            cols in 1u16..200,
+            operations in prop::collection::vec(operation(), 0..60)
+        ) {
+            let size = Size::new(rows, cols);
+            let mut root = SubDivision::new(Point::new(0, 0), size);
+            let mut ids: Vec<usize> = Vec::new();
+            let mut next_id = 0;
+
+            for operation in operations {
+                match operation {
+                    Operation::Open => {
+                        if let Some((path, _, origin)) = root.next_panel_details() {
+                            let panel = PanelPtr::new(next_id, (origin.column(), origin.row()));
+
+                            root.open_panel_at_path(panel, path).unwrap();
+                            ids.push(next_id);
+                            next_id += 1;
+                        }
+                    }
+                    Operation::Split(i, split, ratio) => {
+                        let _ = root.split_panel(pick(&ids, i), split, ratio);
+                    }
+                    Operation::Resize(i, grow) => {
+                        if let Some(id) = pick(&ids, i) {
+                            let _ = root.resize_panel(id, grow);
+                        }
+                    }
+                    Operation::Close(i) => {
+                        if let Some(id) = pick(&ids, i) {
+                            assert!(root.close_panel_with_id(id));
+                            ids.retain(|i| *i != id);
+                        }
+                    }
+                    Operation::Merge(i) => {
+                        let _ = root.merge_selected_panel(pick(&ids, i));
+                    }
+                    Operation::Focus(i, direction) => {
+                        if let Some(id) = pick(&ids, i) {
+                            check_focus(&root, &ids, id, direction);
+                        }
+                    }
+                }
+
+                check_invariants(&root, size, &ids);
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use crate::config::{StatusBar, StatusBarElement};
+use std::ffi::CString;
+use std::path::Path;
+use std::time::Instant;
+
+/// The directory the kernel lists the power supplies in.
+const POWER_SUPPLY_DIRECTORY: &str = "/sys/class/power_supply";
+
+/// The text of the status bar's widgets. The values read from the system are kept until the
+/// widgets are updated, rather than being read each time the status bar is displayed.
+pub struct Widgets {
+    /// The text of each of the status bar's elements, None for the elements that aren't widgets
+    /// and the widgets that couldn't be read.
+    texts: Vec<Option<String>>,
+    /// The time the widgets were last updated.
+    updated: Option<Instant>,
+}
+
+impl Widgets {
+    pub fn new() -> Self {
+        return Self {
+            texts: Vec::new(),
+            updated: None,
+        };
+    }
+
+    /// Reads the value of each widget again, returns true if the text of any of them changed.
+    pub fn update(&mut self, status_bar: &StatusBar) -> bool {
+        let texts: Vec<Option<String>> = status_bar
+            .elements()
+            .iter()
+            .map(|element| Self::read(element, status_bar))
+            .collect();
+        let changed = texts != self.texts;
+
+        self.texts = texts;
+        self.updated = Some(Instant::now());
+
+        return changed;
+    }
+
+    /// Returns the text of the element at the index of the status bar's elements, if it is a
+    /// widget that could be read.
+    pub fn text(&self, index: usize) -> Option<&String> {
+        return self.texts.get(index).and_then(|text| text.as_ref());
+    }
+
+    /// Returns the time the widgets should next be updated, None if the status bar has no
+    /// widgets.
+    pub fn next_update(&self, status_bar: &StatusBar) -> Option<Instant> {
+        if !status_bar.has_widgets() {
+            return None;
+        }
+
+        return Some(self.updated.map_or_else(Instant::now, |updated| {
+            updated + status_bar.update_interval()
+        }));
+    }
+
+    fn read(element: &StatusBarElement, status_bar: &StatusBar) -> Option<String> {
+        return match element {
+            StatusBarElement::Clock => Some(format_time(
+                unsafe { libc::time(std::ptr::null_mut()) },
+                status_bar.clock_format(),
+            )),
+            StatusBarElement::Hostname => hostname(),
+            StatusBarElement::Battery => battery(Path::new(POWER_SUPPLY_DIRECTORY)),
+            StatusBarElement::LoadAverage => load_average(),
+            _ => None,
+        };
+    }
+}
+
+/// Returns the local time formatted with a strftime format, empty if it can't be formatted.
+fn format_time(time: libc::time_t, format: &str) -> String {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return String::new();
+    }
+
+    let format = match CString::new(format) {
+        Ok(format) => format,
+        Err(_) => return String::new(),
+    };
+    let mut buffer = [0u8; 256];
+    let len = unsafe {
+        libc::strftime(
+            buffer.as_mut_ptr() as *mut libc::c_char,
+            buffer.len(),
+            format.as_ptr(),
+            &tm,
+        )
+    };
+
+    return String::from_utf8_lossy(&buffer[..len]).to_string();
+}
+
+/// Returns the name of the host up to the first dot, i.e. "laptop" rather than
+/// "laptop.example.com".
+fn hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    let name = nix::unistd::gethostname(&mut buffer).ok()?.to_str().ok()?;
+
+    return name.split('.').next().map(String::from);
+}
+
+/// Returns the charge of the first battery in the directory of power supplies, followed by a + if
+/// it is charging. None if there is no battery.
+fn battery(directory: &Path) -> Option<String> {
+    let mut supplies: Vec<_> = std::fs::read_dir(directory)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    supplies.sort();
+
+    for supply in supplies {
+        let read = |name: &str| {
+            std::fs::read_to_string(supply.join(name))
+                .ok()
+                .map(|value| value.trim().to_string())
+        };
+
+        if read("type").as_deref() != Some("Battery") {
+            continue;
+        }
+
+        let capacity = read("capacity")?;
+        let charging = read("status").as_deref() == Some("Charging");
+
+        return Some(format!("{}%{}", capacity, if charging { "+" } else { "" }));
+    }
+
+    return None;
+}
+
+/// Returns the load averages over the last 1, 5 and 15 minutes.
+fn load_average() -> Option<String> {
+    // /proc is only available on Linux, other systems are asked through sysctl.
+    let averages = match std::fs::read_to_string("/proc/loadavg") {
+        Ok(averages) => averages,
+        Err(_) => {
+            let output = std::process::Command::new("sysctl")
+                .args(["-n", "vm.loadavg"])
+                .output()
+                .ok()?;
+
+            String::from_utf8(output.stdout).ok()?
+        }
+    };
+
+    return load_average_text(&averages);
+}
+
+/// Returns the first three load averages in the output of /proc/loadavg, "0.52 0.48 0.40 1/123
+/// 4567", or sysctl, "{ 0.52 0.48 0.40 }".
+fn load_average_text(averages: &str) -> Option<String> {
+    let averages: Vec<&str> = averages
+        .split_whitespace()
+        .filter(|value| value.parse::<f64>().is_ok())
+        .take(3)
+        .collect();
+
+    if averages.len() != 3 {
+        return None;
+    }
+
+    return Some(averages.join(" "));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{battery, load_average_text, Widgets};
+    use crate::config::Config;
+    use std::fs;
+
+    #[test]
+    fn test_battery() {
+        let directory = std::env::temp_dir().join(format!("muxide-battery-{}", std::process::id()));
+        let adapter = directory.join("AC");
+        let battery_directory = directory.join("BAT0");
+        fs::create_dir_all(&adapter).unwrap();
+        fs::create_dir_all(&battery_directory).unwrap();
+        fs::write(adapter.join("type"), "Mains\n").unwrap();
+        assert_eq!(battery(&directory), None);
+
+        fs::write(battery_directory.join("type"), "Battery\n").unwrap();
+        fs::write(battery_directory.join("capacity"), "85\n").unwrap();
+        fs::write(battery_directory.join("status"), "Discharging\n").unwrap();
+        assert_eq!(battery(&directory), Some(String::from("85%")));
+
+        fs::write(battery_directory.join("status"), "Charging\n").unwrap();
+        assert_eq!(battery(&directory), Some(String::from("85%+")));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_load_average_text() {
+        assert_eq!(
+            load_average_text("0.52 0.48 0.40 1/123 4567\n"),
+            Some(String::from("0.52 0.48 0.40"))
+        );
+        assert_eq!(
+            load_average_text("{ 1.20 1.10 1.00 }\n"),
+            Some(String::from("1.20 1.10 1.00"))
+        );
+        assert_eq!(load_average_text(""), None);
+    }
+
+    #[test]
+    fn test_update() {
+        let config = Config::from_toml_string(
+            "
+            [status_bar]\n\
+            elements = [\"Workspaces\", \"Clock\", { Text = \"text\" }]\n\
+            clock_format = \"clock\"\n\
+            ",
+        )
+        .unwrap();
+        let mut widgets = Widgets::new();
+
+        assert!(widgets.update(config.status_bar()));
+        assert_eq!(widgets.text(0), None);
+        assert_eq!(widgets.text(1), Some(&String::from("clock")));
+        assert_eq!(widgets.text(2), None);
+        assert!(!widgets.update(config.status_bar()));
+        assert!(widgets.next_update(config.status_bar()).is_some());
+        assert_eq!(widgets.next_update(Config::default().status_bar()), None);
+    }
+}
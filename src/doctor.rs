@@ -0,0 +1,240 @@
+use crate::pty::Pty;
+use crate::Config;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// The outcome of one of the checks run by `muxide doctor`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    /// What was found, or why the check failed and how to fix it.
+    pub detail: String,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: String) -> Self {
+        return Self {
+            name,
+            passed: true,
+            detail,
+        };
+    }
+
+    fn fail(name: &'static str, detail: String) -> Self {
+        return Self {
+            name,
+            passed: false,
+            detail,
+        };
+    }
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = if self.passed { "PASS" } else { "FAIL" };
+
+        return write!(f, "[{}] {}: {}", status, self.name, self.detail);
+    }
+}
+
+/// Runs every check against the loaded config, or the error from loading it. The other checks use
+/// the default config if it couldn't be loaded.
+pub fn run_checks(config: Result<Config, String>) -> Vec<Check> {
+    let (config_check, config) = match config {
+        Ok(config) => (Check::pass("Config", String::from("Parsed")), config),
+        Err(e) => (
+            Check::fail(
+                "Config",
+                format!("{} Fix the config, the defaults are used below.", e),
+            ),
+            Config::default(),
+        ),
+    };
+
+    return vec![
+        config_check,
+        check_pty(),
+        check_shell(config.get_panel_init_command()),
+        check_terminal(),
+        check_password_file(config.get_password_ref().password_file_location()),
+    ];
+}
+
+fn check_pty() -> Check {
+    return match Pty::check_allocation() {
+        Ok(_) => Check::pass("PTY", String::from("A pty was allocated")),
+        Err(e) => Check::fail(
+            "PTY",
+            format!(
+                "Failed to allocate a pty ({}). Check that /dev/ptmx exists and the pty limit \
+                 hasn't been reached.",
+                e
+            ),
+        ),
+    };
+}
+
+fn check_shell(command: &str) -> Check {
+    let path = match find_executable(command) {
+        Some(path) => path,
+        None => {
+            return Check::fail(
+                "Shell",
+                format!(
+                    "'{}' wasn't found. Set panel_init_command in the [environment] config to an \
+                     installed shell.",
+                    command
+                ),
+            )
+        }
+    };
+
+    let executable = path
+        .metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+
+    if executable {
+        return Check::pass("Shell", format!("{}", path.display()));
+    } else {
+        return Check::fail(
+            "Shell",
+            format!(
+                "{} isn't an executable file. Make it executable or set panel_init_command to \
+                 another shell.",
+                path.display()
+            ),
+        );
+    }
+}
+
+/// Returns the path of a command, commands without a '/' are searched for in the PATH.
+fn find_executable(command: &str) -> Option<PathBuf> {
+    if command.contains('/') {
+        return Some(PathBuf::from(command)).filter(|path| path.exists());
+    }
+
+    return std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|directory| directory.join(command))
+        .find(|path| path.is_file());
+}
+
+fn check_terminal() -> Check {
+    if unsafe { libc::isatty(libc::STDOUT_FILENO) } != 1 {
+        return Check::fail(
+            "Terminal",
+            String::from("stdout isn't a terminal. Run muxide from an interactive terminal."),
+        );
+    }
+
+    let database = match terminfo::Database::from_env() {
+        Ok(database) => database,
+        Err(e) => {
+            return Check::fail(
+                "Terminal",
+                format!(
+                    "No terminfo entry for TERM={} ({}). Set TERM to a terminal that is \
+                     installed, i.e. xterm-256color.",
+                    std::env::var("TERM").unwrap_or_default(),
+                    e
+                ),
+            )
+        }
+    };
+
+    let mut missing = Vec::new();
+
+    if database
+        .get::<terminfo::capability::EnterCaMode>()
+        .is_none()
+    {
+        missing.push("the alternate screen");
+    }
+
+    let colors = database
+        .get::<terminfo::capability::MaxColors>()
+        .map_or(0, |colors| colors.0);
+
+    if colors < 8 {
+        missing.push("colors");
+    }
+
+    if missing.is_empty() {
+        return Check::pass(
+            "Terminal",
+            format!("{} with {} colors", database.name(), colors),
+        );
+    } else {
+        return Check::fail(
+            "Terminal",
+            format!(
+                "{} doesn't support {}. Use a terminal that does, or set TERM to match the \
+                 terminal.",
+                database.name(),
+                missing.join(" or ")
+            ),
+        );
+    }
+}
+
+fn check_password_file(location: &str) -> Check {
+    let path = Path::new(location);
+
+    if !path.exists() {
+        return Check::pass(
+            "Password file",
+            format!(
+                "No password is set, one is requested on the next start ({})",
+                location
+            ),
+        );
+    }
+
+    let mut contents = String::new();
+
+    return match File::open(path).and_then(|mut file| file.read_to_string(&mut contents)) {
+        Ok(_) if contents.trim().is_empty() => Check::fail(
+            "Password file",
+            format!(
+                "{} is empty. Run muxide --change-password or remove the file.",
+                location
+            ),
+        ),
+        Ok(_) => Check::pass("Password file", format!("{} is readable", location)),
+        Err(e) => Check::fail(
+            "Password file",
+            format!(
+                "Failed to read {} ({}). Check its permissions.",
+                location, e
+            ),
+        ),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_password_file, check_shell, run_checks};
+
+    #[test]
+    fn test_shell_check() {
+        assert!(check_shell("/bin/sh").passed);
+        assert!(check_shell("sh").passed);
+        assert!(!check_shell("/missing/shell").passed);
+        assert!(!check_shell("/etc").passed);
+    }
+
+    #[test]
+    fn test_config_failure_is_reported() {
+        let checks = run_checks(Err(String::from("Invalid config.")));
+
+        assert!(!checks[0].passed);
+        assert_eq!(checks.len(), 5);
+    }
+
+    #[test]
+    fn test_missing_password_file() {
+        assert!(check_password_file("/missing/password").passed);
+    }
+}
@@ -1,4 +1,8 @@
+use crate::geometry::Size;
 use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::sync::Arc;
 
 #[derive(Clone, PartialEq, Debug, Hash)]
 pub enum ErrorType {
@@ -73,6 +77,10 @@ pub enum ErrorType {
         message: String,
     },
 
+    ProtocolError {
+        reason: String,
+    },
+
     DisplayNotRunningError,
     InputManagerRunningError,
     InvalidSubdivisionState,
@@ -91,10 +99,38 @@ pub enum ErrorType {
     NoAvailableSubdivisionToMerge,
     NoSubdivisionAtPath,
     NoPanelAtPath,
+    PopupAlreadyOpen,
+    TerminalTooSmallForPopup,
+    PanelTooSmallToSplit {
+        minimum: Size,
+    },
+    PanelCannotBeResized,
+    NoEmptyWorkspace,
+    NoPanelInWorkspace(usize),
+    NoLayoutToUndo,
+    LayoutCannotBeRestored,
+    NoSshCommand(usize),
+    SshCommandRunning(usize),
 }
 
-#[derive(Clone, PartialEq, Hash)]
+/// An error with both a human readable description and the structured information it was
+/// created from, so callers can act on the kind of error that occurred. The error that caused it
+/// is kept as its source, and the operations it occurred during as its context. The type and
+/// descriptions are boxed, keeping the results that return an error small.
+#[derive(Clone)]
 pub struct MuxideError {
+    kind: Box<ErrorType>,
+    details: Box<ErrorDetails>,
+    panel_id: Option<usize>,
+    path: Option<String>,
+    source: Option<Arc<dyn Error + Send + Sync + 'static>>,
+    /// The descriptions of the operations that failed, outermost first.
+    context: Vec<String>,
+}
+
+/// The descriptions generated for an error type.
+#[derive(Clone, PartialEq, Hash)]
+struct ErrorDetails {
     debug_description: String,
     description: String,
     terminate: bool,
@@ -109,12 +145,161 @@ impl ErrorType {
         return Self::QueueExecuteError {
             reason: io_error.to_string(),
         }
-        .into_error();
+        .into_error()
+        .with_source(io_error);
     }
 }
 
 impl MuxideError {
     pub fn new(tp: ErrorType) -> Self {
+        let panel_id = match &tp {
+            ErrorType::NoPanelWithIDError { id } => Some(*id),
+            _ => None,
+        };
+
+        let path = match &tp {
+            ErrorType::IOError { target, .. } => Some(target.clone()),
+            _ => None,
+        };
+
+        return Self {
+            details: Box::new(ErrorDetails::from_type(tp.clone())),
+            kind: Box::new(tp),
+            panel_id,
+            path,
+            source: None,
+            context: Vec::new(),
+        };
+    }
+
+    /// Attach the id of the panel this error relates to.
+    pub fn with_panel_id(mut self, id: usize) -> Self {
+        self.panel_id = Some(id);
+        return self;
+    }
+
+    /// Attach the underlying error that caused this error.
+    pub fn with_source<E: Error + Send + Sync + 'static>(mut self, source: E) -> Self {
+        self.source = Some(Arc::new(source));
+        return self;
+    }
+
+    /// Attach a description of the operation that failed because of this error, such as
+    /// "Failed to resize panel 3". Context added later describes an outer operation, so it comes
+    /// first in the descriptions.
+    pub fn with_context<T: Into<String>>(mut self, context: T) -> Self {
+        self.context.insert(0, context.into());
+        return self;
+    }
+
+    /// Returns the type of error that occurred.
+    pub fn kind(&self) -> &ErrorType {
+        return &self.kind;
+    }
+
+    pub fn panel_id(&self) -> Option<usize> {
+        return self.panel_id;
+    }
+
+    pub fn path(&self) -> Option<&String> {
+        return self.path.as_ref();
+    }
+
+    /// Returns the descriptions of the operations that failed, outermost first.
+    pub fn context(&self) -> &[String] {
+        return &self.context;
+    }
+
+    /// Returns the description of the underlying error and the errors that caused it, separated
+    /// by colons.
+    pub fn source_description(&self) -> Option<String> {
+        let source = self.source.as_ref()?;
+        return Some(Self::chain(vec![source.to_string()], source.source()));
+    }
+
+    pub fn description(&self) -> String {
+        return format!(
+            "PTY Error: {}",
+            self.with_context_description(&self.details.description)
+        );
+    }
+
+    /// Returns the detailed description of the error, including its context and the chain of
+    /// errors that caused it, for example
+    /// "PTY Error: Failed to resize panel 3: ioctl TIOCSWINSZ: EBADF".
+    pub fn debug_description(&self) -> String {
+        let description = self.with_context_description(&self.details.debug_description);
+        let source = self
+            .source
+            .as_ref()
+            .map(|s| s.as_ref() as &(dyn Error + 'static));
+        return format!("PTY Error: {}", Self::chain(vec![description], source));
+    }
+
+    fn with_context_description(&self, description: &str) -> String {
+        let mut parts = self.context.clone();
+        parts.push(description.to_string());
+        return Self::chain(parts, None);
+    }
+
+    /// Joins the descriptions with those of the chain of errors, without the full stops that
+    /// would come before the colons. An error that is already described isn't repeated, since
+    /// many errors include the description of their source.
+    fn chain(mut parts: Vec<String>, mut source: Option<&(dyn Error + 'static)>) -> String {
+        while let Some(error) = source {
+            let description = error.to_string();
+
+            if !parts.iter().any(|part| part.contains(&description)) {
+                parts.push(description);
+            }
+
+            source = error.source();
+        }
+
+        let last = parts.len().saturating_sub(1);
+
+        return parts
+            .iter()
+            .enumerate()
+            .map(|(i, part)| {
+                if i == last {
+                    part.as_str()
+                } else {
+                    part.trim_end_matches('.')
+                }
+            })
+            .collect::<Vec<&str>>()
+            .join(": ");
+    }
+
+    pub fn should_terminate(&self) -> bool {
+        return self.details.terminate;
+    }
+
+    /// Returns true if the error was caused by an IO operation that was interrupted or would
+    /// have blocked, so trying again may succeed.
+    pub fn is_transient(&self) -> bool {
+        let mut source = Error::source(self);
+
+        while let Some(error) = source {
+            if let Some(e) = error.downcast_ref::<io::Error>() {
+                return matches!(
+                    e.kind(),
+                    io::ErrorKind::Interrupted
+                        | io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                );
+            }
+
+            source = error.source();
+        }
+
+        return false;
+    }
+}
+
+impl ErrorDetails {
+    fn from_type(tp: ErrorType) -> Self {
         return match tp {
             ErrorType::IOCTLError { code, outcome } => Self::new_ioctl_error(code, outcome),
             ErrorType::PTYSpawnError { description } => Self::new_pty_spawn_error(description),
@@ -290,19 +475,100 @@ impl MuxideError {
                     terminate: false,
                 };
             }
-        };
-    }
 
-    pub fn description(&self) -> String {
-        return format!("PTY Error: {}", self.description);
-    }
+            ErrorType::PopupAlreadyOpen => {
+                return Self {
+                    debug_description: "A popup is already open.".to_string(),
+                    description: "A popup is already open.".to_string(),
+                    terminate: false,
+                };
+            }
 
-    pub fn debug_description(&self) -> String {
-        return format!("PTY Error: {}", self.debug_description);
-    }
+            ErrorType::TerminalTooSmallForPopup => {
+                return Self {
+                    debug_description: "The terminal is too small to display a popup.".to_string(),
+                    description: "The terminal is too small to display a popup.".to_string(),
+                    terminate: false,
+                };
+            }
 
-    pub fn should_terminate(&self) -> bool {
-        return self.terminate;
+            ErrorType::PanelTooSmallToSplit { minimum } => {
+                return Self {
+                    debug_description: format!(
+                        "The panel is too small to split, each half must be at least {}x{}.",
+                        minimum.get_cols(),
+                        minimum.get_rows()
+                    ),
+                    description: "The panel is too small to split.".to_string(),
+                    terminate: false,
+                };
+            }
+
+            ErrorType::PanelCannotBeResized => {
+                return Self {
+                    debug_description: "The panel can't be resized any further.".to_string(),
+                    description: "The panel can't be resized any further.".to_string(),
+                    terminate: false,
+                };
+            }
+
+            ErrorType::NoEmptyWorkspace => {
+                return Self {
+                    debug_description: "Every workspace contains a panel.".to_string(),
+                    description: "There is no empty workspace to move the panel to.".to_string(),
+                    terminate: false,
+                };
+            }
+
+            ErrorType::NoPanelInWorkspace(id) => {
+                return Self {
+                    debug_description: format!("Workspace {} has no panels.", id),
+                    description: format!("There are no panels in workspace {}", id),
+                    terminate: false,
+                };
+            }
+
+            ErrorType::NoLayoutToUndo => {
+                return Self {
+                    debug_description: "The layout history of the workspace is empty.".to_string(),
+                    description: "There is no layout change to undo.".to_string(),
+                    terminate: false,
+                };
+            }
+
+            ErrorType::LayoutCannotBeRestored => {
+                return Self {
+                    debug_description: "The previous layout has no space for the open panels."
+                        .to_string(),
+                    description: "The previous layout doesn't fit the open panels.".to_string(),
+                    terminate: false,
+                };
+            }
+
+            ErrorType::NoSshCommand(id) => {
+                return Self {
+                    debug_description: format!("Panel {} hasn't run ssh.", id),
+                    description: format!("There is no ssh connection to reconnect in panel {}", id),
+                    terminate: false,
+                };
+            }
+
+            ErrorType::SshCommandRunning(id) => {
+                return Self {
+                    debug_description: format!("Panel {} is still running ssh.", id),
+                    description: format!("Panel {} is still connected", id),
+                    terminate: false,
+                };
+            }
+
+            ErrorType::ProtocolError { reason } => {
+                return Self {
+                    debug_description: format!("Session protocol error. Reason: {}", reason),
+                    description: "Failed to communicate with the session.".to_string(),
+                    terminate: true,
+                };
+            }
+        };
     }
 
     fn new_ioctl_error(code: i32, outcome: String) -> Self {
@@ -316,7 +582,7 @@ impl MuxideError {
     fn new_pty_spawn_error(description: String) -> Self {
         return Self {
             debug_description: format!("Failed to spawn new PTY. Reason {}", description),
-            description: format!("Failed to spawn new PTY."),
+            description: "Failed to spawn new PTY.".to_string(),
             terminate: true,
         };
     }
@@ -324,7 +590,7 @@ impl MuxideError {
     fn new_poll_creation_error(reason: String) -> Self {
         return Self {
             debug_description: format!("Failed to create the IO poll. Reason: {}", reason),
-            description: format!("Failed to create the IO poll."),
+            description: "Failed to create the IO poll.".to_string(),
             terminate: true,
         };
     }
@@ -332,7 +598,7 @@ impl MuxideError {
     fn new_determine_terminal_size_error(reason: String) -> Self {
         return Self {
             debug_description: format!("Failed to determine terminal size. Reason: {}", reason),
-            description: format!("Failed to determine terminal size."),
+            description: "Failed to determine terminal size.".to_string(),
             terminate: true,
         };
     }
@@ -340,7 +606,7 @@ impl MuxideError {
     fn new_polling_error(reason: String) -> Self {
         return Self {
             debug_description: format!("Failed to poll the IO poll. Reason: {}", reason),
-            description: format!("Failed to poll the IO poll."),
+            description: "Failed to poll the IO poll.".to_string(),
             terminate: true,
         };
     }
@@ -365,7 +631,7 @@ impl MuxideError {
         return Self {
             debug_description: format!("Failed to flush stdout. Reason: {}", reason),
             description: "Failed to flush stdout".to_string(),
-            terminate: true,
+            terminate: false,
         };
     }
 
@@ -435,7 +701,7 @@ impl MuxideError {
                 "Failed to queue or execute display element. Reason: {}",
                 reason
             ),
-            terminate: true,
+            terminate: false,
         };
     }
 
@@ -493,14 +759,149 @@ impl MuxideError {
 
 impl std::fmt::Display for MuxideError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        return write!(f, "{}", self.description);
+        return write!(
+            f,
+            "{}",
+            self.with_context_description(&self.details.description)
+        );
     }
 }
 
 impl std::fmt::Debug for MuxideError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        return write!(f, "{}", self.debug_description);
+        write!(
+            f,
+            "{}",
+            self.with_context_description(&self.details.debug_description)
+        )?;
+
+        if let Some(id) = self.panel_id {
+            write!(f, " (panel: {})", id)?;
+        }
+
+        if let Some(path) = self.path.as_ref() {
+            write!(f, " (path: {})", path)?;
+        }
+
+        if let Some(source) = self.source_description() {
+            write!(f, " (source: {})", source)?;
+        }
+
+        return Ok(());
     }
 }
 
-impl Error for MuxideError {}
+impl Error for MuxideError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        return self
+            .source
+            .as_ref()
+            .map(|source| source.as_ref() as &(dyn Error + 'static));
+    }
+}
+
+/// Errors are compared by their descriptions of their sources, which can't be compared directly.
+impl PartialEq for MuxideError {
+    fn eq(&self, other: &Self) -> bool {
+        return self.kind == other.kind
+            && self.details == other.details
+            && self.panel_id == other.panel_id
+            && self.path == other.path
+            && self.context == other.context
+            && self.source_description() == other.source_description();
+    }
+}
+
+impl Hash for MuxideError {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+        self.details.hash(state);
+        self.panel_id.hash(state);
+        self.path.hash(state);
+        self.context.hash(state);
+        self.source_description().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ErrorType;
+    use std::error::Error;
+    use std::io;
+
+    #[test]
+    fn test_context_and_source_chain() {
+        let error = ErrorType::IOCTLError {
+            code: -1,
+            outcome: "ioctl TIOCSWINSZ".to_string(),
+        }
+        .into_error()
+        .with_source(io::Error::from_raw_os_error(libc::EBADF))
+        .with_context("Failed to resize panel 3");
+
+        assert!(matches!(error.kind(), ErrorType::IOCTLError { .. }));
+        assert_eq!(error.context(), ["Failed to resize panel 3".to_string()]);
+        assert_eq!(
+            error.debug_description(),
+            format!(
+                "PTY Error: Failed to resize panel 3: ioctl call returned error code: -1. ioctl TIOCSWINSZ: {}",
+                io::Error::from_raw_os_error(libc::EBADF)
+            )
+        );
+
+        let source = error.source().unwrap().downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.raw_os_error(), Some(libc::EBADF));
+    }
+
+    #[test]
+    fn test_described_source_is_not_repeated() {
+        let source = io::Error::new(io::ErrorKind::NotFound, "missing");
+        let error = ErrorType::IOError {
+            read: true,
+            target: "config".to_string(),
+            reason: source.to_string(),
+        }
+        .into_error()
+        .with_source(source)
+        .with_context("Loading")
+        .with_context("Starting");
+
+        assert_eq!(
+            error.debug_description(),
+            "PTY Error: Starting: Loading: Failed to read from config. Reason: missing"
+        );
+        assert_eq!(
+            error.to_string(),
+            "Starting: Loading: Failed to read from config."
+        );
+        assert_eq!(error.clone(), error);
+        assert_eq!(error.path(), Some(&"config".to_string()));
+    }
+
+    #[test]
+    fn test_transient_display_errors() {
+        let interrupted =
+            ErrorType::new_display_qe_error(io::Error::from(io::ErrorKind::Interrupted));
+        assert!(interrupted.is_transient());
+        assert!(!interrupted.should_terminate());
+
+        let closed = ErrorType::StdoutFlushError {
+            reason: "closed".to_string(),
+        }
+        .into_error()
+        .with_source(crossterm::ErrorKind::IoError(io::Error::from(
+            io::ErrorKind::BrokenPipe,
+        )));
+        assert!(!closed.is_transient());
+        assert!(!closed.should_terminate());
+
+        let would_block = ErrorType::StdoutFlushError {
+            reason: "busy".to_string(),
+        }
+        .into_error()
+        .with_source(crossterm::ErrorKind::IoError(io::Error::from(
+            io::ErrorKind::WouldBlock,
+        )));
+        assert!(would_block.is_transient());
+    }
+}
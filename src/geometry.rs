@@ -24,16 +24,20 @@ pub struct Point<T: PrimInt + Unsigned + Zero> {
 }
 
 impl Size {
-    pub fn new(rows: u16, cols: u16) -> Self {
+    pub const fn new(rows: u16, cols: u16) -> Self {
         return Self { rows, cols };
     }
 
-    pub fn to_winsize(&self) -> Winsize {
+    /// Returns the window size with pixel dimensions for cells of the specified width and
+    /// height in pixels, the pixel dimensions are 0 if the cell size is unknown.
+    pub fn to_winsize(self, cell_pixels: Option<(u16, u16)>) -> Winsize {
+        let (cell_width, cell_height) = cell_pixels.unwrap_or((0, 0));
+
         return Winsize {
             ws_row: self.rows,
             ws_col: self.cols,
-            ws_xpixel: 0,
-            ws_ypixel: 0,
+            ws_xpixel: self.cols.saturating_mul(cell_width),
+            ws_ypixel: self.rows.saturating_mul(cell_height),
         };
     }
 
@@ -45,6 +49,11 @@ impl Size {
         return self.rows;
     }
 
+    /// Returns true if this size is at least as large as the other size in both directions.
+    pub fn contains(&self, other: Size) -> bool {
+        return self.rows >= other.rows && self.cols >= other.cols;
+    }
+
     pub fn divide_width_by_const(&mut self, constant: u16) {
         self.cols /= constant;
     }
@@ -57,8 +66,12 @@ impl Size {
 impl Sub for Size {
     type Output = Self;
 
+    /// Saturates at zero, so a size can't underflow when the terminal is very small.
     fn sub(self, rhs: Self) -> Self::Output {
-        return Self::new(self.rows - rhs.rows, self.cols - rhs.cols);
+        return Self::new(
+            self.rows.saturating_sub(rhs.rows),
+            self.cols.saturating_sub(rhs.cols),
+        );
     }
 }
 
@@ -79,15 +92,6 @@ impl<T: PrimInt + Unsigned + Zero> Point<T> {
         };
     }
 
-    /// Creates a new point and shifts by (x, y)
-    pub fn new_origin(column: T, row: T, origin: (T, T)) -> Self {
-        return Self {
-            x: column + origin.0,
-            y: row + origin.1,
-            origin,
-        };
-    }
-
     #[allow(dead_code)]
     pub fn get_origin(&self) -> (T, T) {
         return self.origin;
@@ -125,3 +129,28 @@ impl<T: PrimInt + Unsigned + Zero> Sub for Point<T> {
         return Self::new(self.x - rhs.x, self.y - rhs.y);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Size;
+
+    #[test]
+    fn test_size_subtraction() {
+        let size = Size::new(2, 10);
+
+        assert_eq!(size - Size::new(3, 4), Size::new(0, 6));
+        assert!(size.contains(Size::new(2, 9)));
+        assert!(!size.contains(Size::new(3, 1)));
+    }
+
+    #[test]
+    fn test_winsize_pixels() {
+        let size = Size::new(10, 40);
+
+        let winsize = size.to_winsize(Some((8, 16)));
+        assert_eq!((winsize.ws_xpixel, winsize.ws_ypixel), (320, 160));
+
+        let winsize = size.to_winsize(None);
+        assert_eq!((winsize.ws_xpixel, winsize.ws_ypixel), (0, 0));
+    }
+}
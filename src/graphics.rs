@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+/// An image a panel's program drew with a graphics protocol, it is passed through to the outer
+/// terminal at the position of the cursor when it was drawn.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PanelGraphic {
+    pub col: u16,
+    pub row: u16,
+    pub sequence: Arc<[u8]>,
+}
+
+/// A part of a panel's output, either output for the panel's screen or a complete graphics
+/// sequence.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Segment {
+    Output(Vec<u8>),
+    Graphic(Vec<u8>),
+}
+
+/// Whether the start of some output begins a graphics sequence.
+enum SequenceStart {
+    /// The length of the sequence's introducer and whether it is an OSC, which can also be
+    /// terminated by BEL.
+    Graphic(usize, bool),
+    /// More output is needed to tell.
+    Incomplete,
+    NotGraphic,
+}
+
+/// Separates the sixel (DCS ... q), kitty graphics (APC G) and iTerm2 inline image (OSC 1337;File=)
+/// sequences from a panel's output. A sequence can span several reads, it is returned once it is
+/// terminated.
+#[derive(Default)]
+pub struct GraphicsExtractor {
+    /// The graphics sequence being read.
+    sequence: Option<Vec<u8>>,
+    /// Set if the sequence being read can be terminated by BEL.
+    bel_terminated: bool,
+    /// Set if the sequence being read is too large and is being discarded.
+    oversized: bool,
+    /// The end of the last output that could start a sequence or terminator, it is joined with
+    /// the next output.
+    held: Vec<u8>,
+}
+
+impl GraphicsExtractor {
+    /// Sequences larger than this are discarded rather than buffered.
+    const SEQUENCE_LIMIT: usize = 16 * 1024 * 1024;
+    const KITTY_INTRODUCER: &'static [u8] = b"\x1b_G";
+    const ITERM_INTRODUCER: &'static [u8] = b"\x1b]1337;File=";
+
+    /// Splits output into the output for the panel's screen and the graphics sequences it
+    /// contains, in the order they were output.
+    pub fn split(&mut self, bytes: &[u8]) -> Vec<Segment> {
+        let joined;
+        let bytes = if self.held.is_empty() {
+            bytes
+        } else {
+            joined = [std::mem::take(&mut self.held).as_slice(), bytes].concat();
+            joined.as_slice()
+        };
+
+        let mut segments = Vec::new();
+        let mut output_start = 0;
+        let mut i = 0;
+
+        while i < bytes.len() {
+            if self.sequence.is_some() {
+                i = self.read_sequence(bytes, i, &mut segments);
+                output_start = i;
+                continue;
+            }
+
+            let escape = match bytes[i..].iter().position(|b| *b == 0x1b) {
+                Some(position) => i + position,
+                None => break,
+            };
+
+            match Self::sequence_start(&bytes[escape..]) {
+                SequenceStart::Graphic(len, bel_terminated) => {
+                    if escape > output_start {
+                        segments.push(Segment::Output(bytes[output_start..escape].to_vec()));
+                    }
+
+                    self.sequence = Some(bytes[escape..escape + len].to_vec());
+                    self.bel_terminated = bel_terminated;
+                    self.oversized = false;
+                    i = escape + len;
+                    output_start = i;
+                }
+                SequenceStart::Incomplete => {
+                    if escape > output_start {
+                        segments.push(Segment::Output(bytes[output_start..escape].to_vec()));
+                    }
+
+                    self.held = bytes[escape..].to_vec();
+                    return segments;
+                }
+                SequenceStart::NotGraphic => i = escape + 1,
+            }
+        }
+
+        if output_start < bytes.len() && self.sequence.is_none() {
+            segments.push(Segment::Output(bytes[output_start..].to_vec()));
+        }
+
+        return segments;
+    }
+
+    /// Reads the sequence being read from the output at start, returning where the sequence
+    /// ends in the output.
+    fn read_sequence(&mut self, bytes: &[u8], start: usize, segments: &mut Vec<Segment>) -> usize {
+        let rest = &bytes[start..];
+        let end = rest.iter().enumerate().find_map(|(i, b)| match b {
+            0x07 if self.bel_terminated => Some(i + 1),
+            0x1b if rest.get(i + 1) == Some(&b'\\') => Some(i + 2),
+            _ => None,
+        });
+
+        let (read, terminated) = match end {
+            Some(end) => (&rest[..end], true),
+            // An escape at the end could start the terminator.
+            None if rest.last() == Some(&0x1b) => {
+                self.held = vec![0x1b];
+                (&rest[..rest.len() - 1], false)
+            }
+            None => (rest, false),
+        };
+
+        let sequence = self.sequence.as_mut().unwrap();
+
+        if !self.oversized {
+            sequence.extend_from_slice(read);
+
+            if sequence.len() > Self::SEQUENCE_LIMIT {
+                self.oversized = true;
+                *sequence = Vec::new();
+            }
+        }
+
+        if !terminated {
+            return bytes.len();
+        }
+
+        let sequence = self.sequence.take().unwrap();
+
+        if !self.oversized {
+            segments.push(Segment::Graphic(sequence));
+        }
+
+        return start + read.len();
+    }
+
+    fn sequence_start(bytes: &[u8]) -> SequenceStart {
+        if bytes.len() < 2 {
+            return SequenceStart::Incomplete;
+        }
+
+        match bytes[1] {
+            // Sixel images are a DCS with numeric parameters and a final byte of 'q'.
+            b'P' => {
+                for (i, b) in bytes.iter().enumerate().skip(2) {
+                    match b {
+                        b'0'..=b'9' | b';' => continue,
+                        b'q' => return SequenceStart::Graphic(i + 1, false),
+                        _ => return SequenceStart::NotGraphic,
+                    }
+                }
+
+                return SequenceStart::Incomplete;
+            }
+            b'_' => return Self::introducer(bytes, Self::KITTY_INTRODUCER, false),
+            b']' => return Self::introducer(bytes, Self::ITERM_INTRODUCER, true),
+            _ => return SequenceStart::NotGraphic,
+        }
+    }
+
+    fn introducer(bytes: &[u8], introducer: &[u8], bel_terminated: bool) -> SequenceStart {
+        if bytes.starts_with(introducer) {
+            return SequenceStart::Graphic(introducer.len(), bel_terminated);
+        } else if introducer.starts_with(bytes) {
+            return SequenceStart::Incomplete;
+        } else {
+            return SequenceStart::NotGraphic;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GraphicsExtractor, Segment};
+
+    fn output(bytes: &[u8]) -> Segment {
+        return Segment::Output(bytes.to_vec());
+    }
+
+    fn graphic(bytes: &[u8]) -> Segment {
+        return Segment::Graphic(bytes.to_vec());
+    }
+
+    #[test]
+    fn test_graphics_are_separated() {
+        let mut extractor = GraphicsExtractor::default();
+
+        assert_eq!(
+            extractor.split(b"a\x1bP0;1;0q#0~~\x1b\\b"),
+            vec![
+                output(b"a"),
+                graphic(b"\x1bP0;1;0q#0~~\x1b\\"),
+                output(b"b")
+            ]
+        );
+        assert_eq!(
+            extractor.split(b"\x1b]1337;File=inline=1:AAAA\x07\x1b]0;title\x07"),
+            vec![
+                graphic(b"\x1b]1337;File=inline=1:AAAA\x07"),
+                output(b"\x1b]0;title\x07")
+            ]
+        );
+        assert_eq!(
+            extractor.split(b"\x1bP$q\"p\x1b\\\x1b[2J"),
+            vec![output(b"\x1bP$q\"p\x1b\\\x1b[2J")]
+        );
+    }
+
+    #[test]
+    fn test_graphics_split_across_output() {
+        let mut extractor = GraphicsExtractor::default();
+
+        assert_eq!(extractor.split(b"text\x1b_"), vec![output(b"text")]);
+        assert_eq!(extractor.split(b"Ga=T;AAAA\x1b"), vec![]);
+        assert_eq!(
+            extractor.split(b"\\more"),
+            vec![graphic(b"\x1b_Ga=T;AAAA\x1b\\"), output(b"more")]
+        );
+        assert_eq!(extractor.split(b"\x1b_X\x1b"), vec![output(b"\x1b_X")]);
+        assert_eq!(extractor.split(b"[m"), vec![output(b"\x1b[m")]);
+    }
+}
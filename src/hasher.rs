@@ -14,12 +14,10 @@ pub fn hash_password(password: &str, settings: &PasswordSettings) -> Option<Stri
     };
 }
 
-pub fn check_password(
-    password: &str,
-    settings: &PasswordSettings,
-    comparison: &str,
-) -> Option<bool> {
-    return match settings.algorithm() {
+/// Checks a password against a stored hash. The algorithm is determined from the stored hash
+/// rather than the config, so a hash created with different settings can still be verified.
+pub fn check_password(password: &str, comparison: &str) -> Option<bool> {
+    return match detect_algorithm(comparison)? {
         #[cfg(feature = "argon2")]
         HashAlgorithm::Argon2 => compare_argon2(password, comparison),
         #[cfg(feature = "scrypt")]
@@ -32,6 +30,58 @@ pub fn check_password(
     };
 }
 
+/// Determines the algorithm a stored hash was created with from its PHC string identifier.
+/// Hashes that are not PHC strings are plain text passwords. Returns None if the algorithm is
+/// not supported by this build.
+pub fn detect_algorithm(hash: &str) -> Option<HashAlgorithm> {
+    let identifier = match hash.strip_prefix('$') {
+        Some(rest) => rest.split('$').next().unwrap_or(""),
+        None => return Some(HashAlgorithm::None),
+    };
+
+    return match identifier {
+        #[cfg(feature = "argon2")]
+        "argon2i" | "argon2d" | "argon2id" => Some(HashAlgorithm::Argon2),
+        #[cfg(feature = "scrypt")]
+        "scrypt" => Some(HashAlgorithm::Scrypt),
+        #[cfg(feature = "pbkdf2")]
+        "pbkdf2-sha256" => Some(HashAlgorithm::PBKDF2SHA256),
+        #[cfg(feature = "pbkdf2")]
+        "pbkdf2-sha512" => Some(HashAlgorithm::PBKDF2SHA512),
+        _ => None,
+    };
+}
+
+/// Returns true if a stored hash was not created with the algorithm and parameters in the
+/// password settings, i.e. the config was changed after the password was set.
+pub fn needs_rehash(hash: &str, settings: &PasswordSettings) -> bool {
+    if detect_algorithm(hash) != Some(settings.algorithm()) {
+        return true;
+    }
+
+    #[cfg(feature = "pbkdf2")]
+    {
+        if let HashAlgorithm::PBKDF2SHA256 | HashAlgorithm::PBKDF2SHA512 = settings.algorithm() {
+            return pbkdf2_iterations(hash) != Some(settings.pbkdf2_iterations());
+        }
+    }
+
+    return false;
+}
+
+/// Reads the number of iterations from the parameters of a PBKDF2 PHC string, i.e.
+/// "$pbkdf2-sha256$i=10000,l=32$salt$hash".
+#[cfg(feature = "pbkdf2")]
+fn pbkdf2_iterations(hash: &str) -> Option<usize> {
+    return hash
+        .split('$')
+        .nth(2)?
+        .split(',')
+        .find_map(|param| param.strip_prefix("i="))?
+        .parse::<usize>()
+        .ok();
+}
+
 #[cfg(feature = "argon2")]
 fn hash_argon2(password: &str) -> Option<String> {
     use argon2::password_hash::{PasswordHasher, SaltString};
@@ -148,6 +198,13 @@ fn compare_pbkdf2(password: &str, comp: &str) -> Option<bool> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_detect_plain_text() {
+        assert_eq!(detect_algorithm("password"), Some(HashAlgorithm::None));
+        assert_eq!(detect_algorithm("$unknown$abc"), None);
+        assert!(check_password("password", "password").unwrap());
+    }
+
     #[cfg(feature = "argon2")]
     mod argon2 {
         use super::*;
@@ -168,6 +225,13 @@ mod tests {
             let comp = hash_argon2("password").unwrap();
             assert!(!compare_argon2("password2", &comp).unwrap());
         }
+
+        #[test]
+        fn test_argon2_detect() {
+            let comp = hash_argon2("password").unwrap();
+            assert_eq!(detect_algorithm(&comp), Some(HashAlgorithm::Argon2));
+            assert!(check_password("password", &comp).unwrap());
+        }
     }
 
     #[cfg(feature = "scrypt")]
@@ -229,5 +293,12 @@ mod tests {
             let comp = hash_pbkdf2_sha512("password", 10_000).unwrap();
             assert!(!compare_pbkdf2("password2", &comp).unwrap());
         }
+
+        #[test]
+        fn test_pbkdf2_iterations() {
+            let comp = hash_pbkdf2_sha256("password", 10_000).unwrap();
+            assert_eq!(detect_algorithm(&comp), Some(HashAlgorithm::PBKDF2SHA256));
+            assert_eq!(pbkdf2_iterations(&comp), Some(10_000));
+        }
     }
 }
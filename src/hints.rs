@@ -0,0 +1,128 @@
+use crate::geometry::Point;
+use lazy_static::lazy_static;
+use regex::Regex;
+use unicode_width::UnicodeWidthStr;
+
+lazy_static! {
+    static ref URL_REGEX: Regex = Regex::new(r#"(?:https?|ftp|file)://[^\s<>"'`]+"#).unwrap();
+    static ref PATH_REGEX: Regex =
+        Regex::new(r#"(?:^|[\s"'(=:])((?:~|\.{1,2}|[\w.\-]+)?/[\w.\-/~+@%]+)"#).unwrap();
+}
+
+/// The characters hint labels are made from, the keys of the home row come first.
+const LABEL_CHARACTERS: &'static str = "asdfghjklqwertyuiopzxcvbnm";
+/// Punctuation that usually ends a sentence rather than a URL or path.
+const TRAILING_PUNCTUATION: &'static [char] = &['.', ',', ';', ':', '!', '?', ')', ']', '}'];
+
+/// A URL or file path found in a panel, with the label that is typed to select it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Hint {
+    pub label: String,
+    pub text: String,
+    /// The position of the start of the text in the panel, (col, row).
+    pub position: Point<u16>,
+}
+
+/// Returns the URLs and paths in the rows of a panel's text, labelled from the top of the panel.
+/// A path that is part of a URL isn't included separately.
+pub fn find_hints(rows: &[String]) -> Vec<Hint> {
+    let mut matches = Vec::new();
+
+    for (row_number, row) in rows.iter().enumerate() {
+        let mut row_matches: Vec<(usize, &str)> = URL_REGEX
+            .find_iter(row)
+            .map(|m| (m.start(), trim_match(m.as_str())))
+            .collect();
+
+        for captures in PATH_REGEX.captures_iter(row) {
+            let m = captures.get(1).unwrap();
+            let inside_url = row_matches
+                .iter()
+                .any(|(start, text)| m.start() < start + text.len() && *start < m.end());
+            let text = trim_match(m.as_str());
+
+            // Fractions and dates, i.e. 1/2, aren't paths.
+            if !inside_url && text.chars().any(|c| c.is_alphabetic()) {
+                row_matches.push((m.start(), text));
+            }
+        }
+
+        row_matches.sort_by_key(|(start, _)| *start);
+        matches.extend(row_matches.into_iter().map(|(start, text)| {
+            (
+                Point::new(row[..start].width() as u16, row_number as u16),
+                text.to_string(),
+            )
+        }));
+    }
+
+    let labels = labels(matches.len());
+    return matches
+        .into_iter()
+        .zip(labels)
+        .map(|((position, text), label)| Hint {
+            label,
+            text,
+            position,
+        })
+        .collect();
+}
+
+/// Returns unique labels for a number of hints, a single character if there are few enough
+/// hints, otherwise two characters so no label is the start of another.
+fn labels(count: usize) -> Vec<String> {
+    let characters: Vec<char> = LABEL_CHARACTERS.chars().collect();
+
+    if count <= characters.len() {
+        return characters[..count].iter().map(|c| c.to_string()).collect();
+    }
+
+    return characters
+        .iter()
+        .flat_map(|a| characters.iter().map(move |b| format!("{}{}", a, b)))
+        .take(count)
+        .collect();
+}
+
+fn trim_match(text: &str) -> &str {
+    return text.trim_end_matches(TRAILING_PUNCTUATION);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_hints, labels};
+    use crate::geometry::Point;
+
+    #[test]
+    fn test_find_hints() {
+        let rows = vec![
+            "see https://example.com/a/b.html.".to_string(),
+            "edited src/main.rs and ~/notes.txt".to_string(),
+            "nothing here, 1/2".to_string(),
+        ];
+        let hints = find_hints(&rows);
+        let found: Vec<(&str, &str, Point<u16>)> = hints
+            .iter()
+            .map(|h| (h.label.as_str(), h.text.as_str(), h.position))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                ("a", "https://example.com/a/b.html", Point::new(4, 0)),
+                ("s", "src/main.rs", Point::new(7, 1)),
+                ("d", "~/notes.txt", Point::new(23, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(labels(2), vec!["a".to_string(), "s".to_string()]);
+
+        let many = labels(30);
+        assert_eq!(many.len(), 30);
+        assert_eq!(many[0], "aa");
+        assert!(many.iter().all(|l| l.len() == 2));
+    }
+}
@@ -0,0 +1,682 @@
+use crate::config::{key_bytes, ModifiedKey};
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+use termion::event::Key;
+use unicode_width::UnicodeWidthChar;
+
+/// An input event received from the terminal, each keeps the bytes it was parsed from so it can be
+/// forwarded to a panel unchanged.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum InputEvent {
+    Key(Key, Vec<u8>),
+    /// Text pasted whilst bracketed paste is enabled, without the surrounding sequences.
+    Paste(Vec<u8>),
+    /// A character made of several code points, i.e. a letter followed by a combining accent from
+    /// an input method or an emoji sequence. It is handled as a single key.
+    Grapheme(String),
+    FocusGained,
+    FocusLost,
+    /// A key with modifiers the legacy encoding can't express, with the bytes sent to a panel in
+    /// its place.
+    Modified(ModifiedKey, Vec<u8>),
+    /// The terminal's reply to the kitty keyboard protocol query, it supports the protocol.
+    KeyboardProtocolSupported,
+    /// The terminal's reply to the device attributes query.
+    DeviceAttributes,
+    /// A sequence that isn't understood, i.e. a mouse report.
+    Unknown(Vec<u8>),
+}
+
+/// The result of trying to parse an event from the start of the buffer.
+enum Parsed {
+    Event(InputEvent, usize),
+    PasteStart(usize),
+    Incomplete,
+}
+
+/// Splits the bytes read from the terminal into events. Sequences can be split across reads, so
+/// incomplete sequences are kept until more input arrives. An escape character on its own is
+/// ambiguous, it is treated as the escape key if nothing follows it within a short timeout.
+pub struct InputParser {
+    buffer: Vec<u8>,
+    /// The text of a bracketed paste that hasn't ended yet.
+    paste: Option<Vec<u8>>,
+    /// The time at which incomplete input in the buffer is parsed regardless.
+    deadline: Option<Instant>,
+}
+
+impl InputParser {
+    /// The amount of time to wait for the rest of an escape sequence.
+    const ESCAPE_TIMEOUT: Duration = Duration::from_millis(25);
+    /// The amount of time to wait for the rest of a UTF-8 character, it isn't ambiguous so it can
+    /// wait longer than an escape sequence.
+    const CHARACTER_TIMEOUT: Duration = Duration::from_millis(500);
+    const PASTE_END: &'static [u8] = b"\x1b[201~";
+
+    pub fn new() -> Self {
+        return Self {
+            buffer: Vec::new(),
+            paste: None,
+            deadline: None,
+        };
+    }
+
+    /// Returns the time at which the incomplete input should be parsed with `flush`.
+    pub fn deadline(&self) -> Option<Instant> {
+        return self.deadline;
+    }
+
+    /// Parse the events that are complete once the bytes are added to the buffered input.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<InputEvent> {
+        self.buffer.extend_from_slice(bytes);
+
+        return self.parse(false);
+    }
+
+    /// Parse all of the buffered input, treating incomplete sequences as complete. A bracketed
+    /// paste that hasn't ended is not affected.
+    pub fn flush(&mut self) -> Vec<InputEvent> {
+        return self.parse(true);
+    }
+
+    fn parse(&mut self, force: bool) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        let mut start = 0;
+
+        loop {
+            if let Some(paste) = self.paste.as_mut() {
+                let remaining = &self.buffer[start..];
+
+                match remaining
+                    .windows(Self::PASTE_END.len())
+                    .position(|w| w == Self::PASTE_END)
+                {
+                    Some(end) => {
+                        paste.extend_from_slice(&remaining[..end]);
+                        events.push(InputEvent::Paste(self.paste.take().unwrap()));
+                        start += end + Self::PASTE_END.len();
+                    }
+                    None => {
+                        // Keep anything that could be the start of the end sequence.
+                        let keep = (1..Self::PASTE_END.len())
+                            .rev()
+                            .find(|len| remaining.ends_with(&Self::PASTE_END[..*len]))
+                            .unwrap_or(0);
+
+                        paste.extend_from_slice(&remaining[..remaining.len() - keep]);
+                        start = self.buffer.len() - keep;
+                        break;
+                    }
+                }
+            }
+
+            if start == self.buffer.len() {
+                break;
+            }
+
+            match Self::parse_event(&self.buffer[start..], force) {
+                Parsed::Event(event, len) => {
+                    events.push(event);
+                    start += len;
+                }
+                Parsed::PasteStart(len) => {
+                    self.paste = Some(Vec::new());
+                    start += len;
+                }
+                Parsed::Incomplete => break,
+            }
+        }
+
+        self.buffer.drain(..start);
+
+        self.deadline = if self.buffer.is_empty() || self.paste.is_some() {
+            None
+        } else if self.buffer[0] == 0x1b {
+            Some(Instant::now() + Self::ESCAPE_TIMEOUT)
+        } else {
+            Some(Instant::now() + Self::CHARACTER_TIMEOUT)
+        };
+
+        return events;
+    }
+
+    /// Parse the event at the start of the bytes. If the bytes are incomplete and forced, the
+    /// available bytes are used.
+    fn parse_event(bytes: &[u8], force: bool) -> Parsed {
+        let key =
+            |key: Key, len: usize| Parsed::Event(InputEvent::Key(key, bytes[..len].to_vec()), len);
+
+        return match bytes[0] {
+            b'\x1b' => match bytes.get(1) {
+                None if force => key(Key::Esc, 1),
+                None => Parsed::Incomplete,
+                Some(b'[') => Self::parse_csi(bytes, force),
+                Some(b'O') => match bytes.get(2) {
+                    Some(val @ b'P'..=b'S') => key(Key::F(1 + val - b'P'), 3),
+                    Some(b'A') => key(Key::Up, 3),
+                    Some(b'B') => key(Key::Down, 3),
+                    Some(b'C') => key(Key::Right, 3),
+                    Some(b'D') => key(Key::Left, 3),
+                    Some(b'H') => key(Key::Home, 3),
+                    Some(b'F') => key(Key::End, 3),
+                    Some(_) => Parsed::Event(InputEvent::Unknown(bytes[..3].to_vec()), 3),
+                    None if force => key(Key::Alt('O'), 2),
+                    None => Parsed::Incomplete,
+                },
+                // Pressing escape twice, the first is the escape key.
+                Some(b'\x1b') => key(Key::Esc, 1),
+                Some(_) => match Self::parse_char(&bytes[1..], force) {
+                    Some(Ok((ch, len))) => key(Key::Alt(ch), len + 1),
+                    Some(Err(len)) => {
+                        Parsed::Event(InputEvent::Unknown(bytes[..len + 1].to_vec()), len + 1)
+                    }
+                    None => Parsed::Incomplete,
+                },
+            },
+            b'\n' | b'\r' => key(Key::Char('\n'), 1),
+            b'\t' => key(Key::Char('\t'), 1),
+            b'\x7f' => key(Key::Backspace, 1),
+            c @ b'\x01'..=b'\x1a' => key(Key::Ctrl((c - 0x1 + b'a') as char), 1),
+            c @ b'\x1c'..=b'\x1f' => key(Key::Ctrl((c - 0x1c + b'4') as char), 1),
+            b'\0' => key(Key::Null, 1),
+            _ => match Self::parse_grapheme(bytes, force) {
+                Some(Ok((text, len))) => {
+                    let mut chars = text.chars();
+
+                    match (chars.next(), chars.next()) {
+                        (Some(ch), None) => key(Key::Char(ch), len),
+                        _ => Parsed::Event(InputEvent::Grapheme(text), len),
+                    }
+                }
+                Some(Err(len)) => Parsed::Event(InputEvent::Unknown(bytes[..len].to_vec()), len),
+                None => Parsed::Incomplete,
+            },
+        };
+    }
+
+    /// Returns the key reported by a CSI sequence without parameters, i.e. "ESC [ A".
+    fn final_byte_key(final_byte: u8) -> Option<Key> {
+        return match final_byte {
+            b'A' => Some(Key::Up),
+            b'B' => Some(Key::Down),
+            b'C' => Some(Key::Right),
+            b'D' => Some(Key::Left),
+            b'H' => Some(Key::Home),
+            b'F' => Some(Key::End),
+            val @ b'P'..=b'S' => Some(Key::F(1 + val - b'P')),
+            _ => None,
+        };
+    }
+
+    /// Returns the key reported by a CSI sequence ending in '~', i.e. "ESC [ 5 ~".
+    fn tilde_key(param: u8) -> Option<Key> {
+        return match param {
+            1 | 7 => Some(Key::Home),
+            2 => Some(Key::Insert),
+            3 => Some(Key::Delete),
+            4 | 8 => Some(Key::End),
+            5 => Some(Key::PageUp),
+            6 => Some(Key::PageDown),
+            v @ 11..=15 => Some(Key::F(v - 10)),
+            v @ 17..=21 => Some(Key::F(v - 11)),
+            v @ 23..=24 => Some(Key::F(v - 12)),
+            _ => None,
+        };
+    }
+
+    /// Returns the key with a unicode key code, as reported by the kitty keyboard protocol and
+    /// modifyOtherKeys.
+    fn code_key(code: u32) -> Option<Key> {
+        return match code {
+            13 => Some(Key::Char('\n')),
+            9 => Some(Key::Char('\t')),
+            8 | 127 => Some(Key::Backspace),
+            27 => Some(Key::Esc),
+            // The private use area is used for keys without a character, i.e. keypad keys.
+            0xe000..=0xf8ff => None,
+            code => std::char::from_u32(code)
+                .filter(|ch| !ch.is_control())
+                .map(Key::Char),
+        };
+    }
+
+    /// Parse a key reported with its modifiers, i.e. "ESC [ 97 ; 6 u" from the kitty keyboard
+    /// protocol, "ESC [ 27 ; 6 ; 97 ~" from modifyOtherKeys or "ESC [ 1 ; 5 A" for a named key.
+    /// Keys the legacy encoding can express are reported as regular keys.
+    fn parse_modified_key(params: &[u8], final_byte: u8, sequence: &[u8]) -> Option<InputEvent> {
+        let params = std::str::from_utf8(params).ok()?;
+        let fields: Vec<&str> = params.split(';').collect();
+        // Only the first sub-parameter of each field is used, i.e. the unshifted key code.
+        let field = |i: usize| fields.get(i).and_then(|f| f.split(':').next());
+        let number = |i: usize| field(i).and_then(|f| f.parse::<u32>().ok());
+
+        // The key codes are sent to panels using the legacy encoding, named keys with modifiers
+        // are already sent that way.
+        let (key, modifiers, forwarded) = match final_byte {
+            b'u' if !params.is_empty() => (
+                Self::code_key(number(0)?)?,
+                field(1).map_or(Some(1), |_| number(1))?,
+                None,
+            ),
+            b'~' if fields.len() == 3 && number(0) == Some(27) => {
+                (Self::code_key(number(2)?)?, number(1)?, None)
+            }
+            b'~' if fields.len() == 2 => (
+                Self::tilde_key(u8::try_from(number(0)?).ok()?)?,
+                number(1)?,
+                Some(sequence),
+            ),
+            _ if fields.len() == 2 && number(0) == Some(1) => (
+                Self::final_byte_key(final_byte)?,
+                number(1)?,
+                Some(sequence),
+            ),
+            _ => return None,
+        };
+
+        // The modifiers are sent as one more than a bit field of shift, alt and ctrl, any others
+        // are ignored.
+        let modifiers = modifiers.saturating_sub(1);
+        let key = ModifiedKey::new(
+            key,
+            modifiers & 4 != 0,
+            modifiers & 2 != 0,
+            modifiers & 1 != 0,
+        );
+
+        return Some(match key.legacy_key() {
+            Some(legacy) => InputEvent::Key(
+                legacy,
+                forwarded.map_or_else(|| key_bytes(legacy).unwrap_or_default(), |s| s.to_vec()),
+            ),
+            None => InputEvent::Modified(
+                key,
+                forwarded.map_or_else(|| key.legacy_bytes(), |s| s.to_vec()),
+            ),
+        });
+    }
+
+    /// Parse the character at the start of the bytes along with the code points that are part of
+    /// it, so they aren't split into separate keys. Returns None if the character is incomplete
+    /// and the length of the invalid bytes if it isn't valid.
+    fn parse_grapheme(bytes: &[u8], force: bool) -> Option<Result<(String, usize), usize>> {
+        let (first, mut len) = match Self::parse_char(bytes, force)? {
+            Ok(parsed) => parsed,
+            Err(len) => return Some(Err(len)),
+        };
+        let mut text = first.to_string();
+        let mut previous = first;
+
+        while len < bytes.len() {
+            let (ch, ch_len) = match Self::parse_char(&bytes[len..], false) {
+                Some(Ok(parsed)) => parsed,
+                // The next character may be part of this one, so wait for the rest of it.
+                None if !force => return None,
+                _ => break,
+            };
+
+            if !Self::extends_grapheme(previous, ch, text.chars().count()) {
+                break;
+            }
+
+            text.push(ch);
+            len += ch_len;
+            previous = ch;
+        }
+
+        return Some(Ok((text, len)));
+    }
+
+    /// Returns true if the character is part of the grapheme that ends with the previous
+    /// character, `count` is the number of characters in the grapheme.
+    fn extends_grapheme(previous: char, ch: char, count: usize) -> bool {
+        const ZERO_WIDTH_JOINER: char = '\u{200d}';
+        let regional_indicator = |c: char| ('\u{1f1e6}'..='\u{1f1ff}').contains(&c);
+
+        return previous == ZERO_WIDTH_JOINER
+            || ch == ZERO_WIDTH_JOINER
+            // Emoji skin tone modifiers.
+            || ('\u{1f3fb}'..='\u{1f3ff}').contains(&ch)
+            // Flags are a pair of regional indicators.
+            || (count == 1 && regional_indicator(previous) && regional_indicator(ch))
+            // Combining marks and variation selectors.
+            || (!ch.is_control() && ch.width() == Some(0));
+    }
+
+    /// Parse a control sequence, the bytes start with "ESC [".
+    fn parse_csi(bytes: &[u8], force: bool) -> Parsed {
+        // The Linux console reports F1-F5 as "ESC [ [ A-E".
+        if bytes.get(2) == Some(&b'[') {
+            return match bytes.get(3) {
+                Some(val @ b'A'..=b'E') => Parsed::Event(
+                    InputEvent::Key(Key::F(1 + val - b'A'), bytes[..4].to_vec()),
+                    4,
+                ),
+                Some(_) => Parsed::Event(InputEvent::Unknown(bytes[..4].to_vec()), 4),
+                None if force => Parsed::Event(InputEvent::Unknown(bytes.to_vec()), bytes.len()),
+                None => Parsed::Incomplete,
+            };
+        }
+
+        // The parameters and intermediate bytes are followed by a final byte.
+        let final_index = match bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b)) {
+            Some(i) => i + 2,
+            None if force => {
+                return Parsed::Event(InputEvent::Unknown(bytes.to_vec()), bytes.len());
+            }
+            None => return Parsed::Incomplete,
+        };
+
+        let mut len = final_index + 1;
+        let params = &bytes[2..final_index];
+
+        // The replies to the queries sent when requesting a keyboard protocol.
+        if params.starts_with(b"?") {
+            match bytes[final_index] {
+                b'u' => return Parsed::Event(InputEvent::KeyboardProtocolSupported, len),
+                b'c' => return Parsed::Event(InputEvent::DeviceAttributes, len),
+                _ => (),
+            }
+        }
+
+        if let Some(event) = Self::parse_modified_key(params, bytes[final_index], &bytes[..len]) {
+            return Parsed::Event(event, len);
+        }
+
+        let key = match (params, bytes[final_index]) {
+            (b"", b'Z') => Some(Key::BackTab),
+            (b"", b'I') => {
+                return Parsed::Event(InputEvent::FocusGained, len);
+            }
+            (b"", b'O') => {
+                return Parsed::Event(InputEvent::FocusLost, len);
+            }
+            (b"", b'M') => {
+                // X10 mouse reports are followed by 3 bytes for the button and position.
+                if bytes.len() < len + 3 && !force {
+                    return Parsed::Incomplete;
+                }
+
+                len = bytes.len().min(len + 3);
+                None
+            }
+            (b"200", b'~') => return Parsed::PasteStart(len),
+            (params, b'~') => std::str::from_utf8(params)
+                .ok()
+                .and_then(|p| p.parse::<u8>().ok())
+                .and_then(Self::tilde_key),
+            (b"", final_byte) => Self::final_byte_key(final_byte),
+            _ => None,
+        };
+
+        return match key {
+            Some(key) => Parsed::Event(InputEvent::Key(key, bytes[..len].to_vec()), len),
+            None => Parsed::Event(InputEvent::Unknown(bytes[..len].to_vec()), len),
+        };
+    }
+
+    /// Parse the UTF-8 character at the start of the bytes. Returns None if the character is
+    /// incomplete and the length of the invalid bytes if it isn't valid.
+    fn parse_char(bytes: &[u8], force: bool) -> Option<Result<(char, usize), usize>> {
+        let len = match bytes[0] {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => return Some(Err(1)),
+        };
+
+        // A character that ends early is invalid, the bytes that follow it are parsed separately.
+        if let Some(i) = bytes[1..len.min(bytes.len())]
+            .iter()
+            .position(|b| b & 0xc0 != 0x80)
+        {
+            return Some(Err(i + 1));
+        }
+
+        if bytes.len() < len {
+            return if force { Some(Err(bytes.len())) } else { None };
+        }
+
+        return Some(
+            std::str::from_utf8(&bytes[..len])
+                .ok()
+                .and_then(|s| s.chars().next())
+                .map(|ch| (ch, len))
+                .ok_or(len),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputEvent, InputParser};
+    use crate::config::ModifiedKey;
+    use termion::event::Key;
+
+    fn keys(events: Vec<InputEvent>) -> Vec<Key> {
+        return events
+            .into_iter()
+            .map(|e| match e {
+                InputEvent::Key(k, _) => k,
+                e => panic!("Expected a key, found: {:?}", e),
+            })
+            .collect();
+    }
+
+    #[test]
+    fn test_multiple_keys() {
+        let mut parser = InputParser::new();
+
+        assert_eq!(
+            keys(parser.feed("a\x01\r\x7fé\x1b[A\x1bOP\x1b[5~".as_bytes())),
+            vec![
+                Key::Char('a'),
+                Key::Ctrl('a'),
+                Key::Char('\n'),
+                Key::Backspace,
+                Key::Char('é'),
+                Key::Up,
+                Key::F(1),
+                Key::PageUp
+            ]
+        );
+        assert!(parser.deadline().is_none());
+    }
+
+    #[test]
+    fn test_raw_bytes_are_kept() {
+        let mut parser = InputParser::new();
+
+        assert_eq!(
+            parser.feed(b"\x1b[15~x"),
+            vec![
+                InputEvent::Key(Key::F(5), b"\x1b[15~".to_vec()),
+                InputEvent::Key(Key::Char('x'), b"x".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lone_escape() {
+        let mut parser = InputParser::new();
+
+        assert!(parser.feed(b"\x1b").is_empty());
+        assert!(parser.deadline().is_some());
+        assert_eq!(keys(parser.flush()), vec![Key::Esc]);
+        assert!(parser.deadline().is_none());
+    }
+
+    #[test]
+    fn test_alt_and_double_escape() {
+        let mut parser = InputParser::new();
+
+        assert_eq!(
+            keys(parser.feed(b"\x1bx\x1b\x1b[B")),
+            vec![Key::Alt('x'), Key::Esc, Key::Down]
+        );
+    }
+
+    #[test]
+    fn test_sequence_split_across_reads() {
+        let mut parser = InputParser::new();
+
+        assert!(parser.feed(b"\x1b[").is_empty());
+        assert!(parser.feed(b"1").is_empty());
+        assert_eq!(keys(parser.feed(b"~")), vec![Key::Home]);
+
+        assert!(parser.feed(&"é".as_bytes()[..1]).is_empty());
+        assert_eq!(
+            keys(parser.feed(&"é".as_bytes()[1..])),
+            vec![Key::Char('é')]
+        );
+    }
+
+    #[test]
+    fn test_invalid_character() {
+        let mut parser = InputParser::new();
+
+        assert_eq!(
+            parser.feed(b"\xe2\x82a"),
+            vec![
+                InputEvent::Unknown(b"\xe2\x82".to_vec()),
+                InputEvent::Key(Key::Char('a'), b"a".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cjk_input() {
+        let mut parser = InputParser::new();
+
+        assert_eq!(
+            keys(parser.feed("日本語".as_bytes())),
+            vec![Key::Char('日'), Key::Char('本'), Key::Char('語')]
+        );
+        assert!(parser.deadline().is_none());
+    }
+
+    #[test]
+    fn test_combined_characters() {
+        let mut parser = InputParser::new();
+
+        assert_eq!(
+            parser.feed("e\u{301}a".as_bytes()),
+            vec![
+                InputEvent::Grapheme("e\u{301}".to_string()),
+                InputEvent::Key(Key::Char('a'), b"a".to_vec())
+            ]
+        );
+        assert_eq!(
+            parser.feed("👩\u{200d}💻🇯🇵🇺🇸".as_bytes()),
+            vec![
+                InputEvent::Grapheme("👩\u{200d}💻".to_string()),
+                InputEvent::Grapheme("🇯🇵".to_string()),
+                InputEvent::Grapheme("🇺🇸".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_combining_mark_split_across_reads() {
+        let mut parser = InputParser::new();
+        let text = "o\u{308}".as_bytes();
+
+        assert!(parser.feed(&text[..2]).is_empty());
+        assert_eq!(
+            parser.feed(&text[2..]),
+            vec![InputEvent::Grapheme("o\u{308}".to_string())]
+        );
+
+        // A character that isn't part of the previous one is parsed separately.
+        assert!(parser.feed(&"xé".as_bytes()[..2]).is_empty());
+        assert_eq!(
+            keys(parser.feed(&"xé".as_bytes()[2..])),
+            vec![Key::Char('x'), Key::Char('é')]
+        );
+    }
+
+    #[test]
+    fn test_extended_keyboard_protocols() {
+        let mut parser = InputParser::new();
+
+        assert_eq!(
+            parser.feed(b"\x1b[97;6u\x1b[13;5u\x1b[27;6;65~"),
+            vec![
+                InputEvent::Modified(
+                    ModifiedKey::from_string("ctrl+shift+a").unwrap(),
+                    vec![0x01]
+                ),
+                InputEvent::Modified(ModifiedKey::from_string("ctrl+enter").unwrap(), vec![b'\r']),
+                InputEvent::Modified(
+                    ModifiedKey::from_string("ctrl+shift+a").unwrap(),
+                    vec![0x01]
+                ),
+            ]
+        );
+
+        // Keys the legacy encoding can express are regular keys.
+        assert_eq!(
+            parser.feed(b"\x1b[27u\x1b[97;5u\x1b[120;3u"),
+            vec![
+                InputEvent::Key(Key::Esc, vec![0x1b]),
+                InputEvent::Key(Key::Ctrl('a'), vec![0x01]),
+                InputEvent::Key(Key::Alt('x'), b"\x1bx".to_vec()),
+            ]
+        );
+
+        // Named keys with modifiers are sent to panels unchanged.
+        assert_eq!(
+            parser.feed(b"\x1b[1;5A\x1b[5;2~"),
+            vec![
+                InputEvent::Modified(
+                    ModifiedKey::from_string("ctrl+up").unwrap(),
+                    b"\x1b[1;5A".to_vec()
+                ),
+                InputEvent::Modified(
+                    ModifiedKey::from_string("shift+pageup").unwrap(),
+                    b"\x1b[5;2~".to_vec()
+                ),
+            ]
+        );
+
+        assert_eq!(
+            parser.feed(b"\x1b[?1u\x1b[?62;22c"),
+            vec![
+                InputEvent::KeyboardProtocolSupported,
+                InputEvent::DeviceAttributes
+            ]
+        );
+    }
+
+    #[test]
+    fn test_paste() {
+        let mut parser = InputParser::new();
+
+        assert!(parser.feed(b"\x1b[200~line 1\n\x1b[A").is_empty());
+        assert!(parser.feed(b"line 2\x1b[20").is_empty());
+        // Pastes don't time out.
+        assert!(parser.flush().is_empty());
+        assert_eq!(
+            parser.feed(b"1~q"),
+            vec![
+                InputEvent::Paste(b"line 1\n\x1b[Aline 2".to_vec()),
+                InputEvent::Key(Key::Char('q'), b"q".to_vec())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_focus_and_unknown() {
+        let mut parser = InputParser::new();
+
+        assert_eq!(
+            parser.feed(b"\x1b[I\x1b[<0;1;1M\x1b[O"),
+            vec![
+                InputEvent::FocusGained,
+                InputEvent::Unknown(b"\x1b[<0;1;1M".to_vec()),
+                InputEvent::FocusLost
+            ]
+        );
+    }
+}
@@ -1,15 +1,40 @@
+#![allow(
+    clippy::needless_return,
+    clippy::enum_variant_names,
+    clippy::redundant_static_lifetimes,
+    clippy::module_inception
+)]
+
+#[doc(hidden)]
+pub mod bench;
+mod builder;
 mod channel_controller;
 mod color;
 mod command;
 mod config;
 mod display;
+pub mod doctor;
 mod error;
 mod geometry;
+mod graphics;
 pub mod hasher;
+mod hints;
 mod input_manager;
+mod input_parser;
+pub mod log_rotation;
+pub mod logging;
 mod logic_manager;
+mod panel_log;
+mod panel_screen;
+pub mod protocol;
 mod pty;
+mod reflow;
+pub mod session;
+mod ssh;
+mod stats;
+mod terminal_guard;
 
+pub use builder::{Muxide, MuxideBuilder};
 use color::Color;
 pub use config::{Config, PasswordSettings};
 pub use error::{ErrorType, MuxideError};
@@ -0,0 +1,269 @@
+use crate::error::{ErrorType, MuxideError};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The log file that is rotated as it is written and the settings it is rotated with.
+struct Rotation {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    compress: bool,
+    /// The number of messages written since the size of the log file was last checked.
+    unchecked: usize,
+}
+
+impl Rotation {
+    /// The number of messages written between checks of the log file's size, so writing a message
+    /// doesn't usually need a syscall.
+    const CHECK_INTERVAL: usize = 64;
+
+    /// Count a message that is about to be written, returns true if the size of the log file
+    /// should be checked before it is.
+    fn should_check(&mut self) -> bool {
+        self.unchecked += 1;
+
+        if self.unchecked < Self::CHECK_INTERVAL {
+            return false;
+        }
+
+        self.unchecked = 0;
+
+        return true;
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref ROTATION: Mutex<Option<Rotation>> = Mutex::new(None);
+}
+
+/// Rotates the log file if it is at least the maximum size. The log file becomes `<file>.1`, the
+/// previous `<file>.1` becomes `<file>.2` and so on, keeping at most `max_files` rotated files.
+/// The rotated files are compressed with gzip and end in `.gz` if compress is set. Returns whether
+/// the file was rotated.
+pub fn rotate_log_file(
+    path: &Path,
+    max_size: u64,
+    max_files: usize,
+    compress: bool,
+) -> Result<bool, MuxideError> {
+    let size = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(io_error(path, true, e)),
+    };
+
+    if size < max_size {
+        return Ok(false);
+    }
+
+    if max_files == 0 {
+        fs::remove_file(path).map_err(|e| io_error(path, false, e))?;
+        return Ok(true);
+    }
+
+    // The oldest file is replaced, so it is removed first.
+    remove_if_exists(&rotated_path(path, max_files, compress))?;
+
+    for i in (1..max_files).rev() {
+        let from = rotated_path(path, i, compress);
+
+        if from.exists() {
+            fs::rename(&from, rotated_path(path, i + 1, compress))
+                .map_err(|e| io_error(&from, false, e))?;
+        }
+    }
+
+    if compress {
+        compress_file(path, &rotated_path(path, 1, true))?;
+        fs::remove_file(path).map_err(|e| io_error(path, false, e))?;
+    } else {
+        fs::rename(path, rotated_path(path, 1, false)).map_err(|e| io_error(path, false, e))?;
+    }
+
+    return Ok(true);
+}
+
+/// Rotate the log file before messages are written to it once it reaches the maximum size, see
+/// [`rotate_log_file`]. Its size is checked every few messages, so it may grow a little past the
+/// maximum size.
+pub fn rotate_on_write(path: &Path, max_size: u64, max_files: usize, compress: bool) {
+    *ROTATION.lock().unwrap_or_else(|e| e.into_inner()) = Some(Rotation {
+        path: path.to_path_buf(),
+        max_size,
+        max_files,
+        compress,
+        unchecked: 0,
+    });
+}
+
+/// Called by the log writer before each message is written. Rotates the log file if it has
+/// reached its maximum size and opens a new log file in its place.
+pub(crate) fn rotate_if_needed() -> Result<(), MuxideError> {
+    let mut rotation = ROTATION.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Some(r) = rotation.as_mut() {
+        if r.should_check() && rotate_log_file(&r.path, r.max_size, r.max_files, r.compress)? {
+            let path = r.path.to_string_lossy();
+
+            muxide_logging::set_output_file(&path).map_err(|reason| {
+                ErrorType::IOError {
+                    read: false,
+                    target: path.to_string(),
+                    reason,
+                }
+                .into_error()
+            })?;
+        }
+    }
+
+    return Ok(());
+}
+
+/// Write a gzip compressed copy of a file.
+fn compress_file(from: &Path, to: &Path) -> Result<(), MuxideError> {
+    let mut input = File::open(from).map_err(|e| io_error(from, true, e))?;
+    let output = File::create(to).map_err(|e| io_error(to, false, e))?;
+    let mut encoder = GzEncoder::new(output, Compression::default());
+
+    io::copy(&mut input, &mut encoder).map_err(|e| io_error(to, false, e))?;
+    encoder.finish().map_err(|e| io_error(to, false, e))?;
+
+    return Ok(());
+}
+
+/// Returns the path of the rotated log file with the specified index, i.e. `muxide.log.2`, or
+/// `muxide.log.2.gz` if it is compressed.
+fn rotated_path(path: &Path, index: usize, compressed: bool) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", index));
+
+    if compressed {
+        name.push(".gz");
+    }
+
+    return PathBuf::from(name);
+}
+
+fn remove_if_exists(path: &Path) -> Result<(), MuxideError> {
+    return match fs::remove_file(path) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(io_error(path, false, e)),
+    };
+}
+
+fn io_error(path: &Path, read: bool, error: std::io::Error) -> MuxideError {
+    return ErrorType::IOError {
+        read,
+        target: path.to_string_lossy().to_string(),
+        reason: error.to_string(),
+    }
+    .into_error();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rotate_log_file, rotated_path, Rotation};
+    use flate2::read::GzDecoder;
+    use std::fs::{self, File};
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    fn test_directory(name: &str) -> PathBuf {
+        let mut path = std::env::temp_dir();
+        path.push(format!(
+            "muxide-log-rotation-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+
+        return path;
+    }
+
+    #[test]
+    fn test_rotate_log_file() {
+        let directory = test_directory("rotate");
+        let log = directory.join("muxide.log");
+
+        fs::write(&log, "small").unwrap();
+        assert!(!rotate_log_file(&log, 100, 2, false).unwrap());
+
+        for contents in &["first", "second", "third"] {
+            fs::write(&log, contents).unwrap();
+            assert!(rotate_log_file(&log, 5, 2, false).unwrap());
+        }
+
+        assert!(!log.exists());
+        assert_eq!(
+            fs::read_to_string(rotated_path(&log, 1, false)).unwrap(),
+            "third"
+        );
+        assert_eq!(
+            fs::read_to_string(rotated_path(&log, 2, false)).unwrap(),
+            "second"
+        );
+        assert!(!rotated_path(&log, 3, false).exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_compressed_log_file() {
+        let directory = test_directory("compress");
+        let log = directory.join("muxide.log");
+
+        for contents in &["first", "second"] {
+            fs::write(&log, contents).unwrap();
+            assert!(rotate_log_file(&log, 5, 2, true).unwrap());
+        }
+
+        let read = |index| {
+            let mut contents = String::new();
+            GzDecoder::new(File::open(rotated_path(&log, index, true)).unwrap())
+                .read_to_string(&mut contents)
+                .unwrap();
+
+            return contents;
+        };
+
+        assert!(!log.exists());
+        assert_eq!(read(1), "second");
+        assert_eq!(read(2), "first");
+        assert!(!rotated_path(&log, 1, false).exists());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_missing_log_file() {
+        let directory = test_directory("missing");
+
+        assert!(!rotate_log_file(&directory.join("muxide.log"), 0, 2, false).unwrap());
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_check_interval() {
+        let mut rotation = Rotation {
+            path: PathBuf::from("muxide.log"),
+            max_size: 1,
+            max_files: 1,
+            compress: false,
+            unchecked: 0,
+        };
+
+        for _ in 1..Rotation::CHECK_INTERVAL {
+            assert!(!rotation.should_check());
+        }
+
+        assert!(rotation.should_check());
+        assert!(!rotation.should_check());
+    }
+}
@@ -0,0 +1,544 @@
+use crate::log_rotation;
+use muxide_logging::format::FormatItem;
+use muxide_logging::log::{Log, LogLevel};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Once, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The severity of a queued log message, ordered from the least to the most severe.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
+pub enum Level {
+    Information,
+    Warning,
+    Error,
+}
+
+/// Determines which messages are logged from the module they were logged in, i.e.
+/// `muxide::display=warn,muxide::logic_manager=error`. A level without a module applies to every
+/// module that isn't listed, and the most specific module listed is used.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct LogFilter {
+    /// The least severe level logged for modules that aren't listed, None if nothing is logged.
+    default: Option<Level>,
+    targets: Vec<(String, Option<Level>)>,
+}
+
+/// A bounded queue of log messages. When it is full the oldest message is dropped, so logging
+/// never waits for the log file to be written.
+struct LogQueue<T> {
+    messages: Mutex<VecDeque<T>>,
+    available: Condvar,
+    empty: Condvar,
+}
+
+/// The most recent messages that were logged, kept in memory so they can be viewed in muxide. Once
+/// it holds as many messages as its capacity the oldest message is dropped.
+struct LogHistory {
+    messages: VecDeque<(Level, String)>,
+    capacity: usize,
+}
+
+impl LogHistory {
+    /// The number of messages kept until the capacity is set.
+    const DEFAULT_CAPACITY: usize = 500;
+
+    fn new(capacity: usize) -> Self {
+        return Self {
+            messages: VecDeque::new(),
+            capacity,
+        };
+    }
+
+    fn push(&mut self, level: Level, message: String) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        while self.messages.len() >= self.capacity {
+            self.messages.pop_front();
+        }
+
+        self.messages.push_back((level, message));
+    }
+
+    /// Change the capacity, dropping the oldest messages that no longer fit.
+    fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+
+        while self.messages.len() > capacity {
+            self.messages.pop_front();
+        }
+    }
+
+    /// Returns up to the last count messages that are at least as severe as the minimum level,
+    /// oldest first.
+    fn recent(&self, count: usize, minimum: Level) -> Vec<(Level, String)> {
+        let mut messages: Vec<(Level, String)> = self
+            .messages
+            .iter()
+            .rev()
+            .filter(|(level, _)| *level >= minimum)
+            .take(count)
+            .cloned()
+            .collect();
+        messages.reverse();
+
+        return messages;
+    }
+}
+
+impl<T> LogQueue<T> {
+    /// The maximum number of messages waiting to be written.
+    const CAPACITY: usize = 1024;
+
+    fn new() -> Self {
+        return Self {
+            messages: Mutex::new(VecDeque::with_capacity(Self::CAPACITY)),
+            available: Condvar::new(),
+            empty: Condvar::new(),
+        };
+    }
+
+    fn push(&self, message: T) {
+        let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+
+        if messages.len() == Self::CAPACITY {
+            messages.pop_front();
+        }
+
+        messages.push_back(message);
+        self.available.notify_one();
+    }
+
+    /// Wait for the next message.
+    fn pop(&self) -> T {
+        let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+
+        loop {
+            if let Some(message) = messages.pop_front() {
+                if messages.is_empty() {
+                    self.empty.notify_all();
+                }
+
+                return message;
+            }
+
+            messages = self
+                .available
+                .wait(messages)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Wait until every queued message has been taken by the writer or the timeout expires.
+    fn wait_until_empty(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let mut messages = self.messages.lock().unwrap_or_else(|e| e.into_inner());
+
+        while !messages.is_empty() {
+            let now = Instant::now();
+
+            if now >= deadline {
+                return;
+            }
+
+            messages = self
+                .empty
+                .wait_timeout(messages, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+}
+
+impl LogFilter {
+    /// Parse a comma separated list of `module=level` or `level` entries. The levels are `off`,
+    /// `error`, `warn`, `info` and `debug`, which are the same as `info`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut filter = Self::default();
+
+        for entry in spec.split(',').map(|e| e.trim()).filter(|e| !e.is_empty()) {
+            match entry.rfind('=') {
+                Some(i) => {
+                    let target = entry[..i].trim();
+
+                    if target.is_empty() {
+                        return Err(format!("Missing the module for the entry: {}", entry));
+                    }
+
+                    let level = Self::parse_level(entry[i + 1..].trim())?;
+                    filter.targets.push((target.to_string(), level));
+                }
+                None => filter.default = Self::parse_level(entry)?,
+            }
+        }
+
+        return Ok(filter);
+    }
+
+    /// Returns whether a message with the specified level, logged from the specified module,
+    /// should be logged.
+    pub fn allows(&self, target: &str, level: Level) -> bool {
+        let minimum = self
+            .targets
+            .iter()
+            .filter(|(t, _)| {
+                target == t
+                    || (target.starts_with(t.as_str()) && target[t.len()..].starts_with("::"))
+            })
+            .max_by_key(|(t, _)| t.len())
+            .map(|(_, l)| *l)
+            .unwrap_or(self.default);
+
+        return minimum.map(|m| level >= m).unwrap_or(false);
+    }
+
+    fn parse_level(level: &str) -> Result<Option<Level>, String> {
+        return match level.to_lowercase().as_str() {
+            "off" => Ok(None),
+            "error" => Ok(Some(Level::Error)),
+            "warn" | "warning" => Ok(Some(Level::Warning)),
+            "info" | "debug" | "trace" => Ok(Some(Level::Information)),
+            _ => Err(format!("Unknown log level: {}", level)),
+        };
+    }
+}
+
+impl Level {
+    pub fn label(&self) -> &'static str {
+        return match self {
+            Level::Information => "INFO",
+            Level::Warning => "WARN",
+            Level::Error => "ERROR",
+        };
+    }
+
+    fn log_level(&self) -> LogLevel {
+        return match self {
+            Level::Information => LogLevel::Information,
+            Level::Warning => LogLevel::Warning,
+            Level::Error => LogLevel::Error,
+        };
+    }
+}
+
+/// Maps a log format onto muxide_logging's format items. The placeholders "{time}", "{level}",
+/// "{file}", "{line}", "{thread}" and "{message}" are replaced with the details of each message
+/// and the rest of the format is copied into each line, "{{" and "}}" are a literal brace. The
+/// time is formatted with the strftime time format.
+pub fn parse_format(format: &str, time_format: &str) -> Result<Vec<FormatItem>, String> {
+    let mut items = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                let mut closed = false;
+
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+
+                    name.push(c);
+                }
+
+                if !closed {
+                    return Err(format!("Unclosed log format placeholder: {{{}", name));
+                }
+
+                let item = match name.as_str() {
+                    "time" => FormatItem::Time(time_format.to_string()),
+                    "level" => FormatItem::LogLevel,
+                    "file" => FormatItem::FileName,
+                    "line" => FormatItem::LineNumber,
+                    "thread" => FormatItem::ThreadID,
+                    "message" => FormatItem::Message,
+                    _ => return Err(format!("Unknown log format placeholder: {{{}}}", name)),
+                };
+
+                if !text.is_empty() {
+                    items.push(FormatItem::Text(std::mem::take(&mut text)));
+                }
+
+                items.push(item);
+            }
+            _ => text.push(c),
+        }
+    }
+
+    if !text.is_empty() {
+        items.push(FormatItem::Text(text));
+    }
+
+    return Ok(items);
+}
+
+/// Returns a number identifying the current thread, threads are numbered from 1 in the order
+/// they first log a message. The runtime's worker threads share a name, the number tells them
+/// apart.
+fn thread_id() -> usize {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+    thread_local! {
+        static ID: usize = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    }
+
+    return ID.with(|id| *id);
+}
+
+impl Default for LogFilter {
+    /// Logs every message.
+    fn default() -> Self {
+        return Self {
+            default: Some(Level::Information),
+            targets: Vec::new(),
+        };
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref QUEUE: Arc<LogQueue<Log>> = Arc::new(LogQueue::new());
+    static ref FILTER: RwLock<LogFilter> = RwLock::new(LogFilter::default());
+    static ref HISTORY: Mutex<LogHistory> =
+        Mutex::new(LogHistory::new(LogHistory::DEFAULT_CAPACITY));
+}
+
+static START_WRITER: Once = Once::new();
+
+/// Set the filter that determines which messages are logged.
+pub fn set_filter(filter: LogFilter) {
+    *FILTER.write().unwrap_or_else(|e| e.into_inner()) = filter;
+}
+
+/// Set the number of recent messages kept in memory, 0 keeps none.
+pub fn set_history_size(size: usize) {
+    HISTORY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .set_capacity(size);
+}
+
+/// Returns up to the last count messages that were logged and are at least as severe as the
+/// minimum level, oldest first. Only the messages kept in memory are searched, see
+/// [`set_history_size`].
+pub fn recent_messages(count: usize, minimum: Level) -> Vec<(Level, String)> {
+    return HISTORY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .recent(count, minimum);
+}
+
+/// Queue a message to be written to the log by the background writer thread, which is started
+/// when the first message is logged. The target is the module the message was logged from, the
+/// file and line are where it was logged and the thread logging it is recorded with them.
+pub fn log(target: &str, file: &str, line: u32, level: Level, message: String) {
+    if !FILTER
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .allows(target, level)
+    {
+        return;
+    }
+
+    START_WRITER.call_once(|| {
+        let queue = QUEUE.clone();
+
+        thread::spawn(move || loop {
+            let message = queue.pop();
+
+            // There is nowhere to report a message that couldn't be written, or a log file that
+            // couldn't be rotated.
+            let _ = log_rotation::rotate_if_needed();
+            let _ = muxide_logging::write_log(message);
+        });
+    });
+
+    HISTORY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(level, message.clone());
+    QUEUE.push(
+        Log::new(level.log_level(), message, file, line).with_thread_id(thread_id().to_string()),
+    );
+}
+
+/// Give the writer thread a chance to write the queued messages, used before exiting.
+pub fn flush(timeout: Duration) {
+    QUEUE.wait_until_empty(timeout);
+}
+
+/// Queue an error message, see [`log`].
+#[macro_export]
+macro_rules! error {
+    ($message:expr) => {
+        $crate::logging::log(
+            module_path!(),
+            file!(),
+            line!(),
+            $crate::logging::Level::Error,
+            ($message).to_string(),
+        )
+    };
+}
+
+/// Queue a warning message, see [`log`].
+#[macro_export]
+macro_rules! warning {
+    ($message:expr) => {
+        $crate::logging::log(
+            module_path!(),
+            file!(),
+            line!(),
+            $crate::logging::Level::Warning,
+            ($message).to_string(),
+        )
+    };
+}
+
+/// Queue an informational message, see [`log`].
+#[macro_export]
+macro_rules! info {
+    ($message:expr) => {
+        $crate::logging::log(
+            module_path!(),
+            file!(),
+            line!(),
+            $crate::logging::Level::Information,
+            ($message).to_string(),
+        )
+    };
+}
+
+pub(crate) use {error, info, warning};
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_format, thread_id, Level, LogFilter, LogHistory, LogQueue};
+    use muxide_logging::format::FormatItem;
+
+    #[test]
+    fn test_queue_drops_oldest() {
+        let queue = LogQueue::new();
+
+        for i in 0..(LogQueue::<usize>::CAPACITY + 2) {
+            queue.push(i);
+        }
+
+        assert_eq!(queue.pop(), 2);
+        assert_eq!(
+            queue.messages.lock().unwrap().len(),
+            LogQueue::<usize>::CAPACITY - 1
+        );
+    }
+
+    #[test]
+    fn test_history() {
+        let mut history = LogHistory::new(3);
+
+        let levels = [
+            Level::Error,
+            Level::Information,
+            Level::Warning,
+            Level::Information,
+        ];
+
+        for (i, level) in levels.iter().enumerate() {
+            history.push(*level, i.to_string());
+        }
+
+        assert_eq!(
+            history.recent(10, Level::Information),
+            vec![
+                (Level::Information, String::from("1")),
+                (Level::Warning, String::from("2")),
+                (Level::Information, String::from("3")),
+            ]
+        );
+        assert_eq!(
+            history.recent(1, Level::Warning),
+            vec![(Level::Warning, String::from("2"))]
+        );
+        assert_eq!(history.recent(2, Level::Information)[0].1, "2");
+
+        history.set_capacity(1);
+        assert_eq!(history.recent(10, Level::Information).len(), 1);
+
+        history.set_capacity(0);
+        history.push(Level::Error, String::from("4"));
+        assert!(history.recent(10, Level::Information).is_empty());
+    }
+
+    #[test]
+    fn test_log_filter() {
+        let filter =
+            LogFilter::parse("warn, muxide::display=error,muxide::display::panel=info").unwrap();
+
+        assert!(filter.allows("muxide::logic_manager", Level::Warning));
+        assert!(!filter.allows("muxide::logic_manager", Level::Information));
+        assert!(!filter.allows("muxide::display", Level::Warning));
+        assert!(filter.allows("muxide::display::subdivision", Level::Error));
+        assert!(filter.allows("muxide::display::panel", Level::Information));
+        assert!(filter.allows("muxide::displayed", Level::Warning));
+
+        let filter = LogFilter::parse("off,muxide::pty=debug").unwrap();
+
+        assert!(!filter.allows("muxide::display", Level::Error));
+        assert!(filter.allows("muxide::pty", Level::Information));
+    }
+
+    #[test]
+    fn test_invalid_log_filter() {
+        assert!(LogFilter::parse("muxide=loud").is_err());
+        assert!(LogFilter::parse("=warn").is_err());
+        assert_eq!(LogFilter::parse("").unwrap(), LogFilter::default());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        let items = parse_format("{{{level}}} {file}:{line} ({thread}) - {message}", "%k").unwrap();
+
+        assert!(matches!(
+            items.as_slice(),
+            [
+                FormatItem::Text(open),
+                FormatItem::LogLevel,
+                FormatItem::Text(_),
+                FormatItem::FileName,
+                FormatItem::Text(_),
+                FormatItem::LineNumber,
+                FormatItem::Text(_),
+                FormatItem::ThreadID,
+                FormatItem::Text(_),
+                FormatItem::Message,
+            ] if open == "{"
+        ));
+        assert!(matches!(
+            parse_format("{time}", "%Y-%m-%d").unwrap().as_slice(),
+            [FormatItem::Time(format)] if format == "%Y-%m-%d"
+        ));
+        assert!(parse_format("{time} {date}", "%Y").is_err());
+        assert!(parse_format("{time", "%Y").is_err());
+    }
+
+    #[test]
+    fn test_thread_id() {
+        let id = thread_id();
+
+        assert_eq!(thread_id(), id);
+        assert_ne!(std::thread::spawn(thread_id).join().unwrap(), id);
+    }
+}
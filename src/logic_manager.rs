@@ -1,35 +1,56 @@
-use crate::channel_controller::{ChannelController, ChannelID, PtyMessage, ServerMessage};
+use crate::channel_controller::{
+    ChannelController, ChannelID, ControllerResponse, CountedSender, PtyMessage, ServerMessage,
+    ServerReceiver,
+};
+use crate::color;
 use crate::command::Command;
-use crate::config::Config;
-use crate::display::Display;
+use crate::config::{
+    self, Config, HighlightRule, KeyTable, KeyboardProtocol, Layout, ModifiedKey, Tuning,
+    WorkspaceSetup,
+};
+use crate::display::{Display, MessageLevel, SubdivisionPath};
 use crate::error::{ErrorType, MuxideError};
-use crate::geometry::{Direction, Size};
+use crate::geometry::{Direction, Point, Size};
 use crate::hasher;
+use crate::hints::{self, Hint};
 use crate::input_manager::InputManager;
-use crate::pty::Pty;
+use crate::input_parser::{InputEvent, InputParser};
+use crate::logging::{self, error, info, warning, Level};
+use crate::panel_log::{self, PanelLog};
+use crate::panel_screen::{PanelFrame, SharedPanelScreen};
+use crate::pty::{Pty, PtyEnvironment};
+use crate::ssh;
+use crate::stats::Stats;
+use crate::terminal_guard::TerminalGuard;
 use binary_set::BinaryTreeSet;
-use muxide_logging::error;
+use nix::errno::Errno;
 use nix::poll;
-use rand::Rng;
-use std::os::unix::io::AsRawFd;
-use termion::event::{self, Event};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::Arc;
+use termion::event::Key;
+use tokio::io::unix::AsyncFd;
+use tokio::io::AsyncReadExt;
 use tokio::select;
-use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::task::JoinHandle;
-use tokio::time::Duration;
-use vt100::Parser;
+use tokio::time::{Duration, Instant};
 
-/// The timeout used when we poll the PTY for if it is available.
-const POLL_TIMEOUT_MS: i32 = 100;
-/// THe timeout used when reporting an error.
-const ERROR_TIMEOUT_MS: u64 = 100;
-/// THe timeout used when writing to a file.
-const FILE_TIMEOUT_MS: u64 = 750;
+/// The maximum number of bytes of output processed for a single frame, a panel that produces
+/// more output than this sends several frames so input is still handled between them.
+const OUTPUT_BUDGET: usize = 64 * 1024;
+/// The time waited before checking if a paused panel has resumed, whilst its output is unread.
+const PAUSED_POLL_MS: u64 = 50;
 
-/// This method runs a pty, handling shutdown messages, stdin and stdout.
+/// This method runs a pty, handling shutdown messages, stdin and stdout. The output is processed
+/// here, so the event loop only receives the panel's contents once they are ready to be rendered.
 /// It should be spawned in a thread.
-async fn pty_manager(mut p: Pty, tx: Sender<PtyMessage>, mut stdin_rx: Receiver<ServerMessage>) {
+async fn pty_manager(
+    id: usize,
+    mut p: Pty,
+    screen: SharedPanelScreen,
+    tx: CountedSender<PtyMessage>,
+    mut stdin_rx: ServerReceiver,
+    tuning: Tuning,
+) {
     macro_rules! pty_error {
         ($tx:expr, $e:expr, $log_message:expr) => {
             error!($log_message);
@@ -39,7 +60,7 @@ async fn pty_manager(mut p: Pty, tx: Sender<PtyMessage>, mut stdin_rx: Receiver<
             // This could error out and if it does then we just assume the controller will deal with it.
             select! {
                 _ = $tx.send(PtyMessage::Error(e)) => {},
-                _ = tokio::time::sleep(Duration::from_millis(ERROR_TIMEOUT_MS)) => {},
+                _ = tokio::time::sleep(tuning.error_timeout()) => {},
             }
         };
 
@@ -53,74 +74,149 @@ async fn pty_manager(mut p: Pty, tx: Sender<PtyMessage>, mut stdin_rx: Receiver<
             // This could error out and if it does then we just assume the controller will deal with it.
             select! {
                 _ = $tx.send(PtyMessage::Error(e)) => {},
-                _ = tokio::time::sleep(Duration::from_millis(ERROR_TIMEOUT_MS)) => {},
+                _ = tokio::time::sleep(tuning.error_timeout()) => {},
             }
         };
-    };
+    }
 
     let pfd = poll::PollFd::new(p.as_raw_fd(), poll::PollFlags::POLLIN);
-
-    loop {
-        select! {
-            res = tokio::spawn(async move {
-                // For some reason rust reports that this value is unassigned.
-                #[allow(unused_assignments)]
-                let mut res = Ok(false);
-
-                loop {
-                    match poll::poll(&mut [pfd], POLL_TIMEOUT_MS) {
-                        Ok(poll_response) => {
-                            // If we get 0, that means the call timed out, a negative value is an error
-                            // in my understanding but nix, I believe should handle that as an error
-                            if poll_response > 0 {
-                                //res = true;
-                                res = Ok(true);
-                                break;
-                            }
+    let mut log: Option<PanelLog> = None;
+    // Input is buffered until the program reads it, input received whilst it waits is written
+    // with it.
+    let mut pending_input: Vec<u8> = Vec::new();
+    // When the program last read its input, whilst input is waiting.
+    let mut input_progress = Instant::now();
+    let mut input_blocked_reported = false;
+
+    // Writes the pending input the program accepts, returning true if any of it was written.
+    macro_rules! write_input {
+        () => {
+            match p.write_available(&pending_input) {
+                Ok(0) => {
+                    if !input_blocked_reported && input_progress.elapsed() >= tuning.file_timeout()
+                    {
+                        input_blocked_reported = true;
+                        let waiting = pending_input.len();
+
+                        if tx.send(PtyMessage::InputBlocked(waiting)).await.is_err() {
+                            pty_error!(tx, ErrorType::FailedToSendMessage);
+                            return;
                         }
-                        Err(e) => {
-                            // If we receive an error here, it is a first class (unrecoverable) error.
-                            res = Err(e);
-                            break;
-                        },
                     }
+
+                    false
                 }
+                Ok(count) => {
+                    pending_input.drain(..count);
+                    input_progress = Instant::now();
+                    input_blocked_reported = false;
 
-                res
-            }) => {
-                if res.is_err() {
-                    pty_error!(tx, ErrorType::FailedReadPoll, "Something unexpected went wrong whilst reading the pty poll");
+                    true
+                }
+                Err(e) => {
+                    pty_error!(
+                        tx,
+                        ErrorType::FailedToWriteToPTY,
+                        format!("Failed to write to the pty. Error: {}", e)
+                    );
                     return;
                 }
+            }
+        };
+    }
 
-                match res.unwrap() {
-                    Ok(b) => {
-                        if !b {
-                            continue;
-                        }
+    // Waits for the pty to be readable, or writable whilst input is waiting. The runtime tracks
+    // the readiness, so waiting doesn't occupy a thread and stops when another branch completes.
+    let ready = match AsyncFd::new(p.as_raw_fd()) {
+        Ok(ready) => ready,
+        Err(e) => {
+            pty_error!(
+                tx,
+                ErrorType::FailedReadPoll,
+                format!("Failed to wait for the pty. Error: {}", e)
+            );
+            return;
+        }
+    };
+
+    loop {
+        select! {
+            res = ready.readable() => {
+                let mut guard = match res {
+                    Ok(guard) => guard,
+                    Err(e) => {
+                        pty_error!(tx, ErrorType::FailedReadPoll, format!("Failed to poll for available data. Error: {}", e));
+                        return;
+                    }
+                };
+
+                // The pty stays ready until its readiness is cleared, which is done once there is
+                // nothing left to read as reading would then block.
+                match poll::poll(&mut [pfd], 0) {
+                    Ok(0) => {
+                        guard.clear_ready();
+                        continue;
                     }
+                    Ok(_) => (),
+                    Err(nix::Error::Sys(Errno::EINTR)) => continue,
                     Err(e) => {
                         pty_error!(tx, ErrorType::FailedReadPoll, format!("Failed to poll for available data. Error: {}", e));
                         return;
                     },
                 }
 
+                // A paused panel that can't buffer any more output leaves it unread, so the program
+                // blocks until the panel resumes.
+                if screen.lock().paused_output_full() {
+                    tokio::time::sleep(Duration::from_millis(PAUSED_POLL_MS)).await;
+                    continue;
+                }
+
                 let mut buf = vec![0u8; 4096];
                 let res = p.file().read(&mut buf).await;
 
                 if let Ok(count) = res {
-                    if count == 0 {
-                        if p.running() == Some(false) {
+                    if count == 0
+                        && p.running() == Some(false) {
                             pty_error!(tx, ErrorType::PTYStoppedRunning);
                             return;
                         }
+
+                    screen.lock().process(&buf[0..count]);
+                    write_log(&mut log, &buf[0..count]).await;
+                    let mut processed = count;
+                    let mut lines = count_lines(&buf[0..count]);
+
+                    // Process the output that is already available, up to the budget, so a busy
+                    // panel sends one frame rather than a frame for every read.
+                    while count != 0
+                        && processed < OUTPUT_BUDGET
+                        && !screen.lock().paused_output_full()
+                        && poll::poll(&mut [pfd], 0).map(|r| r > 0).unwrap_or(false)
+                    {
+                        match p.file().read(&mut buf).await {
+                            Ok(0) => break,
+                            Ok(n) => {
+                                screen.lock().process(&buf[0..n]);
+                                write_log(&mut log, &buf[0..n]).await;
+                                processed += n;
+                                lines += count_lines(&buf[0..n]);
+                            }
+                            Err(_) => {
+                                pty_error!(tx, ErrorType::FailedToReadPTY);
+                                return;
+                            }
+                        }
                     }
 
-                    let mut cpy = vec![0u8; count];
-                    cpy.copy_from_slice(&buf[0..count]);
+                    let frame = PanelFrame {
+                        processed_bytes: processed,
+                        processed_lines: lines,
+                        ..screen.lock().frame()
+                    };
 
                     // Ignore any errors with communicating data.
-                    match tx.send(PtyMessage::Bytes(cpy)).await {
+                    match tx.send(PtyMessage::Frame(frame)).await {
                         Ok(_) => (),
                         Err(_) => {
                             pty_error!(tx, ErrorType::FailedToSendMessage);
@@ -128,7 +224,7 @@ async fn pty_manager(mut p: Pty, tx: Sender<PtyMessage>, mut stdin_rx: Receiver<
                         }
                     }
 
-                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    tokio::time::sleep(tuning.frame_delay()).await;
                 } else {
                     pty_error!(tx, ErrorType::FailedToReadPTY);
                     return;
@@ -138,21 +234,38 @@ async fn pty_manager(mut p: Pty, tx: Sender<PtyMessage>, mut stdin_rx: Receiver<
                 if let Some(message) = res {
                     match message {
                         ServerMessage::Bytes(bytes) => {
-                            select! {
-                                res = p.file().write_all(&bytes) => {
-                                    match res {
-                                        Ok(_) => (),
-                                        Err(_) => {
-                                            pty_error!(tx, ErrorType::FailedToWriteToPTY);
-                                            return;
-                                        },
-                                    }
-                                },
-                                _ = tokio::time::sleep(Duration::from_millis(FILE_TIMEOUT_MS)) => {},
+                            if pending_input.is_empty() {
+                                input_progress = Instant::now();
                             }
+
+                            pending_input.extend_from_slice(&bytes);
+                            write_input!();
                         },
                         ServerMessage::Resize(size) => {
-                            p.resize(&size).unwrap();
+                            // The program keeps running at its previous size.
+                            if let Err(e) = p.resize(&size) {
+                                error!(e.with_context(format!("Failed to resize panel {}", id)).debug_description());
+                            }
+
+                            let frame = {
+                                let mut screen = screen.lock();
+                                screen.set_size(size);
+                                screen.frame()
+                            };
+
+                            if tx.send(PtyMessage::Frame(frame)).await.is_err() {
+                                pty_error!(tx, ErrorType::FailedToSendMessage);
+                                return;
+                            }
+                        },
+                        ServerMessage::StartLogging { path, timestamps } => {
+                            match PanelLog::open(&path, timestamps).await {
+                                Ok(l) => log = Some(l),
+                                Err(e) => error!(format!("Failed to open the panel log. Error: {}", e.description())),
+                            }
+                        },
+                        ServerMessage::StopLogging => {
+                            log = None;
                         },
                         ServerMessage::Shutdown => {
                             break;
@@ -162,17 +275,61 @@ async fn pty_manager(mut p: Pty, tx: Sender<PtyMessage>, mut stdin_rx: Receiver<
                     pty_error!(tx, ErrorType::PtyStdinReceiverClosed);
                     return;
                 }
+            },
+            res = ready.writable(), if !pending_input.is_empty() => {
+                let mut guard = match res {
+                    Ok(guard) => guard,
+                    Err(e) => {
+                        pty_error!(tx, ErrorType::FailedToWriteToPTY, format!("Failed to poll for space for input. Error: {}", e));
+                        return;
+                    }
+                };
+
+                // Nothing is written until the program reads some of its input.
+                if !write_input!() {
+                    guard.clear_ready();
+                }
+            },
+            _ = tokio::time::sleep_until(input_progress + tuning.file_timeout()), if !pending_input.is_empty() && !input_blocked_reported => {
+                write_input!();
             }
         }
     }
 }
 
+/// Returns the number of lines in a panel's output.
+fn count_lines(bytes: &[u8]) -> usize {
+    return bytes.iter().filter(|b| **b == b'\n').count();
+}
+
+/// Append a panel's output to its log, if it is being logged. Logging stops if the log can't be
+/// written to, the panel keeps running.
+async fn write_log(log: &mut Option<PanelLog>, bytes: &[u8]) {
+    if let Some(l) = log.as_mut() {
+        if let Err(e) = l.write(bytes).await {
+            error!(format!("Failed to write to the panel log. Error: {}", e));
+            *log = None;
+        }
+    }
+}
+
 /// Represents a panel, i.e. the output for a process. It tracks the contents being
 /// displayed and assigns an id.
 struct Panel {
-    parser: Parser,
+    screen: SharedPanelScreen,
     id: usize,
-    current_scrollback: usize,
+    /// The master file descriptor of the panel's pty, used to inspect its foreground process.
+    pty_fd: RawFd,
+    /// The file the panel's output is appended to whilst it is logged.
+    log_path: Option<String>,
+    /// The foreground process group when the panel's output was last handled.
+    foreground: Option<i32>,
+    /// The arguments and destination of the last ssh command run in the panel.
+    ssh: Option<(Vec<String>, String)>,
+    /// Set whilst the ssh command is the foreground process.
+    ssh_running: bool,
+    /// Set whilst input isn't sent to the panel, its output is still displayed.
+    read_only: bool,
 }
 
 /// Handles a majority of the overall application logic, i.e. receiving stdin input and the panel
@@ -183,31 +340,78 @@ pub struct LogicManager {
     selected_panel: Option<usize>,
     halt_execution: bool,
     single_key_command: bool,
+    pending_sequence: Vec<char>,
+    /// The time at which an incomplete command sequence is completed.
+    sequence_deadline: Option<Instant>,
+    key_table: KeyTable,
     config: Config,
     connection_manager: ChannelController,
     _input_manager: InputManager,
+    input_parser: InputParser,
     close_handles: Vec<(usize, JoinHandle<()>)>,
     ids: BinaryTreeSet<usize>,
     hashed_password: Option<String>,
     password_input: String,
     locked: bool,
     displaying_help: bool,
+    /// Set when the input scrolled the help message, so it stays open.
+    help_scrolled: bool,
+    identifying_panels: bool,
+    viewing_message_history: bool,
+    viewing_stats: bool,
+    viewing_log: bool,
+    stats: Stats,
+    /// The time the event loop received the message it is handling.
+    handling_since: Option<Instant>,
+    /// The id of the open popup and the panel that is selected again once it closes.
+    popup: Option<(usize, Option<usize>)>,
+    /// The id of the scratch terminal, it keeps running whilst it is hidden.
+    scratch: Option<usize>,
+    /// The panels that are closed if the user confirms merging the selected panel.
+    confirming_merge: Option<Vec<usize>>,
+    /// The highlight rules from the config, shared by the panels' screens.
+    highlight_rules: Arc<Vec<HighlightRule>>,
+    /// The hints displayed over the selected panel and the characters typed to select one.
+    hints: Option<(Vec<Hint>, String)>,
+    /// The configured setups of the workspaces that haven't been displayed yet.
+    pending_setups: Vec<WorkspaceSetup>,
+    /// The name of the session, displayed in the title of the terminal's window.
+    session_name: String,
+    /// Set once the terminal's support for the kitty keyboard protocol is known.
+    keyboard_protocol_negotiated: bool,
 }
 
 impl LogicManager {
     /// The length of the scrollback history we track for each panel.
     const SCROLLBACK_LEN: usize = 120;
+    /// The shell used to run background shell commands.
+    const SHELL: &'static str = "/bin/sh";
+    /// The maximum number of characters of a background command's output that are reported.
+    const REPORT_SNIPPET_LEN: usize = 60;
+    /// The sequence the terminal sends before pasted text when bracketed paste is enabled.
+    const PASTE_START: &'static [u8] = b"\x1b[200~";
+    /// The sequence the terminal sends after pasted text when bracketed paste is enabled.
+    const PASTE_END: &'static [u8] = b"\x1b[201~";
+    /// The sequence sent to a panel, or by the terminal, when it gains focus.
+    const FOCUS_IN: &'static [u8] = b"\x1b[I";
+    /// The sequence sent to a panel, or by the terminal, when it loses focus.
+    const FOCUS_OUT: &'static [u8] = b"\x1b[O";
 
     /// Create a new instance of the logic manager from a config file.
-    pub fn new(config: Config, hashed_password: Option<String>) -> Result<Self, MuxideError> {
+    pub fn new(
+        config: Config,
+        hashed_password: Option<String>,
+        session_name: String,
+    ) -> Result<Self, MuxideError> {
         // Create a new channel controller with a stdin transmitter which we will use in the input
         // manager to send stdin input to the channel controller
-        let (connection_manager, stdin_tx) = ChannelController::new();
+        let (connection_manager, stdin_tx) = ChannelController::new(config.tuning().send_timeout());
         let input_manager = InputManager::start(stdin_tx)?;
-        let display = match Display::new(config.clone()).init() {
-            Some(d) => d,
-            None => return Err(ErrorType::DisplayNotRunningError.into_error()),
-        };
+        let mut display = Display::new(config.clone()).init()?;
+        display.set_session_name(session_name.clone());
+        // The rules are checked when the config is loaded.
+        let highlight_rules = Arc::new(config.highlight_rules().unwrap_or_default());
+        let pending_setups = config.workspace_setups().clone();
 
         return Ok(Self {
             config,
@@ -215,38 +419,203 @@ impl LogicManager {
             panels: Vec::new(),
             connection_manager,
             _input_manager: input_manager,
+            input_parser: InputParser::new(),
             display,
             ids: BinaryTreeSet::new(),
             halt_execution: false,
             close_handles: Vec::new(),
             single_key_command: false,
+            pending_sequence: Vec::new(),
+            sequence_deadline: None,
+            key_table: KeyTable::Root,
             password_input: String::new(),
             hashed_password,
             locked: false,
             displaying_help: false,
+            help_scrolled: false,
+            identifying_panels: false,
+            viewing_message_history: false,
+            viewing_stats: false,
+            viewing_log: false,
+            stats: Stats::new(),
+            handling_since: None,
+            popup: None,
+            scratch: None,
+            confirming_merge: None,
+            highlight_rules,
+            hints: None,
+            pending_setups,
+            session_name,
+            keyboard_protocol_negotiated: false,
         });
     }
 
     /// Start the main event loop, essentially the main application logic.
     pub async fn start_event_loop(mut self) -> Result<(), String> {
+        let workspace = self.display.selected_workspace_number();
+
+        if let Err(e) = self.set_up_workspace(workspace) {
+            self.display.set_error_message(e.description());
+        }
+
         loop {
-            if let Err(e) = self.display.render() {
-                if e.should_terminate() {
-                    self.shutdown().await;
-                    break;
-                } else {
-                    self.display.set_error_message(e.description());
+            if self.viewing_stats {
+                self.show_stats();
+            }
+
+            if let Err(e) = self.update_window_title() {
+                self.display.set_error_message(e.description());
+            }
+
+            self.update_mode_indicator();
+
+            let widgets_due = self
+                .display
+                .next_widget_update()
+                .map(|d| Instant::from_std(d) <= Instant::now())
+                .unwrap_or(false);
+
+            if widgets_due {
+                self.display.update_widgets();
+            }
+
+            match self.display.render() {
+                Ok(_) => self.stats.record_frame(),
+                Err(e) => {
+                    if e.should_terminate() {
+                        self.shutdown().await;
+                        break;
+                    } else {
+                        warning!(format!(
+                            "Failed to render. Error: {}",
+                            e.debug_description()
+                        ));
+                        self.display.set_error_message(e.description());
+                    }
                 }
             }
 
-            let res = self.connection_manager.wait_for_message().await;
+            if let Some(since) = self.handling_since.take() {
+                self.stats.record_latency(since.elapsed());
+            }
+
+            // Wake up when an incomplete command sequence, incomplete input or a display effect
+            // expires, or the status bar's widgets are due to be updated.
+            let deadline = [
+                self.sequence_deadline,
+                self.input_parser.deadline().map(Instant::from_std),
+                self.display.next_effect_deadline().map(Instant::from_std),
+                self.display.next_widget_update().map(Instant::from_std),
+                // Refresh the statistics whilst they are displayed.
+                Some(Instant::now() + Stats::INTERVAL).filter(|_| self.viewing_stats),
+            ]
+            .iter()
+            .flatten()
+            .min()
+            .cloned();
+
+            let res = match deadline {
+                None => self.connection_manager.wait_for_message().await,
+                Some(deadline) => {
+                    match tokio::time::timeout_at(
+                        deadline,
+                        self.connection_manager.wait_for_message(),
+                    )
+                    .await
+                    {
+                        Ok(res) => res,
+                        Err(_) => {
+                            let sequence_expired = self
+                                .sequence_deadline
+                                .map(|d| d <= Instant::now())
+                                .unwrap_or(false);
+
+                            if sequence_expired {
+                                if let Err(e) = self.complete_pending_sequence() {
+                                    if e.should_terminate() {
+                                        self.shutdown().await;
+                                        break;
+                                    } else {
+                                        self.display.set_error_message(e.description());
+                                    }
+                                }
+
+                                if self.halt_execution {
+                                    self.shutdown().await;
+                                    break;
+                                }
+                            }
+
+                            let input_expired = self
+                                .input_parser
+                                .deadline()
+                                .map(|d| Instant::from_std(d) <= Instant::now())
+                                .unwrap_or(false);
+
+                            if !input_expired {
+                                continue;
+                            }
+
+                            // Incomplete input is handled as if no more input was received.
+                            Ok(ControllerResponse {
+                                bytes: Vec::new(),
+                                frame: None,
+                                blocked_input: None,
+                                id: ChannelID::Stdin,
+                            })
+                        }
+                    }
+                }
+            };
+
+            self.handling_since = Some(Instant::now());
 
             match res {
                 Ok(res) => {
-                    if let ChannelID::Pty(id) = res.id {
-                        self.handle_panel_output(id, res.bytes);
+                    if let (ChannelID::Pty(id), Some(frame)) = (res.id, res.frame) {
+                        self.stats.record_panel_output(
+                            id,
+                            frame.processed_bytes,
+                            frame.processed_lines,
+                        );
+
+                        if let Err(e) = self.handle_panel_output(id, frame) {
+                            if e.should_terminate() {
+                                self.shutdown().await;
+                                break;
+                            } else {
+                                self.display.set_error_message(e.description());
+                            }
+                        }
+                    } else if let (ChannelID::Pty(id), Some(waiting)) = (res.id, res.blocked_input)
+                    {
+                        self.display.set_message(
+                            MessageLevel::Warning,
+                            format!(
+                                "Panel {} isn't accepting input, {} bytes are waiting to be sent",
+                                id, waiting
+                            ),
+                        );
+                    } else if res.id == ChannelID::Background {
+                        self.display.set_message(
+                            MessageLevel::Info,
+                            String::from_utf8_lossy(&res.bytes).to_string(),
+                        );
+                    } else if res.id == ChannelID::TerminalResize {
+                        if let Err(e) = self.handle_terminal_resize().await {
+                            if e.should_terminate() {
+                                self.shutdown().await;
+                                break;
+                            } else {
+                                self.display.set_error_message(e.description());
+                            }
+                        }
                     } else {
                         let displaying_help = self.displaying_help;
+                        let identifying_panels = self.identifying_panels;
+                        let viewing_message_history = self.viewing_message_history;
+                        let viewing_stats = self.viewing_stats;
+                        let viewing_log = self.viewing_log;
 
                         if let Err(e) = self.handle_stdin(res.bytes).await {
                             if e.should_terminate() {
@@ -257,16 +626,36 @@ impl LogicManager {
                             }
                         } else {
                             if displaying_help {
-                                self.displaying_help = false;
-                                self.display.hide_help();
-                            } else {
-                                self.display.clear_error_message();
+                                // Scrolling, or starting a command that may scroll, keeps the
+                                // help message open.
+                                if self.help_scrolled || self.single_key_command {
+                                    self.help_scrolled = false;
+                                } else {
+                                    self.displaying_help = false;
+                                    self.display.hide_help();
+                                }
+                            } else if identifying_panels {
+                                self.identifying_panels = false;
+                                self.display.hide_panel_identifiers();
+                            } else if viewing_message_history {
+                                self.viewing_message_history = false;
+                                self.display.hide_message_history();
+                            } else if viewing_stats {
+                                self.viewing_stats = false;
+                                self.display.hide_stats();
+                            } else if viewing_log {
+                                self.viewing_log = false;
+                                self.display.hide_log();
                             }
                         }
                     }
                 }
                 Err(details) => {
                     if let ChannelID::Pty(id) = details.id {
+                        if let Some(e) = details.error.as_ref() {
+                            error!(format!("Panel {} closed with error: {:?}", id, e));
+                        }
+
                         if let Err(e) = self.remove_panel(id) {
                             if e.should_terminate() {
                                 self.shutdown().await;
@@ -300,227 +689,1022 @@ impl LogicManager {
         return Ok(());
     }
 
-    async fn handle_stdin(&mut self, mut bytes: Vec<u8>) -> Result<(), MuxideError> {
-        if bytes.is_empty() {
-            return Ok(());
+    async fn handle_stdin(&mut self, bytes: Vec<u8>) -> Result<(), MuxideError> {
+        // No bytes are received when the input parser's deadline expires.
+        let events = if bytes.is_empty() {
+            self.input_parser.flush()
+        } else {
+            self.input_parser.feed(&bytes)
+        };
+
+        for event in events {
+            self.handle_input_event(event).await?;
         }
 
+        return Ok(());
+    }
+
+    async fn handle_input_event(&mut self, event: InputEvent) -> Result<(), MuxideError> {
+        let mut grapheme = None;
+        let mut modified = None;
+        let (key, bytes) = match event {
+            InputEvent::Key(key, bytes) => (Some(key), bytes),
+            InputEvent::Unknown(bytes) => (None, bytes),
+            // A composed character isn't a key, so it ends a command sequence as a whole rather
+            // than part of it being sent to the panel.
+            InputEvent::Grapheme(text) => {
+                let bytes = text.as_bytes().to_vec();
+                grapheme = Some(text);
+                (None, bytes)
+            }
+            InputEvent::Modified(key, bytes) => {
+                modified = Some(key);
+                (None, bytes)
+            }
+            InputEvent::KeyboardProtocolSupported => {
+                self.keyboard_protocol_negotiated = true;
+                info!("The terminal supports the kitty keyboard protocol.");
+                return Ok(());
+            }
+            // The terminal replies to the device attributes query after the kitty keyboard
+            // protocol query, so without a reply to that query it doesn't support the protocol.
+            InputEvent::DeviceAttributes => {
+                if !self.keyboard_protocol_negotiated
+                    && self.config.get_environment_ref().keyboard_protocol()
+                        == KeyboardProtocol::Auto
+                {
+                    info!("The terminal doesn't support the kitty keyboard protocol, enabling modifyOtherKeys.");
+                    TerminalGuard::enable_modify_other_keys();
+                }
+
+                self.keyboard_protocol_negotiated = true;
+                return Ok(());
+            }
+            InputEvent::Paste(text) => return self.handle_paste(text).await,
+            // The terminal gaining or losing focus is reported to the selected panel.
+            InputEvent::FocusGained | InputEvent::FocusLost => {
+                if let Some(id) = self.selected_panel {
+                    self.send_focus_event(id, event == InputEvent::FocusGained);
+                }
+
+                return Ok(());
+            }
+        };
+
         if self.single_key_command {
-            let ch = bytes.remove(0) as char;
-            self.single_key_command = false;
+            return self.handle_sequence_key(key, modified);
+        }
 
-            let cmd = self.process_single_key_command(ch)?;
-            self.execute_command(&cmd)?;
+        if let Some(ids) = self.confirming_merge.take() {
+            return self.confirm_merge(key, ids);
         }
 
-        // If there was a number of bytes built-up deal with them still.
-        if bytes.is_empty() {
-            return Ok(());
+        if let Some((hints, typed)) = self.hints.take() {
+            return self.select_hint(key, hints, typed);
         }
 
-        let event = match event::parse_event(
-            *bytes.first().unwrap(),
-            &mut bytes[1..bytes.len()].iter().map(|b| Ok(*b)),
-        ) {
-            Ok(e) => e,
-            Err(e) => {
-                return Err(ErrorType::EventParsingError {
-                    message: format!("{}", e),
-                }
-                .into_error())
+        if let Some(cmd) = modified
+            .filter(|_| !self.locked && self.key_table == KeyTable::Root)
+            .and_then(|key| {
+                self.config
+                    .key_map()
+                    .command_for_modified_shortcut(&key)
+                    .cloned()
+            })
+        {
+            if !self.forwards_focus_keys(&cmd) {
+                return self.execute_command(&cmd);
+            }
+        }
+
+        if let Some(key) = key {
+            if self.displaying_help && self.scroll_help_for_key(&key) {
+                return Ok(());
+            }
+
+            if self.shortcut(&key)? {
+                return Ok(());
             }
-        };
 
-        if !self.shortcut(&event)? {
             if self.locked {
-                match event {
-                    Event::Key(k) => match k {
-                        event::Key::Backspace => {
-                            self.password_input.pop();
-                        }
-                        event::Key::Char(ch) => {
-                            if ch == '\n' {
-                                self.check_password()?;
-                            } else {
-                                self.password_input.push(ch);
-                            }
-                        }
-                        _ => (),
-                    },
+                match key {
+                    Key::Backspace => {
+                        self.password_input.pop();
+                    }
+                    Key::Char('\n') => self.check_password()?,
+                    Key::Char(ch) => self.password_input.push(ch),
                     _ => (),
                 }
 
                 return Ok(());
             }
+        } else if self.locked || self.key_table != KeyTable::Root {
+            if let Some(text) = grapheme.filter(|_| self.locked) {
+                self.password_input.push_str(&text);
+            }
 
-            match self.selected_panel {
-                Some(id) => {
-                    self.connection_manager.write_bytes(id, bytes).await?;
-                    self.panel_with_id(id).unwrap().clear_scrollback();
-                }
-                None => (),
+            return Ok(());
+        }
+
+        if let Some(id) = self.input_panel() {
+            self.stats.record_panel_input(id, bytes.len());
+            self.connection_manager.write_bytes(id, bytes).await?;
+
+            if let Some(panel) = self.panel_with_id(id) {
+                panel.screen.lock().clear_scrollback();
             }
         }
 
         return Ok(());
     }
 
-    fn shortcut(&mut self, event: &Event) -> Result<bool, MuxideError> {
-        if let Event::Key(k) = event {
-            if let Some(k) = self
-                .config
-                .key_map()
-                .command_for_shortcut(k)
-                .map(|cmd| cmd.clone())
-            {
-                self.execute_command(&k)?;
-                return Ok(true);
-            } else {
-                return Ok(false);
-            }
-        } else {
-            return Ok(false);
+    /// Adds a key to the command sequence that is being entered, the command is executed once the
+    /// sequence is complete. A key the legacy encoding can't express is only compared with the
+    /// prefix.
+    fn handle_sequence_key(
+        &mut self,
+        key: Option<Key>,
+        modified: Option<ModifiedKey>,
+    ) -> Result<(), MuxideError> {
+        let key_map = self.config.key_map();
+
+        // Pressing the prefix twice sends the prefix to the selected panel.
+        if self.pending_sequence.is_empty()
+            && ((key.is_some() && key == key_map.prefix())
+                || (modified.is_some() && modified == key_map.modified_prefix()))
+        {
+            self.single_key_command = false;
+            return self.execute_command(&Command::SendPrefixCommand);
         }
-    }
 
-    fn handle_panel_output(&mut self, id: usize, bytes: Vec<u8>) {
-        let panel = self.panel_with_id(id).unwrap();
+        let ch = match key {
+            Some(Key::Char(ch)) => Some(ch),
+            Some(key) => config::key_bytes(key)
+                .filter(|b| b.len() == 1)
+                .map(|b| b[0] as char),
+            None => None,
+        };
 
-        panel.parser.process(&bytes);
-        panel.clear_scrollback();
+        let (cmd, has_longer) = match ch {
+            Some(ch) => {
+                self.pending_sequence.push(ch);
 
-        self.update_panel_output(id);
-    }
+                self.config
+                    .key_map()
+                    .command_for_sequence(&self.pending_sequence)
+            }
+            // Keys that can't be part of a sequence end it.
+            None => (None, false),
+        };
 
-    fn update_panel_output(&mut self, id: usize) {
-        let panel = self.panel_with_id(id).unwrap();
+        if has_longer {
+            self.sequence_deadline =
+                Some(Instant::now() + self.config.get_environment_ref().chord_timeout());
+        } else {
+            self.sequence_deadline = None;
+            self.single_key_command = false;
+            let mut sequence: String = self.pending_sequence.drain(..).collect();
 
-        let content = panel
-            .parser
-            .screen()
-            .rows_formatted(0, panel.parser.screen().size().1)
-            .collect();
+            if ch.is_none() {
+                sequence.push_str(
+                    &key.and_then(|k| config::key_to_string(k).ok())
+                        .unwrap_or_default(),
+                );
+            }
 
-        let (curs_row, curs_col) = panel.parser.screen().cursor_position();
-        let cursor_hidden = panel.parser.screen().hide_cursor() || panel.current_scrollback != 0;
+            let cmd = cmd.cloned().ok_or(
+                ErrorType::CommandError {
+                    description: format!("No command mapped to \'{}\'", sequence),
+                }
+                .into_error(),
+            )?;
 
-        self.display.update_panel_content(id, content).unwrap();
+            self.execute_command(&cmd)?;
+        }
 
-        self.display
-            .update_panel_cursor(id, curs_col, curs_row, cursor_hidden);
+        return Ok(());
     }
 
-    fn open_new_panel(&mut self) -> Result<(), MuxideError> {
-        // Checks for an available subdivision
-        let (path, size, origin) = self.display.next_panel_details()?;
-
-        let id = self.get_next_id();
-
-        let (tx, stdin_rx) = self.connection_manager.new_channel(id);
-        let pty = Pty::open(self.config.get_panel_init_command())?;
+    /// Forwards pasted text to the selected panel, it is only wrapped in the bracketed paste
+    /// sequences if the panel's program enabled bracketed paste.
+    async fn handle_paste(&mut self, mut text: Vec<u8>) -> Result<(), MuxideError> {
+        if self.locked {
+            self.password_input.extend(
+                String::from_utf8_lossy(&text)
+                    .chars()
+                    .filter(|ch| !ch.is_control()),
+            );
+        } else if let Some(id) = self.input_panel() {
+            let bracketed = self
+                .panel_with_id(id)
+                .map(|p| p.screen.lock().bracketed_paste())
+                .unwrap_or(false);
+            let mut content = Vec::with_capacity(text.len() + 2 * Self::PASTE_START.len());
+
+            if bracketed {
+                content.extend_from_slice(Self::PASTE_START);
+            }
 
-        let new_sizes = self.display.open_new_panel(id, path, size, origin)?;
-        let new_panel_size = new_sizes.last().unwrap().1;
-        let parser = Parser::new(
-            new_panel_size.get_rows(),
-            new_panel_size.get_cols(),
-            Self::SCROLLBACK_LEN,
-        );
+            content.append(&mut text);
 
-        self.display.update_panel_content(
-            id,
-            parser
-                .screen()
-                .rows_formatted(0, parser.screen().size().1)
-                .collect(),
-        )?;
+            if bracketed {
+                content.extend_from_slice(Self::PASTE_END);
+            }
 
-        // Create a separate thread for interfacing with the new pty.
-        let handle = tokio::spawn(async move {
-            pty_manager(pty, tx, stdin_rx).await;
-        });
+            self.stats.record_panel_input(id, content.len());
+            self.connection_manager.write_bytes(id, content).await?;
 
-        self.close_handles.push((id, handle));
-        self.panels.push(Panel::new(id, parser));
-        self.select_panel(Some(id));
-        futures::executor::block_on(self.resize_panels(new_sizes)).unwrap();
+            if let Some(panel) = self.panel_with_id(id) {
+                panel.screen.lock().clear_scrollback();
+            }
+        }
 
         return Ok(());
     }
 
-    fn close_panel(&mut self, id: usize) -> Result<(), MuxideError> {
-        if self.panel_with_id(id).is_none() {
-            return Err(ErrorType::NoPanelWithIDError { id }.into_error());
-        }
+    /// Scrolls the help message if the key is a navigation key, i.e. the arrow keys. Returns true
+    /// if the help message was scrolled.
+    fn scroll_help_for_key(&mut self, key: &Key) -> bool {
+        let lines = self.config.get_environment_ref().scroll_lines();
 
-        futures::executor::block_on(self.connection_manager.send_shutdown(id));
+        let (up, lines) = match key {
+            Key::Up | Key::Char('k') => (true, 1),
+            Key::Down | Key::Char('j') => (false, 1),
+            Key::PageUp => (true, lines),
+            Key::PageDown => (false, lines),
+            _ => return false,
+        };
 
-        return self.remove_panel(id);
+        self.display.scroll_help(up, lines);
+        self.help_scrolled = true;
+
+        return true;
     }
 
-    fn scroll_panel(&mut self, id: usize, up: bool) -> Result<(), MuxideError> {
-        let lines = self.config.get_environment_ref().scroll_lines();
+    fn shortcut(&mut self, key: &Key) -> Result<bool, MuxideError> {
+        if let Some(cmd) = self
+            .config
+            .key_map()
+            .command_for_table_key(self.key_table, key)
+            .cloned()
+        {
+            if self.key_table == KeyTable::Root && self.forwards_focus_keys(&cmd) {
+                return Ok(false);
+            }
 
-        if let Some(panel) = self.panel_with_id(id) {
-            if up {
-                panel.scroll_up(lines);
-            } else {
-                panel.scroll_down(lines);
+            self.execute_command(&cmd)?;
+            return Ok(true);
+        } else if self.key_table != KeyTable::Root {
+            // Keys that aren't mapped in a key table are not sent to the panel, escape returns
+            // to the root table.
+            if key == &Key::Esc {
+                self.key_table = KeyTable::Root;
             }
 
-            return Ok(());
+            return Ok(true);
         } else {
-            return Err(ErrorType::NoPanelWithIDError { id }.into_error());
+            return Ok(false);
         }
     }
 
-    /// This method is primarily used when a panel closes unexpectedly
-    fn remove_panel(&mut self, id: usize) -> Result<(), MuxideError> {
-        self.display.close_panel(id)?;
-
-        for i in 0..self.close_handles.len() {
-            if self.close_handles[i].0 == id {
-                self.close_handles.remove(i);
-                break;
-            }
+    /// Returns true if a shortcut mapped to a command that moves the focus between panels should
+    /// be sent to the selected panel instead, because its foreground process, i.e. vim, handles
+    /// the same keys to move between its own windows.
+    fn forwards_focus_keys(&self, cmd: &Command) -> bool {
+        match cmd {
+            Command::FocusPanelLeftCommand
+            | Command::FocusPanelRightCommand
+            | Command::FocusPanelUpCommand
+            | Command::FocusPanelDownCommand => (),
+            _ => return false,
         }
 
-        for i in 0..self.panels.len() {
-            if self.panels[i].id == id {
-                self.panels.remove(i);
-                break;
-            }
+        let processes = self
+            .config
+            .get_environment_ref()
+            .focus_passthrough_processes();
+        if processes.is_empty() {
+            return false;
         }
 
-        if let Some(sel_id) = self.selected_panel {
-            if sel_id == id {
-                self.select_panel(self.panels.first().map(|p| p.id));
-            }
+        return self
+            .selected_panel
+            .and_then(|id| self.panels.iter().find(|p| p.id == id))
+            .and_then(|panel| Pty::foreground_process_name(panel.pty_fd))
+            .is_some_and(|name| processes.contains(&name));
+    }
+
+    fn handle_panel_output(&mut self, id: usize, frame: PanelFrame) -> Result<(), MuxideError> {
+        // The hidden scratch terminal's output is displayed once it is shown again.
+        if self.scratch == Some(id) && self.display.popup_id() != Some(id) {
+            return Ok(());
         }
 
-        self.ids.remove(&id);
+        self.update_ssh_command(id);
+        self.display.update_panel_content(id, frame.rows)?;
+
+        self.display
+            .update_panel_cursor(id, frame.cursor_col, frame.cursor_row, frame.hide_cursor);
+        self.display
+            .update_panel_cursor_style(id, frame.cursor_style);
+        self.display.update_panel_graphics(id, frame.graphics);
 
         return Ok(());
     }
 
-    fn process_single_key_command(&self, character: char) -> Result<Command, MuxideError> {
-        return self
-            .config
-            .key_map()
-            .command_for_character(&character)
-            .map(|cmd| cmd.clone())
-            .ok_or(
-                ErrorType::CommandError {
-                    description: format!("No command mapped to \'{}\'", character),
-                }
-                .into_error(),
-            );
-    }
+    /// Track the ssh command running in a panel, its destination is displayed in the panel's title
+    /// whilst it runs, and the name of the panel's foreground process if the panels' labels are
+    /// shown. The foreground process is only inspected once it changes.
+    fn update_ssh_command(&mut self, id: usize) {
+        let show_labels = self.config.get_environment_ref().show_panel_labels();
+        let panel = match self.panels.iter_mut().find(|p| p.id == id) {
+            Some(panel) => panel,
+            None => return,
+        };
+        let foreground = Pty::foreground_process_group(panel.pty_fd);
+        let mut closed = None;
 
-    fn execute_command(&mut self, cmd: &Command) -> Result<(), MuxideError> {
-        if self.locked {
-            return Err(ErrorType::DisplayLocked.into_error());
-        }
+        if foreground != panel.foreground {
+            panel.foreground = foreground;
+
+            if show_labels {
+                self.display
+                    .set_panel_command(id, Pty::foreground_process_name(panel.pty_fd));
+            }
+
+            let args = foreground.and_then(Pty::process_args);
+            let command = args.and_then(|args| ssh::destination(&args).map(|d| (args, d)));
+
+            if command.is_some() {
+                panel.ssh = command;
+                panel.ssh_running = true;
+            } else if panel.ssh_running {
+                panel.ssh_running = false;
+                closed = panel
+                    .ssh
+                    .as_ref()
+                    .map(|(_, destination)| destination.clone());
+            }
+        }
+
+        let title = panel
+            .ssh
+            .as_ref()
+            .filter(|_| panel.ssh_running)
+            .map(|(_, destination)| format!("ssh: {}", destination));
+        self.display.set_panel_title(id, title);
+
+        if let Some(destination) = closed {
+            self.display.set_message(
+                MessageLevel::Info,
+                format!(
+                    "The connection to {} in panel {} closed, ReconnectPanel connects again",
+                    destination, id
+                ),
+            );
+        }
+    }
+
+    /// Show "CMD" and the keys entered so far whilst a command is being entered after the prefix,
+    /// or the name of the active key table.
+    fn update_mode_indicator(&mut self) {
+        let indicator = if self.single_key_command {
+            let sequence: String = self.pending_sequence.iter().collect();
+
+            Some(format!("CMD {}", sequence).trim_end().to_string())
+        } else if self.key_table != KeyTable::Root {
+            Some(self.key_table.to_string().to_uppercase())
+        } else {
+            None
+        };
+
+        self.display.set_mode_indicator(indicator);
+    }
+
+    /// Set the title of the terminal's window from the selected panel. The title set by the
+    /// panel's program is used, otherwise the title displayed on its border.
+    fn update_window_title(&mut self) -> Result<(), MuxideError> {
+        let title = match self.selected_panel {
+            Some(id) => self
+                .panel_with_id(id)
+                .map(|panel| panel.screen.lock().title())
+                .filter(|title| !title.is_empty())
+                .or_else(|| self.display.panel_title(id))
+                .unwrap_or_default(),
+            None => String::new(),
+        };
+
+        if let Some(title) = self.config.get_environment_ref().window_title(
+            &self.session_name,
+            &title,
+            self.display.selected_workspace_number(),
+        ) {
+            self.display.set_window_title(&title)?;
+        }
+
+        return Ok(());
+    }
+
+    /// Run the last ssh command of a panel again in its shell, once it is no longer connected.
+    fn reconnect_panel(&mut self, id: usize) -> Result<(), MuxideError> {
+        let panel = self
+            .panel_with_id(id)
+            .ok_or(ErrorType::NoPanelWithIDError { id }.into_error())?;
+
+        if panel.ssh_running {
+            return Err(ErrorType::SshCommandRunning(id).into_error());
+        }
+
+        let (args, destination) = panel
+            .ssh
+            .clone()
+            .ok_or(ErrorType::NoSshCommand(id).into_error())?;
+
+        // Clear anything typed at the prompt so the command runs on its own.
+        let mut bytes = vec![0x15];
+        bytes.extend_from_slice(ssh::command_line(&args).as_bytes());
+        bytes.push(b'\r');
+
+        futures::executor::block_on(self.connection_manager.write_bytes(id, bytes))?;
+        self.display.set_message(
+            MessageLevel::Info,
+            format!("Reconnecting to {}", destination),
+        );
+
+        return Ok(());
+    }
+
+    /// Render a panel's current contents, used when they change outside of its pty task, e.g.
+    /// when it is scrolled.
+    fn update_panel_output(&mut self, id: usize) -> Result<(), MuxideError> {
+        let frame = self
+            .panel_with_id(id)
+            .ok_or(ErrorType::NoPanelWithIDError { id }.into_error())?
+            .screen
+            .lock()
+            .frame();
+
+        return self.handle_panel_output(id, frame);
+    }
+
+    fn open_new_panel(&mut self) -> Result<(), MuxideError> {
+        // Checks for an available subdivision
+        let (path, size, origin) = self.display.next_panel_details()?;
+        let pty = self.open_init_command_pty()?;
+
+        let id = self.open_panel_at(pty, path, size, origin)?;
+        self.select_panel(Some(id));
+
+        return Ok(());
+    }
+
+    /// The environment of the process in the next panel that is opened. TERM describes muxide
+    /// rather than the outer terminal, COLORTERM is only set if the outer terminal supports 24 bit
+    /// colors, and MUXIDE and MUXIDE_PANEL_ID identify the session and panel to scripts.
+    fn pty_environment(&self) -> PtyEnvironment {
+        let mut environment = PtyEnvironment::default()
+            .set("MUXIDE", &self.session_name)
+            .set("MUXIDE_PANEL_ID", self.get_next_id());
+
+        if let Some(term) = self.config.get_environment_ref().panel_term() {
+            environment = environment.set("TERM", term);
+        }
+
+        if color::truecolor_supported() {
+            return environment.set("COLORTERM", "truecolor");
+        } else {
+            return environment.remove("COLORTERM");
+        }
+    }
+
+    /// Opens a pty running the panel init command. If it can't be spawned the user's shell and then
+    /// /bin/sh are tried instead, with a warning, so a mistake in the config doesn't leave muxide
+    /// without panels.
+    fn open_init_command_pty(&mut self) -> Result<Pty, MuxideError> {
+        let command = self.config.get_panel_init_command().clone();
+        let environment = self.pty_environment();
+        let error = match Pty::open(&command, &environment) {
+            Ok(pty) => return Ok(pty),
+            Err(e) => match e.kind() {
+                ErrorType::PTYSpawnError { .. } => e,
+                _ => return Err(e),
+            },
+        };
+
+        warning!(format!(
+            "Failed to spawn the panel init command '{}': {}",
+            command, error
+        ));
+
+        let mut fallbacks: Vec<String> = std::env::var("SHELL")
+            .into_iter()
+            .filter(|shell| !shell.is_empty())
+            .collect();
+        fallbacks.push(String::from(Self::SHELL));
+        fallbacks.retain(|fallback| *fallback != command);
+        fallbacks.dedup();
+
+        for fallback in fallbacks {
+            match Pty::open(&fallback, &environment) {
+                Ok(pty) => {
+                    warning!(format!("Using {} as the panel init command", fallback));
+                    self.display.set_message(
+                        MessageLevel::Warning,
+                        format!("Failed to run '{}', using {} instead", command, fallback),
+                    );
+
+                    return Ok(pty);
+                }
+                Err(e) => warning!(format!("Failed to spawn {}: {}", fallback, e)),
+            }
+        }
+
+        return Err(error);
+    }
+
+    /// Opens a panel for a pty in the empty space at the path of the selected workspace, returning
+    /// the panel's id.
+    fn open_panel_at(
+        &mut self,
+        pty: Pty,
+        path: SubdivisionPath,
+        size: Size,
+        origin: Point<u16>,
+    ) -> Result<usize, MuxideError> {
+        let id = self.get_next_id();
+
+        let (tx, stdin_rx) = self.connection_manager.new_channel(id);
+        let new_sizes = self.display.open_new_panel(id, path, size, origin)?;
+        let new_panel_size = new_sizes
+            .last()
+            .ok_or(ErrorType::InvalidSubdivisionState.into_error())?
+            .1;
+        let screen = SharedPanelScreen::new(new_panel_size, Self::SCROLLBACK_LEN);
+        screen
+            .lock()
+            .set_highlight_rules(self.highlight_rules.clone());
+        screen
+            .lock()
+            .set_graphics_passthrough(self.config.get_environment_ref().graphics_passthrough());
+        screen.lock().set_history_limit(self.history_limit());
+
+        self.display
+            .update_panel_content(id, screen.lock().frame().rows)?;
+
+        // Create a separate thread for interfacing with the new pty.
+        let pty_fd = pty.as_raw_fd();
+        let pty_screen = screen.clone();
+        let tuning = self.config.tuning();
+        let handle = tokio::spawn(async move {
+            pty_manager(id, pty, pty_screen, tx, stdin_rx, tuning).await;
+        });
+
+        self.close_handles.push((id, handle));
+        self.panels.push(Panel::new(id, screen, pty_fd));
+        self.ids.insert(id);
+        self.stats.record_panel_opened(id);
+        futures::executor::block_on(self.resize_panels(new_sizes))?;
+
+        return Ok(id);
+    }
+
+    /// Display a workspace, opening the panels configured for it the first time it is displayed.
+    fn switch_to_workspace(&mut self, workspace: u8) -> Result<(), MuxideError> {
+        let selected = self.display.switch_to_workspace(workspace)?;
+        self.select_panel(selected);
+
+        return self.set_up_workspace(workspace);
+    }
+
+    /// Split a workspace following its configured layout and run its commands in the panels, if
+    /// it hasn't been set up yet. A workspace that already has panels is left as it is.
+    fn set_up_workspace(&mut self, workspace: u8) -> Result<(), MuxideError> {
+        let setup = match self
+            .pending_setups
+            .iter()
+            .position(|s| s.workspace == workspace)
+        {
+            Some(i) => self.pending_setups.remove(i),
+            None => return Ok(()),
+        };
+
+        if !self.display.workspace_panel_ids().is_empty() {
+            return Ok(());
+        }
+
+        // The layouts are checked when the config is loaded.
+        let layout = setup
+            .layout
+            .as_ref()
+            .and_then(|name| self.config.layout(name))
+            .map(|layout| layout.root.clone())
+            .unwrap_or_default();
+        let spaces = self.display.apply_layout(&layout)?;
+        let layout_commands = layout.leaf_commands();
+        let mut first = None;
+
+        for (i, (path, size, origin)) in spaces.into_iter().enumerate() {
+            // Spaces without a command are left empty.
+            let command = match setup.commands.get(i).or(layout_commands[i].as_ref()) {
+                Some(command) if !command.is_empty() => command.clone(),
+                _ => continue,
+            };
+
+            let pty = Pty::open_with_args(Self::SHELL, &["-c", &command], &self.pty_environment())?;
+            let id = self.open_panel_at(pty, path, size, origin)?;
+            first.get_or_insert(id);
+        }
+
+        if first.is_some() {
+            self.select_panel(first);
+        }
+
+        return Ok(());
+    }
+
+    /// Opens a popup running a command through the shell above the current layout. Input is
+    /// sent to the popup until it closes.
+    fn open_popup(&mut self, command: &str) -> Result<(), MuxideError> {
+        if self.popup.is_some() {
+            return Err(ErrorType::PopupAlreadyOpen.into_error());
+        }
+
+        let (size, _) = self.display.popup_details()?;
+        let pty = Pty::open_with_args(Self::SHELL, &["-c", command], &self.pty_environment())?;
+        self.spawn_popup(pty, size)?;
+
+        return Ok(());
+    }
+
+    /// Shows the scratch terminal above the current layout, starting it if it isn't running, or
+    /// hides it if it is shown. The scratch terminal runs the panel init command.
+    fn toggle_scratch(&mut self) -> Result<(), MuxideError> {
+        match self.scratch {
+            Some(id) if self.display.popup_id() == Some(id) => {
+                self.hide_popup(id);
+            }
+            Some(id) => {
+                if self.popup.is_some() {
+                    return Err(ErrorType::PopupAlreadyOpen.into_error());
+                }
+
+                self.show_popup(id)?;
+            }
+            None => {
+                if self.popup.is_some() {
+                    return Err(ErrorType::PopupAlreadyOpen.into_error());
+                }
+
+                let (size, _) = self.display.popup_details()?;
+                let pty = self.open_init_command_pty()?;
+                self.scratch = Some(self.spawn_popup(pty, size)?);
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Starts the pty task for a popup and shows it, returning the popup's id.
+    fn spawn_popup(&mut self, pty: Pty, size: Size) -> Result<usize, MuxideError> {
+        let id = self.get_next_id();
+
+        let (tx, stdin_rx) = self.connection_manager.new_channel(id);
+        let screen = SharedPanelScreen::new(size, Self::SCROLLBACK_LEN);
+        screen
+            .lock()
+            .set_highlight_rules(self.highlight_rules.clone());
+        screen.lock().set_history_limit(self.history_limit());
+
+        let pty_fd = pty.as_raw_fd();
+        let pty_screen = screen.clone();
+        let tuning = self.config.tuning();
+        let handle = tokio::spawn(async move {
+            pty_manager(id, pty, pty_screen, tx, stdin_rx, tuning).await;
+        });
+
+        self.close_handles.push((id, handle));
+        self.panels.push(Panel::new(id, screen, pty_fd));
+        self.ids.insert(id);
+        self.stats.record_panel_opened(id);
+        self.show_popup(id)?;
+
+        return Ok(id);
+    }
+
+    /// Displays a running panel in the popup and gives it focus.
+    fn show_popup(&mut self, id: usize) -> Result<(), MuxideError> {
+        let (size, origin) = self.display.popup_details()?;
+        self.display.open_popup(id, origin)?;
+        self.popup = Some((id, self.selected_panel));
+        self.change_focus(Some(id));
+        self.update_panel_output(id)?;
+
+        return futures::executor::block_on(self.resize_panels(vec![(id, size)]));
+    }
+
+    /// Stops displaying the popup without closing its panel, focus returns to the panel that was
+    /// selected when it was shown.
+    fn hide_popup(&mut self, id: usize) {
+        if !self.display.close_popup(id) {
+            return;
+        }
+
+        if let Some((_, previous)) = self.popup.take() {
+            let workspace_panels = self.display.workspace_panel_ids();
+            let previous = previous.filter(|p| workspace_panels.contains(p));
+            self.select_panel(previous.or(workspace_panels.first().copied()));
+        }
+    }
+
+    fn close_panel(&mut self, id: usize) -> Result<(), MuxideError> {
+        if self.panel_with_id(id).is_none() {
+            return Err(ErrorType::NoPanelWithIDError { id }.into_error());
+        }
+
+        futures::executor::block_on(self.connection_manager.send_shutdown(id));
+
+        return self.remove_panel(id);
+    }
+
+    fn scroll_panel(&mut self, id: usize, up: bool) -> Result<(), MuxideError> {
+        let lines = self.config.get_environment_ref().scroll_lines();
+
+        if let Some(panel) = self.panel_with_id(id) {
+            if up {
+                panel.screen.lock().scroll_up(lines);
+            } else {
+                panel.screen.lock().scroll_down(lines);
+            }
+
+            return Ok(());
+        } else {
+            return Err(ErrorType::NoPanelWithIDError { id }.into_error());
+        }
+    }
+
+    /// This method is primarily used when a panel closes unexpectedly
+    fn remove_panel(&mut self, id: usize) -> Result<(), MuxideError> {
+        let mut new_sizes = Vec::new();
+        let previous_selection = match self.popup {
+            Some((popup_id, previous)) if popup_id == id => {
+                self.display.close_popup(id);
+                self.popup = None;
+
+                Some(previous)
+            }
+            // The hidden scratch terminal isn't part of the layout.
+            _ if self.scratch == Some(id) => None,
+            _ => {
+                new_sizes = self.display.close_panel(id)?;
+
+                None
+            }
+        };
+
+        for i in 0..self.close_handles.len() {
+            if self.close_handles[i].0 == id {
+                self.close_handles.remove(i);
+                break;
+            }
+        }
+
+        for i in 0..self.panels.len() {
+            if self.panels[i].id == id {
+                // The output is saved in the background, it doesn't delay closing the panel.
+                self.save_scrollback(&self.panels[i]);
+                self.panels.remove(i);
+                break;
+            }
+        }
+
+        self.stats.record_panel_closed(id);
+
+        if self.scratch == Some(id) {
+            self.scratch = None;
+        }
+
+        // Only panels in the current workspace can be selected.
+        let workspace_panels = self.display.workspace_panel_ids();
+
+        if let Some(previous) = previous_selection {
+            // Return focus to the panel that was selected when the popup opened, if it is open.
+            let previous = previous.filter(|p| workspace_panels.contains(p));
+            self.select_panel(previous.or(workspace_panels.first().copied()));
+        } else if let Some(sel_id) = self.selected_panel {
+            if sel_id == id {
+                self.select_panel(workspace_panels.first().copied());
+            }
+        }
+
+        self.ids.remove(&id);
+
+        // The panels that reclaimed the closed panel's space are resized.
+        return futures::executor::block_on(self.resize_panels(new_sizes));
+    }
+
+    /// Closes the panels next to the selected panel if the key is y, the selected panel reclaims
+    /// their space. Any other key cancels the merge.
+    fn confirm_merge(&mut self, key: Option<Key>, ids: Vec<usize>) -> Result<(), MuxideError> {
+        if key != Some(Key::Char('y')) && key != Some(Key::Char('Y')) {
+            self.display
+                .set_message(MessageLevel::Info, String::from("Merge cancelled"));
+            return Ok(());
+        }
+
+        for id in ids {
+            self.close_panel(id)?;
+        }
+
+        return Ok(());
+    }
+
+    /// Adds a key to the label being typed whilst hints are displayed, the hint is copied once its
+    /// label is complete, or opened if the last character was typed in uppercase. Any other key
+    /// hides the hints.
+    fn select_hint(
+        &mut self,
+        key: Option<Key>,
+        hints: Vec<Hint>,
+        mut typed: String,
+    ) -> Result<(), MuxideError> {
+        let ch = match key {
+            Some(Key::Char(ch)) if ch.is_ascii_alphabetic() => ch,
+            _ => {
+                self.display.hide_hints();
+                return Ok(());
+            }
+        };
+
+        typed.push(ch.to_ascii_lowercase());
+
+        match hints.iter().find(|h| h.label == typed) {
+            Some(hint) => {
+                self.display.hide_hints();
+
+                if ch.is_ascii_uppercase() {
+                    self.open_hint(&hint.text);
+                } else {
+                    self.display.copy_to_clipboard(&hint.text)?;
+                    self.display
+                        .set_message(MessageLevel::Info, format!("Copied {}", hint.text));
+                }
+            }
+            None if hints.iter().any(|h| h.label.starts_with(&typed)) => {
+                self.hints = Some((hints, typed));
+            }
+            None => self.display.hide_hints(),
+        }
+
+        return Ok(());
+    }
+
+    /// Start appending a panel's output to a file, or stop if it is already being logged.
+    fn toggle_logging(&mut self, id: usize, path: &str) -> Result<(), MuxideError> {
+        let timestamps = self.config.get_environment_ref().panel_log_timestamps();
+        let panel = self
+            .panel_with_id(id)
+            .ok_or(ErrorType::NoPanelWithIDError { id }.into_error())?;
+
+        if panel.log_path.is_some() {
+            panel.log_path = None;
+            self.display.set_panel_logging(id, false);
+            futures::executor::block_on(
+                self.connection_manager
+                    .write_message(id, ServerMessage::StopLogging),
+            )?;
+            self.display
+                .set_message(MessageLevel::Info, format!("Stopped logging panel {}", id));
+
+            return Ok(());
+        }
+
+        let path = match path.strip_prefix("~/") {
+            Some(relative) => dirs::home_dir()
+                .map(|home| home.join(relative).to_string_lossy().to_string())
+                .unwrap_or(path.to_string()),
+            None => path.to_string(),
+        };
+
+        // The file is opened here so a path that can't be written to is reported, the panel's pty
+        // task opens it again.
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| {
+                ErrorType::IOError {
+                    read: false,
+                    target: path.clone(),
+                    reason: e.to_string(),
+                }
+                .into_error()
+            })?;
+
+        panel.log_path = Some(path.clone());
+        self.display.set_panel_logging(id, true);
+        futures::executor::block_on(self.connection_manager.write_message(
+            id,
+            ServerMessage::StartLogging {
+                path: path.clone(),
+                timestamps,
+            },
+        ))?;
+        self.display.set_message(
+            MessageLevel::Info,
+            format!("Logging panel {} to {}", id, path),
+        );
+
+        return Ok(());
+    }
+
+    /// Opens a panel running tail to follow the file a panel is logged to, the new panel is
+    /// selected.
+    fn follow_log(&mut self, id: usize) -> Result<(), MuxideError> {
+        let path = self
+            .panel_with_id(id)
+            .ok_or(ErrorType::NoPanelWithIDError { id }.into_error())?
+            .log_path
+            .clone()
+            .ok_or(
+                ErrorType::CommandError {
+                    description: format!(
+                        "Panel {} isn't being logged, start logging it with ToggleLogging.",
+                        id
+                    ),
+                }
+                .into_error(),
+            )?;
+        let (subdivision, size, origin) = self.display.next_panel_details()?;
+        let pty = Pty::open_with_args("tail", &["-n", "+1", "-F", &path], &self.pty_environment())?;
+        let follow_id = self.open_panel_at(pty, subdivision, size, origin)?;
+
+        self.panel_with_id(follow_id)
+            .ok_or(ErrorType::NoPanelWithIDError { id: follow_id }.into_error())?
+            .screen
+            .lock()
+            .set_keep_scroll_position(true);
+        self.select_panel(Some(follow_id));
+        self.display
+            .set_message(MessageLevel::Info, format!("Following {}", path));
+
+        return Ok(());
+    }
+
+    /// Opens a URL or path with the configured opener, relative paths are relative to the
+    /// working directory of the selected panel's program when it can be determined.
+    fn open_hint(&mut self, text: &str) {
+        let target = if text.contains("://") {
+            text.to_string()
+        } else if let Some(path) = text.strip_prefix("~/") {
+            dirs::home_dir()
+                .map(|home| home.join(path).to_string_lossy().to_string())
+                .unwrap_or(text.to_string())
+        } else {
+            self.selected_panel
+                .and_then(|id| self.panels.iter().find(|p| p.id == id))
+                .and_then(|panel| Pty::foreground_process_cwd(panel.pty_fd))
+                .map(|cwd| cwd.join(text).to_string_lossy().to_string())
+                .unwrap_or(text.to_string())
+        };
+
+        let command = format!(
+            "{} '{}'",
+            self.config.get_environment_ref().opener(),
+            target.replace('\'', "'\\''")
+        );
+        self.run_shell_command(command);
+    }
+
+    /// Executes the command mapped to the characters entered so far when the chord timeout
+    /// expires, if there is one.
+    fn complete_pending_sequence(&mut self) -> Result<(), MuxideError> {
+        self.single_key_command = false;
+        self.sequence_deadline = None;
+        let sequence: Vec<char> = self.pending_sequence.drain(..).collect();
+
+        let cmd = self
+            .config
+            .key_map()
+            .command_for_sequence(&sequence)
+            .0
+            .cloned()
+            .ok_or(
+                ErrorType::CommandError {
+                    description: format!(
+                        "No command mapped to \'{}\'",
+                        sequence.into_iter().collect::<String>()
+                    ),
+                }
+                .into_error(),
+            )?;
+
+        return self.execute_command(&cmd);
+    }
+
+    fn execute_command(&mut self, cmd: &Command) -> Result<(), MuxideError> {
+        if self.locked {
+            return Err(ErrorType::DisplayLocked.into_error());
+        }
 
         match cmd {
             Command::QuitCommand => {
@@ -532,13 +1716,48 @@ impl LogicManager {
             Command::EnterSingleCharacterCommand => {
                 self.single_key_command = true;
             }
+            Command::SendPrefixCommand => {
+                let key_map = self.config.key_map();
+                let prefix = match key_map.modified_prefix() {
+                    Some(key) => Some(key.legacy_bytes()),
+                    None => key_map.prefix().and_then(config::key_bytes),
+                };
+
+                if let (Some(id), Some(bytes)) = (self.input_panel(), prefix) {
+                    self.stats.record_panel_input(id, bytes.len());
+                    futures::executor::block_on(self.connection_manager.write_bytes(id, bytes))?;
+                }
+            }
             Command::CloseSelectedPanelCommand => {
                 if let Some(panel) = self.selected_panel {
                     self.close_panel(panel)?;
                 }
             }
             Command::FocusWorkspaceCommand(id) => {
-                self.selected_panel = self.display.switch_to_workspace(*id as u8)?;
+                self.switch_to_workspace(*id as u8)?;
+            }
+            Command::NextWorkspaceCommand | Command::PreviousWorkspaceCommand => {
+                let workspace = self
+                    .display
+                    .adjacent_workspace(*cmd == Command::NextWorkspaceCommand);
+                self.switch_to_workspace(workspace)?;
+            }
+            Command::FocusLastWorkspaceCommand => {
+                if let Some(workspace) = self.display.last_workspace() {
+                    self.switch_to_workspace(workspace)?;
+                }
+            }
+            Command::BreakPanelCommand => {
+                let (id, new_sizes) = self.display.break_selected_panel()?;
+
+                futures::executor::block_on(self.resize_panels(new_sizes))?;
+                self.select_panel(Some(id));
+            }
+            Command::JoinPanelCommand(workspace) => {
+                let (id, new_sizes) = self.display.join_panel(*workspace as u8)?;
+
+                futures::executor::block_on(self.resize_panels(new_sizes))?;
+                self.select_panel(Some(id));
             }
             Command::SubdivideSelectedVerticalCommand => {
                 let new_sizes = self.display.subdivide_selected_panel_vertical()?;
@@ -550,68 +1769,286 @@ impl LogicManager {
 
                 futures::executor::block_on(self.resize_panels(new_sizes))?;
             }
+            Command::GrowPanelCommand => {
+                let new_sizes = self.display.resize_selected_panel(true)?;
+
+                futures::executor::block_on(self.resize_panels(new_sizes))?;
+            }
+            Command::ShrinkPanelCommand => {
+                let new_sizes = self.display.resize_selected_panel(false)?;
+
+                futures::executor::block_on(self.resize_panels(new_sizes))?;
+            }
+            Command::UndoLayoutCommand => {
+                let new_sizes = self.display.undo_layout()?;
+
+                futures::executor::block_on(self.resize_panels(new_sizes))?;
+            }
+            Command::NextPanelCommand | Command::PreviousPanelCommand => {
+                let forwards = *cmd == Command::NextPanelCommand;
+
+                if let Some(id) = self.display.adjacent_panel(forwards) {
+                    self.select_panel(Some(id));
+                }
+            }
             Command::FocusPanelLeftCommand => {
                 if let Some(id) = self.display.focus_direction(Direction::Left) {
-                    self.selected_panel = Some(id);
-                    self.display.set_selected_panel(Some(id));
+                    self.select_panel(Some(id));
                 }
             }
             Command::FocusPanelRightCommand => {
                 if let Some(id) = self.display.focus_direction(Direction::Right) {
-                    self.selected_panel = Some(id);
-                    self.display.set_selected_panel(Some(id));
+                    self.select_panel(Some(id));
                 }
             }
             Command::FocusPanelUpCommand => {
                 if let Some(id) = self.display.focus_direction(Direction::Up) {
-                    self.selected_panel = Some(id);
-                    self.display.set_selected_panel(Some(id));
+                    self.select_panel(Some(id));
                 }
             }
             Command::FocusPanelDownCommand => {
                 if let Some(id) = self.display.focus_direction(Direction::Down) {
-                    self.selected_panel = Some(id);
-                    self.display.set_selected_panel(Some(id));
+                    self.select_panel(Some(id));
                 }
             }
             Command::LockCommand => {
                 self.lock();
             }
             Command::MergePanelCommand => {
-                if let Some(new_sizes) = self.display.merge_selected_panel()? {
-                    futures::executor::block_on(self.resize_panels(vec![new_sizes]))?;
+                let siblings = self.display.merge_sibling_ids();
+
+                if siblings.is_empty() {
+                    if let Some(new_sizes) = self.display.merge_selected_panel()? {
+                        futures::executor::block_on(self.resize_panels(vec![new_sizes]))?;
+                    }
+                } else {
+                    let ids: Vec<String> = siblings.iter().map(|id| id.to_string()).collect();
+
+                    self.display.set_message(
+                        MessageLevel::Warning,
+                        format!(
+                            "Merging closes panel {}, press y to confirm",
+                            ids.join(", ")
+                        ),
+                    );
+                    self.confirming_merge = Some(siblings);
                 }
             }
+            Command::ScrollUpCommand if self.displaying_help => {
+                let lines = self.config.get_environment_ref().scroll_lines();
+                self.display.scroll_help(true, lines);
+                self.help_scrolled = true;
+            }
+            Command::ScrollDownCommand if self.displaying_help => {
+                let lines = self.config.get_environment_ref().scroll_lines();
+                self.display.scroll_help(false, lines);
+                self.help_scrolled = true;
+            }
             Command::ScrollUpCommand => {
                 if let Some(id) = self.selected_panel {
                     self.scroll_panel(id, true)?;
-                    self.update_panel_output(id);
+                    self.update_panel_output(id)?;
                 }
             }
             Command::ScrollDownCommand => {
                 if let Some(id) = self.selected_panel {
                     self.scroll_panel(id, false)?;
-                    self.update_panel_output(id);
+                    self.update_panel_output(id)?;
+                }
+            }
+            Command::HintsCommand => {
+                if let Some(id) = self.selected_panel {
+                    let rows = self
+                        .panel_with_id(id)
+                        .ok_or(ErrorType::NoPanelWithIDError { id }.into_error())?
+                        .screen
+                        .lock()
+                        .visible_text();
+                    let hints = hints::find_hints(&rows);
+
+                    if hints.is_empty() {
+                        self.display.set_message(
+                            MessageLevel::Info,
+                            String::from("No URLs or paths found"),
+                        );
+                    } else {
+                        self.display.show_hints(
+                            id,
+                            hints
+                                .iter()
+                                .map(|h| (h.position, h.label.clone()))
+                                .collect(),
+                        );
+                        self.display.set_message(
+                            MessageLevel::Info,
+                            String::from("Type a hint to copy it, or in uppercase to open it"),
+                        );
+                        self.hints = Some((hints, String::new()));
+                    }
+                }
+            }
+            Command::ToggleLoggingCommand(path) => {
+                if let Some(id) = self.selected_panel {
+                    self.toggle_logging(id, path)?;
+                }
+            }
+            Command::FollowLogCommand => {
+                if let Some(id) = self.selected_panel {
+                    self.follow_log(id)?;
+                }
+            }
+            Command::ReconnectPanelCommand => {
+                if let Some(id) = self.selected_panel {
+                    self.reconnect_panel(id)?;
+                }
+            }
+            Command::PauseOutputCommand => {
+                if let Some(id) = self.selected_panel {
+                    let paused = self
+                        .panel_with_id(id)
+                        .ok_or(ErrorType::NoPanelWithIDError { id }.into_error())?
+                        .screen
+                        .lock()
+                        .toggle_paused();
+
+                    self.display.set_panel_paused(id, paused);
+                    self.update_panel_output(id)?;
+                }
+            }
+            Command::ToggleReadOnlyCommand => {
+                if let Some(id) = self.selected_panel {
+                    let panel = self
+                        .panel_with_id(id)
+                        .ok_or(ErrorType::NoPanelWithIDError { id }.into_error())?;
+                    panel.read_only = !panel.read_only;
+                    let read_only = panel.read_only;
+
+                    self.display.set_panel_read_only(id, read_only);
                 }
             }
             Command::HelpMessageCommand  => {
                 self.displaying_help = true;
                 self.display.show_help();
             }
+            Command::EnterKeyTableCommand(table) => {
+                self.key_table = *table;
+            }
+            Command::IdentifyPanelsCommand => {
+                self.identifying_panels = true;
+                self.display.show_panel_identifiers();
+            }
+            Command::ShowMessageHistoryCommand => {
+                self.viewing_message_history = true;
+                self.display.show_message_history();
+            }
+            Command::ShowStatsCommand => {
+                self.viewing_stats = true;
+                self.show_stats();
+            }
+            Command::ShowLogCommand => {
+                self.viewing_log = true;
+                self.display.show_log(
+                    logging::recent_messages(usize::MAX, Level::Information)
+                        .into_iter()
+                        .map(|(level, message)| format!("[{}] {}", level.label(), message))
+                        .collect(),
+                );
+            }
+            Command::RunShellCommand(command) => {
+                self.run_shell_command(command.clone());
+            }
+            Command::PopupCommand(command) => {
+                self.open_popup(command)?;
+            }
+            Command::ToggleScratchCommand => {
+                self.toggle_scratch()?;
+            }
+            Command::SaveLayoutCommand(name) => {
+                let path = Layout::path_for_name(name).ok_or(
+                    ErrorType::CommandError {
+                        description: "Failed to determine the config directory.".to_string(),
+                    }
+                    .into_error(),
+                )?;
+
+                self.display.current_layout(name).save(&path)?;
+                self.display.set_message(
+                    MessageLevel::Info,
+                    format!("Saved layout to {}", path.to_string_lossy()),
+                );
+            }
+            Command::AliasCommand(_, commands) => {
+                for cmd in commands {
+                    self.execute_command(cmd)?;
+                }
+            }
         }
 
         return Ok(());
     }
 
+    /// Spawns a shell command in the background, it is not attached to a panel. Once it exits, its
+    /// status and the start of its output are reported to the event loop to be displayed.
+    fn run_shell_command(&mut self, command: String) {
+        let tx = self.connection_manager.background_sender();
+
+        tokio::spawn(async move {
+            let output = tokio::process::Command::new(Self::SHELL)
+                .arg("-c")
+                .arg(&command)
+                .stdin(std::process::Stdio::null())
+                .output()
+                .await;
+
+            let report = match output {
+                Ok(output) => {
+                    let text = if output.stdout.is_empty() {
+                        String::from_utf8_lossy(&output.stderr).to_string()
+                    } else {
+                        String::from_utf8_lossy(&output.stdout).to_string()
+                    };
+                    let snippet: String = text
+                        .lines()
+                        .find(|l| !l.trim().is_empty())
+                        .unwrap_or("")
+                        .chars()
+                        .take(Self::REPORT_SNIPPET_LEN)
+                        .collect();
+
+                    match output.status.code() {
+                        Some(code) if snippet.is_empty() => {
+                            format!("`{}` exited with status {}", command, code)
+                        }
+                        Some(code) => {
+                            format!("`{}` exited with status {}: {}", command, code, snippet)
+                        }
+                        None => format!("`{}` was terminated by a signal", command),
+                    }
+                }
+                Err(e) => format!("Failed to run `{}`. Error: {}", command, e),
+            };
+
+            info!(report.clone());
+            let _ = tx.send(report.into_bytes()).await;
+        });
+    }
+
     fn check_password(&mut self) -> Result<(), MuxideError> {
         if let Some(comp) = self.hashed_password.as_ref() {
-            if hasher::check_password(
-                &self.password_input,
-                self.config.get_password_ref(),
-                comp.as_str(),
-            )
-            .ok_or(ErrorType::FailedToCheckPassword.into_error())?
+            if hasher::check_password(&self.password_input, comp.as_str())
+                .ok_or(ErrorType::FailedToCheckPassword.into_error())?
             {
+                if hasher::needs_rehash(comp, self.config.get_password_ref()) {
+                    // The password was correct, so failing to update the stored hash shouldn't
+                    // keep the user locked out.
+                    if let Err(e) = self.rehash_password() {
+                        self.display.set_message(
+                            MessageLevel::Warning,
+                            format!("Failed to re-hash the password. {}", e.description()),
+                        );
+                    }
+                }
+
                 self.unlock();
             } else {
                 self.password_input = String::new();
@@ -624,6 +2061,29 @@ impl LogicManager {
         return Ok(());
     }
 
+    /// Hashes the entered password with the current password settings and replaces the stored
+    /// hash, used when the stored hash was created with different settings.
+    fn rehash_password(&mut self) -> Result<(), MuxideError> {
+        let settings = self.config.get_password_ref();
+        let path = settings.password_file_location().clone();
+        let hash = hasher::hash_password(&self.password_input, settings)
+            .ok_or(ErrorType::FailedToCheckPassword.into_error())?;
+
+        std::fs::write(&path, &hash).map_err(|e| {
+            ErrorType::IOError {
+                read: false,
+                target: path.clone(),
+                reason: e.to_string(),
+            }
+            .into_error()
+        })?;
+
+        info!(format!("Re-hashed the password stored in {}", path));
+        self.hashed_password = Some(hash);
+
+        return Ok(());
+    }
+
     fn unlock(&mut self) {
         self.display.unlock();
         self.locked = false;
@@ -635,20 +2095,18 @@ impl LogicManager {
         self.locked = true;
     }
 
-    async fn resize_panels(&mut self, panels: Vec<(usize, Size)>) -> Result<(), MuxideError> {
-        for (id, size) in panels {
-            let mut ok = false;
-
-            for panel in &mut self.panels {
-                if panel.id == id {
-                    ok = true;
+    /// Fit the layout to the resized terminal, each split keeps its ratio, and resize the panels'
+    /// ptys to match.
+    async fn handle_terminal_resize(&mut self) -> Result<(), MuxideError> {
+        let new_sizes = self.display.resize_to_terminal()?;
 
-                    panel.parser.set_size(size.get_rows(), size.get_cols());
-                    break;
-                }
-            }
+        return self.resize_panels(new_sizes).await;
+    }
 
-            if !ok {
+    /// Resize the panels' ptys, their screens are resized by their pty tasks.
+    async fn resize_panels(&mut self, panels: Vec<(usize, Size)>) -> Result<(), MuxideError> {
+        for (id, size) in panels {
+            if self.panel_with_id(id).is_none() {
                 return Err(ErrorType::NoPanelWithIDError { id }.into_error());
             }
 
@@ -658,64 +2116,150 @@ impl LogicManager {
         return Ok(());
     }
 
+    /// The amount of output kept for each panel to save when it closes, None if it isn't saved.
+    fn history_limit(&self) -> Option<usize> {
+        let environment = self.config.get_environment_ref();
+
+        return environment
+            .scrollback_file(&self.session_name, 0)
+            .map(|_| environment.scrollback_max_size());
+    }
+
+    /// Starts saving the panel's recent output to its scrollback file, returning the task saving
+    /// it. Returns None if output isn't saved.
+    fn save_scrollback(&self, panel: &Panel) -> Option<JoinHandle<()>> {
+        let path = self
+            .config
+            .get_environment_ref()
+            .scrollback_file(&self.session_name, panel.id)?;
+        let output = panel.screen.lock().history();
+
+        return Some(tokio::spawn(async move {
+            if let Err(e) = panel_log::save_output(&path, &output).await {
+                error!(format!(
+                    "Failed to save the panel's output. Error: {}",
+                    e.description()
+                ));
+            }
+        }));
+    }
+
     async fn shutdown(self) {
+        // Every panel's output is saved before muxide exits, the output kept for each panel is
+        // limited so this doesn't take long.
+        let saves: Vec<JoinHandle<()>> = self
+            .panels
+            .iter()
+            .filter_map(|panel| self.save_scrollback(panel))
+            .collect();
+
         self.connection_manager.shutdown_all().await;
+        futures::future::join_all(saves).await;
         //self.close_handles.pop().unwrap().await;
     }
 
     fn select_panel(&mut self, id: Option<usize>) {
-        self.selected_panel = id;
+        // Whilst a popup is open it keeps focus, the selection is restored once it closes.
+        if let Some((_, previous)) = self.popup.as_mut() {
+            *previous = id;
+            self.display.set_selected_panel(id);
+
+            return;
+        }
+
+        self.change_focus(id);
         self.display.set_selected_panel(self.selected_panel);
     }
 
-    fn panel_with_id(&mut self, id: usize) -> Option<&mut Panel> {
-        for panel in &mut self.panels {
-            if panel.id == id {
-                return Some(panel);
-            }
+    /// Set the panel that receives input, notifying the previous and new panels of the change.
+    fn change_focus(&mut self, id: Option<usize>) {
+        if self.selected_panel == id {
+            return;
         }
 
-        return None;
+        if let Some(previous) = self.selected_panel {
+            self.send_focus_event(previous, false);
+        }
+
+        self.selected_panel = id;
+
+        if let Some(id) = id {
+            self.send_focus_event(id, true);
+        }
     }
 
-    fn get_next_id(&mut self) -> usize {
-        let mut rng = rand::thread_rng();
-        let mut next_id: usize = rng.gen();
+    /// Tell a panel it gained or lost focus, if its program requested focus reporting.
+    fn send_focus_event(&mut self, id: usize, focused: bool) {
+        let reporting = self
+            .panel_with_id(id)
+            .map(|p| p.screen.lock().focus_reporting())
+            .unwrap_or(false);
 
-        while self.ids.contains(&next_id) {
-            next_id = rng.gen();
+        if !reporting {
+            return;
         }
 
-        return next_id;
+        let sequence = if focused {
+            Self::FOCUS_IN
+        } else {
+            Self::FOCUS_OUT
+        };
+
+        if let Err(e) =
+            futures::executor::block_on(self.connection_manager.write_bytes(id, sequence.to_vec()))
+        {
+            error!(format!(
+                "Failed to send a focus event to panel {}. Error: {}",
+                id,
+                e.description()
+            ));
+        }
     }
-}
 
-impl Panel {
-    pub fn new(id: usize, parser: Parser) -> Self {
-        return Self {
-            parser,
-            id,
-            current_scrollback: 0,
-        };
+    /// Display the current stats, with the messages queued in each panel's channels.
+    fn show_stats(&mut self) {
+        self.stats
+            .record_channel_stats(self.connection_manager.channel_stats());
+        self.display
+            .show_stats(self.stats.report(std::time::Instant::now()));
+    }
+
+    /// Returns the id of the selected panel if input can be sent to it.
+    fn input_panel(&self) -> Option<usize> {
+        return self.selected_panel.filter(|id| {
+            self.panels
+                .iter()
+                .any(|panel| panel.id == *id && !panel.read_only)
+        });
+    }
+
+    fn panel_with_id(&mut self, id: usize) -> Option<&mut Panel> {
+        return self.panels.iter_mut().find(|panel| panel.id == id);
     }
 
-    pub fn scroll_up(&mut self, lines: usize) {
-        self.current_scrollback += lines;
-        let previous = self.parser.screen().scrollback();
-        self.parser.set_scrollback(self.current_scrollback);
+    /// Returns the lowest id, starting from 1, that isn't used by an open panel.
+    fn get_next_id(&self) -> usize {
+        let mut next_id = 1;
 
-        if self.parser.screen().scrollback() == previous {
-            self.current_scrollback -= lines;
+        while self.ids.contains(&next_id) {
+            next_id += 1;
         }
-    }
 
-    pub fn scroll_down(&mut self, lines: usize) {
-        self.current_scrollback = self.current_scrollback.checked_sub(lines).unwrap_or(0);
-        self.parser.set_scrollback(self.current_scrollback);
+        return next_id;
     }
+}
 
-    pub fn clear_scrollback(&mut self) {
-        self.current_scrollback = 0;
-        self.parser.set_scrollback(self.current_scrollback);
+impl Panel {
+    pub fn new(id: usize, screen: SharedPanelScreen, pty_fd: RawFd) -> Self {
+        return Self {
+            screen,
+            id,
+            pty_fd,
+            log_path: None,
+            foreground: None,
+            ssh: None,
+            ssh_running: false,
+            read_only: false,
+        };
     }
 }
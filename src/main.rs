@@ -1,8 +1,14 @@
-use clap::{App, Arg};
-use crossterm::{execute, terminal};
-use muxide::{Config, LogicManager, PasswordSettings};
+#![allow(
+    clippy::needless_return,
+    clippy::enum_variant_names,
+    clippy::redundant_static_lifetimes,
+    clippy::module_inception
+)]
+
+use clap::{App, Arg, SubCommand};
+use muxide::{error, info, warning};
+use muxide::{log_rotation, session, Config, Muxide, PasswordSettings};
 use muxide_logging::log::LogLevel;
-use muxide_logging::{error, info, warning};
 use std::path::Path;
 use std::process::exit;
 use std::{fs::File, io::Write};
@@ -49,6 +55,18 @@ fn main() {
                 .takes_value(false)
                 .help("Print the default config to stdout."),
         )
+        .arg(
+            Arg::with_name("init-config")
+                .long("init-config")
+                .takes_value(false)
+                .help("Write the default config to the default config path."),
+        )
+        .arg(
+            Arg::with_name("list-keys")
+                .long("list-keys")
+                .takes_value(false)
+                .help("Print the configured key bindings to stdout."),
+        )
         .arg(
             Arg::with_name("config-format")
                 .long("config-format")
@@ -65,18 +83,79 @@ fn main() {
                 .takes_value(false)
                 .help("Set a new lockscreen password."),
         )
+        .arg(
+            Arg::with_name("session")
+                .short("s")
+                .long("session")
+                .takes_value(true)
+                .max_values(1)
+                .value_name("NAME")
+                .help("Sets the name of the session."),
+        )
+        .subcommand(
+            SubCommand::with_name("list-sessions")
+                .about("List the known sessions and whether they are still running."),
+        )
+        .subcommand(
+            SubCommand::with_name("doctor")
+                .about("Check that muxide can run in this environment and report any problems."),
+        )
         .get_matches();
 
+    if matches.subcommand_matches("list-sessions").is_some() {
+        list_sessions();
+        return;
+    }
+
     if matches.is_present("print-config") {
         print_default_config(matches.value_of("config-format").unwrap_or("TOML"));
         return;
     }
 
+    if matches.is_present("init-config") {
+        init_config(matches.value_of("config-format").unwrap_or("TOML"));
+        return;
+    }
+
+    // Only use the format argument if it was explicitly provided, otherwise the format is
+    // determined from the config file that is found.
+    let config_format = if matches.occurrences_of("config-format") > 0 {
+        matches.value_of("config-format")
+    } else {
+        None
+    };
+
+    if matches.subcommand_matches("doctor").is_some() {
+        doctor(read_config(
+            matches.value_of("config").map(|s| s.to_string()),
+            config_format,
+        ));
+        return;
+    }
+
     let mut config = load_config(
         matches.value_of("config").map(|s| s.to_string()),
-        matches.value_of("config-format").unwrap_or("TOML"),
+        config_format,
     );
 
+    config.get_environment_mut_ref().resolve_log_file();
+
+    if matches.is_present("list-keys") {
+        for (i, (category, lines)) in config.key_map().help_sections().into_iter().enumerate() {
+            if i != 0 {
+                println!();
+            }
+
+            println!("{}:", category);
+
+            for line in lines {
+                println!("  {}", line);
+            }
+        }
+
+        return;
+    }
+
     if let Some(log_file) = matches.value_of("log_file") {
         config
             .get_environment_mut_ref()
@@ -93,6 +172,39 @@ fn main() {
     }
 
     if let Some(f) = config.get_environment_ref().log_file() {
+        if let Some(parent) = Path::new(f).parent() {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                eprintln!(
+                    "Failed to create the directory for '{}'. Error description: {}",
+                    f, e
+                );
+                exit(1);
+            }
+        }
+
+        if let Some(max_size) = config.get_environment_ref().log_max_size() {
+            let environment = config.get_environment_ref();
+
+            if let Err(e) = log_rotation::rotate_log_file(
+                Path::new(f),
+                max_size,
+                environment.log_max_files(),
+                environment.log_compress(),
+            ) {
+                eprintln!(
+                    "Failed to rotate the log file. Error description: {}",
+                    e.description()
+                );
+            }
+
+            log_rotation::rotate_on_write(
+                Path::new(f),
+                max_size,
+                environment.log_max_files(),
+                environment.log_compress(),
+            );
+        }
+
         if let Err(e) = muxide_logging::set_output_file(f) {
             eprintln!(
                 "Failed to open '{}' for logging. Error description: {}",
@@ -123,10 +235,42 @@ fn main() {
             }
             _ => (),
         }
+
+        match config.get_environment_ref().log_format() {
+            Ok(format) => {
+                if let Err(e) = muxide_logging::set_format(format) {
+                    eprintln!("Failed to set the log format. Error description: {}", e);
+                    exit(1);
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    if let Err(e) = Muxide::configure_logging(&config) {
+        eprintln!("{}", e);
+        exit(1);
     }
 
     info!("Completed config load.");
 
+    match session::remove_stale_sessions() {
+        Ok(removed) => {
+            for name in removed {
+                info!(format!("Removed stale session: {}", name));
+            }
+        }
+        Err(e) => {
+            warning!(format!(
+                "Failed to remove stale sessions. Reason: {}",
+                e.debug_description()
+            ));
+        }
+    }
+
     let password: Option<String>;
 
     match load_password(config.get_password_ref().password_file_location()) {
@@ -157,125 +301,166 @@ fn main() {
                     }
                 };
             } else {
+                if muxide::hasher::needs_rehash(&pword, config.get_password_ref()) {
+                    let stored = muxide::hasher::detect_algorithm(&pword)
+                        .map(|a| format!("{:?}", a))
+                        .unwrap_or("an unsupported algorithm".to_string());
+
+                    eprintln!(
+                        "Warning: The stored password was hashed using {} or different parameters to the [password] config. It will be re-hashed after the next successful unlock.",
+                        stored
+                    );
+                    warning!(format!(
+                        "Stored password hash ({}) does not match the password config",
+                        stored
+                    ));
+                }
+
                 password = Some(pword);
             }
         }
     }
 
-    let rt = tokio::runtime::Builder::new_multi_thread()
-        .enable_io()
-        .enable_time()
-        .build()
-        .unwrap();
+    // The socket is held until muxide exits, marking the session as running.
+    let session = match session::claim_session(matches.value_of("session")) {
+        Ok(session) => session,
+        Err(e) => {
+            eprintln!("Failed to start the session. Error description: {}", e);
+            exit(1);
+        }
+    };
+    info!(format!("Starting session: {}", session.name()));
 
-    rt.enter();
-    if let Some(err) = rt.block_on(async { muxide_start(config, password).await }) {
-        eprintln!("Terminating with error: {}", err);
-        error!(format!("Terminated with error: {}", err));
-    }
-}
+    let mut builder = Muxide::builder()
+        .config(config)
+        .session_name(session.name().to_string());
 
-async fn muxide_start(config: Config, password: Option<String>) -> Option<String> {
-    // We don't care about errors that happen with this function, if it fails that's ok.
-    if let Err(e) = execute!(stdout(), terminal::EnterAlternateScreen) {
-        warning!(format!(
-            "Failed to enter alternate tty screen. Reason: {}",
-            e
-        ));
+    if let Some(password) = password {
+        builder = builder.password(password);
     }
 
-    let logic_manager = LogicManager::new(config, password).unwrap();
-    let err = logic_manager.start_event_loop().await.err();
-
-    // We don't care about errors that happen with this function, if it fails that's ok.
-    if let Err(e) = execute!(
-        stdout(),
-        crossterm::cursor::Show,
-        crossterm::style::ResetColor,
-        terminal::LeaveAlternateScreen
-    ) {
-        warning!(format!(
-            "Failed to leave alternate tty screen. Reason: {}",
-            e
-        ));
+    if let Err(err) = builder.run() {
+        eprintln!("Terminating with error: {}", err);
+        error!(format!("Terminated with error: {}", err));
     }
+}
 
-    return err;
+fn load_config(path: Option<String>, format: Option<&str>) -> Config {
+    return match read_config(path, format) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    };
 }
 
-fn load_config(path: Option<String>, format: &str) -> Config {
+/// Reads and parses the config from the path and in the format provided, otherwise it is
+/// discovered. Returns the default config if there is no config file.
+fn read_config(path: Option<String>, format: Option<&str>) -> Result<Config, String> {
     let path_string;
+    let format = match (path, format) {
+        (Some(path), Some(format)) => {
+            path_string = path;
+            format
+        }
+        (Some(path), None) => {
+            let format = Config::format_for_path(&path).unwrap_or("TOML");
+            path_string = path;
+            format
+        }
+        (None, Some(format)) => {
+            path_string = match Config::default_path(format) {
+                Some(p) => p,
+                None => {
+                    return Err(String::from(
+                        "Could not determine a suitable path for the config file.",
+                    ))
+                }
+            };
 
-    if let Some(path) = path {
-        path_string = path;
-    } else {
-        path_string = match Config::default_path(format) {
-            Some(p) => p,
-            None => {
-                eprintln!("Could not determine a suitable path for the config file.");
-                exit(1);
+            format
+        }
+        (None, None) => match Config::discover_path() {
+            Some((path, format)) => {
+                path_string = path;
+                format
             }
-        };
-    }
+            None => return Ok(Config::default()),
+        },
+    };
 
     let path = Path::new(&path_string);
-    let config;
 
     if !path.exists() {
-        config = Config::default();
-    } else {
-        let mut file = match File::open(path) {
-            Ok(f) => f,
+        return Ok(Config::default());
+    }
+
+    let mut file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => {
+            return Err(format!(
+                "Failed to read config file at path: {}. Error: {}",
+                path_string, e
+            ));
+        }
+    };
+
+    let mut contents = String::new();
+    match file.read_to_string(&mut contents) {
+        Ok(_) => (),
+        Err(e) => {
+            return Err(format!(
+                "Failed to read config file at path: {}. Error: {}",
+                path_string, e
+            ));
+        }
+    }
+
+    let config = match format.to_lowercase().as_str() {
+        "toml" => match Config::from_toml_string(&contents) {
+            Ok(c) => c,
             Err(e) => {
-                eprintln!(
-                    "Failed to read config file at path: {}. Error: {}",
+                return Err(format!(
+                    "Failed to parse config file at path: {}, due to error: {}",
                     path_string, e
-                );
-                exit(1);
+                ));
             }
-        };
-
-        let mut contents = String::new();
-        match file.read_to_string(&mut contents) {
-            Ok(_) => (),
+        },
+        "json" => match Config::from_json_string(&contents) {
+            Ok(c) => c,
             Err(e) => {
-                eprintln!(
-                    "Failed to read config file at path: {}. Error: {}",
+                return Err(format!(
+                    "Failed to parse config file at path: {}, due to error: {}",
                     path_string, e
-                );
-                exit(1);
+                ));
             }
+        },
+        _ => {
+            return Err(String::from(
+                "Invalid format specified. Choose either 'TOML' or 'JSON'.",
+            ))
         }
+    };
 
-        config = match format.to_lowercase().as_str() {
-            "toml" => match Config::from_toml_string(&contents) {
-                Ok(c) => c,
-                Err(e) => {
-                    eprintln!(
-                        "Failed to parse config file at path: {}, due to error: {}",
-                        path_string, e
-                    );
-                    exit(1);
-                }
-            },
-            "json" => match Config::from_json_string(&contents) {
-                Ok(c) => c,
-                Err(e) => {
-                    eprintln!(
-                        "Failed to parse config file at path: {}, due to error: {}",
-                        path_string, e
-                    );
-                    exit(1);
-                }
-            },
-            _ => {
-                eprintln!("Invalid format specified. Choose either 'TOML' or 'JSON'.");
-                exit(1);
-            }
-        };
+    return Ok(config);
+}
+
+fn doctor(config: Result<Config, String>) {
+    let checks = muxide::doctor::run_checks(config);
+
+    for check in checks.iter() {
+        println!("{}", check);
     }
 
-    return config;
+    let failed = checks.iter().filter(|check| !check.passed).count();
+
+    if failed == 0 {
+        println!("\nAll checks passed.");
+    } else {
+        println!("\n{} of {} checks failed.", failed, checks.len());
+        exit(1);
+    }
 }
 
 fn print_default_config(config_format: &str) {
@@ -291,6 +476,72 @@ fn print_default_config(config_format: &str) {
     }
 }
 
+fn init_config(config_format: &str) {
+    let contents = if config_format == "TOML" {
+        toml::to_string(&Config::default()).unwrap()
+    } else if config_format == "JSON" {
+        serde_json::to_string_pretty(&Config::default()).unwrap()
+    } else {
+        eprintln!("Unknown format: {}", config_format);
+        exit(1);
+    };
+
+    let path_string = match Config::default_path(config_format) {
+        Some(p) => p,
+        None => {
+            eprintln!("Could not determine a suitable path for the config file.");
+            exit(1);
+        }
+    };
+
+    let path = Path::new(&path_string);
+
+    if path.exists() {
+        eprintln!("A config file already exists at path: {}", path_string);
+        exit(1);
+    }
+
+    if let Some(parent) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            eprintln!(
+                "Failed to create the directory for \"{}\". Error: {}",
+                path_string, e
+            );
+            exit(1);
+        }
+    }
+
+    if let Err(e) = std::fs::write(path, contents) {
+        eprintln!("Failed to write to \"{}\". Error: {}", path_string, e);
+        exit(1);
+    }
+
+    println!("Wrote the default config to: {}", path_string);
+}
+
+fn list_sessions() {
+    let sessions = match session::list_sessions() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("Failed to list sessions. Error: {}", e);
+            exit(1);
+        }
+    };
+
+    if sessions.is_empty() {
+        println!("No sessions.");
+        return;
+    }
+
+    for session in sessions {
+        println!(
+            "{}: {}",
+            session.name,
+            if session.alive { "running" } else { "stale" }
+        );
+    }
+}
+
 fn load_password(path: &str) -> Result<Option<String>, String> {
     let path = Path::new(path);
 
@@ -364,7 +615,22 @@ fn set_password(path: &str, settings: &PasswordSettings) -> Option<String> {
         }
     };
 
-    let mut file = match OpenOptions::new().create(true).write(true).open(path) {
+    if let Some(parent) = Path::new(path).parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            eprintln!(
+                "Failed to create the directory for \"{}\". Error: {}",
+                path, e
+            );
+            exit(1);
+        }
+    }
+
+    let mut file = match OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(path)
+    {
         Ok(f) => f,
         Err(e) => {
             eprintln!("Failed to open \"{}\" for writing. Error: {}", path, e);
@@ -424,7 +690,7 @@ fn change_password(original: String, settings: &PasswordSettings, path: &str) ->
 
     loop {
         let comp = rpassword::read_password_from_tty(Some("Old Password: ")).unwrap();
-        let result = muxide::hasher::check_password(&comp, settings, &original);
+        let result = muxide::hasher::check_password(&comp, &original);
 
         match result {
             Some(res) => {
@@ -458,7 +724,22 @@ fn change_password(original: String, settings: &PasswordSettings, path: &str) ->
         }
     };
 
-    let mut file = match OpenOptions::new().create(true).write(true).open(path) {
+    if let Some(parent) = Path::new(path).parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            eprintln!(
+                "Failed to create the directory for \"{}\". Error: {}",
+                path, e
+            );
+            exit(1);
+        }
+    }
+
+    let mut file = match OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(path)
+    {
         Ok(f) => f,
         Err(e) => {
             eprintln!("Failed to open \"{}\" for writing. Error: {}", path, e);
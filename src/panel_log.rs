@@ -0,0 +1,222 @@
+use crate::error::{ErrorType, MuxideError};
+use std::path::Path;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+
+/// A file a panel's output is appended to, written by the panel's pty task.
+pub struct PanelLog {
+    file: File,
+    /// Prefix each line with the time it was output.
+    timestamps: bool,
+    /// Set when the next byte of output starts a new line.
+    line_start: bool,
+}
+
+impl PanelLog {
+    /// Open a log file for appending, creating it if it doesn't exist.
+    pub async fn open(path: &str, timestamps: bool) -> Result<Self, MuxideError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .map_err(|e| {
+                ErrorType::IOError {
+                    read: false,
+                    target: path.to_string(),
+                    reason: e.to_string(),
+                }
+                .into_error()
+                .with_source(e)
+            })?;
+
+        return Ok(Self {
+            file,
+            timestamps,
+            line_start: true,
+        });
+    }
+
+    pub async fn write(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        if !self.timestamps {
+            return self.file.write_all(bytes).await;
+        }
+
+        let output = timestamp_lines(bytes, &mut self.line_start, &current_timestamp());
+
+        return self.file.write_all(&output).await;
+    }
+}
+
+/// Saves a panel's output as plain text, replacing any existing file and creating the directories
+/// it is in.
+pub async fn save_output(path: &Path, output: &[u8]) -> Result<(), MuxideError> {
+    let io_error = |e: std::io::Error| {
+        ErrorType::IOError {
+            read: false,
+            target: path.to_string_lossy().to_string(),
+            reason: e.to_string(),
+        }
+        .into_error()
+        .with_source(e)
+    };
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await.map_err(io_error)?;
+    }
+
+    return tokio::fs::write(path, output_text(output))
+        .await
+        .map_err(io_error);
+}
+
+/// Returns the text of a program's output, without its escape sequences or control characters
+/// other than newlines and tabs. Carriage returns and backspaces move back over the line as they
+/// would on a screen, so only the last state of a progress bar is kept.
+fn output_text(bytes: &[u8]) -> String {
+    let output = String::from_utf8_lossy(bytes);
+    let mut chars = output.chars().peekable();
+    let mut text = String::new();
+    let mut line = String::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => {
+                text.push_str(line.trim_end());
+                text.push('\n');
+                line.clear();
+            }
+            '\r' if chars.peek() != Some(&'\n') => line.clear(),
+            '\x08' => {
+                line.pop();
+            }
+            '\t' => line.push(c),
+            '\x1b' => skip_escape_sequence(&mut chars),
+            _ if c.is_control() => (),
+            _ => line.push(c),
+        }
+    }
+
+    text.push_str(line.trim_end());
+
+    return text;
+}
+
+/// Skips the rest of an escape sequence, the escape has already been read. Control sequences end
+/// with a final byte, strings (i.e. OSC and DCS) end with BEL or ST and the other sequences are a
+/// single character.
+fn skip_escape_sequence<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) {
+    match chars.next() {
+        Some('[') => {
+            for c in chars {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+        Some(']') | Some('P') | Some('_') | Some('^') | Some('X') => {
+            while let Some(c) = chars.next() {
+                if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                    break;
+                }
+            }
+        }
+        // Character set designations have an intermediate character.
+        Some('(') | Some(')') | Some('*') | Some('+') | Some('#') => {
+            chars.next();
+        }
+        _ => (),
+    }
+}
+
+/// Returns the output with a timestamp inserted at the start of each line. The start of the
+/// output is a new line if `line_start` is set, it is updated for the next output.
+fn timestamp_lines(bytes: &[u8], line_start: &mut bool, timestamp: &str) -> Vec<u8> {
+    let mut output = Vec::with_capacity(bytes.len());
+
+    for byte in bytes {
+        if *line_start {
+            output.extend_from_slice(timestamp.as_bytes());
+            *line_start = false;
+        }
+
+        output.push(*byte);
+
+        if *byte == b'\n' {
+            *line_start = true;
+        }
+    }
+
+    return output;
+}
+
+/// Returns the local time formatted as "[YYYY-MM-DD HH:MM:SS] ".
+fn current_timestamp() -> String {
+    let now = unsafe { libc::time(std::ptr::null_mut()) };
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        return String::new();
+    }
+
+    return format!(
+        "[{:04}-{:02}-{:02} {:02}:{:02}:{:02}] ",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{output_text, save_output, timestamp_lines};
+    use std::fs;
+
+    #[test]
+    fn test_timestamp_lines() {
+        let mut line_start = true;
+
+        assert_eq!(
+            timestamp_lines(b"one\r\ntw", &mut line_start, "T "),
+            b"T one\r\nT tw".to_vec()
+        );
+        assert!(!line_start);
+
+        assert_eq!(
+            timestamp_lines(b"o\n", &mut line_start, "T "),
+            b"o\n".to_vec()
+        );
+        assert!(line_start);
+    }
+
+    #[test]
+    fn test_output_text() {
+        assert_eq!(
+            output_text(b"\x1b[1;31mred\x1b[0m text \r\n\x1b]0;title\x07next\tline"),
+            "red text\nnext\tline"
+        );
+        assert_eq!(output_text(b"10%\r50%\r100%\r\n"), "100%\n");
+        assert_eq!(output_text(b"tpyo\x08\x08\x08ypo\x1b(B\x07"), "typo");
+        assert_eq!(
+            output_text(b"\xe2\x9c\x93 \x1bP1$r0m\x1b\\done"),
+            "\u{2713} done"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_output() {
+        let directory =
+            std::env::temp_dir().join(format!("muxide-save-output-{}", std::process::id()));
+        let path = directory.join("session").join("1.txt");
+
+        save_output(&path, b"\x1b[1msaved\x1b[0m output\r\n")
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "saved output\n");
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,634 @@
+use crate::config::{highlight_row, HighlightRule};
+use crate::geometry::Size;
+use crate::graphics::{GraphicsExtractor, PanelGraphic, Segment};
+use crate::reflow::reflow;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use vt100::Parser;
+
+/// The terminal state of a panel's program, i.e. its screen, scrollback and the modes the parser
+/// doesn't track.
+pub struct PanelScreen {
+    parser: Parser,
+    /// The number of lines kept in the scrollback.
+    scrollback_len: usize,
+    current_scrollback: usize,
+    /// Set whilst output received when scrolled back keeps the same lines visible, rather than
+    /// returning to the bottom of the scrollback.
+    keep_scroll_position: bool,
+    /// Set when the panel's program has asked to be notified when it gains or loses focus.
+    focus_reporting: bool,
+    /// The last cursor style (DECSCUSR) set by the panel's program.
+    cursor_style: u8,
+    /// The rows of the last frame, unchanged rows are shared with the next frame.
+    previous_rows: Arc<[PanelRow]>,
+    /// The output received whilst the panel is paused, it is processed once the panel resumes.
+    paused_output: Option<Vec<u8>>,
+    /// The start of a UTF-8 character at the end of the last output, the rest of it hasn't been
+    /// read yet.
+    partial_char: Vec<u8>,
+    /// The rules styling the text in the panel's rows that matches them.
+    highlight_rules: Arc<Vec<HighlightRule>>,
+    /// Separates graphics sequences from the output whilst graphics are passed through.
+    graphics_extractor: Option<GraphicsExtractor>,
+    /// The images drawn on the screen, oldest first.
+    graphics: Vec<PanelGraphic>,
+    /// The program's most recent output, kept whilst it is saved when the panel closes.
+    history: Option<VecDeque<u8>>,
+    /// The size in bytes the history is limited to, the oldest output is removed first.
+    history_limit: usize,
+}
+
+/// A panel's screen shared between its pty task, which processes the program's output, and the
+/// event loop, which scrolls it and reads its modes.
+#[derive(Clone)]
+pub struct SharedPanelScreen {
+    screen: Arc<Mutex<PanelScreen>>,
+}
+
+/// A formatted row of a panel's contents.
+pub type PanelRow = Arc<[u8]>;
+
+/// The contents of a panel, ready to be rendered by the display. Cloning a frame doesn't copy
+/// its rows.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PanelFrame {
+    pub rows: Arc<[PanelRow]>,
+    pub cursor_col: u16,
+    pub cursor_row: u16,
+    pub hide_cursor: bool,
+    pub cursor_style: u8,
+    /// The number of bytes of output processed since the previous frame.
+    pub processed_bytes: usize,
+    /// The number of lines of output processed since the previous frame.
+    pub processed_lines: usize,
+    /// The images drawn on the screen, empty whilst scrolled back.
+    pub graphics: Arc<[PanelGraphic]>,
+}
+
+impl PanelScreen {
+    /// The amount of output buffered whilst paused before the program's output stops being read,
+    /// blocking the program until the panel resumes.
+    const PAUSED_OUTPUT_LIMIT: usize = 1024 * 1024;
+    /// The number of images kept for a screen, the oldest are removed first.
+    const GRAPHICS_LIMIT: usize = 16;
+
+    pub fn new(size: Size, scrollback_len: usize) -> Self {
+        return Self {
+            parser: Parser::new(size.get_rows(), size.get_cols(), scrollback_len),
+            scrollback_len,
+            current_scrollback: 0,
+            keep_scroll_position: false,
+            focus_reporting: false,
+            cursor_style: 0,
+            previous_rows: Arc::new([]),
+            paused_output: None,
+            partial_char: Vec::new(),
+            highlight_rules: Arc::new(Vec::new()),
+            graphics_extractor: None,
+            graphics: Vec::new(),
+            history: None,
+            history_limit: 0,
+        };
+    }
+
+    /// Process output from the panel's program, this returns the panel to the bottom of the
+    /// scrollback unless it keeps its scroll position. The output is buffered instead if the
+    /// panel is paused.
+    pub fn process(&mut self, bytes: &[u8]) {
+        self.record_history(bytes);
+
+        if let Some(buffer) = self.paused_output.as_mut() {
+            buffer.extend_from_slice(bytes);
+            return;
+        }
+
+        // A read can end part way through a character, it is processed once the rest is read.
+        let joined;
+        let bytes = if self.partial_char.is_empty() {
+            bytes
+        } else {
+            joined = [std::mem::take(&mut self.partial_char).as_slice(), bytes].concat();
+            joined.as_slice()
+        };
+        let complete = bytes.len() - incomplete_char_len(bytes);
+        self.partial_char = bytes[complete..].to_vec();
+        let bytes = &bytes[..complete];
+
+        match self.graphics_extractor.as_mut() {
+            Some(extractor) => {
+                for segment in extractor.split(bytes) {
+                    match segment {
+                        Segment::Output(output) => self.process_output(&output),
+                        Segment::Graphic(sequence) => self.place_graphic(sequence),
+                    }
+                }
+            }
+            None => self.process_output(bytes),
+        }
+
+        if self.keep_scroll_position && self.current_scrollback != 0 {
+            // The parser moves further back as lines are added, so the same lines stay visible.
+            self.current_scrollback = self.parser.screen().scrollback();
+        } else {
+            self.clear_scrollback();
+        }
+    }
+
+    fn process_output(&mut self, bytes: &[u8]) {
+        self.parser.process(bytes);
+
+        // The parser doesn't track focus reporting or the cursor style, so they are tracked here.
+        if let Some(enabled) = focus_reporting_mode(bytes) {
+            self.focus_reporting = enabled;
+        }
+
+        if let Some(style) = cursor_style(bytes) {
+            self.cursor_style = style;
+        }
+
+        if !self.graphics.is_empty() && clears_screen(bytes) {
+            self.graphics.clear();
+        }
+    }
+
+    /// Records an image drawn at the cursor's position.
+    fn place_graphic(&mut self, sequence: Vec<u8>) {
+        let (row, col) = self.parser.screen().cursor_position();
+
+        if self.graphics.len() == Self::GRAPHICS_LIMIT {
+            self.graphics.remove(0);
+        }
+
+        self.graphics.push(PanelGraphic {
+            col,
+            row,
+            sequence: Arc::from(sequence),
+        });
+    }
+
+    /// Enables or disables separating graphics sequences from the output, so they can be passed
+    /// through to the terminal.
+    pub fn set_graphics_passthrough(&mut self, enabled: bool) {
+        if enabled != self.graphics_extractor.is_some() {
+            self.graphics_extractor = if enabled {
+                Some(GraphicsExtractor::default())
+            } else {
+                None
+            };
+            self.graphics.clear();
+        }
+    }
+
+    /// Keeps the same lines visible when output is received whilst scrolled back, used when
+    /// following a file. Output received at the bottom of the scrollback is still followed.
+    pub fn set_keep_scroll_position(&mut self, enabled: bool) {
+        self.keep_scroll_position = enabled;
+    }
+
+    /// Keep up to the limit of the program's most recent output, so it can be saved, or stop
+    /// keeping it if the limit is None.
+    pub fn set_history_limit(&mut self, limit: Option<usize>) {
+        match limit {
+            Some(limit) => {
+                self.history.get_or_insert_with(VecDeque::new);
+                self.history_limit = limit;
+            }
+            None => self.history = None,
+        }
+    }
+
+    /// Returns the program's most recent output, empty if it isn't kept.
+    pub fn history(&self) -> Vec<u8> {
+        return match self.history.as_ref() {
+            Some(history) => history.iter().copied().collect(),
+            None => Vec::new(),
+        };
+    }
+
+    fn record_history(&mut self, bytes: &[u8]) {
+        let history = match self.history.as_mut() {
+            Some(history) => history,
+            None => return,
+        };
+
+        history.extend(bytes);
+
+        if history.len() > self.history_limit {
+            // Whole lines are removed where possible, so the history doesn't start part way
+            // through a line or an escape sequence.
+            let excess = history.len() - self.history_limit;
+            let end = history
+                .iter()
+                .skip(excess)
+                .position(|b| *b == b'\n')
+                .map_or(excess, |i| excess + i + 1);
+            history.drain(..end);
+        }
+    }
+
+    /// Pause or resume processing the program's output, the output buffered whilst paused is
+    /// processed when the panel resumes. Returns true if the panel is now paused.
+    pub fn toggle_paused(&mut self) -> bool {
+        match self.paused_output.take() {
+            Some(buffer) => {
+                self.process(&buffer);
+                return false;
+            }
+            None => {
+                self.paused_output = Some(Vec::new());
+                return true;
+            }
+        }
+    }
+
+    /// Returns true if the panel is paused and has buffered as much output as it can, no more
+    /// output should be read until it resumes.
+    pub fn paused_output_full(&self) -> bool {
+        return self
+            .paused_output
+            .as_ref()
+            .is_some_and(|b| b.len() >= Self::PAUSED_OUTPUT_LIMIT);
+    }
+
+    pub fn set_highlight_rules(&mut self, rules: Arc<Vec<HighlightRule>>) {
+        self.highlight_rules = rules;
+    }
+
+    /// Resize the screen, the images drawn on it are removed as their positions no longer match.
+    /// When the width changes the lines are rewrapped, unless the alternate screen is in use as
+    /// the programs using it redraw it themselves.
+    pub fn set_size(&mut self, size: Size) {
+        let screen = self.parser.screen();
+
+        if screen.size().1 != size.get_cols() && !screen.alternate_screen() {
+            self.parser = reflow(&mut self.parser, size, self.scrollback_len);
+            self.current_scrollback = 0;
+        } else {
+            self.parser.set_size(size.get_rows(), size.get_cols());
+        }
+
+        self.graphics.clear();
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.current_scrollback += lines;
+        let previous = self.parser.screen().scrollback();
+        self.parser.set_scrollback(self.current_scrollback);
+
+        if self.parser.screen().scrollback() == previous {
+            self.current_scrollback -= lines;
+        }
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.current_scrollback = self.current_scrollback.saturating_sub(lines);
+        self.parser.set_scrollback(self.current_scrollback);
+    }
+
+    pub fn clear_scrollback(&mut self) {
+        self.current_scrollback = 0;
+        self.parser.set_scrollback(self.current_scrollback);
+    }
+
+    /// Returns the text of the visible rows, without their formatting.
+    pub fn visible_text(&self) -> Vec<String> {
+        let screen = self.parser.screen();
+        return screen.rows(0, screen.size().1).collect();
+    }
+
+    pub fn bracketed_paste(&self) -> bool {
+        return self.parser.screen().bracketed_paste();
+    }
+
+    pub fn focus_reporting(&self) -> bool {
+        return self.focus_reporting;
+    }
+
+    /// Returns the title set by the panel's program (OSC 0 or 2), empty if it hasn't set one.
+    pub fn title(&self) -> String {
+        return self.parser.screen().title().to_string();
+    }
+
+    /// Returns the currently visible contents of the panel.
+    pub fn frame(&mut self) -> PanelFrame {
+        let screen = self.parser.screen();
+        let (cursor_row, cursor_col) = screen.cursor_position();
+        let previous_rows = &self.previous_rows;
+        let rules = &self.highlight_rules;
+        // The rules are matched against the text of the rows, without their formatting.
+        let texts: Vec<String> = if rules.is_empty() {
+            Vec::new()
+        } else {
+            screen.rows(0, screen.size().1).collect()
+        };
+        let rows: Arc<[PanelRow]> = screen
+            .rows_formatted(0, screen.size().1)
+            .enumerate()
+            .map(|(i, row)| {
+                let row = texts
+                    .get(i)
+                    .and_then(|text| highlight_row(text, rules))
+                    .unwrap_or(row);
+
+                match previous_rows.get(i) {
+                    Some(previous) if **previous == *row => previous.clone(),
+                    _ => PanelRow::from(row),
+                }
+            })
+            .collect();
+
+        self.previous_rows = rows.clone();
+
+        return PanelFrame {
+            rows,
+            cursor_col,
+            cursor_row,
+            hide_cursor: screen.hide_cursor() || self.current_scrollback != 0,
+            cursor_style: self.cursor_style,
+            processed_bytes: 0,
+            processed_lines: 0,
+            graphics: if self.current_scrollback == 0 {
+                Arc::from(self.graphics.as_slice())
+            } else {
+                Arc::new([])
+            },
+        };
+    }
+}
+
+impl SharedPanelScreen {
+    pub fn new(size: Size, scrollback_len: usize) -> Self {
+        return Self {
+            screen: Arc::new(Mutex::new(PanelScreen::new(size, scrollback_len))),
+        };
+    }
+
+    /// Lock the screen, if a task panicked whilst holding the lock the screen is still used.
+    pub fn lock(&self) -> MutexGuard<'_, PanelScreen> {
+        return self.screen.lock().unwrap_or_else(|e| e.into_inner());
+    }
+}
+
+/// Returns the number of bytes at the end of the output that start a UTF-8 character without
+/// completing it.
+fn incomplete_char_len(bytes: &[u8]) -> usize {
+    // The first byte of a character is the last byte that isn't a continuation byte.
+    let start = match bytes.iter().rev().take(4).position(|b| b & 0xc0 != 0x80) {
+        Some(i) => bytes.len() - 1 - i,
+        None => return 0,
+    };
+
+    let len = match bytes[start] {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => return 0,
+    };
+
+    if bytes.len() - start < len {
+        return bytes.len() - start;
+    } else {
+        return 0;
+    }
+}
+
+/// Returns whether the output enables or disables focus reporting (DECSET/DECRST 1004), the last
+/// occurrence is used. Returns None if the mode isn't changed.
+fn focus_reporting_mode(bytes: &[u8]) -> Option<bool> {
+    let mut mode = None;
+    let mut i = 0;
+
+    while let Some(start) = bytes[i..].windows(3).position(|w| w == b"\x1b[?") {
+        let params_start = i + start + 3;
+        let params_len = match bytes[params_start..]
+            .iter()
+            .position(|b| !(b.is_ascii_digit() || *b == b';'))
+        {
+            Some(len) => len,
+            None => break,
+        };
+        let final_byte = bytes[params_start + params_len];
+
+        if final_byte == b'h' || final_byte == b'l' {
+            let params = &bytes[params_start..params_start + params_len];
+
+            if params.split(|b| *b == b';').any(|p| p == b"1004") {
+                mode = Some(final_byte == b'h');
+            }
+        }
+
+        i = params_start + params_len;
+    }
+
+    return mode;
+}
+
+/// Returns true if the output clears the screen or switches between the normal and alternate
+/// screens, removing any images drawn on it.
+fn clears_screen(bytes: &[u8]) -> bool {
+    const SEQUENCES: [&[u8]; 6] = [
+        b"\x1b[2J",
+        b"\x1b[3J",
+        b"\x1bc",
+        b"\x1b[?1049",
+        b"\x1b[?1047",
+        b"\x1b[?47",
+    ];
+
+    return SEQUENCES
+        .iter()
+        .any(|sequence| bytes.windows(sequence.len()).any(|w| w == *sequence));
+}
+
+/// Returns the last cursor style set in the output with DECSCUSR (CSI Ps SP q).
+fn cursor_style(bytes: &[u8]) -> Option<u8> {
+    let mut style = None;
+    let mut i = 0;
+
+    while let Some(start) = bytes[i..].windows(2).position(|w| w == b"\x1b[") {
+        let params_start = i + start + 2;
+        let params_len = bytes[params_start..]
+            .iter()
+            .position(|b| !b.is_ascii_digit())
+            .unwrap_or(bytes.len() - params_start);
+        let params_end = params_start + params_len;
+
+        if bytes[params_end..].starts_with(b" q") {
+            // Styles above 6 aren't defined, they are treated as the default.
+            style = Some(
+                std::str::from_utf8(&bytes[params_start..params_end])
+                    .ok()
+                    .and_then(|p| p.parse::<u8>().ok())
+                    .filter(|s| *s <= 6)
+                    .unwrap_or(0),
+            );
+        }
+
+        i = params_end;
+    }
+
+    return style;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        clears_screen, cursor_style, focus_reporting_mode, incomplete_char_len, PanelScreen,
+    };
+    use crate::geometry::Size;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_focus_reporting_mode() {
+        assert_eq!(focus_reporting_mode(b"\x1b[?1004h"), Some(true));
+        assert_eq!(focus_reporting_mode(b"abc\x1b[?1004l"), Some(false));
+        assert_eq!(focus_reporting_mode(b"\x1b[?1049;1004h"), Some(true));
+        assert_eq!(
+            focus_reporting_mode(b"\x1b[?1004h text \x1b[?1004l"),
+            Some(false)
+        );
+        assert_eq!(focus_reporting_mode(b"\x1b[?10040h"), None);
+        assert_eq!(focus_reporting_mode(b"\x1b[?2004h\x1b[?1004"), None);
+        assert_eq!(focus_reporting_mode(b"plain output"), None);
+    }
+
+    #[test]
+    fn test_cursor_style() {
+        assert_eq!(cursor_style(b"\x1b[2 q"), Some(2));
+        assert_eq!(cursor_style(b"\x1b[ q"), Some(0));
+        assert_eq!(cursor_style(b"\x1b[6 qtext\x1b[1 q"), Some(1));
+        assert_eq!(cursor_style(b"\x1b[99 q"), Some(0));
+        assert_eq!(cursor_style(b"\x1b[2J\x1b[4m"), None);
+        assert_eq!(cursor_style(b"\x1b[5"), None);
+    }
+
+    #[test]
+    fn test_graphics_passthrough() {
+        let mut screen = PanelScreen::new(Size::new(4, 10), 10);
+        screen.process(b"\x1b_Ga=T;AAAA\x1b\\");
+        assert!(screen.frame().graphics.is_empty());
+
+        screen.set_graphics_passthrough(true);
+        screen.process(b"ab\r\ncd\x1bPq#0~~");
+        screen.process(b"\x1b\\ef");
+
+        let frame = screen.frame();
+        assert_eq!(frame.graphics.len(), 1);
+        assert_eq!((frame.graphics[0].col, frame.graphics[0].row), (2, 1));
+        assert_eq!(&*frame.graphics[0].sequence, b"\x1bPq#0~~\x1b\\");
+        assert_eq!(screen.visible_text()[1], "cdef");
+
+        screen.process(b"\x1b[2J");
+        assert!(screen.frame().graphics.is_empty());
+        assert!(!clears_screen(b"\x1b[2K"));
+    }
+
+    #[test]
+    fn test_incomplete_char_len() {
+        let text = "a€".as_bytes();
+        assert_eq!(incomplete_char_len(text), 0);
+        assert_eq!(incomplete_char_len(&text[..2]), 1);
+        assert_eq!(incomplete_char_len(&text[..3]), 2);
+        assert_eq!(incomplete_char_len(b"abc"), 0);
+        assert_eq!(incomplete_char_len(b"\x80\x80"), 0);
+        assert_eq!(incomplete_char_len(b""), 0);
+    }
+
+    #[test]
+    fn test_char_split_across_output() {
+        let mut screen = PanelScreen::new(Size::new(2, 10), 10);
+        let text = "é€".as_bytes();
+        screen.process(&text[..1]);
+        screen.process(&text[1..3]);
+        assert_eq!(screen.visible_text()[0], "é");
+
+        screen.process(&text[3..]);
+        assert_eq!(screen.visible_text()[0], "é€");
+    }
+
+    #[test]
+    fn test_frame_after_scrolling() {
+        let mut screen = PanelScreen::new(Size::new(2, 10), 10);
+        screen.process(b"1\r\n2\r\n3\r\n4\x1b[4 q");
+
+        let frame = screen.frame();
+        assert!(!frame.hide_cursor);
+        assert_eq!(frame.cursor_style, 4);
+        assert_eq!(frame.rows.len(), 2);
+
+        screen.scroll_up(1);
+        assert!(screen.frame().hide_cursor);
+
+        screen.process(b"5");
+        assert!(!screen.frame().hide_cursor);
+    }
+
+    #[test]
+    fn test_keep_scroll_position() {
+        let mut screen = PanelScreen::new(Size::new(3, 10), 10);
+        screen.set_keep_scroll_position(true);
+        screen.process(b"1\r\n2\r\n3\r\n4\r\n5");
+
+        screen.scroll_up(1);
+        assert_eq!(screen.visible_text(), vec!["2", "3", "4"]);
+
+        screen.process(b"\r\n6");
+        assert_eq!(screen.visible_text(), vec!["2", "3", "4"]);
+
+        screen.scroll_down(2);
+        assert_eq!(screen.visible_text(), vec!["4", "5", "6"]);
+
+        screen.process(b"\r\n7");
+        assert_eq!(screen.visible_text(), vec!["5", "6", "7"]);
+    }
+
+    #[test]
+    fn test_frame_shares_unchanged_rows() {
+        let mut screen = PanelScreen::new(Size::new(2, 10), 10);
+        screen.process(b"first\r\n");
+        let previous = screen.frame();
+
+        screen.process(b"second");
+        let frame = screen.frame();
+
+        assert!(Arc::ptr_eq(&previous.rows[0], &frame.rows[0]));
+        assert!(!Arc::ptr_eq(&previous.rows[1], &frame.rows[1]));
+    }
+
+    #[test]
+    fn test_history() {
+        let mut screen = PanelScreen::new(Size::new(2, 10), 10);
+        screen.process(b"ignored\r\n");
+        assert!(screen.history().is_empty());
+
+        screen.set_history_limit(Some(12));
+        screen.process(b"one\r\ntwo\r\n");
+        assert_eq!(screen.history(), b"one\r\ntwo\r\n".to_vec());
+
+        screen.process(b"three\r\n");
+        assert_eq!(screen.history(), b"three\r\n".to_vec());
+
+        screen.process(b"a very long line");
+        assert_eq!(screen.history(), b"ry long line".to_vec());
+
+        screen.set_history_limit(None);
+        assert!(screen.history().is_empty());
+    }
+
+    #[test]
+    fn test_paused_output() {
+        let mut screen = PanelScreen::new(Size::new(2, 10), 10);
+        screen.process(b"first");
+        let previous = screen.frame();
+
+        assert!(screen.toggle_paused());
+        screen.process(b"\r\nsecond");
+        assert_eq!(screen.frame().rows, previous.rows);
+        assert!(!screen.paused_output_full());
+
+        assert!(!screen.toggle_paused());
+        assert!(!screen.paused_output_full());
+        assert_ne!(screen.frame().rows, previous.rows);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::error::{ErrorType, MuxideError};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// The number of bytes before each frame's payload, a 1 byte tag followed by the payload's length
+/// as a big endian u32.
+const HEADER_LEN: usize = 5;
+/// The largest payload accepted, larger frames are treated as a corrupted stream.
+const MAX_PAYLOAD_LEN: usize = 16 * 1024 * 1024;
+
+const INPUT_TAG: u8 = 1;
+const RENDER_TAG: u8 = 2;
+const RESIZE_TAG: u8 = 3;
+const DETACH_TAG: u8 = 4;
+
+/// A message sent over a session's socket, between the server that owns the session's ptys and
+/// the clients attached to it.
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub enum Frame {
+    /// Input read from a client's terminal.
+    Input(Vec<u8>),
+    /// Output rendered by the server, a client writes it to its terminal as is.
+    Render(Vec<u8>),
+    /// A client's terminal changed size.
+    Resize { rows: u16, cols: u16 },
+    /// A client is detaching, the session keeps running without it.
+    Detach,
+}
+
+impl Frame {
+    /// Encode the frame as a header followed by its payload.
+    pub fn encode(&self) -> Vec<u8> {
+        let (tag, payload) = match self {
+            Frame::Input(bytes) => (INPUT_TAG, bytes.clone()),
+            Frame::Render(bytes) => (RENDER_TAG, bytes.clone()),
+            Frame::Resize { rows, cols } => {
+                let mut payload = rows.to_be_bytes().to_vec();
+                payload.extend_from_slice(&cols.to_be_bytes());
+
+                (RESIZE_TAG, payload)
+            }
+            Frame::Detach => (DETACH_TAG, Vec::new()),
+        };
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+        bytes.push(tag);
+        bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        bytes.extend(payload);
+
+        return bytes;
+    }
+
+    /// Decode the frame at the start of the bytes, returning it and the number of bytes it used.
+    /// Returns None if the bytes don't contain a complete frame yet.
+    pub fn decode(bytes: &[u8]) -> Result<Option<(Self, usize)>, MuxideError> {
+        if bytes.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let payload_len = Self::payload_len(&bytes[1..HEADER_LEN])?;
+
+        if bytes.len() < HEADER_LEN + payload_len {
+            return Ok(None);
+        }
+
+        let frame = Self::from_payload(bytes[0], &bytes[HEADER_LEN..HEADER_LEN + payload_len])?;
+
+        return Ok(Some((frame, HEADER_LEN + payload_len)));
+    }
+
+    /// Read the next frame. Returns None if the stream closed before a new frame started.
+    pub async fn read<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<Self>, MuxideError> {
+        let mut header = [0u8; HEADER_LEN];
+
+        match reader.read_exact(&mut header).await {
+            Ok(_) => (),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => {
+                return Err(ErrorType::ProtocolError {
+                    reason: format!("Failed to read a frame. Error: {}", e),
+                }
+                .into_error());
+            }
+        }
+
+        let mut payload = vec![0u8; Self::payload_len(&header[1..])?];
+
+        reader.read_exact(&mut payload).await.map_err(|e| {
+            ErrorType::ProtocolError {
+                reason: format!("Failed to read a frame's payload. Error: {}", e),
+            }
+            .into_error()
+        })?;
+
+        return Self::from_payload(header[0], &payload).map(Some);
+    }
+
+    /// Write the frame and flush the writer.
+    pub async fn write<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> Result<(), MuxideError> {
+        let map_err = |e: std::io::Error| {
+            ErrorType::ProtocolError {
+                reason: format!("Failed to write a frame. Error: {}", e),
+            }
+            .into_error()
+        };
+
+        writer.write_all(&self.encode()).await.map_err(map_err)?;
+        writer.flush().await.map_err(map_err)?;
+
+        return Ok(());
+    }
+
+    fn payload_len(bytes: &[u8]) -> Result<usize, MuxideError> {
+        let len = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+
+        if len > MAX_PAYLOAD_LEN {
+            return Err(ErrorType::ProtocolError {
+                reason: format!("A frame's payload is too large: {} bytes.", len),
+            }
+            .into_error());
+        }
+
+        return Ok(len);
+    }
+
+    fn from_payload(tag: u8, payload: &[u8]) -> Result<Self, MuxideError> {
+        return match tag {
+            INPUT_TAG => Ok(Frame::Input(payload.to_vec())),
+            RENDER_TAG => Ok(Frame::Render(payload.to_vec())),
+            RESIZE_TAG if payload.len() == 4 => Ok(Frame::Resize {
+                rows: u16::from_be_bytes([payload[0], payload[1]]),
+                cols: u16::from_be_bytes([payload[2], payload[3]]),
+            }),
+            DETACH_TAG if payload.is_empty() => Ok(Frame::Detach),
+            _ => Err(ErrorType::ProtocolError {
+                reason: format!(
+                    "Invalid frame with the tag {} and a {} byte payload.",
+                    tag,
+                    payload.len()
+                ),
+            }
+            .into_error()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Frame;
+
+    #[test]
+    fn test_frame_round_trip() {
+        let frames = vec![
+            Frame::Input(b"ls\r".to_vec()),
+            Frame::Render(b"\x1b[2J\x1b[1;1Hhello".to_vec()),
+            Frame::Resize { rows: 24, cols: 80 },
+            Frame::Detach,
+        ];
+        let bytes: Vec<u8> = frames.iter().flat_map(|f| f.encode()).collect();
+        let mut decoded = Vec::new();
+        let mut start = 0;
+
+        while let Some((frame, len)) = Frame::decode(&bytes[start..]).unwrap() {
+            decoded.push(frame);
+            start += len;
+        }
+
+        assert_eq!(decoded, frames);
+        assert_eq!(start, bytes.len());
+    }
+
+    #[test]
+    fn test_incomplete_frame() {
+        let bytes = Frame::Input(b"abc".to_vec()).encode();
+
+        assert_eq!(Frame::decode(&bytes[..3]).unwrap(), None);
+        assert_eq!(Frame::decode(&bytes[..bytes.len() - 1]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_invalid_frame() {
+        assert!(Frame::decode(&[9, 0, 0, 0, 0]).is_err());
+        assert!(Frame::decode(&[3, 0, 0, 0, 1, 0]).is_err());
+        assert!(Frame::decode(&[1, 0xff, 0xff, 0xff, 0xff]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_write() {
+        let (mut client, mut server) = tokio::io::duplex(64);
+
+        Frame::Resize { rows: 10, cols: 20 }
+            .write(&mut client)
+            .await
+            .unwrap();
+        drop(client);
+
+        assert_eq!(
+            Frame::read(&mut server).await.unwrap(),
+            Some(Frame::Resize { rows: 10, cols: 20 })
+        );
+        assert_eq!(Frame::read(&mut server).await.unwrap(), None);
+    }
+}
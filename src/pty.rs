@@ -4,9 +4,10 @@ This code was heavily based and inspired by https://github.com/pkgw/stund/blob/m
 
 use crate::error::{ErrorType, MuxideError};
 use crate::geometry::Size;
+use nix::errno::Errno;
 use nix::fcntl::{FcntlArg, OFlag};
 use nix::pty::Winsize;
-use nix::{fcntl, unistd};
+use nix::{fcntl, poll, unistd};
 use std::io;
 use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
 use std::process::Stdio;
@@ -22,8 +23,37 @@ pub struct Pty {
     handle: tokio::process::Child,
 }
 
+/// The environment variables set or removed for the process in a pty, the rest of muxide's
+/// environment is inherited.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct PtyEnvironment {
+    variables: Vec<(String, Option<String>)>,
+}
+
+impl PtyEnvironment {
+    pub fn set<T: ToString>(mut self, name: &str, value: T) -> Self {
+        self.variables
+            .push((name.to_string(), Some(value.to_string())));
+        return self;
+    }
+
+    pub fn remove(mut self, name: &str) -> Self {
+        self.variables.push((name.to_string(), None));
+        return self;
+    }
+}
+
 impl Pty {
-    pub fn open(cmd: &str) -> Result<Self, MuxideError> {
+    pub fn open(cmd: &str, environment: &PtyEnvironment) -> Result<Self, MuxideError> {
+        return Self::open_with_args(cmd, &[], environment);
+    }
+
+    /// Open a pty running a command with the specified arguments.
+    pub fn open_with_args(
+        cmd: &str,
+        args: &[&str],
+        environment: &PtyEnvironment,
+    ) -> Result<Self, MuxideError> {
         // Comment taken directly from: https://github.com/pkgw/stund/blob/master/tokio-pty-process/src/lib.rs
         // On MacOS, O_NONBLOCK is not documented as an allowed option to
         // posix_openpt(), but it is in fact allowed and functional, and
@@ -34,19 +64,37 @@ impl Pty {
         // we have to jump through some #[cfg()] hoops.
         const APPLY_NONBLOCK_LATER: bool = cfg!(target_os = "freebsd");
 
-        let (file_descriptor, slave) = Self::open_pty().unwrap();
+        let (file_descriptor, slave) = Self::open_pty()?;
+
+        let mut command = Command::new(cmd);
+        command.args(args);
+
+        for (name, value) in environment.variables.iter() {
+            match value {
+                Some(value) => command.env(name, value),
+                None => command.env_remove(name),
+            };
+        }
+
+        // Each of the standard streams closes its descriptor, so they are given duplicates of the
+        // slave rather than sharing it.
+        let slave = unsafe { std::fs::File::from_raw_fd(slave) };
+        let (stdin, stdout) = match (slave.try_clone(), slave.try_clone()) {
+            (Ok(stdin), Ok(stdout)) => (stdin, stdout),
+            (Err(e), _) | (_, Err(e)) => {
+                return Err(ErrorType::PTYSpawnError {
+                    description: e.to_string(),
+                }
+                .into_error()
+                .with_source(e));
+            }
+        };
 
         let pty_command_handle = match unsafe {
-            Command::new(cmd)
-                .stdin(
-                    Stdio::from_raw_fd(slave), // Unsafe
-                )
-                .stdout(
-                    Stdio::from_raw_fd(slave), // Unsafe
-                )
-                .stderr(
-                    Stdio::from_raw_fd(slave), // Unsafe
-                )
+            command
+                .stdin(Stdio::from(stdin))
+                .stdout(Stdio::from(stdout))
+                .stderr(Stdio::from(slave))
                 .pre_exec(Self::in_between) // Unsafe
                 .kill_on_drop(true)
                 .spawn()
@@ -56,7 +104,8 @@ impl Pty {
                 return Err(ErrorType::PTYSpawnError {
                     description: e.to_string(),
                 }
-                .into_error());
+                .into_error()
+                .with_source(e));
             }
         };
 
@@ -89,15 +138,14 @@ impl Pty {
     }
 
     fn in_between() -> std::io::Result<()> {
-        unistd::setsid()
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-        let res = unsafe { libc::ioctl(0, libc::TIOCSCTTY as u64, 1) };
+        unistd::setsid().map_err(|e| std::io::Error::other(e.to_string()))?;
+        let res = unsafe { libc::ioctl(0, libc::TIOCSCTTY as libc::c_ulong, 1) };
 
         if res != 0 {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Failed to make process the controlling process: {}", res),
-            ));
+            return Err(std::io::Error::other(format!(
+                "Failed to make process the controlling process: {}",
+                res
+            )));
         }
 
         return Ok(());
@@ -118,6 +166,7 @@ impl Pty {
                 reason: e.to_string(),
             }
             .into_error()
+            .with_source(e)
         })?;
 
         let (master, slave) = (res.master, res.slave);
@@ -130,6 +179,7 @@ impl Pty {
                     }
                 }
                 .into_error()
+                .with_source(e)
             })?);
 
         fcntl::fcntl(master, FcntlArg::F_SETFL(res)).map_err(|e| {
@@ -137,25 +187,62 @@ impl Pty {
                 reason: e.to_string(),
             }
             .into_error()
+            .with_source(e)
         })?;
 
         return Ok((master, slave));
     }
 
+    /// Allocate a pty without running a command in it, to check that ptys can be allocated.
+    pub fn check_allocation() -> Result<(), MuxideError> {
+        let (master, slave) = Self::open_pty()?;
+        let _ = unistd::close(slave);
+        let _ = unistd::close(master);
+
+        return Ok(());
+    }
+
     pub fn resize(&self, size: &Size) -> Result<(), MuxideError> {
-        let res = unsafe { libc::ioctl(self.fd, libc::TIOCSWINSZ, &size.to_winsize()) };
+        let winsize = size.to_winsize(Self::cell_pixel_size());
+        let res = unsafe { libc::ioctl(self.fd, libc::TIOCSWINSZ, &winsize) };
 
         if res != 0 {
             return Err(ErrorType::IOCTLError {
                 code: res,
-                outcome: "Failed to resize the PTY.".to_string(),
+                outcome: "ioctl TIOCSWINSZ".to_string(),
             }
-            .into_error());
+            .into_error()
+            .with_source(std::io::Error::last_os_error()));
         }
 
         return Ok(());
     }
 
+    /// Returns the width and height in pixels of a cell of the outer terminal, from the pixel
+    /// size it reports. None if the terminal doesn't report its size in pixels.
+    fn cell_pixel_size() -> Option<(u16, u16)> {
+        let mut size = libc::winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+
+        let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+
+        if res != 0 || size.ws_col == 0 || size.ws_row == 0 {
+            return None;
+        }
+
+        let cell = (size.ws_xpixel / size.ws_col, size.ws_ypixel / size.ws_row);
+
+        if cell.0 == 0 || cell.1 == 0 {
+            return None;
+        }
+
+        return Some(cell);
+    }
+
     pub fn running(&mut self) -> Option<bool> {
         match self.handle.try_wait() {
             Ok(Some(_)) => return Some(false),
@@ -164,9 +251,117 @@ impl Pty {
         }
     }
 
+    /// Writes as much of the input as the pty accepts without blocking, returning the number of
+    /// bytes written. Nothing is written if the program isn't reading its input.
+    pub fn write_available(&mut self, bytes: &[u8]) -> Result<usize, MuxideError> {
+        /// The most written at once, a pty accepts at least this much once it is writable.
+        const WRITE_CHUNK: usize = 512;
+        let mut written = 0;
+
+        while written < bytes.len() {
+            let mut fds = [poll::PollFd::new(self.fd, poll::PollFlags::POLLOUT)];
+
+            match poll::poll(&mut fds, 0) {
+                Ok(0) => break,
+                Ok(_) => (),
+                Err(nix::Error::Sys(Errno::EINTR)) => continue,
+                Err(e) => {
+                    return Err(ErrorType::PTYWriteError {
+                        description: e.to_string(),
+                    }
+                    .into_error()
+                    .with_source(e))
+                }
+            }
+
+            let end = bytes.len().min(written + WRITE_CHUNK);
+
+            match unistd::write(self.fd, &bytes[written..end]) {
+                Ok(count) => written += count,
+                Err(nix::Error::Sys(Errno::EAGAIN)) | Err(nix::Error::Sys(Errno::EINTR)) => break,
+                Err(e) => {
+                    return Err(ErrorType::PTYWriteError {
+                        description: e.to_string(),
+                    }
+                    .into_error()
+                    .with_source(e))
+                }
+            }
+        }
+
+        return Ok(written);
+    }
+
     pub fn file(&mut self) -> &mut File {
         return &mut self.file;
     }
+
+    /// Returns the working directory of the foreground process of the pty with the specified
+    /// master file descriptor. It is only available on Linux.
+    pub fn foreground_process_cwd(fd: RawFd) -> Option<std::path::PathBuf> {
+        let pid = unistd::tcgetpgrp(fd).ok()?;
+
+        return std::fs::read_link(format!("/proc/{}/cwd", pid)).ok();
+    }
+
+    /// Returns the id of the foreground process group of the pty with the specified master file
+    /// descriptor, it is the id of the process that leads the group.
+    pub fn foreground_process_group(fd: RawFd) -> Option<i32> {
+        return unistd::tcgetpgrp(fd).ok().map(|pid| pid.as_raw());
+    }
+
+    /// Returns the arguments of a process, starting with the program.
+    pub fn process_args(pid: i32) -> Option<Vec<String>> {
+        let args: Vec<String> = match std::fs::read(format!("/proc/{}/cmdline", pid)) {
+            Ok(cmdline) => cmdline
+                .strip_suffix(&[0])
+                .unwrap_or(&cmdline)
+                .split(|b| *b == 0)
+                .map(|arg| String::from_utf8_lossy(arg).to_string())
+                .collect(),
+            Err(_) => {
+                // ps separates the arguments with spaces, so arguments containing spaces are
+                // split.
+                let output = std::process::Command::new("ps")
+                    .args(["-o", "args=", "-p", &pid.to_string()])
+                    .output()
+                    .ok()?;
+
+                String::from_utf8(output.stdout)
+                    .ok()?
+                    .split_whitespace()
+                    .map(|arg| arg.to_string())
+                    .collect()
+            }
+        };
+
+        return Some(args).filter(|args| !args.is_empty() && !args[0].is_empty());
+    }
+
+    /// Returns the name of the foreground process of the pty with the specified master file
+    /// descriptor, i.e. "nvim" whilst it is running in the panel's shell.
+    pub fn foreground_process_name(fd: RawFd) -> Option<String> {
+        let pid = unistd::tcgetpgrp(fd).ok()?;
+
+        // /proc is only available on Linux, other systems are asked through ps.
+        let name = match std::fs::read_to_string(format!("/proc/{}/comm", pid)) {
+            Ok(name) => name,
+            Err(_) => {
+                let output = std::process::Command::new("ps")
+                    .args(["-o", "comm=", "-p", &pid.to_string()])
+                    .output()
+                    .ok()?;
+
+                String::from_utf8(output.stdout).ok()?
+            }
+        };
+
+        // ps may report the full path of the program.
+        return std::path::Path::new(name.trim())
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .filter(|n| !n.is_empty());
+    }
 }
 
 impl AsRawFd for Pty {
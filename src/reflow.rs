@@ -0,0 +1,334 @@
+use crate::geometry::Size;
+use std::convert::TryFrom;
+use vt100::{Cell, Color, Parser};
+
+/// The attributes of a cell that are kept when it is rewrapped.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Style {
+    fg: Color,
+    bg: Color,
+    bold: bool,
+    italic: bool,
+    underline: bool,
+    inverse: bool,
+}
+
+/// A cell copied from a screen, so it can be written to a screen of a different width.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct StyledCell {
+    contents: String,
+    /// The number of columns the cell's contents take up, 2 for a wide character.
+    width: usize,
+    style: Style,
+}
+
+impl Style {
+    const DEFAULT: Style = Style {
+        fg: Color::Default,
+        bg: Color::Default,
+        bold: false,
+        italic: false,
+        underline: false,
+        inverse: false,
+    };
+
+    fn from_cell(cell: &Cell) -> Self {
+        return Self {
+            fg: cell.fgcolor(),
+            bg: cell.bgcolor(),
+            bold: cell.bold(),
+            italic: cell.italic(),
+            underline: cell.underline(),
+            inverse: cell.inverse(),
+        };
+    }
+
+    /// Returns the SGR sequence that sets this style, starting from the default style.
+    fn sgr(&self) -> String {
+        let mut params = vec![String::from("0")];
+
+        if self.bold {
+            params.push(String::from("1"));
+        }
+
+        if self.italic {
+            params.push(String::from("3"));
+        }
+
+        if self.underline {
+            params.push(String::from("4"));
+        }
+
+        if self.inverse {
+            params.push(String::from("7"));
+        }
+
+        params.extend(color_params(self.fg, 30, 90, 38));
+        params.extend(color_params(self.bg, 40, 100, 48));
+
+        return format!("\x1b[{}m", params.join(";"));
+    }
+}
+
+/// Returns the SGR parameters for a color, given the codes of the first normal and bright colors
+/// and the code that introduces a 256 or RGB color.
+fn color_params(color: Color, normal: u8, bright: u8, extended: u8) -> Option<String> {
+    return match color {
+        Color::Default => None,
+        Color::Idx(i) if i < 8 => Some((normal + i).to_string()),
+        Color::Idx(i) if i < 16 => Some((bright + i - 8).to_string()),
+        Color::Idx(i) => Some(format!("{};5;{}", extended, i)),
+        Color::Rgb(r, g, b) => Some(format!("{};2;{};{};{}", extended, r, g, b)),
+    };
+}
+
+impl StyledCell {
+    fn blank() -> Self {
+        return Self {
+            contents: String::new(),
+            width: 1,
+            style: Style::DEFAULT,
+        };
+    }
+
+    fn from_cell(cell: &Cell) -> Self {
+        return Self {
+            contents: cell.contents(),
+            width: if cell.is_wide() { 2 } else { 1 },
+            style: Style::from_cell(cell),
+        };
+    }
+
+    fn is_blank(&self) -> bool {
+        return self.contents.is_empty() && self.style == Style::DEFAULT;
+    }
+}
+
+/// Returns a parser of the new size with the scrollback and screen of the parser, with the lines
+/// that wrapped rewrapped at the new width rather than truncated. The cursor stays at the same
+/// place in its line and the parser's modes, title and attributes are kept. The parser is left
+/// with a scrollback offset of 0.
+pub fn reflow(parser: &mut Parser, size: Size, scrollback_len: usize) -> Parser {
+    parser.set_scrollback(0);
+
+    let (rows, cols) = parser.screen().size();
+    let (cursor_row, cursor_col) = parser.screen().cursor_position();
+    let hide_cursor = parser.screen().hide_cursor();
+    // Resizing clears whether the screen's rows wrapped, so they are read first.
+    let screen_wrapped: Vec<bool> = (0..rows)
+        .map(|row| parser.screen().row_wrapped(row))
+        .collect();
+
+    // vt100 can't show more scrollback than the height of the screen, so the screen is made
+    // tall enough to show all of it whilst it is read.
+    let scrollback_rows = u16::try_from(scrollback_len).unwrap_or(u16::MAX);
+    parser.set_size(rows.saturating_add(scrollback_rows), cols);
+    parser.set_scrollback(usize::MAX);
+
+    let screen = parser.screen();
+    let scrolled = screen.scrollback();
+    let total_rows = usize::from(rows) + scrolled;
+    let cursor_row = scrolled + usize::from(cursor_row);
+    let mut lines = vec![Vec::new()];
+    let mut cursor = (0, 0);
+
+    for row in 0..total_rows {
+        let index = lines.len() - 1;
+        let line: &mut Vec<StyledCell> = &mut lines[index];
+
+        if row == cursor_row {
+            cursor = (index, line_width(line) + usize::from(cursor_col));
+        }
+
+        let row = row as u16;
+
+        for col in 0..cols {
+            match screen.cell(row, col) {
+                Some(cell) if !cell.is_wide_continuation() => {
+                    line.push(StyledCell::from_cell(cell))
+                }
+                _ => (),
+            }
+        }
+
+        let wrapped = match usize::from(row).checked_sub(scrolled) {
+            Some(screen_row) => screen_wrapped[screen_row],
+            None => screen.row_wrapped(row),
+        };
+
+        if !wrapped {
+            lines.push(Vec::new());
+        }
+    }
+
+    let attributes = screen.attributes_formatted();
+    let input_mode = screen.input_mode_formatted();
+    let title = screen.title_formatted();
+
+    parser.set_scrollback(0);
+    parser.set_size(rows, cols);
+
+    // The blank lines below the cursor and the text are removed, as is the blank space at the end
+    // of each line.
+    let last_line = lines
+        .iter()
+        .rposition(|line| line.iter().any(|cell| !cell.is_blank()))
+        .map_or(cursor.0, |last| last.max(cursor.0));
+    lines.truncate(last_line + 1);
+
+    for line in lines.iter_mut() {
+        while line.last().is_some_and(StyledCell::is_blank) {
+            line.pop();
+        }
+    }
+
+    // The cursor's line is padded so the cursor is within it.
+    let cursor_line = &mut lines[cursor.0];
+    let padding = cursor.1.saturating_sub(line_width(cursor_line));
+    cursor_line.extend((0..padding).map(|_| StyledCell::blank()));
+
+    let new_cols = usize::from(size.get_cols().max(1));
+    let mut output = Vec::new();
+    let mut style = Style::DEFAULT;
+    let mut wrapped_rows = 0;
+    let mut cursor_position = (0, 0);
+
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            // Resetting the style first stops the new line being filled with the background.
+            if style != Style::DEFAULT {
+                output.extend_from_slice(b"\x1b[m");
+                style = Style::DEFAULT;
+            }
+
+            output.extend_from_slice(b"\r\n");
+        }
+
+        for cell in line {
+            if cell.style != style {
+                output.extend_from_slice(cell.style.sgr().as_bytes());
+                style = cell.style;
+            }
+
+            if cell.contents.is_empty() {
+                output.push(b' ');
+            } else {
+                output.extend_from_slice(cell.contents.as_bytes());
+            }
+        }
+
+        if i == cursor.0 {
+            let (row, col) = wrapped_position(line, cursor.1, new_cols);
+            cursor_position = (wrapped_rows + row, col);
+        }
+
+        wrapped_rows += wrapped_position(line, usize::MAX, new_cols).0 + 1;
+    }
+
+    // The rows above the screen are in the scrollback.
+    let top = wrapped_rows.saturating_sub(usize::from(size.get_rows()));
+    let cursor_row = cursor_position.0.saturating_sub(top);
+
+    output.extend_from_slice(b"\x1b[m");
+    output.extend_from_slice(&attributes);
+    output.extend_from_slice(&input_mode);
+    output.extend_from_slice(&title);
+
+    if hide_cursor {
+        output.extend_from_slice(b"\x1b[?25l");
+    }
+
+    output.extend_from_slice(
+        format!("\x1b[{};{}H", cursor_row + 1, cursor_position.1 + 1).as_bytes(),
+    );
+
+    let mut reflowed = Parser::new(size.get_rows(), size.get_cols(), scrollback_len);
+    reflowed.process(&output);
+
+    return reflowed;
+}
+
+fn line_width(line: &[StyledCell]) -> usize {
+    return line.iter().map(|cell| cell.width).sum();
+}
+
+/// Returns the row and column the offset into a line is at once the line is wrapped at the
+/// width, a wide character that doesn't fit at the end of a row is wrapped onto the next row.
+fn wrapped_position(line: &[StyledCell], offset: usize, cols: usize) -> (usize, usize) {
+    let mut row = 0;
+    let mut col = 0;
+    let mut width = 0;
+
+    for cell in line {
+        if width >= offset {
+            break;
+        }
+
+        if col + cell.width > cols {
+            row += 1;
+            col = 0;
+        }
+
+        col += cell.width;
+        width += cell.width;
+    }
+
+    // At the end of a full row the cursor stays on the row until more text is written.
+    return (row, col.min(cols - 1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::reflow;
+    use crate::geometry::Size;
+    use vt100::Parser;
+
+    fn contents(parser: &Parser) -> Vec<String> {
+        let screen = parser.screen();
+        return screen.rows(0, screen.size().1).collect();
+    }
+
+    #[test]
+    fn test_lines_are_rewrapped() {
+        let mut parser = Parser::new(3, 10, 10);
+        parser.process(b"0123456789abcd\r\nshort");
+
+        let mut narrow = reflow(&mut parser, Size::new(4, 5), 10);
+        assert_eq!(contents(&narrow), vec!["01234", "56789", "abcd", "short"]);
+        assert_eq!(narrow.screen().cursor_position(), (3, 4));
+        assert!(narrow.screen().row_wrapped(0));
+        assert!(!narrow.screen().row_wrapped(2));
+
+        let wide = reflow(&mut narrow, Size::new(3, 20), 10);
+        assert_eq!(contents(&wide), vec!["0123456789abcd", "short", ""]);
+        assert_eq!(wide.screen().cursor_position(), (1, 4));
+    }
+
+    #[test]
+    fn test_scrollback_is_rewrapped() {
+        let mut parser = Parser::new(2, 6, 10);
+        parser.process(b"one two\r\nthree\r\nfour\r\nfive");
+
+        let mut reflowed = reflow(&mut parser, Size::new(2, 4), 10);
+        assert_eq!(contents(&reflowed), vec!["four", "five"]);
+
+        reflowed.set_scrollback(2);
+        assert_eq!(contents(&reflowed), vec!["thre", "e"]);
+    }
+
+    #[test]
+    fn test_style_and_cursor_are_kept() {
+        let mut parser = Parser::new(3, 8, 0);
+        parser.process(b"\x1b[1;31mred\x1b[m text\r\n\x1b[?25l\x1b[4mab\x1b[1;2H");
+
+        let reflowed = reflow(&mut parser, Size::new(3, 4), 0);
+        let screen = reflowed.screen();
+        assert_eq!(contents(&reflowed), vec!["red ", "text", "ab"]);
+        assert!(screen.cell(0, 0).unwrap().bold());
+        assert_eq!(screen.cell(0, 2).unwrap().fgcolor(), vt100::Color::Idx(1));
+        assert!(!screen.cell(1, 0).unwrap().bold());
+        assert_eq!(screen.cursor_position(), (0, 1));
+        assert!(screen.hide_cursor());
+        assert!(screen.underline());
+    }
+}
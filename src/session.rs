@@ -0,0 +1,276 @@
+use crate::error::{ErrorType, MuxideError};
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+/// The name used when no better name can be determined for a session.
+pub const FALLBACK_SESSION_NAME: &'static str = "muxide";
+/// The extension given to each session's socket file.
+const SOCKET_EXTENSION: &'static str = "sock";
+
+/// Describes a session that has a socket in the session directory.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SessionInfo {
+    pub name: String,
+    pub path: PathBuf,
+    pub alive: bool,
+}
+
+/// The socket of a running session. Whilst it is held the session is listed as running and no
+/// other session can take its name, the socket is removed when it is dropped.
+pub struct SessionSocket {
+    name: String,
+    path: PathBuf,
+}
+
+impl SessionSocket {
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+}
+
+impl Drop for SessionSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Returns the directory sockets for each session are stored in. This is
+/// `$XDG_RUNTIME_DIR/muxide` when available, otherwise a per user directory in the temp directory.
+pub fn session_directory() -> PathBuf {
+    if let Some(mut path) = dirs::runtime_dir() {
+        path.push("muxide");
+
+        return path;
+    }
+
+    let mut path = std::env::temp_dir();
+    path.push(format!("muxide-{}", nix::unistd::getuid()));
+
+    return path;
+}
+
+/// Lists all the sessions with a socket in the session directory and whether or not they are
+/// still accepting connections.
+pub fn list_sessions() -> Result<Vec<SessionInfo>, MuxideError> {
+    return list_sessions_in(&session_directory());
+}
+
+fn list_sessions_in(directory: &Path) -> Result<Vec<SessionInfo>, MuxideError> {
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(directory).map_err(|e| {
+        ErrorType::IOError {
+            read: true,
+            target: directory.to_string_lossy().to_string(),
+            reason: e.to_string(),
+        }
+        .into_error()
+    })?;
+
+    let mut sessions = Vec::new();
+
+    for entry in entries {
+        let path = match entry {
+            Ok(e) => e.path(),
+            Err(_) => continue,
+        };
+
+        if path
+            .extension()
+            .map(|e| e != SOCKET_EXTENSION)
+            .unwrap_or(true)
+        {
+            continue;
+        }
+
+        let name = match path.file_stem() {
+            Some(n) => n.to_string_lossy().to_string(),
+            None => continue,
+        };
+
+        sessions.push(SessionInfo {
+            name,
+            alive: is_alive(&path),
+            path,
+        });
+    }
+
+    sessions.sort_by(|a, b| a.name.cmp(&b.name));
+
+    return Ok(sessions);
+}
+
+/// Removes the sockets left behind by sessions that are no longer running, i.e. sessions that
+/// crashed. Returns the names of the sessions that were removed.
+pub fn remove_stale_sessions() -> Result<Vec<String>, MuxideError> {
+    let mut removed = Vec::new();
+
+    for session in list_sessions()? {
+        if session.alive {
+            continue;
+        }
+
+        match fs::remove_file(&session.path) {
+            Ok(_) => removed.push(session.name),
+            Err(e) if e.kind() == ErrorKind::NotFound => (),
+            Err(e) => {
+                return Err(ErrorType::IOError {
+                    read: false,
+                    target: session.path.to_string_lossy().to_string(),
+                    reason: e.to_string(),
+                }
+                .into_error());
+            }
+        }
+    }
+
+    return Ok(removed);
+}
+
+/// Claims a unique name for a new session and binds its socket, which is held until the session
+/// ends. If a name is requested it is used as the base, otherwise the name of the current
+/// directory is used. If a running session already uses the name, a numeric suffix is appended,
+/// i.e. "project-2".
+pub fn claim_session(requested: Option<&str>) -> Result<SessionSocket, MuxideError> {
+    let directory = session_directory();
+
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&directory)
+        .map_err(|e| {
+            ErrorType::IOError {
+                read: false,
+                target: directory.to_string_lossy().to_string(),
+                reason: e.to_string(),
+            }
+            .into_error()
+        })?;
+
+    let base = match requested {
+        Some(name) => sanitize_name(name),
+        None => std::env::current_dir()
+            .ok()
+            .and_then(|dir| dir.file_name().map(|n| sanitize_name(&n.to_string_lossy())))
+            .unwrap_or_default(),
+    };
+
+    let base = if base.is_empty() {
+        FALLBACK_SESSION_NAME.to_string()
+    } else {
+        base
+    };
+
+    return claim_name(&directory, &base);
+}
+
+/// Binds the socket for the first name, starting with the base, that no running session uses.
+/// Binding fails if the socket exists, so two sessions started at the same time can't both claim
+/// a name.
+fn claim_name(directory: &Path, base: &str) -> Result<SessionSocket, MuxideError> {
+    let mut name = base.to_string();
+    let mut suffix = 2;
+
+    loop {
+        let path = directory.join(format!("{}.{}", name, SOCKET_EXTENSION));
+
+        // The socket of a session that crashed is replaced.
+        if path.exists() && !is_alive(&path) {
+            let _ = fs::remove_file(&path);
+        }
+
+        match UnixListener::bind(&path) {
+            Ok(listener) => {
+                // There is no server to handle connections yet, they are accepted and closed so
+                // checking whether the session is running never fills the listener's backlog.
+                std::thread::spawn(move || for _ in listener.incoming() {});
+
+                return Ok(SessionSocket { name, path });
+            }
+            Err(e) if e.kind() == ErrorKind::AddrInUse => {
+                name = format!("{}-{}", base, suffix);
+                suffix += 1;
+            }
+            Err(e) => {
+                return Err(ErrorType::IOError {
+                    read: false,
+                    target: path.to_string_lossy().to_string(),
+                    reason: e.to_string(),
+                }
+                .into_error());
+            }
+        }
+    }
+}
+
+/// Replaces any characters that are unsuitable for a file name with a '-'.
+fn sanitize_name(name: &str) -> String {
+    return name
+        .trim()
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || ch == '-' || ch == '_' {
+                ch
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string();
+}
+
+/// A session is considered alive if its socket accepts a connection.
+fn is_alive(path: &Path) -> bool {
+    return path.exists() && UnixStream::connect(path).is_ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{claim_name, list_sessions_in, sanitize_name};
+    use std::fs;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(sanitize_name("my project"), "my-project");
+        assert_eq!(sanitize_name(" .config "), "config");
+        assert_eq!(sanitize_name("muxide_2"), "muxide_2");
+    }
+
+    #[test]
+    fn test_claim_name() {
+        let directory =
+            std::env::temp_dir().join(format!("muxide-sessions-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+
+        let first = claim_name(&directory, "work").unwrap();
+        let second = claim_name(&directory, "work").unwrap();
+        assert_eq!(first.name(), "work");
+        assert_eq!(second.name(), "work-2");
+
+        // A socket nothing listens on is left by a session that crashed.
+        drop(UnixListener::bind(directory.join("stale.sock")).unwrap());
+
+        let sessions = list_sessions_in(&directory).unwrap();
+        let states: Vec<(&str, bool)> = sessions
+            .iter()
+            .map(|session| (session.name.as_str(), session.alive))
+            .collect();
+        assert_eq!(states, [("stale", false), ("work", true), ("work-2", true)]);
+
+        assert_eq!(claim_name(&directory, "stale").unwrap().name(), "stale");
+
+        drop(first);
+        assert!(!directory.join("work.sock").exists());
+        assert_eq!(claim_name(&directory, "work").unwrap().name(), "work");
+
+        drop(second);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+use std::path::Path;
+
+/// The options of ssh that take a value, i.e. `-p 22`.
+const OPTIONS_WITH_VALUES: &'static str = "BbcDEeFIiJLlmOopQRSWw";
+
+/// Returns the destination of an ssh command from its arguments, i.e. "user@host" for
+/// `ssh -p 22 user@host ls`. Returns None if the command isn't ssh.
+pub fn destination(args: &[String]) -> Option<String> {
+    let program = Path::new(args.first()?).file_name()?;
+
+    if program != "ssh" {
+        return None;
+    }
+
+    let mut user = None;
+    let mut args = args[1..].iter();
+
+    while let Some(arg) = args.next() {
+        let host = if arg == "--" {
+            args.next()?
+        } else if let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) {
+            for (i, flag) in flags.char_indices() {
+                if !OPTIONS_WITH_VALUES.contains(flag) {
+                    continue;
+                }
+
+                // The value either follows the option directly or is the next argument.
+                let rest = &flags[i + flag.len_utf8()..];
+                let value = if rest.is_empty() {
+                    args.next()?.as_str()
+                } else {
+                    rest
+                };
+
+                if flag == 'l' {
+                    user = Some(value);
+                }
+
+                break;
+            }
+
+            continue;
+        } else {
+            arg
+        };
+
+        return Some(match user {
+            Some(user) if !host.contains('@') => format!("{}@{}", user, host),
+            _ => host.clone(),
+        });
+    }
+
+    return None;
+}
+
+/// Returns a command line that runs the arguments in a shell, each argument is quoted.
+pub fn command_line(args: &[String]) -> String {
+    return args
+        .iter()
+        .map(|arg| format!("'{}'", arg.replace('\'', "'\\''")))
+        .collect::<Vec<String>>()
+        .join(" ");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{command_line, destination};
+
+    fn args(command: &str) -> Vec<String> {
+        return command.split(' ').map(|a| a.to_string()).collect();
+    }
+
+    #[test]
+    fn test_destination() {
+        assert_eq!(destination(&args("ssh host")), Some("host".to_string()));
+        assert_eq!(
+            destination(&args("/usr/bin/ssh -p 2222 -A me@host ls -l")),
+            Some("me@host".to_string())
+        );
+        assert_eq!(
+            destination(&args("ssh -l me -i/key host")),
+            Some("me@host".to_string())
+        );
+        assert_eq!(destination(&args("ssh -v")), None);
+        assert_eq!(destination(&args("sshd -D")), None);
+    }
+
+    #[test]
+    fn test_command_line() {
+        assert_eq!(
+            command_line(&args("ssh it's")),
+            "'ssh' 'it'\\''s'".to_string()
+        );
+    }
+}
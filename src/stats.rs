@@ -0,0 +1,304 @@
+use crate::channel_controller::ChannelStats;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/// Counters for the throughput of the pty pipeline and the event loop, they are reported for the
+/// last complete interval so they can be compared over time. The activity of each panel is
+/// counted for as long as it is open.
+pub struct Stats {
+    started: Instant,
+    interval_start: Instant,
+    current: Interval,
+    last: Option<(Duration, Interval)>,
+    panels: BTreeMap<usize, PanelActivity>,
+}
+
+/// The totals for a panel since it was opened.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct PanelActivity {
+    opened: Instant,
+    bytes_in: u64,
+    bytes_out: u64,
+    lines: u64,
+    last_activity: Instant,
+    /// The messages queued in the panel's channels when the stats were last reported.
+    channel: ChannelStats,
+}
+
+impl PanelActivity {
+    fn new(now: Instant) -> Self {
+        return Self {
+            opened: now,
+            bytes_in: 0,
+            bytes_out: 0,
+            lines: 0,
+            last_activity: now,
+            channel: ChannelStats::default(),
+        };
+    }
+}
+
+/// The counters for a single interval.
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+struct Interval {
+    /// The number of bytes of output processed for each panel.
+    panel_bytes: BTreeMap<usize, u64>,
+    frames: u64,
+    loop_iterations: u64,
+    total_latency: Duration,
+    max_latency: Duration,
+}
+
+impl Stats {
+    /// The length of the interval the counters are reported for.
+    pub const INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new() -> Self {
+        let now = Instant::now();
+
+        return Self {
+            started: now,
+            interval_start: now,
+            current: Interval::default(),
+            last: None,
+            panels: BTreeMap::new(),
+        };
+    }
+
+    pub fn record_panel_opened(&mut self, id: usize) {
+        self.panels.insert(id, PanelActivity::new(Instant::now()));
+    }
+
+    pub fn record_panel_closed(&mut self, id: usize) {
+        self.panels.remove(&id);
+    }
+
+    /// Record the output of a panel's program that was processed, and the number of lines in it.
+    pub fn record_panel_output(&mut self, id: usize, bytes: usize, lines: usize) {
+        let now = Instant::now();
+        self.roll_over(now);
+        *self.current.panel_bytes.entry(id).or_insert(0) += bytes as u64;
+
+        if let Some(activity) = self.panels.get_mut(&id) {
+            activity.bytes_out += bytes as u64;
+            activity.lines += lines as u64;
+
+            if bytes > 0 {
+                activity.last_activity = now;
+            }
+        }
+    }
+
+    /// Record input written to a panel's program.
+    pub fn record_panel_input(&mut self, id: usize, bytes: usize) {
+        if let Some(activity) = self.panels.get_mut(&id) {
+            activity.bytes_in += bytes as u64;
+            activity.last_activity = Instant::now();
+        }
+    }
+
+    /// Record the number of messages queued in each panel's channels.
+    pub fn record_channel_stats(&mut self, channels: HashMap<usize, ChannelStats>) {
+        for (id, channel) in channels {
+            if let Some(activity) = self.panels.get_mut(&id) {
+                activity.channel = channel;
+            }
+        }
+    }
+
+    pub fn record_frame(&mut self) {
+        self.roll_over(Instant::now());
+        self.current.frames += 1;
+    }
+
+    /// Record the time the event loop took to handle a message and render the result.
+    pub fn record_latency(&mut self, latency: Duration) {
+        self.roll_over(Instant::now());
+        self.current.loop_iterations += 1;
+        self.current.total_latency += latency;
+        self.current.max_latency = self.current.max_latency.max(latency);
+    }
+
+    /// Returns a line describing each counter, from the last complete interval, followed by a table
+    /// of the activity of each panel.
+    pub fn report(&mut self, now: Instant) -> Vec<String> {
+        self.roll_over(now);
+
+        let mut lines = vec![format!(
+            "Uptime: {}",
+            format_duration(now.saturating_duration_since(self.started))
+        )];
+        lines.append(&mut self.interval_report());
+        lines.append(&mut self.panel_report(now));
+
+        return lines;
+    }
+
+    fn interval_report(&self) -> Vec<String> {
+        let (length, interval) = match self.last.as_ref() {
+            Some(last) => last,
+            None => return vec![String::from("Collecting statistics...")],
+        };
+        let seconds = length.as_secs_f64().max(f64::EPSILON);
+        let average_latency = if interval.loop_iterations == 0 {
+            Duration::from_secs(0)
+        } else {
+            interval.total_latency / interval.loop_iterations as u32
+        };
+
+        let mut lines = vec![
+            format!("Frames rendered: {:.1}/s", interval.frames as f64 / seconds),
+            format!(
+                "Event loop latency: {:.2}ms average, {:.2}ms max",
+                average_latency.as_secs_f64() * 1000.0,
+                interval.max_latency.as_secs_f64() * 1000.0
+            ),
+        ];
+
+        if interval.panel_bytes.values().all(|bytes| *bytes == 0) {
+            lines.push(String::from("No panel output"));
+        }
+
+        return lines;
+    }
+
+    /// Returns a table of the activity of each open panel, the output rate is from the last
+    /// complete interval.
+    fn panel_report(&self, now: Instant) -> Vec<String> {
+        if self.panels.is_empty() {
+            return Vec::new();
+        }
+
+        let seconds = self
+            .last
+            .as_ref()
+            .map(|(length, _)| length.as_secs_f64().max(f64::EPSILON));
+        let mut lines = vec![format!(
+            "{:>5} {:>9} {:>9} {:>9} {:>8} {:>9} {:>9} {:>9}",
+            "Panel", "Out/s", "Out", "In", "Lines", "Uptime", "Idle", "Queued"
+        )];
+
+        for (id, activity) in &self.panels {
+            let rate = match (seconds, self.last.as_ref()) {
+                (Some(seconds), Some((_, interval))) => format_bytes(
+                    (*interval.panel_bytes.get(id).unwrap_or(&0) as f64 / seconds) as u64,
+                ),
+                _ => String::from("-"),
+            };
+
+            // The messages waiting to be sent to the panel and waiting to be received from it.
+            let queued = format!(
+                "{}/{}",
+                activity.channel.queued_input, activity.channel.queued_output
+            );
+
+            lines.push(format!(
+                "{:>5} {:>9} {:>9} {:>9} {:>8} {:>9} {:>9} {:>9}",
+                id,
+                rate,
+                format_bytes(activity.bytes_out),
+                format_bytes(activity.bytes_in),
+                activity.lines,
+                format_duration(now.saturating_duration_since(activity.opened)),
+                format_duration(now.saturating_duration_since(activity.last_activity)),
+                queued
+            ));
+        }
+
+        return lines;
+    }
+
+    /// Start a new interval if the current interval has finished.
+    fn roll_over(&mut self, now: Instant) {
+        let length = now.saturating_duration_since(self.interval_start);
+
+        if length >= Self::INTERVAL {
+            self.last = Some((length, std::mem::take(&mut self.current)));
+            self.interval_start = now;
+        }
+    }
+}
+
+/// Returns a duration in its largest units, i.e. "1h 02m" or "45s".
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    if seconds >= 3600 {
+        return format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60);
+    } else if seconds >= 60 {
+        return format!("{}m {:02}s", seconds / 60, seconds % 60);
+    }
+
+    return format!("{}s", seconds);
+}
+
+/// Returns a number of bytes in the largest binary unit it has at least one of.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        return format!("{}B", bytes);
+    }
+
+    return format!("{:.1}{}", size, UNITS[unit]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_bytes, format_duration, Stats};
+    use crate::channel_controller::ChannelStats;
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_report_uses_last_interval() {
+        let mut stats = Stats::new();
+        stats.record_panel_opened(2);
+        stats.record_panel_output(2, 2048, 3);
+        stats.record_panel_input(2, 5);
+        stats.record_frame();
+        stats.record_latency(Duration::from_millis(4));
+
+        let mut channels = HashMap::new();
+        channels.insert(
+            2,
+            ChannelStats {
+                queued_input: 1,
+                queued_output: 4,
+            },
+        );
+        stats.record_channel_stats(channels);
+
+        let lines = stats.report(Instant::now());
+        assert!(lines[0].starts_with("Uptime: "));
+        assert_eq!(lines[1], "Collecting statistics...");
+        assert!(lines[3].ends_with("-    2.0KiB        5B        3        0s        0s       1/4"));
+
+        let lines = stats.report(Instant::now() + Stats::INTERVAL * 2);
+
+        assert_eq!(lines.len(), 5);
+        assert!(lines[2].contains("4.00ms max"));
+        assert!(lines[3].starts_with("Panel"));
+        assert!(lines[4].starts_with("    2 "));
+
+        stats.record_panel_closed(2);
+        assert_eq!(stats.report(Instant::now()).len(), 3);
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_bytes(512), "512B");
+        assert_eq!(format_bytes(1536), "1.5KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0MiB");
+        assert_eq!(format_duration(Duration::from_secs(45)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(125)), "2m 05s");
+        assert_eq!(format_duration(Duration::from_secs(3720)), "1h 02m");
+    }
+}
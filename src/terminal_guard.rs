@@ -0,0 +1,225 @@
+use crate::config::KeyboardProtocol;
+use crate::logging::warning;
+use crossterm::{execute, style, terminal};
+use std::io::stdout;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The attributes of the controlling terminal before it was put into raw mode. It is only written
+/// before the signal handlers are installed.
+static mut ATTRIBUTES: Option<libc::termios> = None;
+/// Set once the terminal has been restored, so it is only restored once.
+static RESTORED: AtomicBool = AtomicBool::new(false);
+/// Set whilst the kitty keyboard protocol's flags are pushed, they are popped when restoring.
+static KITTY_KEYBOARD: AtomicBool = AtomicBool::new(false);
+/// Set whilst modifyOtherKeys is enabled, it is reset when restoring.
+static MODIFY_OTHER_KEYS: AtomicBool = AtomicBool::new(false);
+
+/// Switches the terminal to the alternate screen and restores it when dropped, when the program
+/// panics or when it receives SIGINT, SIGTERM or SIGHUP. Restoring the terminal shows the cursor,
+/// resets the colors and the window's title, leaves raw mode and returns to the main screen.
+///
+/// A SIGKILL can't be handled, in that case the terminal is left as it was.
+pub struct TerminalGuard {
+    _private: (),
+}
+
+impl TerminalGuard {
+    /// Asks the terminal to surround pasted text with escape sequences, so it can be forwarded to
+    /// panels without being interpreted as commands.
+    const ENABLE_BRACKETED_PASTE: &'static str = "\x1b[?2004h";
+    /// Asks the terminal to report when it gains or loses focus, so it can be forwarded to the
+    /// selected panel.
+    const ENABLE_FOCUS_REPORTING: &'static str = "\x1b[?1004h";
+    /// Saves the window's title on the terminal's title stack (XTWINOPS 22), so it can be restored
+    /// after the multiplexer changes it.
+    const SAVE_TITLE: &'static str = "\x1b[22;0t";
+    /// Pushes the kitty keyboard protocol's disambiguate escape codes flag and asks the terminal
+    /// for its flags, a terminal that supports the protocol replies with them.
+    const ENABLE_KITTY_KEYBOARD: &'static str = "\x1b[>1u\x1b[?u";
+    /// Asks for the terminal's primary device attributes, every terminal replies. A reply without
+    /// a preceding reply to the kitty keyboard query means the protocol isn't supported.
+    const QUERY_DEVICE_ATTRIBUTES: &'static str = "\x1b[c";
+    const ENABLE_MODIFY_OTHER_KEYS: &'static str = "\x1b[>4;2m";
+    const RESTORE_KITTY_KEYBOARD: &'static [u8] = b"\x1b[<u";
+    const RESTORE_MODIFY_OTHER_KEYS: &'static [u8] = b"\x1b[>4m";
+    /// Shows the cursor, resets the colors, disables bracketed paste and focus reporting, restores
+    /// the default cursor style and the window's title and leaves the alternate screen.
+    const RESTORE_SEQUENCE: &'static [u8] =
+        b"\x1b[?25h\x1b[0m\x1b[?2004l\x1b[?1004l\x1b[0 q\x1b[23;0t\x1b[?1049l";
+    /// The signals that restore the terminal before terminating the program.
+    const SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+    /// Save the terminal's attributes, install the handlers that restore it, switch to the
+    /// alternate screen and request the keyboard protocol. This should be called before the
+    /// terminal is put into raw mode.
+    pub fn enter(keyboard_protocol: KeyboardProtocol) -> Self {
+        unsafe {
+            ATTRIBUTES = Self::terminal_attributes();
+        }
+
+        RESTORED.store(false, Ordering::SeqCst);
+        Self::install_panic_hook();
+        Self::install_signal_handlers();
+
+        // We don't care about errors that happen with this function, if it fails that's ok.
+        if let Err(e) = execute!(
+            stdout(),
+            terminal::EnterAlternateScreen,
+            style::Print(Self::ENABLE_BRACKETED_PASTE),
+            style::Print(Self::ENABLE_FOCUS_REPORTING),
+            style::Print(Self::SAVE_TITLE)
+        ) {
+            warning!(format!(
+                "Failed to enter alternate tty screen. Reason: {}",
+                e
+            ));
+        }
+
+        match keyboard_protocol {
+            KeyboardProtocol::Legacy => (),
+            KeyboardProtocol::Kitty => Self::enable_kitty_keyboard(false),
+            KeyboardProtocol::ModifyOtherKeys => Self::enable_modify_other_keys(),
+            KeyboardProtocol::Auto => Self::enable_kitty_keyboard(true),
+        }
+
+        return Self { _private: () };
+    }
+
+    /// Request the kitty keyboard protocol, the device attributes are also requested if the
+    /// terminal's support should be checked.
+    fn enable_kitty_keyboard(query_device_attributes: bool) {
+        KITTY_KEYBOARD.store(true, Ordering::SeqCst);
+
+        let query = if query_device_attributes {
+            Self::QUERY_DEVICE_ATTRIBUTES
+        } else {
+            ""
+        };
+
+        if let Err(e) = execute!(
+            stdout(),
+            style::Print(Self::ENABLE_KITTY_KEYBOARD),
+            style::Print(query)
+        ) {
+            warning!(format!(
+                "Failed to request the kitty keyboard protocol. Reason: {}",
+                e
+            ));
+        }
+    }
+
+    /// Enable xterm's modifyOtherKeys, i.e. once the terminal doesn't support the kitty keyboard
+    /// protocol.
+    pub fn enable_modify_other_keys() {
+        MODIFY_OTHER_KEYS.store(true, Ordering::SeqCst);
+
+        if let Err(e) = execute!(stdout(), style::Print(Self::ENABLE_MODIFY_OTHER_KEYS)) {
+            warning!(format!("Failed to enable modifyOtherKeys. Reason: {}", e));
+        }
+    }
+
+    /// Restore the terminal, this only uses async-signal-safe functions so it can be called from a
+    /// signal handler.
+    fn restore() {
+        if RESTORED.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        unsafe {
+            if KITTY_KEYBOARD.swap(false, Ordering::SeqCst) {
+                Self::write_sequence(Self::RESTORE_KITTY_KEYBOARD);
+            }
+
+            if MODIFY_OTHER_KEYS.swap(false, Ordering::SeqCst) {
+                Self::write_sequence(Self::RESTORE_MODIFY_OTHER_KEYS);
+            }
+
+            Self::write_sequence(Self::RESTORE_SEQUENCE);
+
+            if let Some(attributes) = (*std::ptr::addr_of!(ATTRIBUTES)).as_ref() {
+                let fd = libc::open(b"/dev/tty\0".as_ptr() as *const libc::c_char, libc::O_RDWR);
+
+                if fd >= 0 {
+                    libc::tcsetattr(fd, libc::TCSANOW, attributes);
+                    libc::close(fd);
+                }
+            }
+        }
+    }
+
+    /// Write a sequence to stdout, this is async-signal-safe.
+    unsafe fn write_sequence(sequence: &[u8]) {
+        libc::write(
+            libc::STDOUT_FILENO,
+            sequence.as_ptr() as *const libc::c_void,
+            sequence.len(),
+        );
+    }
+
+    /// Restore the terminal before the panic message is printed, otherwise it is printed to the
+    /// alternate screen and the terminal is left in raw mode.
+    fn install_panic_hook() {
+        let previous = std::panic::take_hook();
+
+        std::panic::set_hook(Box::new(move |info| {
+            Self::restore();
+            previous(info);
+        }));
+    }
+
+    fn install_signal_handlers() {
+        for signal in Self::SIGNALS.iter() {
+            unsafe {
+                let mut action = std::mem::zeroed::<libc::sigaction>();
+                action.sa_sigaction =
+                    Self::handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+                // The handler is reset to the default, so raising the signal again terminates the
+                // program.
+                action.sa_flags = libc::SA_RESETHAND;
+                libc::sigemptyset(&mut action.sa_mask);
+
+                if libc::sigaction(*signal, &action, std::ptr::null_mut()) != 0 {
+                    warning!(format!(
+                        "Failed to install the handler for signal {}.",
+                        signal
+                    ));
+                }
+            }
+        }
+    }
+
+    extern "C" fn handle_signal(signal: libc::c_int) {
+        Self::restore();
+
+        unsafe {
+            libc::raise(signal);
+        }
+    }
+
+    /// Returns the attributes of the controlling terminal.
+    fn terminal_attributes() -> Option<libc::termios> {
+        unsafe {
+            let fd = libc::open(b"/dev/tty\0".as_ptr() as *const libc::c_char, libc::O_RDWR);
+
+            if fd < 0 {
+                return None;
+            }
+
+            let mut attributes = std::mem::zeroed::<libc::termios>();
+            let res = libc::tcgetattr(fd, &mut attributes);
+            libc::close(fd);
+
+            if res != 0 {
+                return None;
+            }
+
+            return Some(attributes);
+        }
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        Self::restore();
+    }
+}
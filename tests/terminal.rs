@@ -0,0 +1,410 @@
+//! Runs muxide in a pseudo terminal, types scripted keystrokes into it and checks the screen it
+//! draws. Panels run `cat` so their output only depends on the input they are sent.
+
+use nix::pty::{openpty, Winsize};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ROWS: u16 = 24;
+const COLS: u16 = 80;
+/// How long to wait for the screen to show what a test expects.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+const PREFIX: &[u8] = b"\x01";
+
+const CONFIG: &str = "
+[environment]
+panel_init_command = \"/bin/cat\"
+show_workspaces = false
+
+[borders]
+vertical_character = \"|\"
+horizontal_character = \"-\"
+intersection_character = \"+\"
+
+[password]
+disable_prompt_for_new_password = true
+";
+
+/// The terminal muxide runs in, its output is fed to a vt100 parser that holds the screen it drew.
+struct FakeTerminal {
+    child: Child,
+    master: File,
+    output: Receiver<Vec<u8>>,
+    parser: vt100::Parser,
+    directory: PathBuf,
+}
+
+impl FakeTerminal {
+    fn start(name: &str) -> Self {
+        Self::start_with_config(name, CONFIG)
+    }
+
+    /// Starts muxide with a config in a directory of its own, the name distinguishes the
+    /// directories and sessions of tests running at the same time.
+    fn start_with_config(name: &str, config: &str) -> Self {
+        let directory =
+            std::env::temp_dir().join(format!("muxide-test-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&directory).unwrap();
+
+        let config_path = directory.join("config.toml");
+        let config = format!(
+            "{}password_file_location = \"{}\"\n",
+            config,
+            directory.join("password").display()
+        );
+        fs::write(&config_path, config).unwrap();
+
+        let size = Winsize {
+            ws_row: ROWS,
+            ws_col: COLS,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let pty = openpty(Some(&size), None).unwrap();
+        let master = unsafe { File::from_raw_fd(pty.master) };
+        let slave = unsafe { File::from_raw_fd(pty.slave) };
+
+        let mut command = Command::new(env!("CARGO_BIN_EXE_muxide"));
+        command
+            .arg("-c")
+            .arg(&config_path)
+            .arg("-s")
+            .arg(name)
+            .env("TERM", "xterm-256color")
+            .env("HOME", &directory)
+            .env("XDG_CONFIG_HOME", &directory)
+            .env("XDG_STATE_HOME", &directory)
+            .env("XDG_RUNTIME_DIR", &directory)
+            .env_remove("COLORTERM")
+            .stdin(Stdio::from(slave.try_clone().unwrap()))
+            .stdout(Stdio::from(slave.try_clone().unwrap()))
+            .stderr(Stdio::from(slave));
+
+        // muxide reads its input from /dev/tty, so the pty has to be its controlling terminal.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+
+                Ok(())
+            });
+        }
+
+        let child = command.spawn().unwrap();
+        let mut reader = master.try_clone().unwrap();
+        let (sender, output) = channel();
+
+        thread::spawn(move || {
+            let mut buffer = [0u8; 4096];
+
+            // Reading fails with EIO once muxide exits and the pty is closed.
+            while let Ok(n) = reader.read(&mut buffer) {
+                if n == 0 || sender.send(buffer[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut terminal = Self {
+            child,
+            master,
+            output,
+            parser: vt100::Parser::new(ROWS, COLS, 0),
+            directory,
+        };
+
+        // Input sent before muxide puts the terminal in raw mode would be echoed and lost.
+        terminal.wait_for_text("No Panels Open");
+
+        terminal
+    }
+
+    fn send(&mut self, keys: &[u8]) {
+        self.master.write_all(keys).unwrap();
+        self.master.flush().unwrap();
+    }
+
+    /// Sends the prefix followed by a key.
+    fn command(&mut self, key: u8) {
+        self.send(PREFIX);
+        self.send(&[key]);
+    }
+
+    /// Processes muxide's output until the screen matches the condition, panicking with the
+    /// description and the screen if it doesn't within the timeout.
+    fn wait_for<F: Fn(&vt100::Screen) -> bool>(&mut self, description: &str, condition: F) {
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+
+        loop {
+            if condition(self.parser.screen()) {
+                return;
+            }
+
+            let now = Instant::now();
+
+            if now >= deadline {
+                break;
+            }
+
+            match self.output.recv_timeout(deadline - now) {
+                Ok(bytes) => self.parser.process(&bytes),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        panic!(
+            "Timed out waiting for {}, the screen was:\n{}",
+            description,
+            self.parser.screen().contents()
+        );
+    }
+
+    /// Waits for muxide to exit, panicking if it doesn't within the timeout.
+    fn wait_for_exit(&mut self) {
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+
+        while Instant::now() < deadline {
+            if self.child.try_wait().unwrap().is_some() {
+                return;
+            }
+
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        panic!("Timed out waiting for muxide to exit");
+    }
+
+    /// Waits for the screen to show the text.
+    fn wait_for_text(&mut self, text: &str) {
+        let description = format!("{:?} to be shown", text);
+        self.wait_for(&description, |screen| screen.contents().contains(text));
+    }
+}
+
+impl Drop for FakeTerminal {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+/// Returns the columns that are a vertical border in every row between the top and bottom borders.
+fn vertical_borders(screen: &vt100::Screen) -> Vec<u16> {
+    (1..COLS - 1)
+        .filter(|col| {
+            (1..ROWS - 1).all(|row| {
+                screen
+                    .cell(row, *col)
+                    .map(|cell| cell.contents() == "|")
+                    .unwrap_or(false)
+            })
+        })
+        .collect()
+}
+
+/// Returns the rows that are a horizontal border across the whole screen.
+fn horizontal_borders(screen: &vt100::Screen) -> Vec<u16> {
+    (0..ROWS)
+        .filter(|row| {
+            (1..COLS - 1).all(|col| {
+                screen
+                    .cell(*row, col)
+                    .map(|cell| cell.contents() == "-" || cell.contents() == "+")
+                    .unwrap_or(false)
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_panel_echoes_input() {
+    let mut terminal = FakeTerminal::start("echo");
+
+    terminal.command(b'n');
+    terminal.wait_for("a panel to open", |screen| {
+        !screen.contents().contains("No Panels Open")
+    });
+
+    terminal.send(b"hello muxide\r");
+    terminal.wait_for_text("hello muxide");
+}
+
+#[test]
+fn test_vertical_split() {
+    let mut terminal = FakeTerminal::start("split");
+
+    terminal.command(b'n');
+    terminal.send(b"left\r");
+    terminal.wait_for_text("left");
+
+    terminal.command(b'v');
+    terminal.wait_for("an empty space beside the panel", |screen| {
+        vertical_borders(screen).len() == 1 && screen.contents().contains("No Panels Open")
+    });
+
+    // The new panel fills the empty space and is selected, so typing goes to the right of the
+    // border.
+    terminal.command(b'n');
+    terminal.send(b"right\r");
+    terminal.wait_for("two panels side by side", |screen| {
+        let border = match vertical_borders(screen).first() {
+            Some(border) => *border,
+            None => return false,
+        };
+
+        (0..ROWS).any(|row| {
+            screen
+                .contents_between(row, border + 1, row, COLS)
+                .contains("right")
+        }) && (0..ROWS).any(|row| {
+            screen
+                .contents_between(row, 0, row, border)
+                .contains("left")
+        })
+    });
+}
+
+#[test]
+fn test_horizontal_split_and_close() {
+    let mut terminal = FakeTerminal::start("close");
+
+    terminal.command(b'n');
+    terminal.command(b'h');
+    terminal.command(b'n');
+    terminal.wait_for("two panels stacked", |screen| {
+        horizontal_borders(screen).len() == 1 && !screen.contents().contains("No Panels Open")
+    });
+
+    terminal.command(b'q');
+    terminal.wait_for("the second panel to close", |screen| {
+        horizontal_borders(screen).is_empty()
+    });
+}
+
+#[test]
+fn test_command_indicator() {
+    let config = CONFIG.replace("show_workspaces = false", "show_workspaces = true");
+    let mut terminal = FakeTerminal::start_with_config("indicator", &config);
+
+    terminal.send(PREFIX);
+    terminal.wait_for("the command indicator", |screen| {
+        screen.contents_between(0, 0, 0, COLS).contains(" CMD ")
+    });
+
+    terminal.send(b"n");
+    terminal.wait_for("the command indicator to be removed", |screen| {
+        !screen.contents().contains("No Panels Open")
+            && !screen.contents_between(0, 0, 0, COLS).contains("CMD")
+    });
+}
+
+#[test]
+fn test_read_only_panel() {
+    let mut terminal = FakeTerminal::start("read-only");
+
+    terminal.command(b'n');
+    terminal.command(b'w');
+    terminal.wait_for_text("\u{1f512}");
+
+    terminal.send(b"ignored\r");
+    terminal.command(b'w');
+    terminal.wait_for("the read only indicator to be removed", |screen| {
+        !screen.contents().contains('\u{1f512}')
+    });
+
+    // The input typed whilst read only was never sent, so only the later input is echoed.
+    terminal.send(b"sent\r");
+    terminal.wait_for_text("sent");
+    assert!(!terminal.parser.screen().contents().contains("ignored"));
+}
+
+#[test]
+fn test_scrollback_saved_on_quit() {
+    let config = CONFIG.replace(
+        "[environment]",
+        "[environment]\nscrollback_file = \"scrollback/{session}/{panel}.txt\"",
+    );
+    let mut terminal = FakeTerminal::start_with_config("scrollback", &config);
+
+    terminal.command(b'n');
+    terminal.send(b"saved output\r");
+    terminal.wait_for_text("saved output");
+
+    // Ctrl+Q quits.
+    terminal.send(b"\x11");
+    terminal.wait_for_exit();
+
+    let directory = terminal.directory.join("muxide/scrollback/scrollback");
+    let files: Vec<_> = fs::read_dir(&directory).unwrap().collect();
+    assert_eq!(files.len(), 1);
+
+    let text = fs::read_to_string(files[0].as_ref().unwrap().path()).unwrap();
+    assert!(text.contains("saved output"), "{:?}", text);
+}
+
+#[test]
+fn test_panel_labels() {
+    let config = CONFIG.replace(
+        "show_workspaces = false",
+        "show_workspaces = false\nshow_panel_labels = true",
+    );
+    let mut terminal = FakeTerminal::start_with_config("labels", &config);
+
+    terminal.command(b'n');
+    terminal.wait_for("the first panel's label", |screen| {
+        screen.contents_between(0, 0, 0, COLS).contains(" 1 cat ")
+    });
+
+    terminal.command(b'w');
+    terminal.wait_for("the read only flag in the label", |screen| {
+        screen
+            .contents_between(0, 0, 0, COLS)
+            .contains(" 1 [RO] cat ")
+    });
+}
+
+#[test]
+fn test_status_bar_at_bottom() {
+    let config = CONFIG
+        .replace("show_workspaces = false", "show_workspaces = true")
+        .replace(
+            "[borders]",
+            "[status_bar]\nposition = \"Bottom\"\nelements = [\"Workspaces\", \"Session\"]\n\n[borders]",
+        );
+    let mut terminal = FakeTerminal::start_with_config("bottom-bar", &config);
+
+    terminal.wait_for("the status bar on the last row", |screen| {
+        let last_row = screen.contents_between(ROWS - 1, 0, ROWS - 1, COLS);
+
+        last_row.contains("[1]")
+            && last_row.contains(" bottom-bar ")
+            && !screen.contents_between(0, 0, 0, COLS).contains("[1]")
+    });
+}
+
+#[test]
+fn test_status_bar_widgets() {
+    let config = CONFIG
+        .replace("show_workspaces = false", "show_workspaces = true")
+        .replace(
+            "[borders]",
+            "[status_bar]\nelements = [\"Workspaces\", { Text = \"hello\" }, \"Clock\"]\nclock_format = \"year %Y\"\n\n[borders]",
+        );
+    let mut terminal = FakeTerminal::start_with_config("widgets", &config);
+
+    terminal.wait_for("the widgets in the status bar", |screen| {
+        let first_row = screen.contents_between(0, 0, 0, COLS);
+
+        first_row.contains(" hello ") && first_row.contains(" year 2")
+    });
+}